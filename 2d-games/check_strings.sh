@@ -0,0 +1,6 @@
+#!/bin/sh
+# Check every game's string tables (assets/strings/*.dat) against the keys its
+# code and world files use. Reports missing keys as errors, unused ones as
+# warnings, exits non-zero on errors.
+cd "$(dirname "$0")" || exit 1
+cargo run --quiet --manifest-path sokoban/Cargo.toml -- --check-strings */
@@ -0,0 +1,7 @@
+#!/bin/sh
+# Run the cache tests under Miri to check the unsafe CacheBlock allocation,
+# Deref and drop paths for undefined behavior. The world tests go along since
+# they allocate every manager's block. Needs nightly with the miri component:
+#   rustup component add --toolchain nightly miri
+cd "$(dirname "$0")" || exit 1
+cargo +nightly miri test --manifest-path sokoban/Cargo.toml -- mgfw::cache:: mgfw::ecs::world::
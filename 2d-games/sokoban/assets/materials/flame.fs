@@ -0,0 +1,22 @@
+#version 100
+precision mediump float;
+
+varying vec2 v_uv;
+
+uniform sampler2D tex_sampler;
+uniform vec4 color_uniform;
+uniform float time;
+uniform float strength;
+uniform float speed;
+
+void main() {
+    // sideways wobble, strongest at the tip (top of the image)
+    float tip = 1.0 - v_uv.y;
+    float wave = sin(v_uv.y * 12.0 - time * speed) + 0.5 * sin(v_uv.y * 27.0 - time * speed * 1.7);
+    vec2 uv = vec2(v_uv.x + wave * strength * tip * tip, v_uv.y);
+
+    vec4 color = texture2D(tex_sampler, clamp(uv, 0.0, 1.0)) * color_uniform;
+    // slow flicker
+    color.rgb *= 0.9 + 0.1 * sin(time * speed * 0.7);
+    gl_FragColor = color;
+}
@@ -0,0 +1,34 @@
+#version 100
+precision mediump float;
+
+varying vec2 v_uv;
+
+uniform sampler2D tex_sampler;
+uniform vec2 resolution;
+uniform float threshold;
+uniform float intensity;
+uniform float radius;
+
+vec3 bright(vec2 uv) {
+    vec3 c = texture2D(tex_sampler, uv).rgb;
+    float l = dot(c, vec3(0.2126, 0.7152, 0.0722));
+    return c * smoothstep(threshold, 1.0, l);
+}
+
+void main() {
+    vec3 base = texture2D(tex_sampler, v_uv).rgb;
+    vec2 texel = radius / resolution;
+
+    // 5x5 gaussian over the bright parts of the image
+    vec3 glow = vec3(0.0);
+    float total = 0.0;
+    for (int y = -2; y <= 2; y++) {
+        for (int x = -2; x <= 2; x++) {
+            float w = exp(-float(x * x + y * y) / 4.0);
+            glow += bright(v_uv + vec2(float(x), float(y)) * texel) * w;
+            total += w;
+        }
+    }
+
+    gl_FragColor = vec4(base + glow / total * intensity, 1.0);
+}
@@ -0,0 +1,40 @@
+#version 100
+precision mediump float;
+
+varying vec2 v_uv;
+
+uniform sampler2D tex_sampler;
+// 1 deuteranopia, 2 protanopia, 3 tritanopia
+uniform float mode;
+uniform float strength;
+
+// daltonize: simulate the deficiency in LMS space, then move the color
+// difference it can't see into channels it can
+void main() {
+    vec3 c = texture2D(tex_sampler, v_uv).rgb;
+
+    float L = 17.8824 * c.r + 43.5161 * c.g + 4.11935 * c.b;
+    float M = 3.45565 * c.r + 27.1554 * c.g + 3.86714 * c.b;
+    float S = 0.0299566 * c.r + 0.184309 * c.g + 1.46709 * c.b;
+
+    float l = L;
+    float m = M;
+    float s = S;
+    if (mode < 1.5) {
+        m = 0.494207 * L + 1.24827 * S;
+    } else if (mode < 2.5) {
+        l = 2.02344 * M - 2.52581 * S;
+    } else {
+        s = -0.395913 * L + 0.801109 * M;
+    }
+
+    vec3 sim = vec3(
+        0.0809444479 * l - 0.130504409 * m + 0.116721066 * s,
+        -0.0102485335 * l + 0.0540193266 * m - 0.113614708 * s,
+        -0.000365296938 * l - 0.00412161469 * m + 0.693511405 * s);
+
+    vec3 err = c - sim;
+    vec3 fix = vec3(0.0, 0.7 * err.r + err.g, 0.7 * err.r + err.b);
+
+    gl_FragColor = vec4(clamp(c + fix * strength, 0.0, 1.0), 1.0);
+}
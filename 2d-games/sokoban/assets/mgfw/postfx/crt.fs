@@ -0,0 +1,35 @@
+#version 100
+precision mediump float;
+
+varying vec2 v_uv;
+
+uniform sampler2D tex_sampler;
+uniform vec2 resolution;
+uniform float curvature;
+uniform float scanline;
+uniform float mask;
+
+void main() {
+    // barrel distortion around the center
+    vec2 cc = v_uv - 0.5;
+    vec2 uv = v_uv + cc * dot(cc, cc) * curvature;
+    if (uv.x < 0.0 || uv.x > 1.0 || uv.y < 0.0 || uv.y > 1.0) {
+        gl_FragColor = vec4(0.0, 0.0, 0.0, 1.0);
+        return;
+    }
+
+    vec3 c = texture2D(tex_sampler, uv).rgb;
+
+    // one dark line between each source row
+    float line = 0.5 + 0.5 * cos(uv.y * resolution.y * 6.2831853);
+    c *= 1.0 - scanline * line;
+
+    // aperture grille, one color per window column
+    float col = mod(floor(gl_FragCoord.x), 3.0);
+    vec3 grille = vec3(col == 0.0 ? 1.0 : 1.0 - mask,
+                       col == 1.0 ? 1.0 : 1.0 - mask,
+                       col == 2.0 ? 1.0 : 1.0 - mask);
+    c *= grille;
+
+    gl_FragColor = vec4(c, 1.0);
+}
@@ -0,0 +1,23 @@
+#version 100
+precision mediump float;
+
+varying vec2 v_uv;
+
+uniform sampler2D tex_sampler;
+uniform float brightness;
+uniform float contrast;
+uniform float saturation;
+uniform float gamma;
+uniform vec3 tint;
+
+void main() {
+    vec3 c = texture2D(tex_sampler, v_uv).rgb;
+
+    c = c + brightness;
+    c = (c - 0.5) * contrast + 0.5;
+    float l = dot(c, vec3(0.2126, 0.7152, 0.0722));
+    c = mix(vec3(l), c, saturation);
+    c = pow(clamp(c, 0.0, 1.0), vec3(1.0 / gamma));
+
+    gl_FragColor = vec4(c * tint, 1.0);
+}
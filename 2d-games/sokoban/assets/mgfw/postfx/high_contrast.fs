@@ -0,0 +1,31 @@
+#version 100
+precision mediump float;
+
+varying vec2 v_uv;
+
+uniform sampler2D tex_sampler;
+uniform vec2 resolution;
+uniform float contrast;
+uniform float saturation;
+uniform float outline;
+
+float luma(vec2 uv) {
+    return dot(texture2D(tex_sampler, uv).rgb, vec3(0.2126, 0.7152, 0.0722));
+}
+
+// stretched contrast and saturation, and dark outlines where brightness
+// changes so shapes read without relying on color
+void main() {
+    vec3 c = texture2D(tex_sampler, v_uv).rgb;
+
+    c = (c - 0.5) * contrast + 0.5;
+    float l = dot(c, vec3(0.2126, 0.7152, 0.0722));
+    c = clamp(mix(vec3(l), c, saturation), 0.0, 1.0);
+
+    vec2 px = 1.0 / resolution;
+    float gx = luma(v_uv + vec2(px.x, 0.0)) - luma(v_uv - vec2(px.x, 0.0));
+    float gy = luma(v_uv + vec2(0.0, px.y)) - luma(v_uv - vec2(0.0, px.y));
+    float edge = clamp(length(vec2(gx, gy)) * 4.0, 0.0, 1.0);
+
+    gl_FragColor = vec4(c * (1.0 - edge * outline), 1.0);
+}
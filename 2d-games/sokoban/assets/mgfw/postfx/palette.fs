@@ -0,0 +1,21 @@
+#version 100
+precision mediump float;
+
+varying vec2 v_uv;
+
+uniform sampler2D tex_sampler;
+uniform float levels;
+uniform float dither;
+
+void main() {
+    vec3 c = texture2D(tex_sampler, v_uv).rgb;
+
+    // 2x2 ordered dither, offsets in [-0.375, 0.375] of one level
+    vec2 p = mod(floor(gl_FragCoord.xy), 2.0);
+    float bayer = (p.x * 2.0 + p.y * 3.0 - 4.0 * p.x * p.y) / 4.0 - 0.375;
+
+    float n = max(levels - 1.0, 1.0);
+    c = floor(c * n + 0.5 + bayer * dither) / n;
+
+    gl_FragColor = vec4(clamp(c, 0.0, 1.0), 1.0);
+}
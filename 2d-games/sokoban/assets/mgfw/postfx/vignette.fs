@@ -0,0 +1,16 @@
+#version 100
+precision mediump float;
+
+varying vec2 v_uv;
+
+uniform sampler2D tex_sampler;
+uniform float strength;
+uniform float radius;
+uniform float softness;
+
+void main() {
+    vec3 c = texture2D(tex_sampler, v_uv).rgb;
+    float d = length(v_uv - 0.5) * 1.41421356;
+    float v = 1.0 - smoothstep(radius, radius + softness, d) * strength;
+    gl_FragColor = vec4(c * v, 1.0);
+}
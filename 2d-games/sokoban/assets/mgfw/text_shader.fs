@@ -0,0 +1,12 @@
+#version 100
+precision mediump float;
+
+varying vec2 v_uv;
+varying vec4 v_color;
+
+uniform sampler2D tex_sampler;
+uniform vec4 color_uniform;
+
+void main() {
+    gl_FragColor = texture2D(tex_sampler, v_uv).rgba * v_color * color_uniform;
+}
@@ -2,7 +2,11 @@
 use gl_generator::{Api, Fallbacks, Profile, Registry};
 use std::env;
 use std::fs::File;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+
+#[allow(dead_code)]
+#[path = "src/mgfw/assets/pack.rs"]
+mod pack;
 
 fn main() {
     let dest = PathBuf::from(&env::var("OUT_DIR").unwrap());
@@ -13,4 +17,12 @@ fn main() {
     Registry::new(Api::Gl, (4, 2), Profile::Core, Fallbacks::All, [])
         .write_bindings(gl_generator::StructGenerator, &mut file)
         .unwrap();
+
+    // archive for mgfw::assets to include_bytes!
+    if env::var_os("CARGO_FEATURE_EMBED_ASSETS").is_some() {
+        println!("cargo:rerun-if-changed=assets");
+        println!("cargo:rerun-if-changed=src/mgfw/assets/pack.rs");
+        let files = pack::collect(Path::new(".")).unwrap();
+        std::fs::write(dest.join("assets.pak"), pack::write(&files)).unwrap();
+    }
 }
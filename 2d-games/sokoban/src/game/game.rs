@@ -1,10 +1,13 @@
 extern crate xml;
 
 use crate::mgfw;
+use mgfw::error::Error;
+use mgfw::log;
 use std::process::exit;
-use std::fs::File;
-use std::io::BufReader;
 use xml::reader::{EventReader, XmlEvent};
+use serde::{Deserialize, Serialize};
+use mgfw::leaderboard::{Leaderboard, LEADERBOARD_FORMAT_INTEGER, LEADERBOARD_LOWER_IS_BETTER};
+use mgfw::persist::{Persist, Storage};
 
 
 
@@ -20,10 +23,71 @@ pub struct GameDataHeap {
     level_data: Vec<String>,
     flame_idx: Vec<usize>,
     flame_time: Vec<f32>,
+    ui: mgfw::ui::Ui,
+    storage: Option<Storage>,
+    solved: Vec<usize>,
+    leaderboard: Leaderboard,
+    // Some while the player types a name for a new best
+    name_entry: Option<mgfw::ui::NameEntry>,
+    score_rank: Option<usize>,
+    last_name: String,
+    language: String,
+    // translated menu and score table labels, see ui_text()
+    ui_text: Vec<String>,
+    ui_text_for: (String, u8),
 }
 
+#[derive(Serialize, Deserialize, Default)]
+struct Progress {
+    level: usize,
+    solved: Vec<usize>,
+}
+
+impl Persist for Progress {
+    const VERSION: u32 = 1;
+}
+
+#[derive(Serialize, Deserialize)]
+struct Settings {
+    show_controls: bool,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    retro: bool,
+    #[serde(default = "default_language")]
+    language: String,
+    #[serde(default)]
+    colorblind: u8,
+    #[serde(default)]
+    high_contrast: bool,
+    #[serde(default)]
+    symbols: bool,
+}
+
+fn default_language() -> String {
+    String::from(mgfw::locale::DEFAULT_LANGUAGE)
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            show_controls: true,
+            name: String::new(),
+            retro: false,
+            language: default_language(),
+            colorblind: mgfw::ecs::COLORBLIND_NONE,
+            high_contrast: false,
+            symbols: false,
+        }
+    }
+}
+
+impl Persist for Settings {
+    const VERSION: u32 = 1;
+}
+
+#[derive(Default)]
 pub struct GameData {
-    pub heap: *mut GameDataHeap,
     frame: u8,
     ready: bool,
     level: usize,
@@ -39,26 +103,76 @@ pub struct GameData {
     overlay_lock: bool,
     overlay_alpha: f32,
     level_alpha: f32,
+    paused: bool,
+    show_controls: bool,
+    retro: bool,
+    colorblind: u8,
+    high_contrast: bool,
+    symbols: bool,
+    // menu or score table needs redrawing, they only change on input
+    ui_dirty: bool,
+    ui_mouse: (i32, i32),
 }
 
 #[rustfmt::skip]
 pub fn initialize(cache: &mut GameData, heap: &mut GameDataHeap, world: &mut mgfw::ecs::World) {
 
-    import_levels(heap);
+    if let Err(e) = import_levels(heap) {
+        mgfw::error::exit_with(&e);
+    }
+    heap.ui = mgfw::ui::Ui::new(mgfw::ui::Theme::load("assets/mgfw/ui_theme.dat"));
+    if let Err(e) = world.load_prefabs("assets/prefabs.ron") {
+        mgfw::error::exit_with(&Error::Asset(e));
+    }
 
-    cache.level = 0;
+    let storage = Storage::new("this-is-fine-sokoban");
+    let settings: Settings = storage.load_settings();
+    let progress: Progress = storage.load_or_default("progress");
+    heap.leaderboard = Leaderboard::load(&storage);
+    heap.last_name = settings.name;
+    if let Err(e) = world.load_strings("assets/strings", &settings.language) {
+        log(format!("WARNING: {}", e));
+        if let Err(e) = world.load_strings("assets/strings", mgfw::locale::DEFAULT_LANGUAGE) {
+            log(format!("ERROR: {}", e));
+        }
+    }
+    heap.language = String::from(world.get_language());
+    cache.retro = settings.retro;
+    set_retro(cache, world);
+    cache.colorblind = settings.colorblind;
+    cache.high_contrast = settings.high_contrast;
+    cache.symbols = settings.symbols;
+    world.accessibility_set_colorblind(cache.colorblind);
+    world.accessibility_set_high_contrast(cache.high_contrast);
+    world.accessibility_set_symbols(cache.symbols);
+    // marks files already on a folder, for players who can't tell them by color
+    world.symbol_define("placed", "assets/symbol_placed.png");
+    heap.storage = Some(storage);
+    heap.solved = progress.solved;
+
+    cache.level = progress.level;
+    cache.show_controls = settings.show_controls;
     reset(cache, heap, world);
     load_level(cache, heap, world, cache.level);
     append_overlays(cache, world);
     update_ui(cache, world);
     update_overlay(cache, world);
     update_entities(cache, heap, world);
-    
+
+    world.console_register("level", "<n>  go to level n");
+    world.console_register("restart", "restart the level");
 }
 
 fn load_level(cache: &mut GameData, heap: &mut GameDataHeap, world: &mut mgfw::ecs::World, idx: usize) {
 
     cache.level = idx % heap.level_data.len();
+    mgfw::logging::set_state("level", format!("{}", cache.level + 1));
+    save_progress(cache, heap);
+
+    let mode = level_mode(cache);
+    heap.leaderboard.define(&mode, LEADERBOARD_LOWER_IS_BETTER, LEADERBOARD_FORMAT_INTEGER, 5);
+    heap.name_entry = None;
+    heap.score_rank = None;
 
     let map = heap.level_data[cache.level].as_str();
     let mut y: usize = 0;
@@ -114,38 +228,29 @@ fn load_level(cache: &mut GameData, heap: &mut GameDataHeap, world: &mut mgfw::e
     if 20 == s.round() as i32 { twenty = "_20"; }
 
     for f in floors {
-        let e = world.new_entity();
-        world.entity_set_billboard(e, format!("assets/floor{}.png", twenty));
-        if 0 == (f.0 + f.1) % 2 {
-            world.entity_set_billboard(e, format!("assets/floor-alt{}.png", twenty));
-        }
-        world.entity_set_position_xy(e, cx + f.0 as f32 * s + s2, cy + f.1 as f32 * s + s2);
-        world.entity_set_scale_xy(e, s, s);
+        let mut prefab = "floor";
+        if 0 == (f.0 + f.1) % 2 { prefab = "floor-alt"; }
+        spawn_tile(world, prefab, twenty, cx + f.0 as f32 * s + s2, cy + f.1 as f32 * s + s2, s);
 
         let idx = f.1 * width + f.0;
         heap.map_data[idx] = TILE_FLOOR;
     }
 
     for w in walls {
-        let e = world.new_entity();
-        world.entity_set_billboard(e, format!("assets/clock{}.png", twenty));
-        if 0 == (w.0 + w.1) % 2 {
-            world.entity_set_billboard(e, format!("assets/flame{}.png", twenty));
+        let flame = 0 == (w.0 + w.1) % 2;
+        let prefab = if flame { "flame" } else { "clock" };
+        let e = spawn_tile(world, prefab, twenty, cx + w.0 as f32 * s + s2, cy + w.1 as f32 * s + s2, s);
+        if flame {
             heap.flame_idx.push(e);
             heap.flame_time.push(world.rnd());
         }
-        world.entity_set_position_xy(e, cx + w.0 as f32 * s + s2, cy + w.1 as f32 * s + s2);
-        world.entity_set_scale_xy(e, s, s);
 
         let idx = w.1 * width + w.0;
         heap.map_data[idx] = TILE_WALL;
     }
 
     for f in folders {
-        let e = world.new_entity();
-        world.entity_set_billboard(e, format!("assets/folder{}.png", twenty));
-        world.entity_set_position_xy(e, cx + f.0 as f32 * s + s2, cy + f.1 as f32 * s + s2);
-        world.entity_set_scale_xy(e, s, s);
+        spawn_tile(world, "folder", twenty, cx + f.0 as f32 * s + s2, cy + f.1 as f32 * s + s2, s);
 
         let idx = f.1 * width + f.0;
         heap.map_data[idx] = TILE_FOLDER;
@@ -154,20 +259,15 @@ fn load_level(cache: &mut GameData, heap: &mut GameDataHeap, world: &mut mgfw::e
     cache.file_start_ent = 0;
     cache.num_files = files.len();
     for f in files {
-        let e = world.new_entity();
+        let e = spawn_tile(world, "file", twenty, cx + f.0 as f32 * s + s2, cy + f.1 as f32 * s + s2, s);
         if 0 == cache.file_start_ent { cache.file_start_ent = e; }
-        world.entity_set_billboard(e, format!("assets/file{}.png", twenty));
-        world.entity_set_position_xy(e, cx + f.0 as f32 * s + s2, cy + f.1 as f32 * s + s2);
-        world.entity_set_scale_xy(e, s, s);
 
         heap.files.push(f);
     }
 
-    let e = world.new_entity();
+    let e = spawn_tile(world, "player", twenty, 0.0, 0.0, s);
     cache.player_ent = e;
     cache.player_pos = player;
-    world.entity_set_billboard(e, format!("assets/player{}.png", twenty));
-    world.entity_set_scale_xy(e, s, s);
 
     for i in 0..cache.player_ent+1 {
         world.entity_set_visibility(i, false);
@@ -175,6 +275,17 @@ fn load_level(cache: &mut GameData, heap: &mut GameDataHeap, world: &mut mgfw::e
     }
 }
 
+// tiles come from assets/prefabs.ron, twenty selects the 20 pixel variant
+fn spawn_tile(world: &mut mgfw::ecs::World, prefab: &str, twenty: &str, x: f32, y: f32, s: f32) -> usize {
+    let overrides = mgfw::ecs::SceneEntity { scale: Some((s, s)), ..Default::default() };
+    let pos = mgfw::ecs::Position { x, y };
+    match world.spawn_prefab_with(&format!("{}{}", prefab, twenty), pos, &overrides) {
+        Some(e) => e,
+        // keep entity ids contiguous even if the prefab is missing
+        None => world.new_entity(),
+    }
+}
+
 fn append_overlays(cache: &mut GameData, world: &mut mgfw::ecs::World) {
 
     let e = world.new_entity();
@@ -187,49 +298,44 @@ fn append_overlays(cache: &mut GameData, world: &mut mgfw::ecs::World) {
     world.entity_set_alpha(e, 1.0);
 
     let e = world.new_entity();
-    world.entity_set_text(e, format!("Level {}", cache.level + 1));
+    world.entity_set_text_key(e, "level", &[(cache.level + 1).to_string()]);
     world.entity_set_visibility(e, false);
     world.entity_set_position_xy(e, 320.0, 160.0);
     world.entity_set_scale_xy(e, 2.0, 2.0);
     world.entity_set_color_rgba(e, 1.0, 0.5, 0.2, 1.0);
 
     let e = world.new_entity();
-    world.entity_set_text(e, String::from("You Win!"));
+    world.entity_set_text_key(e, "win", &[cache.move_counter.to_string()]);
     world.entity_set_visibility(e, false);
     world.entity_set_position_xy(e, 320.0, 160.0);
     world.entity_set_scale_xy(e, 2.0, 2.0);
     world.entity_set_color_rgba(e, 1.0, 0.5, 0.2, 1.0);
 
     let e = world.new_entity();
-    world.entity_set_text(e, String::from("Press SPACE for next level"));
+    world.entity_set_text_key(e, "next_level", &[]);
     world.entity_set_visibility(e, false);
     world.entity_set_position_xy(e, 320.0, 200.0);
     world.entity_set_scale_xy(e, 1.0, 1.0);
     world.entity_set_color_rgba(e, 1.0, 0.5, 0.2, 1.0);
 
     for i in 0..3 {
-        let idx = cache.overlay_ent_start + i + 1;
-        let p = world.entity_get_position(idx);
-        let w = world.text_get_width(idx);
-        let s = world.entity_get_scale(idx);
-        world.entity_set_position_xy(idx, p.x - w as f32 * 0.5 * s.x, p.y);
+        world.entity_set_text_align(cache.overlay_ent_start + i + 1, mgfw::ecs::TEXT_ALIGN_CENTER);
     }
 }
 
-fn import_levels(heap: &mut GameDataHeap) {
+fn import_levels(heap: &mut GameDataHeap) -> Result<(), Error> {
 
     let filename = "assets/microban.slc";
     
-    println!("Importing level file: {}", filename);
+    log(format!("Importing level file: {}", filename));
     
-    let file = File::open(filename).unwrap();
-    let file = BufReader::new(file);
+    let data = mgfw::assets::read(filename).map_err(Error::Asset)?;
 
     heap.level_data.clear();
 
     let mut level_data = String::new();
     
-    let parser = EventReader::new(file);
+    let parser = EventReader::new(&data[..]);
     for e in parser {
         match e {
             Ok(XmlEvent::StartElement { name, attributes, .. }) => {
@@ -262,25 +368,35 @@ fn import_levels(heap: &mut GameDataHeap) {
                 //println!("chr: {}", data);
             },
             Err(e) => {
-                println!("Error: {}", e);
+                log(format!("ERROR: {}: {}", filename, e));
                 break;
             }
             _ => {}
         }
     }
+
+    // levels before a parse error are still playable
+    match heap.level_data.is_empty() {
+        true => Err(Error::Asset(format!("{}: no levels", filename))),
+        false => Ok(()),
+    }
 }
 
 fn reset(cache: &mut GameData, heap: &mut GameDataHeap, world: &mut mgfw::ecs::World) {
 
     world.clear();
-    world.parse_world("assets/world.dat");
+    if let Err(e) = world.parse_world("assets/world.dat") {
+        mgfw::error::exit_with(&Error::Asset(e.to_string()));
+    }
 
     cache.move_counter = 0;
     cache.push_counter = 0;
     cache.overlay_lock = false;
     cache.overlay_alpha = 0.0;
     cache.level_alpha = 1.0;
+    cache.paused = false;
 
+    heap.ui.reset();
     heap.files.clear();
     heap.map_data.clear();
     heap.flame_idx.clear();
@@ -289,29 +405,21 @@ fn reset(cache: &mut GameData, heap: &mut GameDataHeap, world: &mut mgfw::ecs::W
 
 fn update_ui(cache: &mut GameData, world: &mut mgfw::ecs::World) {
 
-    world.entity_set_text(0, format!("Level {}", cache.level + 1));
-    world.entity_set_text(1, format!("Moves: {}", cache.move_counter));
-    world.entity_set_text(2, format!("Pushes: {}", cache.push_counter));
-    
-    let w = world.text_get_width(1) as f32;
-    world.entity_set_position_xy(1, (320.0 - w * 0.5).round(), 1.0);
-
-    let w = world.text_get_width(2) as f32;
-    world.entity_set_position_xy(2, 640.0 - 4.0 - w, 1.0);
+    world.entity_set_text_key(0, "level", &[(cache.level + 1).to_string()]);
+    world.entity_set_text_key(1, "moves", &[cache.move_counter.to_string()]);
+    world.entity_set_text_key(2, "pushes", &[cache.push_counter.to_string()]);
 
+    world.entity_set_position_xy(1, 320.0, 1.0);
+    world.entity_set_position_xy(2, 640.0 - 4.0, 1.0);
     world.entity_set_position_xy(3, 4.0, 368.0);
+    world.entity_set_position_xy(4, 185.0, 368.0);
+    world.entity_set_position_xy(5, 320.0, 368.0);
+    world.entity_set_position_xy(6, 460.0, 368.0);
+    world.entity_set_position_xy(7, 640.0 - 4.0, 368.0);
 
-    let w = world.text_get_width(4) as f32;
-    world.entity_set_position_xy(4, (185.0 - w * 0.5).round(), 368.0);
-
-    let w = world.text_get_width(5) as f32;
-    world.entity_set_position_xy(5, (320.0 - w * 0.5).round(), 368.0);
-
-    let w = world.text_get_width(6) as f32;
-    world.entity_set_position_xy(6, (460.0 - w * 0.5).round(), 368.0);
-
-    let w = world.text_get_width(7) as f32;
-    world.entity_set_position_xy(7, 640.0 - 4.0 - w, 368.0);
+    for i in 3..8 {
+        world.entity_set_visibility(i, cache.show_controls);
+    }
 
 }
 
@@ -379,6 +487,8 @@ fn update_entities(cache: &mut GameData, heap: &mut GameDataHeap, world: &mut mg
             cx + f.0 as f32 * s + s2,
             cy + f.1 as f32 * s + s2,
         );
+        let symbol = if is_folder(cache, heap, f) { Some("placed") } else { None };
+        world.entity_set_symbol(cache.file_start_ent + i, symbol);
     }
 }
 
@@ -387,10 +497,30 @@ fn update_entities(cache: &mut GameData, heap: &mut GameDataHeap, world: &mut mg
 pub fn event(
     cache: &mut GameData, heap: &mut GameDataHeap, world: &mut mgfw::ecs::World, event_id: u8) -> bool {
 
+    if mgfw::EVENT_INPUT_KEYBOARD_RELEASED_ESCAPE == event_id {
+        cache.paused = !cache.paused;
+        if cache.paused {
+            heap.ui.set_focus(0);
+            update_menu(cache, heap, world);
+        } else {
+            heap.ui.hide(world);
+        }
+        return true;
+    }
+
+    if cache.paused {
+        cache.ui_dirty |= heap.ui.event(event_id);
+        return true;
+    }
+
+    if cache.overlay_lock && heap.name_entry.is_some() {
+        cache.ui_dirty |= heap.ui.event(event_id);
+        return true;
+    }
+
     let mut tgt = cache.player_pos;
 
     match event_id {
-        mgfw::EVENT_INPUT_KEYBOARD_RELEASED_ESCAPE => exit(0),
         mgfw::EVENT_INPUT_KEYBOARD_RELEASED_SPACE => {
             if cache.overlay_lock {
                 reset(cache, heap, world);
@@ -459,6 +589,107 @@ pub fn event(
     true
 }
 
+// menu labels 0..10, then the score table labels, translated again only when
+// the language or the color filter shown in the menu changes
+fn ui_text(cache: &GameData, heap: &mut GameDataHeap, world: &mut mgfw::ecs::World) {
+    let key = (String::from(world.get_language()), cache.colorblind);
+    if key == heap.ui_text_for && !heap.ui_text.is_empty() { return; }
+
+    let name = world.tr(mgfw::locale::LANGUAGE_KEY, &[]);
+    let filter = colorblind_name(world, cache.colorblind);
+    heap.ui_text = vec![
+        world.tr("paused", &[]),
+        world.tr("resume", &[]),
+        world.tr("restart_level", &[]),
+        world.tr("show_controls", &[]),
+        world.tr("retro_filter", &[]),
+        world.tr("menu_language", &[name]),
+        world.tr("menu_colors", &[filter]),
+        world.tr("high_contrast", &[]),
+        world.tr("symbols", &[]),
+        world.tr("quit", &[]),
+        world.tr("new_best", &[]),
+        world.tr("fewest_moves", &[]),
+    ];
+    heap.ui_text_for = key;
+}
+
+fn update_menu(cache: &mut GameData, heap: &mut GameDataHeap, world: &mut mgfw::ecs::World) {
+
+    ui_text(cache, heap, world);
+    let text = &heap.ui_text;
+
+    let ui = &mut heap.ui;
+    ui.begin(world);
+    ui.panel(world, 220.0, 66.0, 200.0, 252.0);
+    ui.begin_list(228.0, 74.0, 184.0);
+    ui.label(world, &text[0]);
+
+    let resume = ui.button(world, &text[1]);
+    let restart = ui.button(world, &text[2]);
+    let controls = ui.toggle(world, &text[3], &mut cache.show_controls);
+    let retro = ui.toggle(world, &text[4], &mut cache.retro);
+    let language = ui.button(world, &text[5]);
+    let colors = ui.button(world, &text[6]);
+    let contrast = ui.toggle(world, &text[7], &mut cache.high_contrast);
+    let symbols = ui.toggle(world, &text[8], &mut cache.symbols);
+    let quit = ui.button(world, &text[9]);
+    ui.end(world);
+
+    if colors {
+        cache.colorblind = (cache.colorblind + 1) % mgfw::ecs::COLORBLIND_MODES;
+        world.accessibility_set_colorblind(cache.colorblind);
+        save_settings(cache, heap);
+        // the button label names the filter
+        cache.ui_dirty = true;
+    }
+
+    if contrast {
+        world.accessibility_set_high_contrast(cache.high_contrast);
+        save_settings(cache, heap);
+    }
+
+    if symbols {
+        world.accessibility_set_symbols(cache.symbols);
+        save_settings(cache, heap);
+    }
+
+    if language {
+        next_language(world);
+        heap.language = String::from(world.get_language());
+        save_settings(cache, heap);
+        cache.ui_dirty = true;
+    }
+
+    if controls {
+        update_ui(cache, world);
+        save_settings(cache, heap);
+    }
+
+    if retro {
+        set_retro(cache, world);
+        save_settings(cache, heap);
+    }
+
+    if quit {
+        exit(0);
+    }
+
+    if resume {
+        cache.paused = false;
+        heap.ui.hide(world);
+    }
+
+    if restart {
+        reset(cache, heap, world);
+        load_level(cache, heap, world, cache.level);
+        append_overlays(cache, world);
+        update_ui(cache, world);
+        update_overlay(cache, world);
+        update_entities(cache, heap, world);
+    }
+}
+
 fn is_block_open(cache: &mut GameData, heap: &mut GameDataHeap, tgt: (usize, usize)) -> bool {
 
     if tgt.0 >= cache.map_width { return false; }
@@ -530,11 +761,112 @@ fn try_push_or_move(cache: &mut GameData, heap: &mut GameDataHeap, world: &mut m
     
     if check_win(cache, heap) {
         cache.overlay_lock = true;
+        world.entity_set_text_key(cache.overlay_ent_start + 2, "win", &[cache.move_counter.to_string()]);
+        if !heap.solved.contains(&cache.level) {
+            heap.solved.push(cache.level);
+            save_progress(cache, heap);
+        }
+
+        let mode = level_mode(cache);
+        if heap.leaderboard.qualifies(&mode, cache.move_counter as f64).is_some() {
+            heap.name_entry = Some(mgfw::ui::NameEntry::from_name(&heap.last_name, 3));
+            heap.ui.set_focus(0);
+        }
     }
 
     update_ui(cache, world);
 }
 
+fn save_settings(cache: &GameData, heap: &GameDataHeap) {
+    let settings = Settings {
+        show_controls: cache.show_controls,
+        name: heap.last_name.clone(),
+        retro: cache.retro,
+        language: heap.language.clone(),
+        colorblind: cache.colorblind,
+        high_contrast: cache.high_contrast,
+        symbols: cache.symbols,
+    };
+    if let Some(storage) = &heap.storage {
+        if let Err(e) = storage.save_settings(&settings) {
+            log(format!("WARNING: could not save settings: {}", e));
+        }
+    }
+}
+
+// cycles through the string tables in assets/strings
+fn next_language(world: &mut mgfw::ecs::World) {
+    let languages = world.get_languages();
+    let idx = languages.iter().position(|l| l == world.get_language()).map_or(0, |i| i + 1);
+    if let Some(language) = languages.get(idx % languages.len().max(1)) {
+        if let Err(e) = world.set_language(language) {
+            log(format!("WARNING: {}", e));
+        }
+    }
+}
+
+// passes are defined in assets/mgfw/postfx.dat
+fn set_retro(cache: &GameData, world: &mut mgfw::ecs::World) {
+    for pass in ["bloom", "crt", "vignette"] {
+        world.postfx_set_enabled(pass, cache.retro);
+    }
+}
+
+fn colorblind_name(world: &mut mgfw::ecs::World, mode: u8) -> String {
+    match mode {
+        mgfw::ecs::COLORBLIND_DEUTERANOPIA => world.tr("colors_deutan", &[]),
+        mgfw::ecs::COLORBLIND_PROTANOPIA => world.tr("colors_protan", &[]),
+        mgfw::ecs::COLORBLIND_TRITANOPIA => world.tr("colors_tritan", &[]),
+        _ => world.tr("colors_normal", &[]),
+    }
+}
+
+fn level_mode(cache: &GameData) -> String {
+    format!("level{}", cache.level + 1)
+}
+
+// best moves for the level under the win overlay, with name entry for a new best
+fn update_scores(cache: &mut GameData, heap: &mut GameDataHeap, world: &mut mgfw::ecs::World) {
+    let mode = level_mode(cache);
+    ui_text(cache, heap, world);
+    let new_best = &heap.ui_text[10];
+    let fewest_moves = &heap.ui_text[11];
+
+    let ui = &mut heap.ui;
+    ui.begin(world);
+    ui.panel(world, 220.0, 216.0, 200.0, 156.0);
+    ui.begin_list(228.0, 224.0, 184.0);
+    let done = match &mut heap.name_entry {
+        Some(entry) => ui.name_entry(world, new_best, entry),
+        None => { ui.label(world, fewest_moves); false },
+    };
+    heap.leaderboard.draw(ui, world, &mode, heap.score_rank);
+    ui.end(world);
+
+    if done {
+        let name = heap.name_entry.take().map(|e| e.get_name()).unwrap_or_default();
+        heap.score_rank = heap.leaderboard.submit(&mode, &name, cache.move_counter as f64);
+        heap.last_name = name;
+        // swap the name entry for the table with the new best highlighted
+        cache.ui_dirty = true;
+        if let Some(storage) = &heap.storage {
+            if let Err(e) = heap.leaderboard.save(storage) {
+                log(format!("WARNING: could not save leaderboard: {}", e));
+            }
+        }
+        save_settings(cache, heap);
+    }
+}
+
+fn save_progress(cache: &GameData, heap: &GameDataHeap) {
+    let progress = Progress { level: cache.level, solved: heap.solved.clone() };
+    if let Some(storage) = &heap.storage {
+        if let Err(e) = storage.save("progress", &progress) {
+            log(format!("WARNING: could not save progress: {}", e));
+        }
+    }
+}
+
 pub fn check_win(cache: &mut GameData, heap: &mut GameDataHeap) -> bool {
 
     for i in 0..heap.files.len() {
@@ -545,21 +877,39 @@ pub fn check_win(cache: &mut GameData, heap: &mut GameDataHeap) -> bool {
     true
 }
 
+#[rustfmt::skip]
+pub fn command(cache: &mut GameData, heap: &mut GameDataHeap, world: &mut mgfw::ecs::World, args: &[String]) -> Result<String, String> {
+    let a: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
+    let idx = match a[..] {
+        ["level", n] => match n.parse::<usize>() {
+            Ok(n) if 0 < n && n <= heap.level_data.len() => n - 1,
+            _ => return Err(format!("level must be 1 to {}", heap.level_data.len())),
+        },
+        ["restart"] => cache.level,
+        _ => return Err(String::from("usage: level <n> | restart")),
+    };
+
+    reset(cache, heap, world);
+    load_level(cache, heap, world, idx);
+    append_overlays(cache, world);
+    update_ui(cache, world);
+    update_overlay(cache, world);
+    update_entities(cache, heap, world);
+    Ok(format!("level {}", cache.level + 1))
+}
+
 pub fn shutdown(_cache: &mut GameData, heap: &mut GameDataHeap) {
     // deallocate and overwrite existing memory
     *heap = GameDataHeap::default();
-
-    // re-box and consume
-    //let _temp = unsafe { Box::from_raw(cache.heap) };
 }
 
-// this gets called by MGFW at 1200hz
+// this gets called by MGFW once per fixed update step, `micros` long
 #[rustfmt::skip]
-pub fn update(cache: &mut GameData, heap: &mut GameDataHeap, world: &mut mgfw::ecs::World) -> bool {
+pub fn update(cache: &mut GameData, heap: &mut GameDataHeap, world: &mut mgfw::ecs::World, micros: u128) -> bool {
     let mut expect_blown = false;
     cache.frame = (cache.frame + 1) % 128;
 
-    let dt = 1.0 / 1200.0;
+    let dt = micros as f32 * 1.0e-6;
 
     if !cache.ready {
         if 127 == cache.frame {
@@ -573,6 +923,7 @@ pub fn update(cache: &mut GameData, heap: &mut GameDataHeap, world: &mut mgfw::e
         cache.overlay_alpha += dt * 2.0;
         if 1.0 < cache.overlay_alpha {
             cache.overlay_alpha = 1.0;
+            cache.ui_dirty = true;
             for i in 0..cache.player_ent+1 {
                 world.entity_set_alpha(i, 0.0);
             }
@@ -598,6 +949,22 @@ pub fn update(cache: &mut GameData, heap: &mut GameDataHeap, world: &mut mgfw::e
         update_overlay(cache, world);
     }
 
+    // keep mouse hover up to date
+    let mouse = (world.mouse_x, world.mouse_y);
+    if mouse != cache.ui_mouse {
+        cache.ui_mouse = mouse;
+        cache.ui_dirty = true;
+    }
+
+    if cache.ui_dirty {
+        cache.ui_dirty = false;
+        if cache.paused {
+            update_menu(cache, heap, world);
+        } else if cache.overlay_lock && 1.0 - 1.0e-20 <= cache.overlay_alpha {
+            update_scores(cache, heap, world);
+        }
+    }
+
     for i in 0..heap.flame_idx.len()
     {
         heap.flame_time[i] -= dt;
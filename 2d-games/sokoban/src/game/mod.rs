@@ -4,48 +4,40 @@ use crate::mgfw;
 use crate::mgfw::*;
 
 pub struct GameWrapper {
-    data: *mut game::GameData,
+    data: mgfw::cache::CacheBlock<game::GameData>,
     // WARNING: Anything below this line is not in cache!
+    heap: std::boxed::Box<game::GameDataHeap>,
 }
 
 impl GameWrapper {
     #[rustfmt::skip]
     pub fn new(mgr: &mut mgfw::cache::CacheManager) -> GameWrapper {
         log(format!("Constructing Game"));
-        let data = mgr.allocate(std::mem::size_of::<game::GameData>()) as *mut game::GameData;
-        let cache: &mut game::GameData = unsafe { &mut *(data.offset(0)) };
-        cache.heap = Box::into_raw(Box::new(game::GameDataHeap::default()));
-        GameWrapper { data }
+        let data = mgr.allocate("Game", 1);
+        let heap = Box::new(game::GameDataHeap::default());
+        GameWrapper { data, heap }
     }
 
     pub fn initialize(&mut self, world: &mut mgfw::ecs::World) {
         log(format!("Initializing Game"));
-        game::initialize(self.get_cache_ref_mut(), self.get_heap_ref_mut(), world);
+        game::initialize(&mut self.data[0], &mut self.heap, world);
     }
 
-    pub fn update(&mut self, world: &mut mgfw::ecs::World, _micros: u128) -> bool {
-        game::update(self.get_cache_ref_mut(), self.get_heap_ref_mut(), world)
+    pub fn update(&mut self, world: &mut mgfw::ecs::World, micros: u128) -> bool {
+        game::update(&mut self.data[0], &mut self.heap, world, micros)
     }
 
     pub fn event(&mut self, world: &mut mgfw::ecs::World, event_id: u8) -> bool {
-        game::event(
-            self.get_cache_ref_mut(),
-            self.get_heap_ref_mut(),
-            world,
-            event_id,
-        )
+        game::event(&mut self.data[0], &mut self.heap, world, event_id)
     }
 
-    pub fn shutdown(&mut self) {
-        log(format!("Shutdown Game"));
-        game::shutdown(self.get_cache_ref_mut(), self.get_heap_ref_mut());
-    }
-
-    fn get_cache_ref_mut(&self) -> &mut game::GameData {
-        unsafe { &mut *(self.data.offset(0)) }
+    // a command registered with World::console_register, typed in the developer console
+    pub fn command(&mut self, world: &mut mgfw::ecs::World, args: &[String]) -> Result<String, String> {
+        game::command(&mut self.data[0], &mut self.heap, world, args)
     }
 
-    fn get_heap_ref_mut(&self) -> &mut game::GameDataHeap {
-        unsafe { &mut *(self.get_cache_ref_mut().heap) }
+    pub fn shutdown(&mut self) {
+        log(format!("Shutdown Game"));
+        game::shutdown(&mut self.data[0], &mut self.heap);
     }
 }
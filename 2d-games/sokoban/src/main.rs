@@ -6,8 +6,71 @@ const XRES: i32 = 640;
 const YRES: i32 = 384;
 
 fn main() {
-    let el = glutin::event_loop::EventLoop::new();
-    let mut core = mgfw::Core::new(TITLE, XRES, YRES, &el);
+    // check world files without opening a window:
+    //   sokoban --validate [world.dat...]
+    let args: Vec<String> = std::env::args().collect();
+    if 1 < args.len() && "--validate" == args[1] {
+        let mut files: Vec<String> = args[2..].iter().map(|f| cwd_path(f)).collect();
+        if files.is_empty() {
+            files.push(String::from("assets/world.dat"));
+        }
+        let ok = mgfw::ecs::validate_world_files(&files);
+        std::process::exit(if ok { 0 } else { 1 });
+    }
+
+    // check string tables against the keys each game uses:
+    //   sokoban --check-strings [game dir...]
+    if 1 < args.len() && "--check-strings" == args[1] {
+        let mut dirs = args[2..].to_vec();
+        if dirs.is_empty() {
+            dirs.push(String::from("."));
+        }
+        let ok = mgfw::locale::check_strings(&dirs);
+        std::process::exit(if ok { 0 } else { 1 });
+    }
+
+    // convert a world file to the structured scene format:
+    //   sokoban --convert world.dat world.ron
+    if 1 < args.len() && "--convert" == args[1] {
+        if 4 != args.len() {
+            println!("usage: --convert <world.dat> <scene.ron>");
+            std::process::exit(1);
+        }
+        match mgfw::ecs::convert_world_file(&cwd_path(&args[2]), &args[3]) {
+            Ok(n) => println!("{}: wrote {} entities", args[3], n),
+            Err(e) => {
+                println!("error: {}", e);
+                std::process::exit(1);
+            }
+        }
+        std::process::exit(0);
+    }
+
+    // pack ./assets into an archive the game can load instead:
+    //   sokoban --pack-assets [assets.pak]
+    if 1 < args.len() && "--pack-assets" == args[1] {
+        let out = match args.get(2) {
+            Some(f) => f.clone(),
+            None => String::from(mgfw::assets::PACK_FILE),
+        };
+        match mgfw::assets::write_pack(std::path::Path::new("."), std::path::Path::new(&out)) {
+            Ok(n) => println!("{}: packed {} files", out, n),
+            Err(e) => {
+                println!("error: {}", e);
+                std::process::exit(1);
+            }
+        }
+        std::process::exit(0);
+    }
+
+    let el = match mgfw::new_event_loop() {
+        Ok(el) => el,
+        Err(e) => mgfw::error::exit_with(&e),
+    };
+    let mut core = match mgfw::Core::new(TITLE, XRES, YRES, &el) {
+        Ok(core) => core,
+        Err(e) => mgfw::error::exit_with(&e),
+    };
 
     el.run(move |event, _, control_flow| {
         if !core.check_events(&event) {
@@ -15,3 +78,10 @@ fn main() {
         }
     });
 }
+
+// files named on the command line are relative to the working directory,
+// not the asset root
+fn cwd_path(file: &str) -> String {
+    let cwd = std::env::current_dir().unwrap_or_default();
+    cwd.join(file).to_string_lossy().into_owned()
+}
@@ -0,0 +1,239 @@
+pub mod pack;
+
+use super::log;
+use pack::Pack;
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+// Asset file system. Paths like "assets/file.png" are read from
+//
+//   1. loose files under the asset root
+//   2. the asset archive: built into the executable with the embed-assets
+//      feature, or assets.pak next to the executable or in the root
+//
+// so loose files override the archive. The root is MGFW_ASSETS when set,
+// else the nearest directory holding assets/ from the executable upwards
+// (which also finds the project from target/debug), else the working
+// directory. `--pack-assets [file]` writes an archive of ./assets.
+
+pub const ASSETS_ENV: &str = "MGFW_ASSETS";
+pub const PACK_FILE: &str = "assets.pak";
+// target/debug/deps is three levels below the project
+const ROOT_SEARCH_DEPTH: usize = 4;
+
+static VFS: Mutex<Option<Vfs>> = Mutex::new(None);
+
+struct Vfs {
+    root: PathBuf,
+    pack: Option<Pack>,
+}
+
+impl Vfs {
+    fn new(root: Option<&Path>) -> Vfs {
+        let root = match root {
+            Some(r) => r.to_path_buf(),
+            None => find_root(),
+        };
+        let pack = match embedded() {
+            Some(p) => Some(p),
+            None => load_pack(&root),
+        };
+        Vfs { root, pack }
+    }
+
+    // where the loose file for an asset would be, absolute paths are kept
+    fn path(&self, name: &str) -> PathBuf {
+        match Path::new(name).is_absolute() {
+            true => PathBuf::from(name),
+            false => self.root.join(name),
+        }
+    }
+
+    fn read(&self, name: &str) -> Result<Vec<u8>, String> {
+        match std::fs::read(self.path(name)) {
+            Ok(data) => Ok(data),
+            Err(e) => self
+                .pack
+                .as_ref()
+                .and_then(|p| p.get(name))
+                .map(|d| d.to_vec())
+                .ok_or_else(|| format!("{}: {}", name, e)),
+        }
+    }
+
+    fn list(&self, dir: &str) -> Vec<String> {
+        let mut ret = Vec::new();
+        if let Ok(entries) = std::fs::read_dir(self.path(dir)) {
+            for e in entries.flatten() {
+                if e.path().is_file() {
+                    ret.push(e.file_name().to_string_lossy().into_owned());
+                }
+            }
+        }
+        let prefix = format!("{}/", pack::normalize(dir));
+        if let Some(p) = &self.pack {
+            for name in p.names() {
+                if let Some(file) = name.strip_prefix(&prefix) {
+                    if !file.contains('/') {
+                        ret.push(String::from(file));
+                    }
+                }
+            }
+        }
+        ret.sort();
+        ret.dedup();
+        ret
+    }
+}
+
+fn with<T>(f: impl FnOnce(&Vfs) -> T) -> T {
+    let mut vfs = VFS.lock().unwrap_or_else(|e| e.into_inner());
+    f(vfs.get_or_insert_with(|| Vfs::new(None)))
+}
+
+fn exe_dir() -> Option<PathBuf> {
+    std::env::current_exe()
+        .ok()
+        .and_then(|p| p.parent().map(|d| d.to_path_buf()))
+}
+
+fn find_root() -> PathBuf {
+    if let Some(dir) = std::env::var_os(ASSETS_ENV) {
+        return PathBuf::from(dir);
+    }
+    if let Some(exe) = exe_dir() {
+        for dir in exe.ancestors().take(ROOT_SEARCH_DEPTH) {
+            if dir.join("assets").is_dir() {
+                return dir.to_path_buf();
+            }
+        }
+    }
+    PathBuf::from(".")
+}
+
+fn load_pack(root: &Path) -> Option<Pack> {
+    let mut candidates = vec![root.join(PACK_FILE)];
+    if let Some(exe) = exe_dir() {
+        candidates.insert(0, exe.join(PACK_FILE));
+    }
+    for file in candidates {
+        if let Ok(data) = std::fs::read(&file) {
+            match Pack::parse(Cow::Owned(data)) {
+                Ok(p) => return Some(p),
+                Err(e) => log(format!("WARNING: Assets: {}: {}", file.display(), e)),
+            }
+        }
+    }
+    None
+}
+
+#[cfg(feature = "embed-assets")]
+fn embedded() -> Option<Pack> {
+    let data: &'static [u8] = include_bytes!(concat!(env!("OUT_DIR"), "/assets.pak"));
+    match Pack::parse(Cow::Borrowed(data)) {
+        Ok(p) => Some(p),
+        Err(e) => {
+            log(format!("ERROR: Assets: embedded archive: {}", e));
+            None
+        }
+    }
+}
+
+#[cfg(not(feature = "embed-assets"))]
+fn embedded() -> Option<Pack> {
+    None
+}
+
+// None finds the root as described above
+pub fn init(root: Option<&Path>) {
+    let vfs = Vfs::new(root);
+    log(format!("Assets: Root '{}'", vfs.root.display()));
+    if let Some(p) = &vfs.pack {
+        log(format!("Assets: Archive with {} files", p.len()));
+    }
+    *VFS.lock().unwrap_or_else(|e| e.into_inner()) = Some(vfs);
+}
+
+#[allow(dead_code)]
+pub fn get_root() -> PathBuf {
+    with(|vfs| vfs.root.clone())
+}
+
+// where the loose file for an asset would be, absolute paths are kept
+pub fn path(name: &str) -> PathBuf {
+    with(|vfs| vfs.path(name))
+}
+
+pub fn read(name: &str) -> Result<Vec<u8>, String> {
+    with(|vfs| vfs.read(name))
+}
+
+pub fn read_to_string(name: &str) -> Result<String, String> {
+    String::from_utf8(read(name)?).map_err(|e| format!("{}: {}", name, e))
+}
+
+pub fn read_image(name: &str) -> Result<image::DynamicImage, String> {
+    let data = read(name)?;
+    let img = match image::ImageFormat::from_path(name) {
+        Ok(format) => image::load_from_memory_with_format(&data, format),
+        Err(_) => image::load_from_memory(&data),
+    };
+    img.map_err(|e| format!("{}: {}", name, e))
+}
+
+#[allow(dead_code)]
+pub fn exists(name: &str) -> bool {
+    path(name).is_file() || with(|vfs| vfs.pack.as_ref().is_some_and(|p| p.get(name).is_some()))
+}
+
+// file names directly in dir, loose and archived, sorted
+pub fn list(dir: &str) -> Vec<String> {
+    with(|vfs| vfs.list(dir))
+}
+
+// of the loose file, archived assets never change
+pub fn modified(name: &str) -> Option<SystemTime> {
+    std::fs::metadata(path(name)).and_then(|m| m.modified()).ok()
+}
+
+// archive of dir/assets, returns the number of files
+pub fn write_pack(dir: &Path, out: &Path) -> Result<usize, String> {
+    let files = pack::collect(dir)?;
+    std::fs::write(out, pack::write(&files)).map_err(|e| format!("{}: {}", out.display(), e))?;
+    Ok(files.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loose_files_override_the_archive() {
+        let root = std::env::temp_dir().join(format!("mgfw-assets-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("assets/levels")).unwrap();
+        std::fs::write(root.join("assets/levels/1.dat"), "loose").unwrap();
+        std::fs::write(root.join("assets/levels/3.dat"), "loose only").unwrap();
+
+        let files = vec![
+            (String::from("assets/levels/1.dat"), b"packed".to_vec()),
+            (String::from("assets/levels/2.dat"), b"packed only".to_vec()),
+            (String::from("assets/levels/sub/4.dat"), Vec::new()),
+        ];
+        let vfs = Vfs {
+            root: root.clone(),
+            pack: Some(Pack::parse(Cow::Owned(pack::write(&files))).unwrap()),
+        };
+
+        assert_eq!(b"loose".to_vec(), vfs.read("assets/levels/1.dat").unwrap());
+        assert_eq!(b"packed only".to_vec(), vfs.read("assets/levels/2.dat").unwrap());
+        assert_eq!(b"loose only".to_vec(), vfs.read("assets/levels/3.dat").unwrap());
+        assert!(vfs.read("assets/levels/5.dat").is_err());
+
+        // listed once, sub directories left out
+        assert_eq!(vec!["1.dat", "2.dat", "3.dat"], vfs.list("assets/levels"));
+
+        std::fs::remove_dir_all(&root).ok();
+    }
+}
@@ -0,0 +1,211 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::path::Path;
+
+// Asset archive, every file under a game's assets/ in one blob:
+//
+//   "MGPK", u32 version, u32 file count
+//   per file: u16 name length, name, u64 offset, u64 size (offset into the data)
+//   data
+//
+// Integers are little endian, names are "assets/..." with / separators.
+// build.rs includes this file too, so it only uses std.
+
+pub const PACK_MAGIC: &[u8; 4] = b"MGPK";
+pub const PACK_VERSION: u32 = 1;
+
+pub struct Pack {
+    data: Cow<'static, [u8]>,
+    // name -> (start, end) in data
+    entries: HashMap<String, (usize, usize)>,
+}
+
+impl Pack {
+    pub fn parse(data: Cow<'static, [u8]>) -> Result<Pack, String> {
+        let mut r = Reader { data: &data, pos: 0 };
+        if PACK_MAGIC != r.bytes(4)? {
+            return Err(String::from("not an asset archive"));
+        }
+        let version = r.u32()?;
+        if PACK_VERSION != version {
+            return Err(format!("unsupported archive version {}", version));
+        }
+
+        let count = r.u32()? as usize;
+        // the count comes from the file, don't trust it with an allocation
+        let mut index = Vec::with_capacity(count.min(data.len()));
+        for _ in 0..count {
+            let len = r.u16()? as usize;
+            let name = String::from_utf8(r.bytes(len)?.to_vec())
+                .map_err(|_| String::from("bad file name"))?;
+            let offset = r.u64()?;
+            let size = r.u64()?;
+            index.push((name, offset, size));
+        }
+
+        let base = r.pos;
+        let mut entries = HashMap::new();
+        for (name, offset, size) in index {
+            match entry_range(base, offset, size) {
+                Some((start, end)) if end <= data.len() => entries.insert(name, (start, end)),
+                _ => return Err(format!("{}: past the end of the archive", name)),
+            };
+        }
+        Ok(Pack { data, entries })
+    }
+
+    pub fn get(&self, name: &str) -> Option<&[u8]> {
+        self.entries
+            .get(&normalize(name))
+            .map(|(start, end)| &self.data[*start..*end])
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &String> {
+        self.entries.keys()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+}
+
+// (start, end) in the data, None if it doesn't fit in usize
+fn entry_range(base: usize, offset: u64, size: u64) -> Option<(usize, usize)> {
+    let start = base.checked_add(usize::try_from(offset).ok()?)?;
+    let end = start.checked_add(usize::try_from(size).ok()?)?;
+    Some((start, end))
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, n: usize) -> Result<&'a [u8], String> {
+        if self.pos + n > self.data.len() {
+            return Err(String::from("truncated archive"));
+        }
+        self.pos += n;
+        Ok(&self.data[self.pos - n..self.pos])
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        let b = self.bytes(2)?;
+        Ok(u16::from_le_bytes([b[0], b[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        let mut b = [0u8; 4];
+        b.copy_from_slice(self.bytes(4)?);
+        Ok(u32::from_le_bytes(b))
+    }
+
+    fn u64(&mut self) -> Result<u64, String> {
+        let mut b = [0u8; 8];
+        b.copy_from_slice(self.bytes(8)?);
+        Ok(u64::from_le_bytes(b))
+    }
+}
+
+pub fn write(files: &[(String, Vec<u8>)]) -> Vec<u8> {
+    let mut ret = Vec::new();
+    ret.extend_from_slice(PACK_MAGIC);
+    ret.extend_from_slice(&PACK_VERSION.to_le_bytes());
+    ret.extend_from_slice(&(files.len() as u32).to_le_bytes());
+
+    let mut offset: u64 = 0;
+    for (name, data) in files {
+        let name = normalize(name);
+        ret.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        ret.extend_from_slice(name.as_bytes());
+        ret.extend_from_slice(&offset.to_le_bytes());
+        ret.extend_from_slice(&(data.len() as u64).to_le_bytes());
+        offset += data.len() as u64;
+    }
+    for (_, data) in files {
+        ret.extend_from_slice(data);
+    }
+    ret
+}
+
+// every file under dir/assets, sorted by name
+pub fn collect(dir: &Path) -> Result<Vec<(String, Vec<u8>)>, String> {
+    let mut ret = Vec::new();
+    let mut pending = vec![String::from("assets")];
+    while let Some(d) = pending.pop() {
+        let entries = std::fs::read_dir(dir.join(&d)).map_err(|e| format!("{}: {}", d, e))?;
+        for e in entries.flatten() {
+            let name = format!("{}/{}", d, e.file_name().to_string_lossy());
+            if e.path().is_dir() {
+                pending.push(name);
+            } else {
+                let data = std::fs::read(e.path()).map_err(|e| format!("{}: {}", name, e))?;
+                ret.push((name, data));
+            }
+        }
+    }
+    ret.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(ret)
+}
+
+// archive name of a relative path: / separators, no "." or ".." parts
+pub fn normalize(name: &str) -> String {
+    let mut parts: Vec<&str> = Vec::new();
+    for p in name.split(['/', '\\']) {
+        match p {
+            "" | "." => (),
+            ".." => {
+                parts.pop();
+            }
+            _ => parts.push(p),
+        }
+    }
+    parts.join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let files = vec![
+            (String::from("assets/a.dat"), b"hello".to_vec()),
+            (String::from("assets/img/b.png"), vec![1, 2, 3]),
+            (String::from("assets/empty"), Vec::new()),
+        ];
+        let pack = Pack::parse(Cow::Owned(write(&files))).unwrap();
+        assert_eq!(3, pack.len());
+        assert_eq!(Some(&b"hello"[..]), pack.get("assets/a.dat"));
+        assert_eq!(Some(&[1u8, 2, 3][..]), pack.get("./assets/maps/../img/b.png"));
+        assert_eq!(Some(&[][..]), pack.get("assets\\empty"));
+        assert_eq!(None, pack.get("assets/c.dat"));
+
+        let mut data = write(&files);
+        data.truncate(data.len() - 1);
+        assert!(Pack::parse(Cow::Owned(data)).is_err());
+        assert!(Pack::parse(Cow::Borrowed(b"PK\x03\x04")).is_err());
+    }
+
+    #[test]
+    fn reject_out_of_range_entries() {
+        let files = vec![(String::from("assets/a.dat"), b"hello".to_vec())];
+        let data = write(&files);
+        // offset and size of the only entry follow its name
+        let offset_at = 4 + 4 + 4 + 2 + "assets/a.dat".len();
+
+        for (offset, size) in [(u64::MAX, 1), (0, u64::MAX), (u64::MAX - 2, 4), (1, 5)] {
+            let mut data = data.clone();
+            data[offset_at..offset_at + 8].copy_from_slice(&offset.to_le_bytes());
+            data[offset_at + 8..offset_at + 16].copy_from_slice(&size.to_le_bytes());
+            let e = Pack::parse(Cow::Owned(data)).err().unwrap();
+            assert_eq!("assets/a.dat: past the end of the archive", e);
+        }
+
+        // a file count far beyond the data runs out of index instead
+        let mut data = data.clone();
+        data[8..12].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(Pack::parse(Cow::Owned(data)).is_err());
+    }
+}
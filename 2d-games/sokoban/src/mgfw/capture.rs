@@ -0,0 +1,496 @@
+use super::log;
+use super::support::Gl;
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, Frame, RgbaImage};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::{Duration, Instant, SystemTime};
+
+// Screenshots and gameplay clips of the offscreen framebuffer, the game at
+// its own resolution before post-processing and overlays:
+//
+//   F12  screenshot_<ms>.png
+//   F9   clip_<ms>.gif, F10 clip_<ms>.png (APNG)
+//
+// Clips record CAPTURE_SECONDS at CAPTURE_FPS, MGFW_CAPTURE_SECONDS changes
+// the length and pressing the key again stops early. Files go next to the
+// logs in <data dir>/<title>/captures.
+//
+// Frames are read into a ring of pixel buffers and mapped READBACK_LATENCY
+// frames later, once the GPU is done with them, so the render thread never
+// waits on a readback. Flipping, encoding and saving run on worker threads.
+
+pub const CAPTURE_GIF: u8 = 0;
+pub const CAPTURE_APNG: u8 = 1;
+pub const CAPTURE_SECONDS: f32 = 5.0;
+// 50 ms frames, a whole number of GIF centiseconds
+pub const CAPTURE_FPS: u32 = 20;
+const CAPTURE_SECONDS_ENV: &str = "MGFW_CAPTURE_SECONDS";
+const RING_SIZE: usize = 3;
+const READBACK_LATENCY: usize = 2;
+// 1 is best quality, 30 fastest
+const GIF_SPEED: i32 = 10;
+
+struct Pending {
+    frame: usize,
+    time: Instant,
+    screenshot: bool,
+    clip: bool,
+}
+
+struct Recording {
+    sender: mpsc::Sender<(Vec<u8>, Instant)>,
+    end: Instant,
+    next_frame: Instant,
+    stopping: bool,
+}
+
+pub struct Capture {
+    dir: PathBuf,
+    width: u32,
+    height: u32,
+    seconds: f32,
+    ring: Vec<u32>,
+    pending: Vec<Option<Pending>>,
+    next: usize,
+    frame: usize,
+    screenshot: bool,
+    recording: Option<Recording>,
+    workers: Vec<std::thread::JoinHandle<()>>,
+}
+
+#[allow(dead_code)]
+impl Capture {
+    pub fn new(title: &str, gl: &Gl) -> Capture {
+        log(format!("Constructing Capture"));
+        let dir = super::logging::default_dir(title).with_file_name("captures");
+        let seconds = match std::env::var(CAPTURE_SECONDS_ENV) {
+            Ok(s) => match s.parse::<f32>() {
+                Ok(v) if 0.0 < v => v,
+                _ => {
+                    log(format!("WARNING: Capture: ignoring {}={}", CAPTURE_SECONDS_ENV, s));
+                    CAPTURE_SECONDS
+                }
+            },
+            Err(_) => CAPTURE_SECONDS,
+        };
+        let (width, height) = gl.get_frame_size();
+        Capture {
+            dir,
+            width,
+            height,
+            seconds,
+            ring: Vec::new(),
+            pending: Vec::new(),
+            next: 0,
+            frame: 0,
+            screenshot: false,
+            recording: None,
+            workers: Vec::new(),
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.as_ref().is_some_and(|r| !r.stopping)
+    }
+
+    // saved after the next rendered frame
+    pub fn screenshot(&mut self) {
+        self.screenshot = true;
+    }
+
+    pub fn toggle_recording(&mut self, format: u8) {
+        if self.is_recording() {
+            self.stop_recording();
+            return;
+        }
+        if self.recording.is_some() {
+            log(format!("WARNING: Capture: still finishing the last clip"));
+            return;
+        }
+
+        let ext = match format {
+            CAPTURE_APNG => "png",
+            _ => "gif",
+        };
+        let file = self.dir.join(format!("clip_{}.{}", timestamp(), ext));
+        let (sender, receiver) = mpsc::channel();
+        let (width, height) = (self.width, self.height);
+        self.workers.push(std::thread::spawn(move || {
+            match record(receiver, format, width, height, &file) {
+                Ok(0) => log(format!("WARNING: Capture: no frames recorded")),
+                Ok(n) => log(format!("Capture: wrote {} frames to {}", n, file.display())),
+                Err(e) => log(format!("WARNING: Capture: failed to write {}", e)),
+            }
+        }));
+
+        let now = Instant::now();
+        self.recording = Some(Recording {
+            sender,
+            end: now + Duration::from_secs_f32(self.seconds),
+            next_frame: now,
+            stopping: false,
+        });
+        log(format!("Capture: recording {} seconds", self.seconds));
+    }
+
+    pub fn stop_recording(&mut self) {
+        if let Some(r) = &mut self.recording {
+            r.stopping = true;
+        }
+    }
+
+    // after the scene is rendered to the offscreen framebuffer, once per frame
+    pub fn update(&mut self, gl: &Gl) {
+        if self.ring.is_empty() {
+            if !self.screenshot && self.recording.is_none() {
+                return;
+            }
+            self.ring = (0..RING_SIZE).map(|_| gl.gen_frame_buffer()).collect();
+            self.pending = (0..RING_SIZE).map(|_| None).collect();
+        }
+        self.frame += 1;
+        self.workers.retain(|w| !w.is_finished());
+
+        let now = Instant::now();
+        let screenshot = self.screenshot;
+        let mut clip = false;
+        if let Some(r) = &mut self.recording {
+            if !r.stopping && r.end <= now {
+                r.stopping = true;
+            }
+            if !r.stopping && r.next_frame <= now {
+                clip = true;
+                let interval = Duration::from_micros(1_000_000 / CAPTURE_FPS as u64);
+                r.next_frame += interval;
+                if r.next_frame < now {
+                    r.next_frame = now + interval;
+                }
+            }
+        }
+
+        if screenshot || clip {
+            self.screenshot = false;
+            let slot = self.next;
+            if self.pending[slot].is_some() {
+                self.collect(gl, slot);
+            }
+            gl.read_frame_async(self.ring[slot]);
+            self.pending[slot] = Some(Pending {
+                frame: self.frame,
+                time: now,
+                screenshot,
+                clip,
+            });
+            self.next = (slot + 1) % RING_SIZE;
+        }
+
+        // oldest first so clip frames stay in order
+        let frame = self.frame;
+        let mut ready: Vec<(usize, usize)> = self
+            .pending
+            .iter()
+            .enumerate()
+            .filter_map(|(i, p)| p.as_ref().map(|p| (p.frame, i)))
+            .filter(|(f, _)| f + READBACK_LATENCY <= frame)
+            .collect();
+        ready.sort();
+        for (_, slot) in ready {
+            self.collect(gl, slot);
+        }
+
+        // the encoder finishes once the last frames are handed over
+        let stopping = self.recording.as_ref().is_some_and(|r| r.stopping);
+        if stopping && !self.pending.iter().flatten().any(|p| p.clip) {
+            self.recording = None;
+        }
+    }
+
+    fn collect(&mut self, gl: &Gl, slot: usize) {
+        let pending = match self.pending[slot].take() {
+            Some(p) => p,
+            None => return,
+        };
+        let data = match gl.map_frame(self.ring[slot]) {
+            Some(d) => d,
+            None => {
+                log(format!("WARNING: Capture: failed to map frame"));
+                return;
+            }
+        };
+
+        // only copied when a frame is both
+        let mut frame = Some(data);
+        if pending.clip {
+            let clip = match pending.screenshot {
+                true => frame.clone(),
+                false => frame.take(),
+            };
+            if let (Some(r), Some(clip)) = (&self.recording, clip) {
+                let _ = r.sender.send((clip, pending.time));
+            }
+        }
+        if let Some(data) = frame {
+            let file = self.dir.join(format!("screenshot_{}.png", timestamp()));
+            let (width, height) = (self.width, self.height);
+            self.workers.push(std::thread::spawn(move || {
+                match save_png(data, width, height, &file) {
+                    Ok(_) => log(format!("Capture: wrote {}", file.display())),
+                    Err(e) => log(format!("WARNING: Capture: failed to write {}", e)),
+                }
+            }));
+        }
+    }
+
+    // finish outstanding frames and wait for files to be written
+    pub fn shutdown(&mut self, gl: &Gl) {
+        self.stop_recording();
+        for slot in 0..self.pending.len() {
+            self.collect(gl, slot);
+        }
+        self.recording = None;
+        for w in self.workers.drain(..) {
+            let _ = w.join();
+        }
+    }
+}
+
+fn timestamp() -> u128 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis())
+}
+
+// framebuffer rows are bottom to top
+fn to_image(data: Vec<u8>, width: u32, height: u32) -> Result<RgbaImage, String> {
+    let mut img = RgbaImage::from_raw(width, height, data)
+        .ok_or_else(|| format!("frame is not {}x{}", width, height))?;
+    image::imageops::flip_vertical_in_place(&mut img);
+    Ok(img)
+}
+
+fn create(file: &Path) -> Result<BufWriter<File>, String> {
+    if let Some(dir) = file.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    }
+    File::create(file)
+        .map(BufWriter::new)
+        .map_err(|e| format!("{}: {}", file.display(), e))
+}
+
+fn save_png(data: Vec<u8>, width: u32, height: u32, file: &Path) -> Result<(), String> {
+    let img = to_image(data, width, height)?;
+    create(file)?;
+    img.save_with_format(file, image::ImageFormat::Png)
+        .map_err(|e| format!("{}: {}", file.display(), e))
+}
+
+// each frame is shown until the next one was captured
+fn record(
+    receiver: mpsc::Receiver<(Vec<u8>, Instant)>,
+    format: u8,
+    width: u32,
+    height: u32,
+    file: &Path,
+) -> Result<usize, String> {
+    let err = |e: String| format!("{}: {}", file.display(), e);
+    let mut out = create(file)?;
+    let mut gif = match format {
+        CAPTURE_GIF => {
+            let mut encoder = GifEncoder::new_with_speed(out.get_mut(), GIF_SPEED);
+            encoder.set_repeat(Repeat::Infinite).map_err(|e| err(e.to_string()))?;
+            Some(encoder)
+        }
+        _ => None,
+    };
+    let mut apng = Apng::new(width, height);
+
+    let mut count = 0;
+    let mut prev: Option<(RgbaImage, Instant)> = None;
+    let mut add = |img: RgbaImage, ms: u32| -> Result<(), String> {
+        count += 1;
+        match &mut gif {
+            Some(encoder) => encoder
+                .encode_frame(Frame::from_parts(img, 0, 0, Delay::from_numer_denom_ms(ms, 1)))
+                .map_err(|e| err(e.to_string())),
+            None => {
+                apng.add_frame(&img, ms);
+                Ok(())
+            }
+        }
+    };
+    for (data, time) in receiver {
+        let img = to_image(data, width, height).map_err(err)?;
+        if let Some((p, t)) = prev.take() {
+            add(p, time.duration_since(t).as_millis() as u32)?;
+        }
+        prev = Some((img, time));
+    }
+    if let Some((p, _)) = prev {
+        add(p, 1000 / CAPTURE_FPS)?;
+    }
+    // writes the GIF trailer
+    drop(gif);
+
+    if CAPTURE_APNG == format && 0 < count {
+        apng.write(&mut out).map_err(|e| err(e.to_string()))?;
+    }
+    out.flush().map_err(|e| err(e.to_string()))?;
+    if 0 == count {
+        let _ = std::fs::remove_file(file);
+    }
+    Ok(count)
+}
+
+// Animated PNG, which the png crate can't write yet. Frames are full size
+// RGBA, compressed as they arrive and written out together since acTL holds
+// the frame count. The first frame doubles as the still image.
+pub struct Apng {
+    width: u32,
+    height: u32,
+    // zlib data, delay in ms
+    frames: Vec<(Vec<u8>, u32)>,
+}
+
+#[allow(dead_code)]
+impl Apng {
+    pub fn new(width: u32, height: u32) -> Apng {
+        Apng {
+            width,
+            height,
+            frames: Vec::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn add_frame(&mut self, img: &RgbaImage, delay_ms: u32) {
+        // every row with the Sub filter, cheap and good enough for game art
+        let stride = self.width as usize * 4;
+        let mut raw = Vec::with_capacity((stride + 1) * self.height as usize);
+        for row in img.as_raw().chunks(stride) {
+            raw.push(1);
+            for (i, b) in row.iter().enumerate() {
+                raw.push(match i < 4 {
+                    true => *b,
+                    false => b.wrapping_sub(row[i - 4]),
+                });
+            }
+        }
+        let mut z = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::fast());
+        let _ = z.write_all(&raw);
+        self.frames.push((z.finish().unwrap_or_default(), delay_ms));
+    }
+
+    pub fn write(&self, w: &mut impl Write) -> std::io::Result<()> {
+        w.write_all(b"\x89PNG\r\n\x1a\n")?;
+
+        let mut ihdr = Vec::new();
+        ihdr.extend_from_slice(&self.width.to_be_bytes());
+        ihdr.extend_from_slice(&self.height.to_be_bytes());
+        // 8 bit RGBA, deflate, adaptive filtering, no interlace
+        ihdr.extend_from_slice(&[8, 6, 0, 0, 0]);
+        write_chunk(w, b"IHDR", &ihdr)?;
+
+        let mut actl = Vec::new();
+        actl.extend_from_slice(&(self.frames.len() as u32).to_be_bytes());
+        // loop forever
+        actl.extend_from_slice(&0u32.to_be_bytes());
+        write_chunk(w, b"acTL", &actl)?;
+
+        let mut seq: u32 = 0;
+        for (n, (data, delay_ms)) in self.frames.iter().enumerate() {
+            let mut fctl = Vec::new();
+            fctl.extend_from_slice(&seq.to_be_bytes());
+            fctl.extend_from_slice(&self.width.to_be_bytes());
+            fctl.extend_from_slice(&self.height.to_be_bytes());
+            fctl.extend_from_slice(&0u32.to_be_bytes());
+            fctl.extend_from_slice(&0u32.to_be_bytes());
+            fctl.extend_from_slice(&((*delay_ms).min(u16::MAX as u32) as u16).to_be_bytes());
+            fctl.extend_from_slice(&1000u16.to_be_bytes());
+            // dispose none, blend source
+            fctl.extend_from_slice(&[0, 0]);
+            write_chunk(w, b"fcTL", &fctl)?;
+            seq += 1;
+
+            if 0 == n {
+                write_chunk(w, b"IDAT", data)?;
+            } else {
+                let mut fdat = Vec::with_capacity(data.len() + 4);
+                fdat.extend_from_slice(&seq.to_be_bytes());
+                fdat.extend_from_slice(data);
+                write_chunk(w, b"fdAT", &fdat)?;
+                seq += 1;
+            }
+        }
+        write_chunk(w, b"IEND", &[])
+    }
+}
+
+fn write_chunk(w: &mut impl Write, kind: &[u8; 4], data: &[u8]) -> std::io::Result<()> {
+    w.write_all(&(data.len() as u32).to_be_bytes())?;
+    w.write_all(kind)?;
+    w.write_all(data)?;
+    let mut crc = crc32fast::Hasher::new();
+    crc.update(kind);
+    crc.update(data);
+    w.write_all(&crc.finalize().to_be_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn apng_first_frame_decodes() {
+        let mut a = RgbaImage::new(3, 2);
+        a.put_pixel(2, 1, image::Rgba([10, 200, 30, 255]));
+        let b = RgbaImage::from_pixel(3, 2, image::Rgba([1, 2, 3, 4]));
+        let mut apng = Apng::new(3, 2);
+        apng.add_frame(&a, 50);
+        apng.add_frame(&b, 50);
+        assert_eq!(2, apng.len());
+
+        let mut data = Vec::new();
+        apng.write(&mut data).unwrap();
+        assert!(data.windows(4).any(|c| c == b"acTL"));
+        assert_eq!(1, data.windows(4).filter(|c| *c == b"fdAT").count());
+        let img = image::load_from_memory_with_format(&data, image::ImageFormat::Png)
+            .unwrap()
+            .to_rgba8();
+        assert_eq!(a, img);
+    }
+
+    #[test]
+    fn record_gif() {
+        use image::AnimationDecoder;
+        let file = std::env::temp_dir().join(format!("mgfw_capture_{}.gif", std::process::id()));
+        let (sender, receiver) = mpsc::channel();
+        let start = Instant::now();
+        for n in 0..3u8 {
+            let time = start + Duration::from_millis(100 * n as u64);
+            sender.send((vec![n * 80; 4 * 4 * 4], time)).unwrap();
+        }
+        drop(sender);
+        assert_eq!(Ok(3), record(receiver, CAPTURE_GIF, 4, 4, &file));
+
+        let decoder = image::codecs::gif::GifDecoder::new(File::open(&file).unwrap()).unwrap();
+        let frames = decoder.into_frames().collect_frames().unwrap();
+        let _ = std::fs::remove_file(&file);
+        assert_eq!(3, frames.len());
+        assert_eq!((100, 1), frames[0].delay().numer_denom_ms());
+        assert_eq!((1000 / CAPTURE_FPS, 1), frames[2].delay().numer_denom_ms());
+    }
+
+    #[test]
+    fn frames_are_flipped() {
+        let data = vec![1, 1, 1, 1, 2, 2, 2, 2];
+        let img = to_image(data, 1, 2).unwrap();
+        assert_eq!(&[2, 2, 2, 2, 1, 1, 1, 1], img.as_raw().as_slice());
+        assert!(to_image(vec![0; 3], 1, 2).is_err());
+    }
+}
@@ -0,0 +1,486 @@
+use super::ecs::component_render::*;
+use super::ecs::component_render_text::TextSpan;
+use super::ecs::entity::ENTITY_SZ;
+use super::ecs::*;
+use super::log;
+use super::logging;
+use super::support::Gl;
+use std::collections::VecDeque;
+use std::time::Instant;
+
+// Developer console, toggled with the ` key. Lists and inspects entities,
+// edits the selected one (clicking picks the entity under the mouse) and runs
+// the commands the game registered with World::console_register:
+//
+//   > list player
+//   > select 12
+//   > pos 100 50
+//   > level 42
+//
+// Keys go to the console instead of the game while it is open.
+
+const OUTPUT_SZ: usize = 200;
+const HISTORY_SZ: usize = 50;
+const OUTPUT_LINES: usize = 12;
+const REFRESH_MS: u128 = 100;
+
+const LINE_HEIGHT: f32 = 13.0;
+// the panel spans the bottom of the frame, the inspector its right side
+const PANEL_H: f32 = (OUTPUT_LINES + 2) as f32 * LINE_HEIGHT;
+const INSPECT_W: f32 = 240.0;
+
+const HELP: [&str; 12] = [
+    "help                 this list",
+    "list [filter]        entities, by id or name",
+    "select <id|name>     select, or click an entity",
+    "inspect              components of the selection",
+    "pos <x> <y>          move the selection",
+    "scale <x> <y>        resize the selection",
+    "angle <deg>          rotate the selection",
+    "color <r> <g> <b> [a]",
+    "show | hide          selection visibility",
+    "log <level|spec>     verbosity, e.g. debug,ecs=trace",
+    "clear                clear the output",
+    "PgUp/PgDn scroll, Up/Down history, ` or Esc close",
+];
+
+pub struct Console {
+    visible: bool,
+    input: String,
+    output: VecDeque<String>,
+    scroll: usize,
+    history: Vec<String>,
+    history_pos: usize,
+    selected: Option<usize>,
+    last_refresh: Instant,
+    text_vao: u32,
+    text_vbo: u32,
+    spans: Vec<TextSpan>,
+    inspect_vao: u32,
+    inspect_vbo: u32,
+    inspect_spans: Vec<TextSpan>,
+    panel_vao: u32,
+    panel_vbo: u32,
+    num_triangles: usize,
+    panel_w: f32,
+    panel_y: f32,
+}
+
+#[allow(dead_code)]
+impl Console {
+    pub fn new(gl: &Gl) -> Console {
+        log(format!("Constructing Console"));
+        let (xres, yres) = gl.get_frame_size();
+        Console {
+            visible: false,
+            input: String::new(),
+            output: VecDeque::with_capacity(OUTPUT_SZ),
+            scroll: 0,
+            history: Vec::new(),
+            history_pos: 0,
+            selected: None,
+            last_refresh: Instant::now(),
+            text_vao: gl.gen_vao(),
+            text_vbo: gl.gen_vbo(),
+            spans: Vec::new(),
+            inspect_vao: gl.gen_vao(),
+            inspect_vbo: gl.gen_vbo(),
+            inspect_spans: Vec::new(),
+            panel_vao: gl.gen_vao(),
+            panel_vbo: gl.gen_vbo(),
+            num_triangles: 0,
+            panel_w: xres as f32,
+            panel_y: yres as f32 - PANEL_H,
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+        if self.visible && self.output.is_empty() {
+            self.print("type help for commands");
+        }
+        self.dirty();
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    pub fn get_selected(&self) -> Option<usize> {
+        self.selected
+    }
+
+    pub fn print(&mut self, text: &str) {
+        for line in text.lines() {
+            if OUTPUT_SZ == self.output.len() {
+                self.output.pop_front();
+            }
+            self.output.push_back(String::from(line));
+        }
+        self.scroll = 0;
+        self.dirty();
+    }
+
+    pub fn type_char(&mut self, c: char) {
+        if !c.is_control() && '`' != c {
+            self.input.push(c);
+            self.dirty();
+        }
+    }
+
+    pub fn backspace(&mut self) {
+        self.input.pop();
+        self.dirty();
+    }
+
+    // older (-1) or newer (1) history entry into the input line
+    pub fn history_step(&mut self, dir: i32) {
+        if self.history.is_empty() {
+            return;
+        }
+        self.history_pos = match dir {
+            d if d < 0 => self.history_pos.saturating_sub(1),
+            _ => (self.history_pos + 1).min(self.history.len()),
+        };
+        self.input = self.history.get(self.history_pos).cloned().unwrap_or_default();
+        self.dirty();
+    }
+
+    pub fn scroll(&mut self, lines: i32) {
+        let max = self.output.len().saturating_sub(OUTPUT_LINES);
+        self.scroll = (self.scroll as i32 + lines).clamp(0, max as i32) as usize;
+        self.dirty();
+    }
+
+    pub fn pick(&mut self, world: &mut World, x: f32, y: f32) {
+        // clicks on the console itself don't pick
+        if self.panel_y <= y {
+            return;
+        }
+        match world.entity_pick(x, y) {
+            Some(idx) => self.select(world, idx),
+            None => self.print(&format!("nothing at {}, {}", x, y)),
+        }
+    }
+
+    fn select(&mut self, world: &mut World, idx: usize) {
+        self.selected = Some(idx);
+        let lines = describe(world, idx);
+        self.print(&lines[0]);
+    }
+
+    // runs the input line, returns it split up if it is a game command
+    pub fn submit(&mut self, world: &mut World) -> Option<Vec<String>> {
+        let line = std::mem::take(&mut self.input);
+        let args = split_args(&line);
+        if args.is_empty() {
+            return None;
+        }
+        if self.history.last() != Some(&line) {
+            if HISTORY_SZ == self.history.len() {
+                self.history.remove(0);
+            }
+            self.history.push(line.clone());
+        }
+        self.history_pos = self.history.len();
+        self.print(&format!("> {}", line));
+
+        if world.console_get_commands().iter().any(|(n, _)| *n == args[0]) {
+            return Some(args);
+        }
+        if let Err(e) = self.execute(world, &args) {
+            self.print(&e);
+        }
+        None
+    }
+
+    fn execute(&mut self, world: &mut World, args: &[String]) -> Result<(), String> {
+        let a: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
+        match a[..] {
+            ["help"] => {
+                for h in HELP {
+                    self.print(h);
+                }
+                let commands = world.console_get_commands().clone();
+                for (name, help) in commands.iter() {
+                    self.print(&format!("{:<20} {}", name, help));
+                }
+            }
+            ["clear"] => {
+                self.output.clear();
+                self.scroll = 0;
+            }
+            ["list"] => self.list(world, ""),
+            ["list", filter] => self.list(world, filter),
+            ["select", which] => {
+                let idx = find(world, which)?;
+                self.select(world, idx);
+            }
+            ["inspect"] => {
+                let idx = self.get_selection()?;
+                for l in describe(world, idx) {
+                    self.print(&l);
+                }
+            }
+            ["pos", x, y] => {
+                let idx = self.get_selection()?;
+                world.entity_set_position_xy(idx, parse(x)?, parse(y)?);
+            }
+            ["scale", x, y] => {
+                let idx = self.get_selection()?;
+                world.entity_set_scale_xy(idx, parse(x)?, parse(y)?);
+            }
+            ["angle", deg] => {
+                let idx = self.get_selection()?;
+                world.entity_set_angle(idx, super::deg2rad(parse(deg)?));
+            }
+            ["color", r, g, b] => {
+                let idx = self.get_selection()?;
+                let a = world.entity_get_alpha(idx);
+                world.entity_set_color_rgba(idx, parse(r)?, parse(g)?, parse(b)?, a);
+            }
+            ["color", r, g, b, a] => {
+                let idx = self.get_selection()?;
+                world.entity_set_color_rgba(idx, parse(r)?, parse(g)?, parse(b)?, parse(a)?);
+            }
+            ["show"] => world.entity_set_visibility(self.get_selection()?, true),
+            ["hide"] => world.entity_set_visibility(self.get_selection()?, false),
+            ["log"] => self.print(&format!("log level {}", logging::level_name(logging::get_level()))),
+            ["log", spec] => match logging::configure(spec) {
+                true => self.print(&format!("log {}", spec)),
+                false => return Err(format!("bad log spec '{}'", spec)),
+            },
+            _ => return Err(format!("unknown command '{}', try help", args.join(" "))),
+        }
+        Ok(())
+    }
+
+    fn get_selection(&self) -> Result<usize, String> {
+        self.selected.ok_or_else(|| String::from("nothing selected, use select or click an entity"))
+    }
+
+    fn list(&mut self, world: &mut World, filter: &str) {
+        let span = world.get_entities().get_id_span();
+        let mut count = 0;
+        for idx in span.first..=span.last {
+            if !world.entity_is_active(idx) {
+                continue;
+            }
+            let line = describe(world, idx).remove(0);
+            if line.contains(filter) {
+                self.print(&line);
+                count += 1;
+            }
+        }
+        self.print(&format!("{} entities", count));
+    }
+
+    // rebuilds the text a few times a second while shown, so the inspector
+    // follows moving entities
+    pub fn update(&mut self, gl: &Gl, world: &mut World) {
+        if !self.visible || REFRESH_MS > self.last_refresh.elapsed().as_millis() {
+            return;
+        }
+        self.last_refresh = Instant::now();
+
+        let first = self.output.len().saturating_sub(OUTPUT_LINES + self.scroll);
+        let mut text = String::new();
+        for l in self.output.iter().skip(first).take(OUTPUT_LINES) {
+            text += &format!("{}\n", l);
+        }
+        for _ in self.output.len().min(OUTPUT_LINES)..OUTPUT_LINES {
+            text += "\n";
+        }
+        text += &format!("\n> {}_", self.input);
+        self.spans = world.text_build(&text, LINE_HEIGHT, gl, self.text_vao, self.text_vbo);
+
+        let mut bounds = None;
+        self.inspect_spans.clear();
+        if let Some(idx) = self.selected {
+            let inspect = describe(world, idx).join("\n");
+            self.inspect_spans = world.text_build(&inspect, LINE_HEIGHT, gl, self.inspect_vao, self.inspect_vbo);
+            bounds = world.entity_get_bounds(idx);
+        }
+        self.build_panel(gl, bounds);
+    }
+
+    fn dirty(&mut self) {
+        self.last_refresh = Instant::now() - std::time::Duration::from_millis(REFRESH_MS as u64);
+    }
+
+    // background, and an outline around the selected entity
+    fn build_panel(&mut self, gl: &Gl, bounds: Option<(f32, f32, f32, f32)>) {
+        let mut data: Vec<f32> = Vec::new();
+        let mut quad = |x0: f32, y0: f32, x1: f32, y1: f32, c: [f32; 4]| {
+            for (x, y) in [(x0, y0), (x0, y1), (x1, y1), (x0, y0), (x1, y1), (x1, y0)] {
+                data.extend_from_slice(&[x, y, c[0], c[1], c[2], c[3]]);
+            }
+        };
+
+        let (w, y, ix) = (self.panel_w, self.panel_y, self.inspect_x());
+        quad(0.0, y, w, y + PANEL_H, [0.0, 0.0, 0.0, 0.8]);
+        quad(ix - 4.0, y, ix - 3.0, y + PANEL_H, [1.0, 1.0, 1.0, 0.3]);
+
+        if let Some((x0, y0, x1, y1)) = bounds {
+            let c = [1.0, 0.9, 0.2, 1.0];
+            quad(x0 - 1.0, y0 - 1.0, x1 + 1.0, y0, c);
+            quad(x0 - 1.0, y1, x1 + 1.0, y1 + 1.0, c);
+            quad(x0 - 1.0, y0, x0, y1, c);
+            quad(x1, y0, x1 + 1.0, y1, c);
+        }
+
+        self.num_triangles = data.len() / 18;
+        gl.buffer_triangle_data(self.panel_vao, self.panel_vbo, self.num_triangles, data.as_ptr() as *const _);
+    }
+
+    fn inspect_x(&self) -> f32 {
+        self.panel_w - INSPECT_W
+    }
+
+    pub fn draw(&self, gl: &Gl) {
+        if !self.visible {
+            return;
+        }
+        let white = Color {
+            r: 1.0,
+            g: 1.0,
+            b: 1.0,
+            a: 1.0,
+        };
+        gl.draw_triangles(0.0, 0.0, 0.0, 1.0, 1.0, self.panel_vao, self.num_triangles, white);
+        for (x, vao, spans) in [
+            (4.0, self.text_vao, &self.spans),
+            (self.inspect_x(), self.inspect_vao, &self.inspect_spans),
+        ] {
+            for span in spans.iter() {
+                gl.draw_text(x, self.panel_y + 2.0, 0.0, 1.0, 1.0, vao, span.first, span.count, span.tex, white);
+            }
+        }
+    }
+}
+
+// "12" or an entity name
+fn find(world: &World, which: &str) -> Result<usize, String> {
+    if let Ok(idx) = which.parse::<usize>() {
+        return match idx < ENTITY_SZ && world.entity_is_active(idx) {
+            true => Ok(idx),
+            false => Err(format!("no entity {}", idx)),
+        };
+    }
+    world.find_entity(which).ok_or_else(|| format!("no entity named '{}'", which))
+}
+
+fn parse(value: &str) -> Result<f32, String> {
+    value.parse::<f32>().map_err(|_| format!("'{}' is not a number", value))
+}
+
+// a summary line, then a line per component
+fn describe(world: &mut World, idx: usize) -> Vec<String> {
+    let ent = world.get_entities();
+    let has = |c: u32| ent.has_component(idx, c);
+    let mut flags = Vec::new();
+    for (c, name) in [
+        (COMPONENT_VISIBLE, "visible"),
+        (COMPONENT_PHYSICS, "physics"),
+        (COMPONENT_MATERIAL, "material"),
+    ] {
+        if has(c) {
+            flags.push(name);
+        }
+    }
+    let (pos, scale, angle, color) = (
+        has(COMPONENT_POSITION),
+        has(COMPONENT_SCALE),
+        has(COMPONENT_ANGLE),
+        has(COMPONENT_COLOR),
+    );
+
+    let rtype = world.get_manager_render().get_type(idx);
+    let kind = match rtype {
+        RENDER_TYPE_TEXT => "text",
+        RENDER_TYPE_LINE_BUFFER => "lines",
+        RENDER_TYPE_TRIANGLE_BUFFER => "triangles",
+        RENDER_TYPE_BILLBOARD => "billboard",
+        RENDER_TYPE_TILEMAP => "tilemap",
+        _ => "-",
+    };
+    let name = world.entity_get_name(idx).cloned().unwrap_or_default();
+    let mut ret = vec![format!("#{} {} {} {}", idx, name, kind, flags.join(" "))];
+
+    if pos {
+        let p = world.entity_get_position(idx);
+        ret.push(format!("  pos {:.1}, {:.1}", p.x, p.y));
+    }
+    if scale {
+        let s = world.entity_get_scale(idx);
+        ret.push(format!("  scale {:.1}, {:.1}", s.x, s.y));
+    }
+    if angle {
+        let a = world.entity_get_angle(idx);
+        ret.push(format!("  angle {:.1}", super::rad2deg(a)));
+    }
+    if color {
+        let c = world.entity_get_color(idx);
+        ret.push(format!("  color {:.2} {:.2} {:.2} {:.2}", c.r, c.g, c.b, c.a));
+    }
+    match rtype {
+        RENDER_TYPE_TEXT => {
+            let text = world.get_manager_text().get_text(idx).replace('\n', " ");
+            ret.push(format!("  \"{}\"", text.chars().take(24).collect::<String>()));
+        }
+        RENDER_TYPE_BILLBOARD => ret.push(format!("  {}", world.entity_get_billboard(idx))),
+        RENDER_TYPE_TILEMAP => {
+            let tmcm = world.get_manager_tilemap();
+            ret.push(format!(
+                "  {}x{}, {} layers",
+                tmcm.get_columns(idx),
+                tmcm.get_rows(idx),
+                tmcm.get_num_layers(idx)
+            ));
+        }
+        _ => (),
+    }
+    ret
+}
+
+// words, or "quoted words"
+fn split_args(line: &str) -> Vec<String> {
+    let mut ret = Vec::new();
+    let mut cur = String::new();
+    let mut quoted = false;
+    let mut any = false;
+    for c in line.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                any = true;
+            }
+            c if c.is_whitespace() && !quoted => {
+                if any {
+                    ret.push(std::mem::take(&mut cur));
+                    any = false;
+                }
+            }
+            c => {
+                cur.push(c);
+                any = true;
+            }
+        }
+    }
+    if any {
+        ret.push(cur);
+    }
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn args_split_on_spaces_outside_quotes() {
+        assert_eq!(vec!["level", "42"], split_args("  level   42 "));
+        assert_eq!(vec!["select", "big crate", ""], split_args("select \"big crate\" \"\""));
+        assert!(split_args("   ").is_empty());
+    }
+}
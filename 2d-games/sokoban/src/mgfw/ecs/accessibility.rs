@@ -0,0 +1,73 @@
+use std::collections::HashMap;
+
+// colorblind filters, the "mode" uniform of the colorblind postfx pass
+pub const COLORBLIND_NONE: u8 = 0;
+pub const COLORBLIND_DEUTERANOPIA: u8 = 1;
+pub const COLORBLIND_PROTANOPIA: u8 = 2;
+pub const COLORBLIND_TRITANOPIA: u8 = 3;
+pub const COLORBLIND_MODES: u8 = 4;
+
+// passes in assets/mgfw/postfx.dat
+pub const COLORBLIND_PASS: &str = "colorblind";
+pub const HIGH_CONTRAST_PASS: &str = "high_contrast";
+
+// symbol size relative to the entity it is drawn over
+pub const SYMBOL_SCALE: f32 = 0.5;
+
+#[derive(Default)]
+pub struct Accessibility {
+    pub colorblind: u8,
+    pub high_contrast: bool,
+    pub symbols: bool,
+    // symbol kind -> (image, texture handle once loaded), kept across clear()
+    pub symbol_images: HashMap<String, (String, u32)>,
+    // entity -> symbol kind
+    pub entity_symbols: HashMap<usize, String>,
+}
+
+#[allow(dead_code)]
+impl Accessibility {
+    pub fn get_symbol_texture(&self, idx: usize) -> Option<u32> {
+        let kind = self.entity_symbols.get(&idx)?;
+        match self.symbol_images.get(kind) {
+            Some((_, tex)) if *tex != 0 => Some(*tex),
+            _ => None,
+        }
+    }
+
+    pub fn symbols_pending(&self) -> bool {
+        self.symbol_images.values().any(|(_, tex)| *tex == 0)
+    }
+}
+
+pub fn colorblind_name(mode: u8) -> &'static str {
+    match mode {
+        COLORBLIND_DEUTERANOPIA => "deuteranopia",
+        COLORBLIND_PROTANOPIA => "protanopia",
+        COLORBLIND_TRITANOPIA => "tritanopia",
+        _ => "none",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn symbol_texture_needs_kind_and_load() {
+        let mut a = Accessibility::default();
+        a.entity_symbols.insert(3, String::from("placed"));
+        assert_eq!(a.get_symbol_texture(3), None);
+
+        a.symbol_images
+            .insert(String::from("placed"), (String::from("x.png"), 0));
+        assert!(a.symbols_pending());
+        assert_eq!(a.get_symbol_texture(3), None);
+
+        a.symbol_images
+            .insert(String::from("placed"), (String::from("x.png"), 7));
+        assert!(!a.symbols_pending());
+        assert_eq!(a.get_symbol_texture(3), Some(7));
+        assert_eq!(a.get_symbol_texture(4), None);
+    }
+}
@@ -1,13 +1,13 @@
 use super::*;
 use crate::mgfw::log;
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, Default)]
 pub struct Angle {
     pub x: f32,
 }
 
 pub struct AngleComponentManager {
-    data: *mut Angle,
+    data: CacheBlock<Angle>,
     // WARNING: Anything below this line is not in cache!
 }
 
@@ -15,9 +15,8 @@ pub struct AngleComponentManager {
 impl AngleComponentManager {
     pub fn new(mgr: &mut CacheManager) -> AngleComponentManager {
         log(format!("Constructing AngleComponentManager"));
-        let sz_bytes = std::mem::size_of::<Angle>() * ENTITY_SZ;
         AngleComponentManager {
-            data: mgr.allocate(sz_bytes) as *mut Angle,
+            data: mgr.allocate("AngleComponentManager", ENTITY_SZ),
         }
     }
 
@@ -27,7 +26,7 @@ impl AngleComponentManager {
         }
     }
 
-    pub fn set_angle(&self, idx: usize, x: f32) {
+    pub fn set_angle(&mut self, idx: usize, x: f32) {
         let pos = self.get_data_ref_mut(idx);
         pos.x = x;
     }
@@ -37,13 +36,11 @@ impl AngleComponentManager {
         pos.x
     }
 
-    pub fn get_data_ref_mut(&self, idx: usize) -> &mut Angle {
-        assert!(idx < ENTITY_SZ);
-        unsafe { &mut *(self.data.offset(idx as isize)) }
+    pub fn get_data_ref_mut(&mut self, idx: usize) -> &mut Angle {
+        &mut self.data[idx]
     }
 
     pub fn get_data_ref(&self, idx: usize) -> &Angle {
-        assert!(idx < ENTITY_SZ);
-        unsafe { &*(self.data.offset(idx as isize)) }
+        &self.data[idx]
     }
 }
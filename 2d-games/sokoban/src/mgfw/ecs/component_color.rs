@@ -1,7 +1,7 @@
 use super::*;
 use crate::mgfw::log;
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, Default)]
 pub struct Color {
     pub r: f32,
     pub g: f32,
@@ -10,7 +10,7 @@ pub struct Color {
 }
 
 pub struct ColorComponentManager {
-    data: *mut Color,
+    data: CacheBlock<Color>,
     // WARNING: Anything below this line is not in cache!
 }
 
@@ -18,17 +18,13 @@ pub struct ColorComponentManager {
 impl ColorComponentManager {
     pub fn new(mgr: &mut CacheManager) -> ColorComponentManager {
         log(format!("Constructing ColorComponentManager"));
-        let sz_bytes = std::mem::size_of::<Color>() * ENTITY_SZ;
-        let data = mgr.allocate(sz_bytes) as *mut Color;
-
         // default init colors to opaque white
-        for i in 0..ENTITY_SZ {
-            let p = unsafe { &mut *(data.offset(i as isize)) };
-            p.r = 1.0;
-            p.g = 1.0;
-            p.b = 1.0;
-            p.a = 1.0;
-        }
+        let data = mgr.allocate_with("ColorComponentManager", ENTITY_SZ, |_| Color {
+            r: 1.0,
+            g: 1.0,
+            b: 1.0,
+            a: 1.0,
+        });
 
         ColorComponentManager { data }
     }
@@ -39,11 +35,11 @@ impl ColorComponentManager {
         }
     }
 
-    pub fn set_color(&self, idx: usize, color: Color) {
+    pub fn set_color(&mut self, idx: usize, color: Color) {
         self.set_color_rgba(idx, color.r, color.g, color.b, color.a);
     }
 
-    pub fn set_color_rgba(&self, idx: usize, r: f32, g: f32, b: f32, a: f32) {
+    pub fn set_color_rgba(&mut self, idx: usize, r: f32, g: f32, b: f32, a: f32) {
         let clr = self.get_data_ref_mut(idx);
         clr.r = r;
         clr.g = g;
@@ -55,7 +51,7 @@ impl ColorComponentManager {
         self.get_data_ref(idx).clone()
     }
 
-    pub fn set_alpha(&self, idx: usize, alpha: f32) {
+    pub fn set_alpha(&mut self, idx: usize, alpha: f32) {
         let clr = self.get_data_ref_mut(idx);
         clr.a = alpha;
     }
@@ -64,13 +60,11 @@ impl ColorComponentManager {
         self.get_data_ref(idx).a
     }
 
-    fn get_data_ref_mut(&self, idx: usize) -> &mut Color {
-        assert!(idx < ENTITY_SZ);
-        unsafe { &mut *(self.data.offset(idx as isize)) }
+    fn get_data_ref_mut(&mut self, idx: usize) -> &mut Color {
+        &mut self.data[idx]
     }
 
     fn get_data_ref(&self, idx: usize) -> &Color {
-        assert!(idx < ENTITY_SZ);
-        unsafe { &*(self.data.offset(idx as isize)) }
+        &self.data[idx]
     }
 }
@@ -1,7 +1,7 @@
 use super::*;
 use crate::mgfw::log;
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, Default)]
 pub struct Ease {
     pub end: f32,
     pub dxdt: f32,
@@ -20,7 +20,7 @@ const EASING_VAR_INACTIVE: u8 = 0;
 pub const EASING_VAR_ALPHA: u8 = 1;
 
 pub struct EasingComponentManager {
-    data: *mut Ease,
+    data: CacheBlock<Ease>,
     // WARNING: Anything below this line is not in cache!
     cursor: usize, // current insertion cursor
     span: EasingIdSpan,
@@ -30,9 +30,8 @@ pub struct EasingComponentManager {
 impl EasingComponentManager {
     pub fn new(mgr: &mut CacheManager) -> EasingComponentManager {
         log(format!("Constructing EasingComponentManager"));
-        let sz_bytes = std::mem::size_of::<Ease>() * EASING_SZ;
         EasingComponentManager {
-            data: mgr.allocate(sz_bytes) as *mut Ease,
+            data: mgr.allocate("EasingComponentManager", EASING_SZ),
             cursor: 0,
             span: EasingIdSpan {
                 first: EASING_SZ - 1,
@@ -132,13 +131,11 @@ impl EasingComponentManager {
         self.span
     }
 
-    fn get_data_ref_mut(&self, idx: usize) -> &mut Ease {
-        assert!(idx < EASING_SZ);
-        unsafe { &mut *(self.data.offset(idx as isize)) }
+    fn get_data_ref_mut(&mut self, idx: usize) -> &mut Ease {
+        &mut self.data[idx]
     }
 
     pub fn get_data_ref(&self, idx: usize) -> &Ease {
-        assert!(idx < EASING_SZ);
-        unsafe { &*(self.data.offset(idx as isize)) }
+        &self.data[idx]
     }
 }
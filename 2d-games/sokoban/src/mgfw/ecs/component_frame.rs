@@ -1,13 +1,13 @@
 use super::*;
 use crate::mgfw::log;
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, Default)]
 pub struct Frame {
     pub frame: u16,
 }
 
 pub struct FrameComponentManager {
-    data: *mut Frame,
+    data: CacheBlock<Frame>,
     // WARNING: Anything below this line is not in cache!
 }
 
@@ -15,9 +15,8 @@ pub struct FrameComponentManager {
 impl FrameComponentManager {
     pub fn new(mgr: &mut CacheManager) -> FrameComponentManager {
         log(format!("Constructing FrameComponentManager"));
-        let sz_bytes = std::mem::size_of::<Frame>() * ENTITY_SZ;
         FrameComponentManager {
-            data: mgr.allocate(sz_bytes) as *mut Frame,
+            data: mgr.allocate("FrameComponentManager", ENTITY_SZ),
         }
     }
 
@@ -27,7 +26,7 @@ impl FrameComponentManager {
         }
     }
 
-    pub fn set_frame(&self, idx: usize, frame: u16) {
+    pub fn set_frame(&mut self, idx: usize, frame: u16) {
         let d = self.get_data_ref_mut(idx);
         d.frame = frame;
     }
@@ -37,13 +36,11 @@ impl FrameComponentManager {
         d.frame
     }
 
-    pub fn get_data_ref_mut(&self, idx: usize) -> &mut Frame {
-        assert!(idx < ENTITY_SZ);
-        unsafe { &mut *(self.data.offset(idx as isize)) }
+    pub fn get_data_ref_mut(&mut self, idx: usize) -> &mut Frame {
+        &mut self.data[idx]
     }
 
     pub fn get_data_ref(&self, idx: usize) -> &Frame {
-        assert!(idx < ENTITY_SZ);
-        unsafe { &*(self.data.offset(idx as isize)) }
+        &self.data[idx]
     }
 }
@@ -0,0 +1,249 @@
+use super::*;
+use crate::mgfw::log;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+// A material names a shader program and the uniforms it needs. Entities
+// with a material are drawn with it instead of the default shader for their
+// render type. Material files are one entry per line:
+//
+//   vertex,<vertex shader>          (optional, default for the render type)
+//   fragment,<fragment shader>      (optional, default for the render type)
+//   float,<uniform>,<value>
+//   vec2,<uniform>,<x>,<y>          (vec3 and vec4 likewise)
+//   texture,<uniform>,<image>
+//
+// Besides these, a material shader gets the uniforms of the default shader
+// (MVP, color_uniform, tex_sampler, ...) and `time` in seconds.
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum MaterialValue {
+    Float(f32),
+    Vec2([f32; 2]),
+    Vec3([f32; 3]),
+    Vec4([f32; 4]),
+    Texture(String),
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Material {
+    // empty means the default shader source for the render type
+    pub vertex: String,
+    pub fragment: String,
+    pub uniforms: Vec<(String, MaterialValue)>,
+}
+
+#[allow(dead_code)]
+impl Material {
+    pub fn new(vertex: &str, fragment: &str) -> Material {
+        Material {
+            vertex: String::from(vertex),
+            fragment: String::from(fragment),
+            uniforms: Vec::new(),
+        }
+    }
+
+    pub fn load(filename: &str) -> Result<Material, String> {
+        let buffer = assets::read_to_string(filename)?;
+        Material::parse(filename, &buffer)
+    }
+
+    pub fn parse(filename: &str, buffer: &str) -> Result<Material, String> {
+        let mut ret = Material::default();
+        for (n, line) in buffer.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with("//") {
+                continue;
+            }
+            let split: Vec<&str> = line.split(',').map(|s| s.trim()).collect();
+            if !ret.apply(&split) {
+                return Err(format!("{}:{}: invalid entry '{}'", filename, n + 1, line));
+            }
+        }
+        Ok(ret)
+    }
+
+    fn apply(&mut self, split: &[&str]) -> bool {
+        let floats = |values: &[&str]| -> Option<Vec<f32>> {
+            values.iter().map(|v| v.parse::<f32>().ok()).collect()
+        };
+        let value = match (split[0], split.len()) {
+            ("vertex", 2) => {
+                self.vertex = String::from(split[1]);
+                return true;
+            }
+            ("fragment", 2) => {
+                self.fragment = String::from(split[1]);
+                return true;
+            }
+            ("texture", 3) => MaterialValue::Texture(String::from(split[2])),
+            ("float", 3) | ("vec2", 4) | ("vec3", 5) | ("vec4", 6) => {
+                let v = match floats(&split[2..]) {
+                    Some(v) => v,
+                    None => return false,
+                };
+                match v.len() {
+                    1 => MaterialValue::Float(v[0]),
+                    2 => MaterialValue::Vec2([v[0], v[1]]),
+                    3 => MaterialValue::Vec3([v[0], v[1], v[2]]),
+                    _ => MaterialValue::Vec4([v[0], v[1], v[2], v[3]]),
+                }
+            }
+            _ => return false,
+        };
+        self.set(split[1], value);
+        true
+    }
+
+    pub fn set(&mut self, name: &str, value: MaterialValue) {
+        match self.uniforms.iter_mut().find(|(n, _)| n == name) {
+            Some(u) => u.1 = value,
+            None => self.uniforms.push((String::from(name), value)),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&MaterialValue> {
+        self.uniforms.iter().find(|(n, _)| n == name).map(|(_, v)| v)
+    }
+}
+
+#[derive(Default)]
+struct MaterialComponentManagerData {
+    // material id + 1, 0 for none
+    material: u32,
+}
+
+pub struct MaterialComponentManager {
+    cache_data: CacheBlock<MaterialComponentManagerData>,
+    // WARNING: Anything below this line is not in cache!
+    // definitions are kept across clear(), only assignments are reset
+    materials: std::boxed::Box<Vec<Rc<Material>>>,
+    // material file -> id, so entities loading the same file share it
+    files: std::boxed::Box<HashMap<String, usize>>,
+}
+
+#[allow(dead_code)]
+impl MaterialComponentManager {
+    pub fn new(mgr: &mut CacheManager) -> MaterialComponentManager {
+        log(format!("Constructing MaterialComponentManager"));
+
+        let cache_data = mgr.allocate("MaterialComponentManager", ENTITY_SZ);
+
+        MaterialComponentManager {
+            cache_data,
+            materials: Box::new(Vec::new()),
+            files: Box::new(HashMap::new()),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        for i in 0..ENTITY_SZ {
+            self.get_data_ref_mut(i).material = 0;
+        }
+    }
+
+    pub fn add(&mut self, material: Material) -> usize {
+        self.materials.push(Rc::new(material));
+        self.materials.len() - 1
+    }
+
+    pub fn load(&mut self, filename: &str) -> Result<usize, String> {
+        if let Some(id) = self.files.get(filename) {
+            return Ok(*id);
+        }
+        let id = self.add(Material::load(filename)?);
+        self.files.insert(String::from(filename), id);
+        Ok(id)
+    }
+
+    // file the material was loaded from, if any
+    pub fn get_file(&self, material: usize) -> Option<&String> {
+        self.files
+            .iter()
+            .find(|(_, id)| **id == material)
+            .map(|(f, _)| f)
+    }
+
+    pub fn get(&self, material: usize) -> Option<&Rc<Material>> {
+        self.materials.get(material)
+    }
+
+    // entities drawn with this material pick up the change on the next frame
+    pub fn set_uniform(&mut self, material: usize, name: &str, value: MaterialValue) {
+        match self.materials.get_mut(material) {
+            Some(m) => Rc::make_mut(m).set(name, value),
+            None => log(format!("WARNING: Material: unknown material {}", material)),
+        }
+    }
+
+    pub fn set_material(&mut self, idx: usize, material: Option<usize>) {
+        self.get_data_ref_mut(idx).material = match material {
+            Some(m) => match u32::try_from(m).ok().and_then(|m| m.checked_add(1)) {
+                Some(id) => id,
+                None => {
+                    log(format!("WARNING: Material: material id {} out of range", m));
+                    return;
+                }
+            },
+            None => 0,
+        };
+    }
+
+    pub fn get_material(&self, idx: usize) -> Option<usize> {
+        match self.get_data_ref(idx).material {
+            0 => None,
+            m => Some(m as usize - 1),
+        }
+    }
+
+    pub fn get_entity_material(&self, idx: usize) -> Option<&Rc<Material>> {
+        self.get_material(idx).and_then(|m| self.materials.get(m))
+    }
+
+    fn get_data_ref_mut(&mut self, idx: usize) -> &mut MaterialComponentManagerData {
+        &mut self.cache_data[idx]
+    }
+
+    fn get_data_ref(&self, idx: usize) -> &MaterialComponentManagerData {
+        &self.cache_data[idx]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_material_file() {
+        let m = Material::parse(
+            "m.mat",
+            "// comment\nfragment,a.fs\nfloat,strength,0.5\nvec2,dir,1,0\ntexture,noise,n.png\nfloat,strength,0.25\n",
+        )
+        .unwrap();
+        assert_eq!("", m.vertex);
+        assert_eq!("a.fs", m.fragment);
+        assert_eq!(3, m.uniforms.len());
+        assert_eq!(Some(&MaterialValue::Float(0.25)), m.get("strength"));
+        assert_eq!(Some(&MaterialValue::Vec2([1.0, 0.0])), m.get("dir"));
+        assert_eq!(Some(&MaterialValue::Texture(String::from("n.png"))), m.get("noise"));
+
+        let e = Material::parse("m.mat", "fragment,a.fs\nvec3,c,1,2\n").unwrap_err();
+        assert!(e.starts_with("m.mat:2:"));
+    }
+
+    #[test]
+    fn material_ids_past_u16() {
+        let mut mcm = MaterialComponentManager::new(&mut CacheManager::new());
+        mcm.set_material(0, Some(70000));
+        mcm.set_material(1, Some(0));
+        assert_eq!(Some(70000), mcm.get_material(0));
+        assert_eq!(Some(0), mcm.get_material(1));
+
+        // out of range ids keep the previous assignment
+        mcm.set_material(1, Some(u32::MAX as usize));
+        assert_eq!(Some(0), mcm.get_material(1));
+
+        mcm.set_material(0, None);
+        assert_eq!(None, mcm.get_material(0));
+    }
+}
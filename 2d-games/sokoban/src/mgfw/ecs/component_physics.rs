@@ -1,18 +1,19 @@
 use super::*;
 use crate::mgfw::log;
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, Default)]
 pub struct Velocity {
     pub x: f32,
     pub y: f32,
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, Default)]
 pub struct Acceleration {
     pub x: f32,
     pub y: f32,
 }
 
+#[derive(Default)]
 pub struct PhysicsComponentManagerData {
     pub velocity: Velocity,
     pub acceleration: Acceleration,
@@ -20,7 +21,7 @@ pub struct PhysicsComponentManagerData {
 }
 
 pub struct PhysicsComponentManager {
-    data: *mut PhysicsComponentManagerData,
+    data: CacheBlock<PhysicsComponentManagerData>,
     // WARNING: Anything below this line is not in cache!
 }
 
@@ -28,9 +29,8 @@ pub struct PhysicsComponentManager {
 impl PhysicsComponentManager {
     pub fn new(mgr: &mut CacheManager) -> PhysicsComponentManager {
         log(format!("Constructing PhysicsComponentManager"));
-        let sz_bytes = std::mem::size_of::<PhysicsComponentManagerData>() * ENTITY_SZ;
         PhysicsComponentManager {
-            data: mgr.allocate(sz_bytes) as *mut PhysicsComponentManagerData,
+            data: mgr.allocate("PhysicsComponentManager", ENTITY_SZ),
         }
     }
 
@@ -57,30 +57,28 @@ impl PhysicsComponentManager {
         data.angular_velocity
     }
 
-    pub fn set_angular_velocity(&self, idx: usize, val: f32) {
+    pub fn set_angular_velocity(&mut self, idx: usize, val: f32) {
         let data = self.get_data_ref_mut(idx);
         data.angular_velocity = val;
     }
 
-    pub fn set_velocity(&self, idx: usize, x: f32, y: f32) {
+    pub fn set_velocity(&mut self, idx: usize, x: f32, y: f32) {
         let data = self.get_data_ref_mut(idx);
         data.velocity.x = x;
         data.velocity.y = y;
     }
 
-    pub fn set_acceleration(&self, idx: usize, x: f32, y: f32) {
+    pub fn set_acceleration(&mut self, idx: usize, x: f32, y: f32) {
         let data = self.get_data_ref_mut(idx);
         data.acceleration.x = x;
         data.acceleration.y = y;
     }
 
-    pub fn get_data_ref_mut(&self, idx: usize) -> &mut PhysicsComponentManagerData {
-        assert!(idx < ENTITY_SZ);
-        unsafe { &mut *(self.data.offset(idx as isize)) }
+    pub fn get_data_ref_mut(&mut self, idx: usize) -> &mut PhysicsComponentManagerData {
+        &mut self.data[idx]
     }
 
     pub fn get_data_ref(&self, idx: usize) -> &PhysicsComponentManagerData {
-        assert!(idx < ENTITY_SZ);
-        unsafe { &*(self.data.offset(idx as isize)) }
+        &self.data[idx]
     }
 }
@@ -1,14 +1,14 @@
 use super::*;
 use crate::mgfw::log;
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, Default)]
 pub struct Position {
     pub x: f32,
     pub y: f32,
 }
 
 pub struct PositionComponentManager {
-    data: *mut Position,
+    data: CacheBlock<Position>,
     // WARNING: Anything below this line is not in cache!
 }
 
@@ -16,9 +16,8 @@ pub struct PositionComponentManager {
 impl PositionComponentManager {
     pub fn new(mgr: &mut CacheManager) -> PositionComponentManager {
         log(format!("Constructing PositionComponentManager"));
-        let sz_bytes = std::mem::size_of::<Position>() * ENTITY_SZ;
         PositionComponentManager {
-            data: mgr.allocate(sz_bytes) as *mut Position,
+            data: mgr.allocate("PositionComponentManager", ENTITY_SZ),
         }
     }
 
@@ -28,7 +27,7 @@ impl PositionComponentManager {
         }
     }
 
-    pub fn set_position(&self, idx: usize, x: f32, y: f32) {
+    pub fn set_position(&mut self, idx: usize, x: f32, y: f32) {
         let pos = self.get_data_ref_mut(idx);
         pos.x = x;
         pos.y = y;
@@ -39,13 +38,11 @@ impl PositionComponentManager {
         pos.clone()
     }
 
-    pub fn get_data_ref_mut(&self, idx: usize) -> &mut Position {
-        assert!(idx < ENTITY_SZ);
-        unsafe { &mut *(self.data.offset(idx as isize)) }
+    pub fn get_data_ref_mut(&mut self, idx: usize) -> &mut Position {
+        &mut self.data[idx]
     }
 
     pub fn get_data_ref(&self, idx: usize) -> &Position {
-        assert!(idx < ENTITY_SZ);
-        unsafe { &*(self.data.offset(idx as isize)) }
+        &self.data[idx]
     }
 }
@@ -8,12 +8,13 @@ pub const RENDER_TYPE_TRIANGLE_BUFFER: u8 = 3;
 pub const RENDER_TYPE_BILLBOARD: u8 = 4;
 pub const RENDER_TYPE_TILEMAP: u8 = 5;
 
+#[derive(Default)]
 struct RenderComponentManagerData {
     render_type: u8,
 }
 
 pub struct RenderComponentManager {
-    data: *mut RenderComponentManagerData,
+    data: CacheBlock<RenderComponentManagerData>,
     // WARNING: Anything below this line is not in cache!
 }
 
@@ -22,8 +23,7 @@ impl RenderComponentManager {
     pub fn new(mgr: &mut CacheManager) -> RenderComponentManager {
         log(format!("Constructing RenderComponentManager"));
         // allocate system memory in cache
-        let sz_bytes = std::mem::size_of::<RenderComponentManagerData>() * ENTITY_SZ;
-        let data = mgr.allocate(sz_bytes) as *mut RenderComponentManagerData;
+        let data = mgr.allocate("RenderComponentManager", ENTITY_SZ);
 
         RenderComponentManager { data }
     }
@@ -46,13 +46,11 @@ impl RenderComponentManager {
         self.get_data_ref(idx).render_type == render_type
     }
 
-    fn get_data_ref_mut(&self, idx: usize) -> &mut RenderComponentManagerData {
-        assert!(idx < ENTITY_SZ);
-        unsafe { &mut *(self.data.offset(idx as isize)) }
+    fn get_data_ref_mut(&mut self, idx: usize) -> &mut RenderComponentManagerData {
+        &mut self.data[idx]
     }
 
     fn get_data_ref(&self, idx: usize) -> &RenderComponentManagerData {
-        assert!(idx < ENTITY_SZ);
-        unsafe { &*(self.data.offset(idx as isize)) }
+        &self.data[idx]
     }
 }
@@ -2,15 +2,16 @@ use super::*;
 use crate::mgfw::log;
 use std::collections::HashMap;
 
+#[derive(Default)]
 struct BillboardRenderComponentManagerData {
-    texture: u16,
+    texture: u32,
     constructed: bool,
     reconstruct_needed: bool,
     load_image_needed: bool,
 }
 
 pub struct BillboardRenderComponentManager {
-    cache_data: *mut BillboardRenderComponentManagerData,
+    cache_data: CacheBlock<BillboardRenderComponentManagerData>,
     // WARNING: Anything below this line is not in cache!
     texture_files: std::boxed::Box<HashMap<usize, String>>,
     texture_handles: std::boxed::Box<HashMap<String, u32>>,
@@ -25,8 +26,7 @@ impl BillboardRenderComponentManager {
         let hdata: HashMap<String, u32> = HashMap::new();
 
         // allocate system memory in cache
-        let sz_bytes = std::mem::size_of::<BillboardRenderComponentManagerData>() * ENTITY_SZ;
-        let cache_data = mgr.allocate(sz_bytes) as *mut BillboardRenderComponentManagerData;
+        let cache_data = mgr.allocate("BillboardRenderComponentManager", ENTITY_SZ);
 
         BillboardRenderComponentManager {
             texture_files: Box::new(fdata),
@@ -68,20 +68,25 @@ impl BillboardRenderComponentManager {
     pub fn load_image(&mut self, idx: usize, gl: &Gl) {
         if self.get_data_ref(idx).load_image_needed {
             let filename = self.texture_files.get(&idx).unwrap().to_string();
-            let handle: u32 = match self.texture_handles.contains_key(&filename) {
-                true => *self.texture_handles.get(&filename).unwrap(),
-                false => {
-                    let h = gl.load_texture(&filename);
-                    self.texture_handles.insert(filename, h);
-                    h
-                }
-            };
-
-            self.get_data_ref_mut(idx).texture = handle as u16;
+            let handle = self.get_texture(&filename, gl);
+
+            self.get_data_ref_mut(idx).texture = handle;
             self.get_data_ref_mut(idx).load_image_needed = false;
         }
     }
 
+    // shared with every billboard using the same image
+    pub fn get_texture(&mut self, filename: &str, gl: &Gl) -> u32 {
+        match self.texture_handles.get(filename) {
+            Some(h) => *h,
+            None => {
+                let h = gl.load_texture(&String::from(filename));
+                self.texture_handles.insert(String::from(filename), h);
+                h
+            }
+        }
+    }
+
     pub fn construct(&mut self, idx: usize, gl: &Gl, vao: u32, vbo: u32) {
         self.load_image(idx, gl);
 
@@ -112,17 +117,15 @@ impl BillboardRenderComponentManager {
         cache_data.constructed = true;
     }
 
-    pub fn get_tex_handle(&self, idx: usize) -> u16 {
+    pub fn get_tex_handle(&self, idx: usize) -> u32 {
         self.get_data_ref(idx).texture
     }
 
-    fn get_data_ref_mut(&self, idx: usize) -> &mut BillboardRenderComponentManagerData {
-        assert!(idx < ENTITY_SZ);
-        unsafe { &mut *(self.cache_data.offset(idx as isize)) }
+    fn get_data_ref_mut(&mut self, idx: usize) -> &mut BillboardRenderComponentManagerData {
+        &mut self.cache_data[idx]
     }
 
     fn get_data_ref(&self, idx: usize) -> &BillboardRenderComponentManagerData {
-        assert!(idx < ENTITY_SZ);
-        unsafe { &*(self.cache_data.offset(idx as isize)) }
+        &self.cache_data[idx]
     }
 }
@@ -7,6 +7,7 @@ struct LineBuffer {
     clrs: Vec<Color>,
 }
 
+#[derive(Default)]
 struct LineRenderComponentManagerData {
     num_lines: u16,
     constructed: bool,
@@ -14,7 +15,7 @@ struct LineRenderComponentManagerData {
 }
 
 pub struct LineRenderComponentManager {
-    cache_data: *mut LineRenderComponentManagerData,
+    cache_data: CacheBlock<LineRenderComponentManagerData>,
     // WARNING: Anything below this line is not in cache!
     data: std::boxed::Box<Vec<LineBuffer>>,
 }
@@ -33,8 +34,7 @@ impl LineRenderComponentManager {
         }
 
         // allocate system memory in cache
-        let sz_bytes = std::mem::size_of::<LineRenderComponentManagerData>() * ENTITY_SZ;
-        let cache_data = mgr.allocate(sz_bytes) as *mut LineRenderComponentManagerData;
+        let cache_data = mgr.allocate("LineRenderComponentManager", ENTITY_SZ);
 
         LineRenderComponentManager {
             data: Box::new(data),
@@ -52,6 +52,14 @@ impl LineRenderComponentManager {
         self.data[idx].clrs = clrs.clone();
     }
 
+    pub fn get_points(&self, idx: usize) -> &Vec<Position> {
+        &self.data[idx].pnts
+    }
+
+    pub fn get_colors(&self, idx: usize) -> &Vec<Color> {
+        &self.data[idx].clrs
+    }
+
     pub fn is_constructed(&self, idx: usize) -> bool {
         self.get_data_ref(idx).constructed
     }
@@ -60,9 +68,7 @@ impl LineRenderComponentManager {
         self.get_data_ref(idx).reconstruct_needed
     }
 
-    pub fn construct(&self, idx: usize, gl: &Gl, vao: u32, vbo: u32) {
-        let cache_data = self.get_data_ref_mut(idx);
-
+    pub fn construct(&mut self, idx: usize, gl: &Gl, vao: u32, vbo: u32) {
         let pnts = &self.data[idx].pnts;
         let clrs = &self.data[idx].clrs;
 
@@ -82,6 +88,7 @@ impl LineRenderComponentManager {
         let data_ptr = vertex_data.as_ptr() as *const _;
         gl.buffer_line_data(vao, vbo, num_lines, data_ptr);
 
+        let cache_data = self.get_data_ref_mut(idx);
         cache_data.reconstruct_needed = false;
         cache_data.constructed = true;
         cache_data.num_lines = num_lines as u16;
@@ -91,13 +98,11 @@ impl LineRenderComponentManager {
         self.get_data_ref(idx).num_lines as usize
     }
 
-    fn get_data_ref_mut(&self, idx: usize) -> &mut LineRenderComponentManagerData {
-        assert!(idx < ENTITY_SZ);
-        unsafe { &mut *(self.cache_data.offset(idx as isize)) }
+    fn get_data_ref_mut(&mut self, idx: usize) -> &mut LineRenderComponentManagerData {
+        &mut self.cache_data[idx]
     }
 
     fn get_data_ref(&self, idx: usize) -> &LineRenderComponentManagerData {
-        assert!(idx < ENTITY_SZ);
-        unsafe { &*(self.cache_data.offset(idx as isize)) }
+        &self.cache_data[idx]
     }
 }
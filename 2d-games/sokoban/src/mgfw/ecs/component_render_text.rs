@@ -1,23 +1,40 @@
 use super::*;
 use crate::mgfw::log;
+use std::collections::HashMap;
+
+pub const FONT_DEFAULT: usize = 0;
+
+#[derive(Copy, Clone)]
+pub struct TextSpan {
+    pub tex: u32,
+    pub first: usize,
+    pub count: usize,
+}
 
 struct Text {
     // WARNING: Anything below this line is not in cache!
     text: String,
+    font: usize,
+    layout: fonts::layout::TextLayout,
+    height: f32,
+    spans: Vec<TextSpan>,
 }
 
+#[derive(Default)]
 struct TextRenderComponentManagerData {
     width: u16,
-    num_chars: u16,
+    num_chars: u32,
     constructed: bool,
     reconstruct_needed: bool,
 }
 
 pub struct TextRenderComponentManager {
-    cache_data: *mut TextRenderComponentManagerData,
+    cache_data: CacheBlock<TextRenderComponentManagerData>,
     // WARNING: Anything below this line is not in cache!
     data: std::boxed::Box<Vec<Text>>,
-    font: std::boxed::Box<fonts::retro_gaming::Font>,
+    fonts: std::boxed::Box<Vec<fonts::bmfont::Font>>,
+    font_files: std::boxed::Box<HashMap<String, usize>>,
+    font_textures: std::boxed::Box<Vec<Vec<u32>>>,
 }
 
 #[allow(dead_code)]
@@ -29,22 +46,84 @@ impl TextRenderComponentManager {
         for _i in 0..ENTITY_SZ {
             data.push(Text {
                 text: String::new(),
+                font: FONT_DEFAULT,
+                layout: fonts::layout::TextLayout::default(),
+                height: 0.0,
+                spans: Vec::new(),
             });
         }
 
         // allocate system memory in cache
-        let sz_bytes = std::mem::size_of::<TextRenderComponentManagerData>() * ENTITY_SZ;
-        let cache_data = mgr.allocate(sz_bytes) as *mut TextRenderComponentManagerData;
+        let cache_data = mgr.allocate("TextRenderComponentManager", ENTITY_SZ);
+
+        // built-in font is always FONT_DEFAULT
+        let font: fonts::bmfont::Font = fonts::retro_gaming::Font::new().into();
+        let mut font_files: HashMap<String, usize> = HashMap::new();
+        font_files.insert(String::from("retro_gaming"), FONT_DEFAULT);
 
         TextRenderComponentManager {
             data: Box::new(data),
-            font: Box::new(fonts::retro_gaming::Font::new()),
+            fonts: Box::new(vec![font]),
+            font_files: Box::new(font_files),
+            font_textures: Box::new(vec![Vec::new()]),
             cache_data,
         }
     }
 
     pub fn clear(&mut self) {
-        
+        for i in 0..ENTITY_SZ {
+            self.data[i].font = FONT_DEFAULT;
+            self.data[i].layout = fonts::layout::TextLayout::default();
+        }
+    }
+
+    // returns the font id, falls back to FONT_DEFAULT if the file can't be loaded
+    pub fn load_font(&mut self, filename: &str) -> usize {
+        if let Some(id) = self.font_files.get(filename) {
+            return *id;
+        }
+
+        match fonts::bmfont::Font::load(filename) {
+            Ok(font) => {
+                let id = self.fonts.len();
+                self.fonts.push(font);
+                self.font_textures.push(Vec::new());
+                self.font_files.insert(String::from(filename), id);
+                id
+            }
+            Err(e) => {
+                log(format!("WARNING: Failed to load font {}", e));
+                FONT_DEFAULT
+            }
+        }
+    }
+
+    pub fn get_font_id(&self, filename: &str) -> Option<usize> {
+        self.font_files.get(filename).copied()
+    }
+
+    pub fn get_font_file(&self, font: usize) -> Option<String> {
+        self.font_files
+            .iter()
+            .find(|(_, id)| font == **id)
+            .map(|(f, _)| f.clone())
+    }
+
+    pub fn set_font(&mut self, idx: usize, font: usize) {
+        if font >= self.fonts.len() {
+            log(format!("WARNING: Invalid font id {}", font));
+            return;
+        }
+        self.get_data_ref_mut(idx).reconstruct_needed = true;
+        self.data[idx].font = font;
+    }
+
+    pub fn get_font(&self, idx: usize) -> usize {
+        self.data[idx].font
+    }
+
+    pub fn get_line_height(&self, idx: usize) -> usize {
+        self.fonts[self.data[idx].font].line_height as usize
     }
 
     pub fn set_text(&mut self, idx: usize, text: String) {
@@ -52,6 +131,10 @@ impl TextRenderComponentManager {
         self.data[idx].text = text;
     }
 
+    pub fn get_text(&self, idx: usize) -> &String {
+        &self.data[idx].text
+    }
+
     pub fn is_constructed(&self, idx: usize) -> bool {
         self.get_data_ref(idx).constructed
     }
@@ -61,114 +144,207 @@ impl TextRenderComponentManager {
     }
 
     // potential cache miss
-    fn recalc_width(&self, idx: usize) {
-        let cache_data = self.get_data_ref_mut(idx);
+    fn recalc_layout(&mut self, idx: usize) -> fonts::layout::LayoutResult {
+        let font = &self.fonts[self.data[idx].font];
+        let layout = fonts::layout::layout(font, &self.data[idx].text, &self.data[idx].layout);
+        self.get_data_ref_mut(idx).width = layout.width as u16;
+        layout
+    }
 
-        let bytes = self.data[idx].text.as_bytes();
-        let mut basex: f32 = 0.0;
+    pub fn set_layout(&mut self, idx: usize, layout: fonts::layout::TextLayout) {
+        self.get_data_ref_mut(idx).reconstruct_needed = true;
+        self.data[idx].layout = layout;
+    }
+
+    pub fn get_layout(&self, idx: usize) -> fonts::layout::TextLayout {
+        self.data[idx].layout
+    }
 
-        for i in 0..bytes.len() {
-            let idx = bytes[i] as u16;
-            let data = self.font.data[&idx];
-            let advance = data[6] as f32;
-            basex += advance;
+    pub fn set_fallback_glyph(&mut self, font: usize, c: char) -> bool {
+        let ret = match self.fonts.get_mut(font) {
+            Some(f) => f.set_fallback_glyph(c),
+            None => {
+                log(format!("WARNING: Invalid font id {}", font));
+                false
+            }
+        };
+        if ret {
+            self.mark_font_dirty(font);
         }
+        ret
+    }
 
-        cache_data.width = basex as u16;
+    pub fn clear_fallback_glyph(&mut self, font: usize) {
+        match self.fonts.get_mut(font) {
+            Some(f) => f.clear_fallback_glyph(),
+            None => {
+                log(format!("WARNING: Invalid font id {}", font));
+                return;
+            }
+        }
+        self.mark_font_dirty(font);
+    }
+
+    // characters in text that the font would draw with the fallback glyph,
+    // empty for an unknown font
+    pub fn get_missing_glyphs(&self, font: usize, text: &str) -> Vec<char> {
+        match self.fonts.get(font) {
+            Some(f) => f.missing_glyphs(text),
+            None => Vec::new(),
+        }
+    }
+
+    fn mark_font_dirty(&mut self, font: usize) {
+        for i in 0..ENTITY_SZ {
+            if font == self.data[i].font && !self.data[i].text.is_empty() {
+                self.get_data_ref_mut(i).reconstruct_needed = true;
+            }
+        }
+    }
+
+    fn load_font_pages(&mut self, font: usize, gl: &Gl) {
+        if !self.font_textures[font].is_empty() {
+            return;
+        }
+        for p in self.fonts[font].page_files.iter() {
+            self.font_textures[font].push(gl.load_texture(p));
+        }
     }
 
     // probable cache miss
-    pub fn construct(&self, idx: usize, gl: &Gl, vao: u32, vbo: u32) {
+    pub fn construct(&mut self, idx: usize, gl: &Gl, vao: u32, vbo: u32) {
+        let font_id = self.data[idx].font;
+        let text = self.data[idx].text.clone();
+        let missing = self.fonts[font_id].missing_glyphs(&fonts::layout::strip_markup(&text));
+        if !missing.is_empty() {
+            log(format!(
+                "WARNING: Text {}: font {} missing glyphs {:?}",
+                idx, font_id, missing
+            ));
+        }
+
+        let text_layout = self.data[idx].layout;
+        let (spans, layout) = self.build(font_id, &text, &text_layout, gl, vao, vbo);
+        let num_chars: usize = spans.iter().map(|s| s.count).sum();
+
+        self.data[idx].spans = spans;
+        self.data[idx].height = layout.height;
+
         let cache_data = self.get_data_ref_mut(idx);
+        cache_data.reconstruct_needed = false;
+        cache_data.constructed = true;
+        cache_data.num_chars = num_chars as u32;
+        cache_data.width = layout.width as u16;
+    }
+
+    // lays out and buffers text that doesn't belong to an entity, the spans
+    // are drawn with Gl::draw_text
+    pub fn build(
+        &mut self,
+        font_id: usize,
+        text: &str,
+        text_layout: &fonts::layout::TextLayout,
+        gl: &Gl,
+        vao: u32,
+        vbo: u32,
+    ) -> (Vec<TextSpan>, fonts::layout::LayoutResult) {
+        self.load_font_pages(font_id, gl);
 
-        /*println!("{}, {}", self.font.scale_w, self.font.scale_h);
-        println!("{:?}", self.font.page_files);
-        println!("{:?}", self.font.data[&('t' as u16)]);
-        println!("{:?}", self.data[idx].text);
-        let bytes = self.data[idx].text.as_bytes();
-        for i in 0..bytes.len() {
-            let idx = bytes[i] as u16;
-            println!("{:?} {:?}", bytes, self.font.data[&idx]);
-        }*/
-
-        let ww = 256.0;
-        let hh = 64.0;
+        let font = &self.fonts[font_id];
+        let ww = font.scale_w as f32;
+        let hh = font.scale_h as f32;
         let mut vertex_data: Vec<f32> = Vec::new();
+        let mut spans: Vec<TextSpan> = Vec::new();
 
-        let bytes = self.data[idx].text.as_bytes();
-        let num_chars = bytes.len();
+        let layout = fonts::layout::layout(font, text, text_layout);
+        let mut num_chars: usize = 0;
 
-        let mut basex: f32 = 0.0;
+        // glyphs are grouped by page so each page is a single draw call
+        for page in 0..font.page_files.len() {
+            let first = num_chars;
 
-        for i in 0..num_chars {
-            let idx = bytes[i] as u16;
+            for g in layout.glyphs.iter() {
+                let data = g.glyph;
+                if page != data[7] as usize {
+                    continue;
+                }
 
-            let data = self.font.data[&idx];
-            let dx = data[0] as f32 / ww;
-            let dy = data[1] as f32 / hh;
-            let dw = data[2] as f32;
-            let dh = data[3] as f32;
-            let dwt = dw as f32 / ww;
-            let dht = dh as f32 / hh;
-            let dxoff = data[4] as f32;
-            let dyoff = data[5] as f32;
-            let advance = data[6] as f32;
+                let dx = data[0] as f32 / ww;
+                let dy = data[1] as f32 / hh;
+                let dw = data[2] as f32;
+                let dh = data[3] as f32;
+                let dwt = dw / ww;
+                let dht = dh / hh;
+                let x0 = g.x + data[4] as f32;
+                let y0 = g.y + data[5] as f32;
+                let c = g.color;
 
-            let p0 = [basex + 0.0 + dxoff, 0.0 + dyoff, dx, dy];
-            let p1 = [basex + 0.0 + dxoff, dh + dyoff, dx, dy + dht];
-            let p2 = [basex + dw + dxoff, dh + dyoff, dx + dwt, dy + dht];
-            let p3 = [basex + dw + dxoff, 0.0 + dyoff, dx + dwt, dy];
+                let p0 = [x0, y0, dx, dy, c[0], c[1], c[2], c[3]];
+                let p1 = [x0, y0 + dh, dx, dy + dht, c[0], c[1], c[2], c[3]];
+                let p2 = [x0 + dw, y0 + dh, dx + dwt, dy + dht, c[0], c[1], c[2], c[3]];
+                let p3 = [x0 + dw, y0, dx + dwt, dy, c[0], c[1], c[2], c[3]];
 
-            for i in 0..4 {
-                vertex_data.push(p0[i]);
-            }
-            for i in 0..4 {
-                vertex_data.push(p1[i]);
-            }
-            for i in 0..4 {
-                vertex_data.push(p2[i]);
-            }
-            for i in 0..4 {
-                vertex_data.push(p0[i]);
-            }
-            for i in 0..4 {
-                vertex_data.push(p2[i]);
-            }
-            for i in 0..4 {
-                vertex_data.push(p3[i]);
+                vertex_data.extend_from_slice(&p0);
+                vertex_data.extend_from_slice(&p1);
+                vertex_data.extend_from_slice(&p2);
+                vertex_data.extend_from_slice(&p0);
+                vertex_data.extend_from_slice(&p2);
+                vertex_data.extend_from_slice(&p3);
+
+                num_chars += 1;
             }
 
-            basex += advance;
+            if num_chars > first {
+                spans.push(TextSpan {
+                    tex: self.font_textures[font_id][page],
+                    first,
+                    count: num_chars - first,
+                });
+            }
         }
 
         let data_ptr = vertex_data.as_ptr() as *const _;
         gl.buffer_font_data(vao, vbo, num_chars, data_ptr);
 
-        cache_data.reconstruct_needed = false;
-        cache_data.constructed = true;
-        cache_data.num_chars = num_chars as u16;
-        cache_data.width = basex as u16;
+        (spans, layout)
+    }
+
+    pub fn get_spans(&self, idx: usize) -> &Vec<TextSpan> {
+        &self.data[idx].spans
     }
 
     pub fn get_length(&self, idx: usize) -> usize {
         self.get_data_ref(idx).num_chars as usize
     }
 
-    pub fn get_width(&self, idx: usize) -> usize {
+    pub fn get_width(&mut self, idx: usize) -> usize {
         if self.reconstruct(idx) {
             // force recalc if hasn't happened on its own yet
-            self.recalc_width(idx);
+            self.recalc_layout(idx);
         }
         self.get_data_ref(idx).width as usize
     }
 
-    fn get_data_ref_mut(&self, idx: usize) -> &mut TextRenderComponentManagerData {
-        assert!(idx < ENTITY_SZ);
-        unsafe { &mut *(self.cache_data.offset(idx as isize)) }
+    pub fn get_height(&mut self, idx: usize) -> usize {
+        if self.reconstruct(idx) {
+            return self.recalc_layout(idx).height as usize;
+        }
+        self.data[idx].height as usize
+    }
+
+    // stands in for construct() in tests, which needs a GL context
+    #[cfg(test)]
+    pub fn set_constructed(&mut self, idx: usize) {
+        let cache_data = self.get_data_ref_mut(idx);
+        cache_data.reconstruct_needed = false;
+        cache_data.constructed = true;
+    }
+
+    fn get_data_ref_mut(&mut self, idx: usize) -> &mut TextRenderComponentManagerData {
+        &mut self.cache_data[idx]
     }
 
     fn get_data_ref(&self, idx: usize) -> &TextRenderComponentManagerData {
-        assert!(idx < ENTITY_SZ);
-        unsafe { &*(self.cache_data.offset(idx as isize)) }
+        &self.cache_data[idx]
     }
 }
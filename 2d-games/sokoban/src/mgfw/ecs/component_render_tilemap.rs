@@ -1,5 +1,14 @@
 use super::*;
 use crate::mgfw::log;
+use std::collections::HashMap;
+
+// per tile flags, the diagonal flip (swap x and y) is applied first
+pub const TILE_FLIP_HORIZONTAL: u8 = 1 << 0;
+pub const TILE_FLIP_VERTICAL: u8 = 1 << 1;
+pub const TILE_FLIP_DIAGONAL: u8 = 1 << 2;
+
+// floats per tile quad, 6 vertices of (x, y, u, v)
+const TILE_FLOATS: usize = 2 * 3 * 4;
 
 struct TilesetBuffer {
     // WARNING: Anything below this line is not in cache!
@@ -9,13 +18,26 @@ struct TilesetBuffer {
     tile_height: usize,
     span: usize,
     count: usize,
+    // tile -> (tile, seconds) frames, tiles as in the map data
+    animations: HashMap<u16, Vec<(u16, f32)>>,
+}
+
+struct TilemapLayer {
+    data: Vec<u16>,
+    flags: Vec<u8>,
 }
 
 struct TilemapBuffer {
     // WARNING: Anything below this line is not in cache!
-    data: Vec<u16>,
+    // drawn in order, each one a full columns x rows grid
+    layers: Vec<TilemapLayer>,
+    // quads to rewrite on the next update, slot = layer * cells + cell
+    dirty: Vec<usize>,
+    // animated slots and the tile they currently show
+    animated: Vec<(usize, u16)>,
 }
 
+#[derive(Default)]
 struct TilemapRenderComponentManagerData {
     columns: usize,
     rows: usize,
@@ -26,10 +48,11 @@ struct TilemapRenderComponentManagerData {
 }
 
 pub struct TilemapRenderComponentManager {
-    cache_data: *mut TilemapRenderComponentManagerData,
+    cache_data: CacheBlock<TilemapRenderComponentManagerData>,
     // WARNING: Anything below this line is not in cache!
     tileset: std::boxed::Box<Vec<TilesetBuffer>>,
     tilemap: std::boxed::Box<Vec<TilemapBuffer>>,
+    start: std::time::Instant,
 }
 
 #[allow(dead_code)]
@@ -47,23 +70,30 @@ impl TilemapRenderComponentManager {
                 image_height: 240,
                 span: 16,
                 count: 1,
+                animations: HashMap::new(),
+            });
+            tilemap.push(TilemapBuffer {
+                layers: Vec::new(),
+                dirty: Vec::new(),
+                animated: Vec::new(),
             });
-            tilemap.push(TilemapBuffer { data: Vec::new() });
         }
 
         // allocate system memory in cache
-        let sz_bytes = std::mem::size_of::<TilemapRenderComponentManagerData>() * ENTITY_SZ;
-        let cache_data = mgr.allocate(sz_bytes) as *mut TilemapRenderComponentManagerData;
+        let cache_data = mgr.allocate("TilemapRenderComponentManager", ENTITY_SZ);
 
         TilemapRenderComponentManager {
             tileset: Box::new(tileset),
             tilemap: Box::new(tilemap),
             cache_data,
+            start: std::time::Instant::now(),
         }
     }
 
     pub fn clear(&mut self) {
-        
+        for i in 0..ENTITY_SZ {
+            self.tileset[i].animations.clear();
+        }
     }
 
     pub fn set_tileset(
@@ -83,12 +113,29 @@ impl TilemapRenderComponentManager {
         self.tileset[idx].count =
             self.tileset[idx].span * (image_height - (image_height % tile_height)) / tile_height;
 
-        println!(
-            "tileset {},{},{}",
+        log(format!(
+            "DEBUG: tileset {},{},{}",
             idx, self.tileset[idx].span, self.tileset[idx].count
-        );
+        ));
+    }
+
+    // tile shows each frame for its duration in turn, frames empty to stop
+    pub fn set_animation(&mut self, tileset_idx: usize, tile: u16, frames: &[(u16, f32)]) {
+        match frames.iter().any(|f| 0.0 < f.1) {
+            true => self.tileset[tileset_idx].animations.insert(tile, frames.to_vec()),
+            false => self.tileset[tileset_idx].animations.remove(&tile),
+        };
+
+        // maps using the tileset need to find their animated tiles again
+        for i in 0..ENTITY_SZ {
+            let cache_data = self.get_data_ref_mut(i);
+            if cache_data.constructed && tileset_idx == cache_data.tileset {
+                cache_data.reconstruct_needed = true;
+            }
+        }
     }
 
+    // replaces every layer with this one
     pub fn set_tilemap(&mut self, idx: usize, tileset_idx: usize, columns: usize, data: &Vec<u16>) {
         let cache_data = self.get_data_ref_mut(idx);
         cache_data.reconstruct_needed = true;
@@ -99,7 +146,102 @@ impl TilemapRenderComponentManager {
         assert!(0 != n);
         assert!(0 == n % columns);
         cache_data.rows = (n - (n % columns)) / columns;
-        self.tilemap[idx].data = data.clone();
+        self.tilemap[idx].layers = vec![TilemapLayer {
+            data: data.clone(),
+            flags: vec![0; n],
+        }];
+    }
+
+    // layers past the last one are added, empty ones in between
+    pub fn set_layer(&mut self, idx: usize, layer: usize, data: &Vec<u16>) {
+        let cache_data = self.get_data_ref_mut(idx);
+        let n = cache_data.columns * cache_data.rows;
+        if n != data.len() {
+            log(format!(
+                "WARNING: TilemapRenderComponentManager: layer {} of {} has {} tiles, expected {}",
+                layer,
+                idx,
+                data.len(),
+                n
+            ));
+            return;
+        }
+        cache_data.reconstruct_needed = true;
+        let layers = &mut self.tilemap[idx].layers;
+        while layers.len() <= layer {
+            layers.push(TilemapLayer {
+                data: vec![EMPTY_TILE; n],
+                flags: vec![0; n],
+            });
+        }
+        layers[layer] = TilemapLayer {
+            data: data.clone(),
+            flags: vec![0; n],
+        };
+    }
+
+    pub fn get_num_layers(&self, idx: usize) -> usize {
+        self.tilemap[idx].layers.len()
+    }
+
+    // single tile changes only rewrite that tile in the vertex buffer
+    pub fn set_tile(&mut self, idx: usize, layer: usize, column: usize, row: usize, tile: u16) {
+        if let Some(slot) = self.get_slot(idx, layer, column, row) {
+            let cell = slot % self.get_num_cells(idx);
+            let l = &mut self.tilemap[idx].layers[layer];
+            if tile != l.data[cell] {
+                l.data[cell] = tile;
+                self.mark_dirty(idx, slot);
+            }
+        }
+    }
+
+    pub fn get_tile(&self, idx: usize, layer: usize, column: usize, row: usize) -> u16 {
+        match self.get_slot(idx, layer, column, row) {
+            Some(slot) => self.tilemap[idx].layers[layer].data[slot % self.get_num_cells(idx)],
+            None => EMPTY_TILE,
+        }
+    }
+
+    pub fn set_tile_flags(&mut self, idx: usize, layer: usize, column: usize, row: usize, flags: u8) {
+        if let Some(slot) = self.get_slot(idx, layer, column, row) {
+            let cell = slot % self.get_num_cells(idx);
+            let l = &mut self.tilemap[idx].layers[layer];
+            if flags != l.flags[cell] {
+                l.flags[cell] = flags;
+                self.mark_dirty(idx, slot);
+            }
+        }
+    }
+
+    pub fn get_tile_flags(&self, idx: usize, layer: usize, column: usize, row: usize) -> u8 {
+        match self.get_slot(idx, layer, column, row) {
+            Some(slot) => self.tilemap[idx].layers[layer].flags[slot % self.get_num_cells(idx)],
+            None => 0,
+        }
+    }
+
+    // (image_width, image_height, tile_width, tile_height)
+    pub fn get_tileset(&self, idx: usize) -> (usize, usize, usize, usize) {
+        let t = &self.tileset[idx];
+        (t.image_width, t.image_height, t.tile_width, t.tile_height)
+    }
+
+    pub fn get_columns(&self, idx: usize) -> usize {
+        self.get_data_ref(idx).columns
+    }
+
+    pub fn get_rows(&self, idx: usize) -> usize {
+        self.get_data_ref(idx).rows
+    }
+
+    // first layer
+    pub fn get_tilemap(&self, idx: usize) -> &Vec<u16> {
+        &self.tilemap[idx].layers[0].data
+    }
+
+    pub fn get_layer(&self, idx: usize, layer: usize) -> Option<&Vec<u16>> {
+        self.tilemap[idx].layers.get(layer).map(|l| &l.data)
     }
 
     pub fn is_constructed(&self, idx: usize) -> bool {
@@ -110,92 +252,228 @@ impl TilemapRenderComponentManager {
         self.get_data_ref(idx).reconstruct_needed
     }
 
+    // changed or animated tiles are waiting for update()
+    pub fn update_needed(&self, idx: usize) -> bool {
+        let t = &self.tilemap[idx];
+        self.get_data_ref(idx).constructed && (!t.dirty.is_empty() || !t.animated.is_empty())
+    }
+
     pub fn get_tileset_idx(&self, idx: usize) -> usize {
         self.get_data_ref(idx).tileset
     }
 
+    // tile quads in the vertex buffer, empty tiles included
     pub fn get_num_tiles(&self, idx: usize) -> usize {
         self.get_data_ref(idx).num_tiles as usize
     }
 
-    pub fn construct(&self, idx: usize, gl: &Gl, vao: u32, vbo: u32) {
+    pub fn construct(&mut self, idx: usize, gl: &Gl, vao: u32, vbo: u32) {
+        let cells = self.get_num_cells(idx);
+        let num_tiles = cells * self.tilemap[idx].layers.len();
+        if num_tiles > u16::MAX as usize {
+            log(format!(
+                "WARNING: TilemapRenderComponentManager: {} has {} tiles, only {} are drawn",
+                idx,
+                num_tiles,
+                u16::MAX
+            ));
+        }
+        let num_tiles = num_tiles.min(u16::MAX as usize);
+
+        let time = self.start.elapsed().as_secs_f32();
+        let mut vertex_data: Vec<f32> = Vec::with_capacity(num_tiles * TILE_FLOATS);
+        let mut animated: Vec<(usize, u16)> = Vec::new();
+        for slot in 0..num_tiles {
+            let tile = self.get_shown_tile(idx, slot, time);
+            if self.is_animated(idx, slot) {
+                animated.push((slot, tile));
+            }
+            vertex_data.extend_from_slice(&self.get_quad(idx, slot, tile));
+        }
+
+        let data_ptr = vertex_data.as_ptr() as *const _;
+        gl.buffer_tilemap_data(vao, vbo, num_tiles, data_ptr);
+
+        self.tilemap[idx].dirty.clear();
+        self.tilemap[idx].animated = animated;
+
         let cache_data = self.get_data_ref_mut(idx);
-        let cols = cache_data.columns;
+        cache_data.reconstruct_needed = false;
+        cache_data.constructed = true;
+        cache_data.num_tiles = num_tiles as u16;
+        log(format!("DEBUG: Constructing tilemap {}", idx));
+    }
 
-        let mut vertex_data: Vec<f32> = Vec::new();
+    // rewrites changed tiles and animation frames in place
+    pub fn update(&mut self, idx: usize, gl: &Gl, vbo: u32) {
+        let time = self.start.elapsed().as_secs_f32();
+        let mut animated = std::mem::take(&mut self.tilemap[idx].animated);
+        for a in animated.iter_mut() {
+            let tile = self.get_shown_tile(idx, a.0, time);
+            if tile != a.1 {
+                a.1 = tile;
+                self.tilemap[idx].dirty.push(a.0);
+            }
+        }
+        self.tilemap[idx].animated = animated;
+
+        let mut dirty = std::mem::take(&mut self.tilemap[idx].dirty);
+        dirty.sort_unstable();
+        dirty.dedup();
+        let num_tiles = self.get_num_tiles(idx);
+        for slot in dirty.into_iter().filter(|s| *s < num_tiles) {
+            let tile = self.get_shown_tile(idx, slot, time);
+            let quad = self.get_quad(idx, slot, tile);
+            gl.update_tilemap_data(vbo, slot, 1, quad.as_ptr() as *const _);
+        }
+    }
 
-        let map = &self.tilemap[idx].data;
+    fn get_num_cells(&self, idx: usize) -> usize {
+        let cache_data = self.get_data_ref(idx);
+        cache_data.columns * cache_data.rows
+    }
 
-        let mut num_tiles: usize = 0;
+    fn get_slot(&self, idx: usize, layer: usize, column: usize, row: usize) -> Option<usize> {
+        let cache_data = self.get_data_ref(idx);
+        if layer >= self.tilemap[idx].layers.len() || column >= cache_data.columns || row >= cache_data.rows {
+            return None;
+        }
+        Some(layer * self.get_num_cells(idx) + row * cache_data.columns + column)
+    }
 
-        let tileset = &self.tileset[cache_data.tileset];
-        let uscale = tileset.tile_width as f32 / tileset.image_width as f32;
-        let vscale = tileset.tile_height as f32 / tileset.image_height as f32;
-        let usub = uscale * 0.0; //(0.2 / tileset.tile_width as f32);
-        let vsub = uscale * 0.0; //(0.2 / tileset.tile_height as f32);
+    fn mark_dirty(&mut self, idx: usize, slot: usize) {
+        let map = &mut self.tilemap[idx];
+        map.dirty.push(slot);
+        // the tile may have started or stopped being animated
+        let animated = self.is_animated(idx, slot);
+        let map = &mut self.tilemap[idx];
+        match map.animated.iter().position(|a| a.0 == slot) {
+            Some(i) if !animated => {
+                map.animated.swap_remove(i);
+            }
+            None if animated => map.animated.push((slot, EMPTY_TILE)),
+            _ => (),
+        }
+    }
+
+    fn get_slot_tile(&self, idx: usize, slot: usize) -> (u16, u8) {
+        let cells = self.get_num_cells(idx);
+        let l = &self.tilemap[idx].layers[slot / cells];
+        (l.data[slot % cells], l.flags[slot % cells])
+    }
 
-        for i in 0..map.len() {
-            let t0 = map[i] as usize;
-            if EMPTY_TILE == t0 as u16 || tileset.count < t0 {
-                continue;
+    fn is_animated(&self, idx: usize, slot: usize) -> bool {
+        let tileset = &self.tileset[self.get_data_ref(idx).tileset];
+        tileset.animations.contains_key(&self.get_slot_tile(idx, slot).0)
+    }
+
+    // the animation frame for animated tiles
+    fn get_shown_tile(&self, idx: usize, slot: usize, time: f32) -> u16 {
+        let tile = self.get_slot_tile(idx, slot).0;
+        let tileset = &self.tileset[self.get_data_ref(idx).tileset];
+        let frames = match tileset.animations.get(&tile) {
+            Some(f) => f,
+            None => return tile,
+        };
+        let total: f32 = frames.iter().map(|f| f.1.max(0.0)).sum();
+        let mut t = time % total;
+        for f in frames.iter() {
+            if t < f.1 {
+                return f.0;
             }
-            let t0 = t0 - 1;
-
-            let u0 = (t0 % tileset.span) as f32 * uscale + usub;
-            let v0 = ((t0 - (t0 % tileset.span)) / tileset.span) as f32 * vscale + vsub;
-            let u1 = u0 + uscale - usub;
-            let v1 = v0 + vscale - vsub;
-
-            let x0 = (i % cols) as f32;
-            let y0 = 1.0 * (((i - (i % cols)) / cols) as f32);
-            let x1 = x0 + 1.0;
-            let y1 = y0 + 1.0;
-
-            vertex_data.push(x0);
-            vertex_data.push(y0);
-            vertex_data.push(u0);
-            vertex_data.push(v0);
-            vertex_data.push(x0);
-            vertex_data.push(y1);
-            vertex_data.push(u0);
-            vertex_data.push(v1);
-            vertex_data.push(x1);
-            vertex_data.push(y1);
-            vertex_data.push(u1);
-            vertex_data.push(v1);
-
-            vertex_data.push(x0);
-            vertex_data.push(y0);
-            vertex_data.push(u0);
-            vertex_data.push(v0);
-            vertex_data.push(x1);
-            vertex_data.push(y1);
-            vertex_data.push(u1);
-            vertex_data.push(v1);
-            vertex_data.push(x1);
-            vertex_data.push(y0);
-            vertex_data.push(u1);
-            vertex_data.push(v0);
-
-            num_tiles += 1;
+            t -= f.1.max(0.0);
         }
+        frames[frames.len() - 1].0
+    }
 
-        let data_ptr = vertex_data.as_ptr() as *const _;
-        gl.buffer_tilemap_data(vao, vbo, num_tiles, data_ptr);
+    // empty and out of range tiles are degenerate quads, so every slot keeps
+    // its place in the vertex buffer
+    fn get_quad(&self, idx: usize, slot: usize, tile: u16) -> [f32; TILE_FLOATS] {
+        let cache_data = self.get_data_ref(idx);
+        let cols = cache_data.columns;
+        let tileset = &self.tileset[cache_data.tileset];
+        let t0 = tile as usize;
+        if EMPTY_TILE == tile || tileset.count < t0 {
+            return [0.0; TILE_FLOATS];
+        }
+        let t0 = t0 - 1;
+        let flags = self.get_slot_tile(idx, slot).1;
 
-        cache_data.reconstruct_needed = false;
-        cache_data.constructed = true;
-        cache_data.num_tiles = num_tiles as u16;
-        println!("Constructing tilemap {}", idx);
+        let uscale = tileset.tile_width as f32 / tileset.image_width as f32;
+        let vscale = tileset.tile_height as f32 / tileset.image_height as f32;
+        let u0 = (t0 % tileset.span) as f32 * uscale;
+        let v0 = ((t0 - (t0 % tileset.span)) / tileset.span) as f32 * vscale;
+        let u1 = u0 + uscale;
+        let v1 = v0 + vscale;
+
+        let i = slot % self.get_num_cells(idx);
+        let x0 = (i % cols) as f32;
+        let y0 = ((i - (i % cols)) / cols) as f32;
+
+        // texture corner shown at quad corner (cx, cy)
+        let uv = |cx: f32, cy: f32| -> [f32; 4] {
+            let mut a = cx;
+            let mut b = cy;
+            if 0 != flags & TILE_FLIP_HORIZONTAL {
+                a = 1.0 - a;
+            }
+            if 0 != flags & TILE_FLIP_VERTICAL {
+                b = 1.0 - b;
+            }
+            if 0 != flags & TILE_FLIP_DIAGONAL {
+                std::mem::swap(&mut a, &mut b);
+            }
+            [x0 + cx, y0 + cy, u0 + (u1 - u0) * a, v0 + (v1 - v0) * b]
+        };
+
+        let mut ret = [0.0; TILE_FLOATS];
+        for (k, (cx, cy)) in [(0.0, 0.0), (0.0, 1.0), (1.0, 1.0), (0.0, 0.0), (1.0, 1.0), (1.0, 0.0)]
+            .iter()
+            .enumerate()
+        {
+            ret[k * 4..k * 4 + 4].copy_from_slice(&uv(*cx, *cy));
+        }
+        ret
     }
 
-    fn get_data_ref_mut(&self, idx: usize) -> &mut TilemapRenderComponentManagerData {
-        assert!(idx < ENTITY_SZ);
-        unsafe { &mut *(self.cache_data.offset(idx as isize)) }
+    fn get_data_ref_mut(&mut self, idx: usize) -> &mut TilemapRenderComponentManagerData {
+        &mut self.cache_data[idx]
     }
 
     fn get_data_ref(&self, idx: usize) -> &TilemapRenderComponentManagerData {
-        assert!(idx < ENTITY_SZ);
-        unsafe { &*(self.cache_data.offset(idx as isize)) }
+        &self.cache_data[idx]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layers_flips_and_animation_frames() {
+        let mut cache = CacheManager::new();
+        let mut tmcm = TilemapRenderComponentManager::new(&mut cache);
+        tmcm.set_tileset(0, 32, 32, 16, 16);
+        tmcm.set_tilemap(1, 0, 2, &vec![1, 2, 0, 4]);
+        tmcm.set_layer(1, 2, &vec![3, 0, 0, 0]);
+        assert_eq!(3, tmcm.get_num_layers(1));
+        assert_eq!(Some(&vec![0, 0, 0, 0]), tmcm.get_layer(1, 1));
+
+        tmcm.set_tile(1, 1, 1, 1, 2);
+        assert_eq!(2, tmcm.get_tile(1, 1, 1, 1));
+        assert_eq!(EMPTY_TILE, tmcm.get_tile(1, 1, 2, 0));
+
+        // tile 2 is the top right of the image, flipped horizontally
+        tmcm.set_tile_flags(1, 0, 1, 0, TILE_FLIP_HORIZONTAL);
+        let quad = tmcm.get_quad(1, 1, 2);
+        assert_eq!([1.0, 0.0, 1.0, 0.0], quad[0..4]);
+        assert_eq!([2.0, 1.0, 0.5, 0.5], quad[8..12]);
+        assert_eq!([0.0; TILE_FLOATS], tmcm.get_quad(1, 2, EMPTY_TILE));
+
+        tmcm.set_animation(0, 4, &[(4, 0.5), (1, 0.25)]);
+        assert_eq!(4, tmcm.get_shown_tile(1, 3, 0.25));
+        assert_eq!(1, tmcm.get_shown_tile(1, 3, 0.5));
+        assert_eq!(4, tmcm.get_shown_tile(1, 3, 0.8));
+        assert_eq!(2, tmcm.get_shown_tile(1, 1, 0.5));
     }
 }
@@ -1,14 +1,14 @@
 use super::*;
 use crate::mgfw::log;
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, Default)]
 pub struct Scale {
     pub x: f32,
     pub y: f32,
 }
 
 pub struct ScaleComponentManager {
-    data: *mut Scale,
+    data: CacheBlock<Scale>,
     // WARNING: Anything below this line is not in cache!
 }
 
@@ -16,9 +16,8 @@ pub struct ScaleComponentManager {
 impl ScaleComponentManager {
     pub fn new(mgr: &mut CacheManager) -> ScaleComponentManager {
         log(format!("Constructing ScaleComponentManager"));
-        let sz_bytes = std::mem::size_of::<Scale>() * ENTITY_SZ;
         ScaleComponentManager {
-            data: mgr.allocate(sz_bytes) as *mut Scale,
+            data: mgr.allocate("ScaleComponentManager", ENTITY_SZ),
         }
     }
 
@@ -28,7 +27,7 @@ impl ScaleComponentManager {
         }
     }
 
-    pub fn set_scale(&self, idx: usize, x: f32, y: f32) {
+    pub fn set_scale(&mut self, idx: usize, x: f32, y: f32) {
         let scale = self.get_data_ref_mut(idx);
         scale.x = x;
         scale.y = y;
@@ -39,13 +38,11 @@ impl ScaleComponentManager {
         scale.clone()
     }
 
-    pub fn get_data_ref_mut(&self, idx: usize) -> &mut Scale {
-        assert!(idx < ENTITY_SZ);
-        unsafe { &mut *(self.data.offset(idx as isize)) }
+    pub fn get_data_ref_mut(&mut self, idx: usize) -> &mut Scale {
+        &mut self.data[idx]
     }
 
     pub fn get_data_ref(&self, idx: usize) -> &Scale {
-        assert!(idx < ENTITY_SZ);
-        unsafe { &*(self.data.offset(idx as isize)) }
+        &self.data[idx]
     }
 }
@@ -9,12 +9,13 @@ pub struct EntityIdSpan {
     pub last: usize,
 }
 
+#[derive(Default)]
 struct Entity {
     components: u32,
 }
 
 pub struct EntityRegistry {
-    data: *mut Entity,
+    data: CacheBlock<Entity>,
     // WARNING: Anything below this line is not in cache!
     cursor: usize, // current insertion cursor
     span: EntityIdSpan,
@@ -24,9 +25,8 @@ pub struct EntityRegistry {
 impl EntityRegistry {
     pub fn new(mgr: &mut CacheManager) -> EntityRegistry {
         log(format!("Constructing EntityRegistry"));
-        let sz_bytes = std::mem::size_of::<Entity>() * ENTITY_SZ;
         EntityRegistry {
-            data: mgr.allocate(sz_bytes) as *mut Entity,
+            data: mgr.allocate("EntityRegistry", ENTITY_SZ),
             cursor: 0,
             span: EntityIdSpan {
                 first: ENTITY_SZ - 1,
@@ -134,13 +134,11 @@ impl EntityRegistry {
         }
     }
 
-    fn get_data_ref_mut(&self, idx: usize) -> &mut Entity {
-        assert!(idx < ENTITY_SZ);
-        unsafe { &mut *(self.data.offset(idx as isize)) }
+    fn get_data_ref_mut(&mut self, idx: usize) -> &mut Entity {
+        &mut self.data[idx]
     }
 
     fn get_data_ref(&self, idx: usize) -> &Entity {
-        assert!(idx < ENTITY_SZ);
-        unsafe { &*(self.data.offset(idx as isize)) }
+        &self.data[idx]
     }
 }
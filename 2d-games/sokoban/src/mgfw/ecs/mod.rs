@@ -1,7 +1,9 @@
+pub mod accessibility;
 pub mod component_angle;
 pub mod component_color;
 pub mod component_easing;
 pub mod component_frame;
+pub mod component_material;
 pub mod component_physics;
 pub mod component_position;
 pub mod component_render;
@@ -12,18 +14,26 @@ pub mod component_render_tilemap;
 pub mod component_render_triangle_buffer;
 pub mod component_scale;
 pub mod entity;
+pub mod scene;
 pub mod system_easing;
 pub mod system_physics;
 pub mod system_render;
+pub mod tiled;
 pub mod world;
+pub mod world_parser;
 
+pub use accessibility::*;
 use entity::*;
+pub use scene::*;
+pub use tiled::*;
 pub use world::*;
+pub use world_parser::*;
 
 pub use component_angle::*;
 pub use component_color::*;
 use component_easing::*;
 use component_frame::*;
+pub use component_material::*;
 pub use component_physics::*;
 pub use component_position::*;
 use component_render::*;
@@ -38,8 +48,10 @@ pub use system_easing::*;
 pub use system_physics::*;
 pub use system_render::*;
 
-use super::cache::CacheManager;
+use super::assets;
+use super::cache::{CacheBlock, CacheManager};
 use super::fonts;
+pub use super::fonts::layout::{TEXT_ALIGN_CENTER, TEXT_ALIGN_LEFT, TEXT_ALIGN_RIGHT};
 use super::support::Gl;
 
 pub const COMPONENT_ACTIVE: u32 = 1 << 0;
@@ -51,4 +63,5 @@ pub const COMPONENT_ANGLE: u32 = 1 << 5;
 pub const COMPONENT_SCALE: u32 = 1 << 6;
 pub const COMPONENT_COLOR: u32 = 1 << 7;
 pub const COMPONENT_FRAME: u32 = 1 << 8;
+pub const COMPONENT_MATERIAL: u32 = 1 << 9;
 pub const EMPTY_TILE: u16 = 0;
@@ -0,0 +1,769 @@
+use super::*;
+use crate::mgfw::log;
+use ron::extensions::Extensions;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// Structured scene files (RON), an alternative to world.dat:
+//
+//   // comments work anywhere
+//   Scene(
+//       entities: [
+//           (
+//               name: "hud",
+//               position: (320, 8),
+//               children: [
+//                   // children are positioned relative to their parent
+//                   (text: "Level 1", align: Center, color: (1.0, 0.5, 0.2, 1.0)),
+//                   (billboard: "assets/flame.png", position: (40, 0), scale: (32, 32)),
+//               ],
+//           ),
+//           (id: 10, name: "tiles", tileset: (image: "assets/tiles.png",
+//               image_width: 128, image_height: 128, tile_width: 16, tile_height: 16)),
+//           (tilemap: (tileset: "tiles", columns: 2, data: [1, 2, 3, 4])),
+//           // any field set next to `prefab` overrides the prefab's value
+//           (prefab: "coin", position: (100, 50)),
+//       ],
+//       prefabs: {
+//           "coin": (billboard: "assets/coin.png", scale: (16, 16)),
+//       },
+//   )
+//
+// Every field is optional. Entities without an id get the next free one,
+// entities without `visible` are shown. Angles are in degrees. Prefabs are
+// registered with the World and can be spawned from code with spawn_prefab().
+
+// x, y, r, g, b, a
+pub type SceneVertex = (f32, f32, f32, f32, f32, f32);
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum SceneAlign {
+    Left,
+    Center,
+    Right,
+}
+
+// reference to another entity by world id or by name
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum SceneRef {
+    Id(usize),
+    Name(String),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SceneTileset {
+    pub image: String,
+    pub image_width: usize,
+    pub image_height: usize,
+    pub tile_width: usize,
+    pub tile_height: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SceneTilemap {
+    pub tileset: SceneRef,
+    pub columns: usize,
+    pub data: Vec<u16>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum SceneEase {
+    Alpha { start: f32, end: f32, duration: f32 },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct SceneEntity {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prefab: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub visible: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub position: Option<(f32, f32)>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub velocity: Option<(f32, f32)>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub acceleration: Option<(f32, f32)>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scale: Option<(f32, f32)>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub angle: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub angular_velocity: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<(f32, f32, f32, f32)>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alpha: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub font: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub align: Option<SceneAlign>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wrap: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line_height: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub billboard: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub frame: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tileset: Option<SceneTileset>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tilemap: Option<SceneTilemap>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line_buffer: Option<Vec<SceneVertex>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tri_buffer: Option<Vec<SceneVertex>>,
+    // material file, see component_material
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub material: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub easing: Vec<SceneEase>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<SceneEntity>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Scene {
+    #[serde(default)]
+    pub entities: Vec<SceneEntity>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub prefabs: BTreeMap<String, SceneEntity>,
+}
+
+// prefabs may use other prefabs, this bounds accidental cycles
+const PREFAB_DEPTH_MAX: usize = 16;
+
+impl SceneEntity {
+    // fields set in overrides win, children of both are kept
+    pub fn merge(&self, overrides: &SceneEntity) -> SceneEntity {
+        fn pick<T: Clone>(base: &Option<T>, over: &Option<T>) -> Option<T> {
+            over.clone().or_else(|| base.clone())
+        }
+        let mut children = self.children.clone();
+        children.extend(overrides.children.iter().cloned());
+        SceneEntity {
+            prefab: None,
+            id: pick(&self.id, &overrides.id),
+            name: pick(&self.name, &overrides.name),
+            visible: pick(&self.visible, &overrides.visible),
+            position: pick(&self.position, &overrides.position),
+            velocity: pick(&self.velocity, &overrides.velocity),
+            acceleration: pick(&self.acceleration, &overrides.acceleration),
+            scale: pick(&self.scale, &overrides.scale),
+            angle: pick(&self.angle, &overrides.angle),
+            angular_velocity: pick(&self.angular_velocity, &overrides.angular_velocity),
+            color: pick(&self.color, &overrides.color),
+            alpha: pick(&self.alpha, &overrides.alpha),
+            text: pick(&self.text, &overrides.text),
+            font: pick(&self.font, &overrides.font),
+            align: pick(&self.align, &overrides.align),
+            wrap: pick(&self.wrap, &overrides.wrap),
+            line_height: pick(&self.line_height, &overrides.line_height),
+            billboard: pick(&self.billboard, &overrides.billboard),
+            frame: pick(&self.frame, &overrides.frame),
+            tileset: pick(&self.tileset, &overrides.tileset),
+            tilemap: pick(&self.tilemap, &overrides.tilemap),
+            line_buffer: pick(&self.line_buffer, &overrides.line_buffer),
+            tri_buffer: pick(&self.tri_buffer, &overrides.tri_buffer),
+            material: pick(&self.material, &overrides.material),
+            easing: if overrides.easing.is_empty() {
+                self.easing.clone()
+            } else {
+                overrides.easing.clone()
+            },
+            children,
+        }
+    }
+}
+
+#[allow(dead_code)]
+impl Scene {
+    pub fn load(filename: &str) -> Result<Scene, String> {
+        log(format!("Scene: Loading '{}'", filename));
+        let buffer = assets::read_to_string(filename)?;
+        Scene::parse(filename, &buffer)
+    }
+
+    // filename is only used in diagnostics
+    pub fn parse(filename: &str, buffer: &str) -> Result<Scene, String> {
+        ron::Options::default()
+            .with_default_extension(Extensions::IMPLICIT_SOME)
+            .from_str(buffer)
+            .map_err(|e| format!("{}:{}", filename, e))
+    }
+
+    pub fn save(&self, filename: &str) -> Result<(), String> {
+        log(format!("Scene: Saving '{}'", filename));
+        let buffer = self.to_ron()?;
+        std::fs::write(filename, buffer).map_err(|e| format!("{}: {}", filename, e))
+    }
+
+    pub fn to_ron(&self) -> Result<String, String> {
+        let pretty = ron::ser::PrettyConfig::new()
+            .struct_names(true)
+            .extensions(Extensions::IMPLICIT_SOME);
+        ron::ser::to_string_pretty(self, pretty).map_err(|e| e.to_string())
+    }
+
+    // one scene entity per world.dat entity id, in order of first appearance
+    pub fn from_world_file(world: &WorldFile) -> Scene {
+        let mut entities: Vec<SceneEntity> = Vec::new();
+
+        for entry in world.entries.iter() {
+            let e = match entities.iter().position(|e| Some(entry.id) == e.id) {
+                Some(n) => &mut entities[n],
+                None => {
+                    entities.push(SceneEntity {
+                        id: Some(entry.id),
+                        // world.dat entities are hidden unless made visible
+                        visible: Some(false),
+                        ..Default::default()
+                    });
+                    entities.last_mut().unwrap()
+                }
+            };
+
+            match &entry.component {
+                WorldComponent::Text(t) => e.text = Some(t.clone()),
+                WorldComponent::Font(f) => e.font = Some(f.clone()),
+                WorldComponent::Align(a) => e.align = Some(align_to_scene(*a)),
+                WorldComponent::Wrap(w) => e.wrap = Some(*w),
+                WorldComponent::LineHeight(h) => e.line_height = Some(*h),
+                WorldComponent::Color(c) => e.color = Some((c.r, c.g, c.b, c.a)),
+                WorldComponent::Billboard(b) => e.billboard = Some(b.clone()),
+                WorldComponent::LineBuffer(p, c) => e.line_buffer = Some(to_vertices(p, c)),
+                WorldComponent::TriBuffer(p, c) => e.tri_buffer = Some(to_vertices(p, c)),
+                WorldComponent::Visible(v) => e.visible = Some(*v),
+                WorldComponent::Position(x, y) => e.position = Some((*x, *y)),
+                WorldComponent::Velocity(x, y) => e.velocity = Some((*x, *y)),
+                WorldComponent::Scale(x, y) => e.scale = Some((*x, *y)),
+                WorldComponent::Angle(a) => e.angle = Some(*a),
+                WorldComponent::Alpha(a) => e.alpha = Some(*a),
+                WorldComponent::AngularVelocity(a) => e.angular_velocity = Some(*a),
+                WorldComponent::Acceleration(x, y) => e.acceleration = Some((*x, *y)),
+            }
+        }
+
+        Scene {
+            entities,
+            ..Default::default()
+        }
+    }
+}
+
+// world.dat -> scene file, returns the number of entities written
+pub fn convert_world_file(src: &str, dst: &str) -> Result<usize, String> {
+    let world = parse_world_file(src).map_err(|e| e.to_string())?;
+    for w in world.warnings.iter() {
+        println!("warning: {}", w);
+    }
+    let scene = Scene::from_world_file(&world);
+    scene.save(dst)?;
+    Ok(scene.entities.len())
+}
+
+fn strip_ids(e: &mut SceneEntity, prefab: &str) {
+    if e.id.take().is_some() {
+        log(format!("WARNING: Scene: ignoring fixed id in prefab '{}'", prefab));
+    }
+    for c in e.children.iter_mut() {
+        strip_ids(c, prefab);
+    }
+}
+
+fn align_to_scene(align: u8) -> SceneAlign {
+    match align {
+        TEXT_ALIGN_CENTER => SceneAlign::Center,
+        TEXT_ALIGN_RIGHT => SceneAlign::Right,
+        _ => SceneAlign::Left,
+    }
+}
+
+fn to_vertices(pnts: &[Position], clrs: &[Color]) -> Vec<SceneVertex> {
+    pnts.iter()
+        .zip(clrs.iter())
+        .map(|(p, c)| (p.x, p.y, c.r, c.g, c.b, c.a))
+        .collect()
+}
+
+fn from_vertices(verts: &[SceneVertex]) -> (Vec<Position>, Vec<Color>) {
+    let pnts = verts.iter().map(|v| Position { x: v.0, y: v.1 }).collect();
+    let clrs = verts
+        .iter()
+        .map(|v| Color { r: v.2, g: v.3, b: v.4, a: v.5 })
+        .collect();
+    (pnts, clrs)
+}
+
+#[allow(dead_code)]
+impl World {
+    // returns the handles of the top level entities
+    pub fn load_scene(&mut self, filename: &str) -> Result<Vec<usize>, String> {
+        let scene = Scene::load(filename)?;
+        Ok(self.spawn_scene(&scene))
+    }
+
+    // registers the prefabs of a scene file without spawning its entities,
+    // returns the number of prefabs
+    pub fn load_prefabs(&mut self, filename: &str) -> Result<usize, String> {
+        let scene = Scene::load(filename)?;
+        for (name, e) in scene.prefabs.iter() {
+            self.register_prefab(name, e.clone());
+        }
+        Ok(scene.prefabs.len())
+    }
+
+    pub fn spawn_prefab(&mut self, name: &str, pos: Position) -> Option<usize> {
+        self.spawn_prefab_with(name, pos, &SceneEntity::default())
+    }
+
+    // returns the root entity, or None if there is no such prefab
+    pub fn spawn_prefab_with(
+        &mut self,
+        name: &str,
+        pos: Position,
+        overrides: &SceneEntity,
+    ) -> Option<usize> {
+        if !self.has_prefab(name) {
+            log(format!("WARNING: Scene: unknown prefab '{}'", name));
+            return None;
+        }
+        let e = SceneEntity {
+            prefab: Some(String::from(name)),
+            position: Some((pos.x, pos.y)),
+            ..overrides.clone()
+        };
+        let scene = Scene {
+            entities: vec![e],
+            ..Default::default()
+        };
+        self.spawn_scene(&scene).first().copied()
+    }
+
+    pub fn spawn_scene(&mut self, scene: &Scene) -> Vec<usize> {
+        for (name, e) in scene.prefabs.iter() {
+            self.register_prefab(name, e.clone());
+        }
+        let entities: Vec<SceneEntity> = scene
+            .entities
+            .iter()
+            .map(|e| self.expand_prefab(e, 0))
+            .collect();
+
+        // fixed ids first so auto-allocated entities can't take them
+        for e in entities.iter() {
+            self.reserve_scene_ids(e);
+        }
+
+        // then allocate and name everything so references resolve in any order
+        let mut spawned: Vec<(usize, &SceneEntity, (f32, f32))> = Vec::new();
+        let mut roots: Vec<usize> = Vec::new();
+        for e in entities.iter() {
+            roots.push(self.assign_scene_ids(e, (0.0, 0.0), &mut spawned));
+        }
+
+        for (idx, e, pos) in spawned {
+            self.apply_scene_entity(idx, e, pos);
+        }
+        roots
+    }
+
+    // replaces prefab references with the merged definition, recursively
+    fn expand_prefab(&self, e: &SceneEntity, depth: usize) -> SceneEntity {
+        let mut ret = match &e.prefab {
+            Some(name) if PREFAB_DEPTH_MAX <= depth => {
+                log(format!("WARNING: Scene: prefab '{}' nested too deep, skipped", name));
+                e.merge(&SceneEntity::default())
+            }
+            Some(name) => match self.get_prefab(name) {
+                Some(prefab) => {
+                    let mut base = self.expand_prefab(prefab, depth + 1);
+                    // a prefab is spawned many times, fixed ids would collide
+                    strip_ids(&mut base, name);
+                    base.merge(e)
+                }
+                None => {
+                    log(format!("WARNING: Scene: unknown prefab '{}'", name));
+                    e.merge(&SceneEntity::default())
+                }
+            },
+            None => e.clone(),
+        };
+        ret.children = ret
+            .children
+            .iter()
+            .map(|c| self.expand_prefab(c, depth + 1))
+            .collect();
+        ret
+    }
+
+    fn reserve_scene_ids(&mut self, e: &SceneEntity) {
+        if let Some(id) = e.id {
+            if id < ENTITY_SZ {
+                self.entity_add_component(id, COMPONENT_ACTIVE);
+            } else {
+                log(format!("WARNING: Scene: entity id {} out of range", id));
+            }
+        }
+        for c in e.children.iter() {
+            self.reserve_scene_ids(c);
+        }
+    }
+
+    fn assign_scene_ids<'a>(
+        &mut self,
+        e: &'a SceneEntity,
+        parent: (f32, f32),
+        spawned: &mut Vec<(usize, &'a SceneEntity, (f32, f32))>,
+    ) -> usize {
+        let idx = match e.id {
+            Some(id) if id < ENTITY_SZ => id,
+            _ => self.new_entity(),
+        };
+        if let Some(name) = &e.name {
+            self.entity_set_name(idx, name);
+        }
+
+        let (x, y) = e.position.unwrap_or((0.0, 0.0));
+        let pos = (parent.0 + x, parent.1 + y);
+        spawned.push((idx, e, pos));
+
+        for c in e.children.iter() {
+            self.assign_scene_ids(c, pos, spawned);
+        }
+        idx
+    }
+
+    fn resolve_scene_ref(&self, r: &SceneRef) -> Option<usize> {
+        match r {
+            SceneRef::Id(id) => Some(*id),
+            SceneRef::Name(name) => self.find_entity(name),
+        }
+    }
+
+    fn apply_scene_entity(&mut self, idx: usize, e: &SceneEntity, pos: (f32, f32)) {
+        if let Some(text) = &e.text {
+            self.entity_set_text_source(idx, text);
+        }
+        if let Some(font) = &e.font {
+            let font = self.load_font(font);
+            self.entity_set_font(idx, font);
+        }
+        if let Some(align) = e.align {
+            let align = match align {
+                SceneAlign::Left => TEXT_ALIGN_LEFT,
+                SceneAlign::Center => TEXT_ALIGN_CENTER,
+                SceneAlign::Right => TEXT_ALIGN_RIGHT,
+            };
+            self.entity_set_text_align(idx, align);
+        }
+        if let Some(wrap) = e.wrap {
+            self.entity_set_text_wrap(idx, wrap);
+        }
+        if let Some(height) = e.line_height {
+            self.entity_set_text_line_height(idx, height);
+        }
+        if let Some(image) = &e.billboard {
+            self.entity_set_billboard(idx, image.clone());
+        }
+        if let Some(t) = &e.tileset {
+            self.entity_set_tileset(
+                idx,
+                t.image.clone(),
+                t.image_width,
+                t.image_height,
+                t.tile_width,
+                t.tile_height,
+            );
+        }
+        if let Some(t) = &e.tilemap {
+            match self.resolve_scene_ref(&t.tileset) {
+                Some(tileset) if 0 < t.columns && 0 == t.data.len() % t.columns => {
+                    self.entity_set_tilemap(idx, tileset, t.columns, &t.data);
+                }
+                Some(_) => log(format!(
+                    "WARNING: Scene: entity {} tilemap size is not a multiple of {} columns",
+                    idx, t.columns
+                )),
+                None => log(format!(
+                    "WARNING: Scene: entity {} tileset {:?} not found",
+                    idx, t.tileset
+                )),
+            }
+        }
+        if let Some(verts) = &e.line_buffer {
+            let (pnts, clrs) = from_vertices(verts);
+            self.entity_set_line_buffer(idx, &pnts, &clrs);
+        }
+        if let Some(verts) = &e.tri_buffer {
+            let (pnts, clrs) = from_vertices(verts);
+            self.entity_set_triangle_buffer(idx, &pnts, &clrs);
+        }
+        if let Some((r, g, b, a)) = e.color {
+            self.entity_set_color_rgba(idx, r, g, b, a);
+        }
+        if let Some(alpha) = e.alpha {
+            self.entity_set_alpha(idx, alpha);
+        }
+
+        self.entity_set_visibility(idx, e.visible.unwrap_or(true));
+        self.entity_set_position_xy(idx, pos.0, pos.1);
+
+        if let Some((x, y)) = e.velocity {
+            self.entity_set_velocity_xy(idx, x, y);
+        }
+        if let Some((x, y)) = e.acceleration {
+            self.entity_set_acceleration_xy(idx, x, y);
+        }
+        if let Some((x, y)) = e.scale {
+            self.entity_set_scale_xy(idx, x, y);
+        }
+        if let Some(angle) = e.angle {
+            self.entity_set_angle(idx, crate::mgfw::deg2rad(angle));
+        }
+        if let Some(avel) = e.angular_velocity {
+            self.entity_set_angular_velocity(idx, crate::mgfw::deg2rad(avel));
+        }
+        if let Some(frame) = e.frame {
+            self.entity_set_frame(idx, frame);
+        }
+        if let Some(file) = &e.material {
+            match self.load_material(file) {
+                Ok(m) => self.entity_set_material(idx, m),
+                Err(err) => log(format!("WARNING: Scene: {}", err)),
+            }
+        }
+        for ease in e.easing.iter() {
+            match ease {
+                SceneEase::Alpha {
+                    start,
+                    end,
+                    duration,
+                } => self.entity_set_alpha_ease(idx, *start, *end, *duration),
+            }
+        }
+    }
+
+    pub fn save_scene(&self, filename: &str) -> Result<(), String> {
+        self.to_scene().save(filename)
+    }
+
+    // snapshot of every active entity as a flat list with fixed ids,
+    // easing in progress is not saved
+    pub fn to_scene(&self) -> Scene {
+        let ent = self.get_entities();
+        let tcm = self.get_manager_text();
+        let tmcm = self.get_manager_tilemap();
+        let phcm = self.get_manager_physics();
+
+        let mut tilesets: Vec<usize> = Vec::new();
+        for idx in 0..ENTITY_SZ {
+            if ent.is_active(idx) && self.get_manager_render().has_type(idx, RENDER_TYPE_TILEMAP) {
+                tilesets.push(tmcm.get_tileset_idx(idx));
+            }
+        }
+
+        let mut entities: Vec<SceneEntity> = Vec::new();
+        for idx in 0..ENTITY_SZ {
+            if !ent.is_active(idx) {
+                continue;
+            }
+
+            let pos = self.get_manager_position().get_position(idx);
+            let mut e = SceneEntity {
+                id: Some(idx),
+                name: self.entity_get_name(idx).cloned(),
+                visible: Some(ent.is_visible(idx)),
+                position: Some((pos.x, pos.y)),
+                ..Default::default()
+            };
+
+            if ent.has_component(idx, COMPONENT_SCALE) {
+                let s = self.get_manager_scale().get_scale(idx);
+                e.scale = Some((s.x, s.y));
+            }
+            if ent.has_component(idx, COMPONENT_ANGLE) {
+                e.angle = Some(crate::mgfw::rad2deg(self.get_manager_angle().get_angle(idx)));
+            }
+            if ent.has_component(idx, COMPONENT_PHYSICS) {
+                let v = phcm.get_velocity(idx);
+                let a = phcm.get_acceleration(idx);
+                let w = phcm.get_angular_velocity(idx);
+                if 0.0 != v.x || 0.0 != v.y {
+                    e.velocity = Some((v.x, v.y));
+                }
+                if 0.0 != a.x || 0.0 != a.y {
+                    e.acceleration = Some((a.x, a.y));
+                }
+                if 0.0 != w {
+                    e.angular_velocity = Some(crate::mgfw::rad2deg(w));
+                }
+            }
+            if ent.has_component(idx, COMPONENT_COLOR) {
+                let c = self.entity_get_color(idx);
+                e.color = Some((c.r, c.g, c.b, c.a));
+            }
+            if ent.has_component(idx, COMPONENT_FRAME) {
+                e.frame = Some(self.get_manager_frame().get_frame(idx));
+            }
+            // materials built in code have no file and are not saved
+            if let Some(m) = self.entity_get_material(idx) {
+                e.material = self.get_manager_material().get_file(m).cloned();
+            }
+
+            match self.get_manager_render().get_type(idx) {
+                RENDER_TYPE_TEXT => {
+                    e.text = Some(tcm.get_text(idx).clone());
+                    let font = tcm.get_font(idx);
+                    if component_render_text::FONT_DEFAULT != font {
+                        e.font = tcm.get_font_file(font);
+                    }
+                    let layout = tcm.get_layout(idx);
+                    if TEXT_ALIGN_LEFT != layout.align {
+                        e.align = Some(align_to_scene(layout.align));
+                    }
+                    if 0.0 < layout.wrap_width {
+                        e.wrap = Some(layout.wrap_width);
+                    }
+                    if 0.0 < layout.line_height {
+                        e.line_height = Some(layout.line_height);
+                    }
+                }
+                RENDER_TYPE_BILLBOARD => {
+                    let image = self.entity_get_billboard(idx);
+                    if tilesets.contains(&idx) {
+                        let (iw, ih, tw, th) = tmcm.get_tileset(idx);
+                        e.tileset = Some(SceneTileset {
+                            image,
+                            image_width: iw,
+                            image_height: ih,
+                            tile_width: tw,
+                            tile_height: th,
+                        });
+                    } else {
+                        e.billboard = Some(image);
+                    }
+                }
+                RENDER_TYPE_TILEMAP => {
+                    let tileset = tmcm.get_tileset_idx(idx);
+                    e.tilemap = Some(SceneTilemap {
+                        tileset: match self.entity_get_name(tileset) {
+                            Some(name) => SceneRef::Name(name.clone()),
+                            None => SceneRef::Id(tileset),
+                        },
+                        columns: tmcm.get_columns(idx),
+                        data: tmcm.get_tilemap(idx).clone(),
+                    });
+                }
+                RENDER_TYPE_LINE_BUFFER => {
+                    let lcm = self.get_manager_line();
+                    e.line_buffer = Some(to_vertices(lcm.get_points(idx), lcm.get_colors(idx)));
+                }
+                RENDER_TYPE_TRIANGLE_BUFFER => {
+                    let trm = self.get_manager_triangle();
+                    e.tri_buffer = Some(to_vertices(trm.get_points(idx), trm.get_colors(idx)));
+                }
+                _ => (),
+            }
+
+            entities.push(e);
+        }
+
+        Scene {
+            entities,
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_nested_entities_with_comments() {
+        let src = r#"
+            // title screen
+            Scene(entities: [
+                (
+                    name: "hud",
+                    position: (320, 8),
+                    children: [
+                        (text: "Level, 1", align: Center), /* inline */
+                        (billboard: "assets/flame.png", position: (40, 0)),
+                    ],
+                ),
+                (tilemap: (tileset: "tiles", columns: 2, data: [1, 2, 3, 4])),
+                (tilemap: (tileset: 7, columns: 1, data: [1])),
+            ])
+        "#;
+        let scene = Scene::parse("test.ron", src).unwrap();
+        assert_eq!(3, scene.entities.len());
+        let hud = &scene.entities[0];
+        assert_eq!(Some(String::from("hud")), hud.name);
+        assert_eq!(Some((320.0, 8.0)), hud.position);
+        assert_eq!(Some(String::from("Level, 1")), hud.children[0].text);
+        assert_eq!(Some(SceneAlign::Center), hud.children[0].align);
+        assert_eq!(
+            Some(SceneRef::Name(String::from("tiles"))),
+            scene.entities[1].tilemap.as_ref().map(|t| t.tileset.clone())
+        );
+        assert_eq!(
+            Some(SceneRef::Id(7)),
+            scene.entities[2].tilemap.as_ref().map(|t| t.tileset.clone())
+        );
+    }
+
+    #[test]
+    fn errors_report_position() {
+        let e = Scene::parse("test.ron", "Scene(entities: [(position: (1, ))])").unwrap_err();
+        assert!(e.starts_with("test.ron:1:"), "{}", e);
+    }
+
+    #[test]
+    fn converts_world_file_and_round_trips() {
+        let world = parse_world_str(
+            "world.dat",
+            "0,text,\"Level: 1\"\n0,visible,true\n0,align,center\n1,billboard,a.png\n1,angle,90\n",
+        )
+        .unwrap();
+        let scene = Scene::from_world_file(&world);
+        assert_eq!(2, scene.entities.len());
+        assert_eq!(Some(0), scene.entities[0].id);
+        assert_eq!(Some(true), scene.entities[0].visible);
+        assert_eq!(Some(SceneAlign::Center), scene.entities[0].align);
+        assert_eq!(Some(false), scene.entities[1].visible);
+        assert_eq!(Some(90.0), scene.entities[1].angle);
+
+        let text = scene.to_ron().unwrap();
+        assert_eq!(scene, Scene::parse("world.ron", &text).unwrap());
+    }
+
+    #[test]
+    fn prefab_overrides_win_and_children_are_kept() {
+        let scene = Scene::parse(
+            "test.ron",
+            r#"Scene(
+                entities: [(prefab: "coin", scale: (2, 2), children: [(text: "x")])],
+                prefabs: {
+                    "coin": (billboard: "coin.png", scale: (16, 16), children: [(text: "$")]),
+                },
+            )"#,
+        )
+        .unwrap();
+        let prefab = &scene.prefabs["coin"];
+        let e = prefab.merge(&scene.entities[0]);
+        assert_eq!(None, e.prefab);
+        assert_eq!(Some(String::from("coin.png")), e.billboard);
+        assert_eq!(Some((2.0, 2.0)), e.scale);
+        assert_eq!(2, e.children.len());
+    }
+}
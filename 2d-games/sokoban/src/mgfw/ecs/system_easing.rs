@@ -1,12 +1,13 @@
 use super::*;
 use crate::mgfw::log;
 
+#[derive(Default)]
 struct EasingSystemData {
     _temp: usize,
 }
 
 pub struct EasingSystem {
-    data: *mut EasingSystemData,
+    data: CacheBlock<EasingSystemData>,
     // WARNING: Anything below this line is not in cache!
     frame: usize,
 }
@@ -16,8 +17,7 @@ impl EasingSystem {
     pub fn new(mgr: &mut CacheManager) -> EasingSystem {
         log(format!("Constructing EasingSystem"));
         // allocate system memory in cache
-        let sz_bytes = std::mem::size_of::<EasingSystemData>();
-        let data = mgr.allocate(sz_bytes) as *mut EasingSystemData;
+        let data = mgr.allocate("EasingSystem", 1);
 
         EasingSystem { data, frame: 0 }
     }
@@ -50,12 +50,12 @@ impl EasingSystem {
         expect_blown
     }
 
-    fn get_data_ref_mut(&self) -> &mut EasingSystemData {
-        unsafe { &mut *(self.data.offset(0)) }
+    fn get_data_ref_mut(&mut self) -> &mut EasingSystemData {
+        &mut self.data[0]
     }
 
     fn get_data_ref(&self) -> &EasingSystemData {
-        unsafe { &*(self.data.offset(0)) }
+        &self.data[0]
     }
 }
 
@@ -1,12 +1,13 @@
 use super::*;
 use crate::mgfw::log;
 
+#[derive(Default)]
 struct PhysicsSystemData {
     frame: usize,
 }
 
 pub struct PhysicsSystem {
-    data: *mut PhysicsSystemData,
+    data: CacheBlock<PhysicsSystemData>,
     // WARNING: Anything below this line is not in cache!
 }
 
@@ -15,8 +16,7 @@ impl PhysicsSystem {
     pub fn new(mgr: &mut CacheManager) -> PhysicsSystem {
         log(format!("Constructing PhysicsSystem"));
         // allocate system memory in cache
-        let sz_bytes = std::mem::size_of::<PhysicsSystemData>();
-        let data = mgr.allocate(sz_bytes) as *mut PhysicsSystemData;
+        let data = mgr.allocate("PhysicsSystem", 1);
 
         PhysicsSystem { data }
     }
@@ -31,40 +31,33 @@ impl PhysicsSystem {
 
     pub fn update(&mut self, world: &mut World, micros: u128) -> bool {
         let expect_blown = false;
-        let data = self.get_data_ref_mut();
-        let ent = world.get_entities();
-        let pcm = world.get_manager_position();
-        let acm = world.get_manager_angle();
-        let phcm = world.get_manager_physics();
+        let frame = self.get_data_ref().frame;
 
-        let span = ent.get_id_span();
+        let span = world.get_entities().get_id_span();
         for i in span.first..=span.last {
             if self.skip_entity(i, world) {
                 continue;
             }
 
             // Amortize workload
-            match data.frame % 2 {
+            match frame % 2 {
                 // priority 1
                 0 => {
                     let dt = micros as f32 * 1.0e-6 * 2.0; // 150hz
+                    let phcm = world.get_manager_physics();
                     let accel = phcm.get_acceleration(i);
                     let mut vel = phcm.get_velocity(i);
-                    let mut pos = pcm.get_position(i);
+                    let avel = phcm.get_angular_velocity(i);
+                    let mut pos = world.get_manager_position().get_position(i);
+                    let mut ang = world.get_manager_angle().get_angle(i);
 
                     pos.x += vel.x * dt;
                     pos.y += vel.y * dt;
                     vel.x += accel.x * dt;
                     vel.y += accel.y * dt;
-
-                    pcm.set_position(i, pos.x, pos.y);
-                    phcm.set_velocity(i, vel.x, vel.y);
-
-                    let avel = phcm.get_angular_velocity(i);
-                    let mut ang = acm.get_angle(i);
-
                     ang += avel * dt;
-                    acm.set_angle(i, ang);
+
+                    world.physics_set_motion(i, pos, vel, ang);
                 }
 
                 // priority 2
@@ -74,15 +67,15 @@ impl PhysicsSystem {
                 _ => (),
             }
         }
-        data.frame += 1;
+        self.get_data_ref_mut().frame += 1;
         expect_blown
     }
 
-    fn get_data_ref_mut(&self) -> &mut PhysicsSystemData {
-        unsafe { &mut *(self.data.offset(0)) }
+    fn get_data_ref_mut(&mut self) -> &mut PhysicsSystemData {
+        &mut self.data[0]
     }
 
     fn get_data_ref(&self) -> &PhysicsSystemData {
-        unsafe { &*(self.data.offset(0)) }
+        &self.data[0]
     }
 }
@@ -1,16 +1,46 @@
 use super::*;
 use crate::mgfw::log;
+use crate::mgfw::timing::{lerp, lerp_angle};
 
+#[derive(Default)]
 struct RenderSystemData {
     vao_pri: u32,
     vbo_pri: u32,
+    // state before and after the last physics step, see snapshot_prev()
+    snapshot_valid: bool,
+    prev: Placement,
+    current: Placement,
+}
+
+// where an entity is drawn this frame
+#[derive(Copy, Clone, Default)]
+struct Placement {
+    x: f32,
+    y: f32,
+    angle: f32,
+    scale: Scale,
+}
+
+impl Placement {
+    fn same(&self, other: &Placement) -> bool {
+        self.x == other.x
+            && self.y == other.y
+            && self.angle == other.angle
+            && self.scale.x == other.scale.x
+            && self.scale.y == other.scale.y
+    }
 }
 
 pub struct RenderSystem {
-    data: *mut RenderSystemData,
+    data: CacheBlock<RenderSystemData>,
     // WARNING: Anything below this line is not in cache!
     frame: usize,
     screen_vao: u32,
+    symbol_vao: u32,
+    // world generation of the last snapshot
+    snapshot_generation: usize,
+    // since the last take_vbo_rebuilds, for the profiler
+    vbo_rebuilds: usize,
 }
 
 #[allow(dead_code)]
@@ -18,16 +48,36 @@ impl RenderSystem {
     pub fn new(mgr: &mut CacheManager, gl: &Gl) -> RenderSystem {
         log(format!("Constructing RenderSystem"));
         // allocate system memory in cache
-        let sz_bytes = std::mem::size_of::<RenderSystemData>() * ENTITY_SZ;
-        let data = mgr.allocate(sz_bytes) as *mut RenderSystemData;
+        let data = mgr.allocate("RenderSystem", ENTITY_SZ);
 
         // screen quad VAO
-        let screen_vao: u32 = gl.gen_vao();
-        let screen_vbo: u32 = gl.gen_vbo();
+        let screen_vao = RenderSystem::gen_quad(gl, 1.0);
+        // accessibility symbols, same quad as billboards
+        let symbol_vao = RenderSystem::gen_quad(gl, 0.5);
 
-        let mut vertex_data: Vec<f32> = Vec::new();
+        let mut ret = RenderSystem {
+            data,
+            frame: 0,
+            screen_vao,
+            symbol_vao,
+            snapshot_generation: 0,
+            vbo_rebuilds: 0,
+        };
 
-        let w: f32 = 1.0;
+        // pre-generate a VAO/VBO for each entity
+        for i in 0..ENTITY_SZ {
+            let d = ret.get_data_ref_mut(i);
+            d.vao_pri = gl.gen_vao();
+            d.vbo_pri = gl.gen_vbo();
+        }
+        ret
+    }
+
+    fn gen_quad(gl: &Gl, w: f32) -> u32 {
+        let vao: u32 = gl.gen_vao();
+        let vbo: u32 = gl.gen_vbo();
+
+        let mut vertex_data: Vec<f32> = Vec::new();
 
         vertex_data.extend_from_slice(&[-w, -w]); // pos
         vertex_data.extend_from_slice(&[0.0, 0.0]); // uv
@@ -47,21 +97,8 @@ impl RenderSystem {
         vertex_data.extend_from_slice(&[w, -w]); // pos
         vertex_data.extend_from_slice(&[1.0, 0.0]); // uv
 
-        gl.buffer_billboard_data(screen_vao, screen_vbo, vertex_data.as_ptr() as *const _);
-
-        let ret = RenderSystem {
-            data,
-            frame: 0,
-            screen_vao,
-        };
-
-        // pre-generate a VAO/VBO for each entity
-        for i in 0..ENTITY_SZ {
-            let d = ret.get_data_ref_mut(i);
-            d.vao_pri = gl.gen_vao();
-            d.vbo_pri = gl.gen_vbo();
-        }
-        ret
+        gl.buffer_billboard_data(vao, vbo, vertex_data.as_ptr() as *const _);
+        vao
     }
 
     fn skip_entity(&self, idx: usize, world: &World) -> bool {
@@ -79,6 +116,10 @@ impl RenderSystem {
     pub fn update(&mut self, gl: &Gl, world: &mut World) -> bool {
         let mut expect_blown = false;
 
+        if world.accessibility_get_symbols() {
+            world.symbols_load(gl);
+        }
+
         let span = world.get_entities().get_id_span();
         for i in span.first..=span.last {
             if self.skip_entity(i, world) {
@@ -104,8 +145,103 @@ impl RenderSystem {
         expect_blown
     }
 
+    // Rendering interpolates between the state before and after the last
+    // physics step, so call snapshot_prev() right before the step and
+    // snapshot_current() right after it (and after easing). Entities changed
+    // outside the step, e.g. moved by the game, are drawn where they are
+    // instead of sliding there.
+    pub fn snapshot_prev(&mut self, world: &World) {
+        self.snapshot_generation = world.get_generation();
+        let ent = world.get_entities();
+        let span = ent.get_id_span();
+        for i in span.first..=span.last {
+            let valid = ent.is_active(i);
+            let p = RenderSystem::current(i, world);
+            let d = self.get_data_ref_mut(i);
+            d.snapshot_valid = valid;
+            d.prev = p;
+        }
+    }
+
+    pub fn snapshot_current(&mut self, world: &World) {
+        if self.snapshot_generation != world.get_generation() {
+            return;
+        }
+        let ent = world.get_entities();
+        let span = ent.get_id_span();
+        for i in span.first..=span.last {
+            let valid = ent.is_active(i);
+            let p = RenderSystem::current(i, world);
+            let d = self.get_data_ref_mut(i);
+            d.snapshot_valid &= valid;
+            d.current = p;
+        }
+    }
+
+    fn current(idx: usize, world: &World) -> Placement {
+        let ent = world.get_entities();
+        let pos = world.get_manager_position().get_data_ref(idx);
+        let mut angle = 0.0 as f32;
+        if ent.has_component(idx, COMPONENT_ANGLE) {
+            angle = world.get_manager_angle().get_angle(idx);
+        }
+        let mut scale = Scale { x: 1.0, y: 1.0 };
+        if ent.has_component(idx, COMPONENT_SCALE) {
+            scale = world.get_manager_scale().get_scale(idx);
+        }
+        Placement {
+            x: pos.x,
+            y: pos.y,
+            angle,
+            scale,
+        }
+    }
+
+    // alpha of the way from the state before the last physics step to the
+    // state after it
+    fn placement(&self, idx: usize, world: &World, alpha: f32) -> Placement {
+        let live = RenderSystem::current(idx, world);
+        let d = self.get_data_ref(idx);
+        if !d.snapshot_valid
+            || self.snapshot_generation != world.get_generation()
+            || !live.same(&d.current)
+        {
+            return live;
+        }
+        let (a, b) = (d.prev, d.current);
+        Placement {
+            x: lerp(a.x, b.x, alpha),
+            y: lerp(a.y, b.y, alpha),
+            angle: lerp_angle(a.angle, b.angle, alpha),
+            scale: Scale {
+                x: lerp(a.scale.x, b.scale.x, alpha),
+                y: lerp(a.scale.y, b.scale.y, alpha),
+            },
+        }
+    }
+
+    pub fn take_vbo_rebuilds(&mut self) -> usize {
+        std::mem::replace(&mut self.vbo_rebuilds, 0)
+    }
+
+    // entities with a constructed vertex buffer
+    pub fn get_num_vbos(&self, world: &World) -> usize {
+        let span = world.get_entities().get_id_span();
+        (span.first..=span.last)
+            .filter(|i| !self.skip_entity(*i, world))
+            .filter(|i| match world.get_manager_render().get_type(*i) {
+                RENDER_TYPE_LINE_BUFFER => world.get_manager_line().is_constructed(*i),
+                RENDER_TYPE_TRIANGLE_BUFFER => world.get_manager_triangle().is_constructed(*i),
+                RENDER_TYPE_TEXT => world.get_manager_text().is_constructed(*i),
+                RENDER_TYPE_BILLBOARD => world.get_manager_billboard().is_constructed(*i),
+                RENDER_TYPE_TILEMAP => world.get_manager_tilemap().is_constructed(*i),
+                _ => false,
+            })
+            .count()
+    }
+
     // WARNING: Expect Blown
-    fn update_vbo(&self, idx: usize, gl: &Gl, world: &mut World) -> bool {
+    fn update_vbo(&mut self, idx: usize, gl: &Gl, world: &mut World) -> bool {
         let mut expect_blown = false;
 
         // Update Text VBOs
@@ -145,61 +281,58 @@ impl RenderSystem {
                     let dat = self.get_data_ref(idx);
                     world.tilemap_construct(idx, gl, dat.vao_pri, dat.vbo_pri);
                     expect_blown = true;
+                } else if world.get_manager_tilemap().update_needed(idx) {
+                    // changed and animated tiles only, cheap enough every frame
+                    let dat = self.get_data_ref(idx);
+                    world.tilemap_update(idx, gl, dat.vbo_pri);
                 }
             }
             _ => (),
         }
+        if expect_blown {
+            self.vbo_rebuilds += 1;
+        }
         expect_blown
     }
 
-    pub fn render(&self, gl: &Gl, world: &World, start_time: std::time::Instant) {
-        let pcm = world.get_manager_position();
-        let scm = world.get_manager_scale();
-        let acm = world.get_manager_angle();
+    // alpha is how far into the current physics step this frame is
+    pub fn render(&self, gl: &Gl, world: &World, alpha: f32) {
         let fcm = world.get_manager_frame();
-        let phcm = world.get_manager_physics();
         let rcm = world.get_manager_render();
         let tcm = world.get_manager_text();
         let bbcm = world.get_manager_billboard();
         let tmcm = world.get_manager_tilemap();
         let lcm = world.get_manager_line();
         let trm = world.get_manager_triangle();
+        let mcm = world.get_manager_material();
         let ent = world.get_entities();
 
         gl.bind_framebuffer();
         gl.clear_frame();
 
+        let symbols = world.accessibility_get_symbols();
+
         let span = ent.get_id_span();
         for i in span.first..=span.last {
             if !ent.is_visible(i) || self.skip_entity(i, world) {
                 continue;
             }
             let color = world.entity_get_color(i);
+            let p = self.placement(i, world, alpha);
+            let (angle, scale) = (p.angle, p.scale);
 
-            let mut angle = 0.0 as f32;
-            if ent.has_component(i, COMPONENT_ANGLE) {
-                angle = acm.get_angle(i);
-            }
-
-            let mut scale = Scale { x: 1.0, y: 1.0 };
-            if ent.has_component(i, COMPONENT_SCALE) {
-                scale = scm.get_scale(i);
-            }
-
-            let dt = std::time::Instant::now()
-                .duration_since(start_time)
-                .as_micros() as f32
-                * 1.0e-6;
+            gl.bind_material(match ent.has_component(i, COMPONENT_MATERIAL) {
+                true => mcm.get_entity_material(i).cloned(),
+                false => None,
+            });
 
             match rcm.get_type(i) {
                 RENDER_TYPE_LINE_BUFFER => {
                     if lcm.is_constructed(i) {
                         let vao = self.get_data_ref(i).vao_pri;
-                        let pos = pcm.get_data_ref(i);
-                        let phys = phcm.get_data_ref(i);
                         gl.draw_lines(
-                            pos.x + phys.velocity.x * dt,
-                            pos.y + phys.velocity.y * dt,
+                            p.x,
+                            p.y,
                             angle,
                             scale.x,
                             scale.y,
@@ -212,11 +345,9 @@ impl RenderSystem {
                 RENDER_TYPE_TRIANGLE_BUFFER => {
                     if trm.is_constructed(i) {
                         let vao = self.get_data_ref(i).vao_pri;
-                        let pos = pcm.get_data_ref(i);
-                        let phys = phcm.get_data_ref(i);
                         gl.draw_triangles(
-                            pos.x + phys.velocity.x * dt,
-                            pos.y + phys.velocity.y * dt,
+                            p.x,
+                            p.y,
                             angle,
                             scale.x,
                             scale.y,
@@ -229,25 +360,25 @@ impl RenderSystem {
                 RENDER_TYPE_TEXT => {
                     if tcm.is_constructed(i) && !tcm.reconstruct(i) {
                         let vao = self.get_data_ref(i).vao_pri;
-                        let pos = pcm.get_data_ref(i);
-                        let phys = phcm.get_data_ref(i);
-                        gl.draw_text(
-                            pos.x + phys.velocity.x * dt,
-                            pos.y + phys.velocity.y * dt,
-                            angle,
-                            scale.x,
-                            scale.y,
-                            vao,
-                            tcm.get_length(i),
-                            color,
-                        );
+                        for span in tcm.get_spans(i) {
+                            gl.draw_text(
+                                p.x,
+                                p.y,
+                                angle,
+                                scale.x,
+                                scale.y,
+                                vao,
+                                span.first,
+                                span.count,
+                                span.tex,
+                                color,
+                            );
+                        }
                     }
                 }
                 RENDER_TYPE_BILLBOARD => {
                     if bbcm.is_constructed(i) {
                         let vao = self.get_data_ref(i).vao_pri;
-                        let pos = pcm.get_data_ref(i);
-                        let phys = phcm.get_data_ref(i);
 
                         let mut framed = false;
                         let mut u = 0.0 as f32;
@@ -265,8 +396,8 @@ impl RenderSystem {
                         }
 
                         gl.draw_billboard(
-                            pos.x + phys.velocity.x * dt,
-                            pos.y + phys.velocity.y * dt,
+                            p.x,
+                            p.y,
                             angle,
                             scale.x,
                             scale.y,
@@ -284,13 +415,11 @@ impl RenderSystem {
                 RENDER_TYPE_TILEMAP => {
                     if tmcm.is_constructed(i) {
                         let vao = self.get_data_ref(i).vao_pri;
-                        let pos = pcm.get_data_ref(i);
-                        let phys = phcm.get_data_ref(i);
                         let num_tiles = tmcm.get_num_tiles(i);
                         let tex_handle = bbcm.get_tex_handle(tmcm.get_tileset_idx(i));
                         gl.draw_tilemap(
-                            pos.x + phys.velocity.x * dt,
-                            pos.y + phys.velocity.y * dt,
+                            p.x,
+                            p.y,
                             angle,
                             scale.x,
                             scale.y,
@@ -304,6 +433,38 @@ impl RenderSystem {
 
                 _ => (),
             }
+
+            if symbols {
+                if let Some(tex) = world.entity_get_symbol_texture(i) {
+                    gl.bind_material(None);
+                    gl.draw_billboard(
+                        p.x,
+                        p.y,
+                        0.0,
+                        scale.x * SYMBOL_SCALE,
+                        scale.y * SYMBOL_SCALE,
+                        self.symbol_vao,
+                        tex,
+                        Color {
+                            r: 1.0,
+                            g: 1.0,
+                            b: 1.0,
+                            a: color.a,
+                        },
+                        false,
+                        0.0,
+                        0.0,
+                        1.0,
+                        1.0,
+                    );
+                }
+            }
+        }
+
+        gl.bind_material(None);
+
+        if gl.draw_postfx(world) {
+            return;
         }
 
         gl.unbind_framebuffer();
@@ -320,13 +481,89 @@ impl RenderSystem {
         );
     }
 
-    fn get_data_ref_mut(&self, idx: usize) -> &mut RenderSystemData {
-        assert!(idx < ENTITY_SZ);
-        unsafe { &mut *(self.data.offset(idx as isize)) }
+    fn get_data_ref_mut(&mut self, idx: usize) -> &mut RenderSystemData {
+        &mut self.data[idx]
     }
 
     fn get_data_ref(&self, idx: usize) -> &RenderSystemData {
-        assert!(idx < ENTITY_SZ);
-        unsafe { &*(self.data.offset(idx as isize)) }
+        &self.data[idx]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // no GL context in tests, the VAOs are never used
+    fn render_system(mgr: &mut CacheManager) -> RenderSystem {
+        RenderSystem {
+            data: mgr.allocate("RenderSystem", ENTITY_SZ),
+            frame: 0,
+            screen_vao: 0,
+            symbol_vao: 0,
+            snapshot_generation: 0,
+            vbo_rebuilds: 0,
+        }
+    }
+
+    fn x_at(rs: &RenderSystem, idx: usize, world: &World, alpha: f32) -> f32 {
+        rs.placement(idx, world, alpha).x
+    }
+
+    #[test]
+    fn placement_interpolates_physics_steps_only() {
+        let mut mgr = CacheManager::new();
+        let mut rs = render_system(&mut mgr);
+        let mut world = World::new(&mut mgr);
+        let idx = world.new_entity();
+        world.entity_set_position_xy(idx, 0.0, 0.0);
+
+        // no snapshots yet
+        assert_eq!(0.0, x_at(&rs, idx, &world, 0.5));
+
+        // physics moves it by 10
+        rs.snapshot_prev(&world);
+        world.entity_set_position_xy(idx, 10.0, 0.0);
+        rs.snapshot_current(&world);
+        assert_eq!(0.0, x_at(&rs, idx, &world, 0.0));
+        assert_eq!(5.0, x_at(&rs, idx, &world, 0.5));
+        assert_eq!(10.0, x_at(&rs, idx, &world, 1.0));
+
+        // the game moving it between steps snaps
+        world.entity_set_position_xy(idx, 50.0, 0.0);
+        assert_eq!(50.0, x_at(&rs, idx, &world, 0.5));
+
+        // and a step without movement keeps it there
+        rs.snapshot_prev(&world);
+        rs.snapshot_current(&world);
+        assert_eq!(50.0, x_at(&rs, idx, &world, 0.5));
+    }
+
+    #[test]
+    fn placement_ignores_snapshots_from_before_clear() {
+        let mut mgr = CacheManager::new();
+        let mut rs = render_system(&mut mgr);
+        let mut world = World::new(&mut mgr);
+        let idx = world.new_entity();
+        world.entity_set_position_xy(idx, 0.0, 0.0);
+        rs.snapshot_prev(&world);
+        world.entity_set_position_xy(idx, 10.0, 0.0);
+        rs.snapshot_current(&world);
+
+        // the same slot is a different entity after clear()
+        world.clear();
+        let other = world.new_entity();
+        assert_eq!(idx, other);
+        world.entity_set_position_xy(other, 10.0, 0.0);
+        assert_eq!(10.0, x_at(&rs, other, &world, 0.5));
+
+        // an after-step snapshot alone doesn't make it interpolate
+        rs.snapshot_current(&world);
+        assert_eq!(10.0, x_at(&rs, other, &world, 0.5));
+
+        rs.snapshot_prev(&world);
+        world.entity_set_position_xy(other, 20.0, 0.0);
+        rs.snapshot_current(&world);
+        assert_eq!(15.0, x_at(&rs, other, &world, 0.5));
     }
 }
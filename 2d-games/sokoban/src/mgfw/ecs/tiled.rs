@@ -0,0 +1,712 @@
+use super::*;
+use crate::mgfw::log;
+use base64::Engine;
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use xml::reader::{EventReader, XmlEvent};
+
+// Maps made with the Tiled editor (https://www.mapeditor.org), .tmx with
+// inline or external (.tsx) tilesets. Supported:
+//   - orthogonal, finite maps
+//   - tile layers in csv or base64 (uncompressed, zlib, gzip) encoding
+//   - object layers, groups (flattened, offsets add up)
+//   - custom properties on the map, layers, objects and tiles
+//   - flipped and rotated tiles
+//   - tile animations, played by the tileset entity
+//
+//   let map = TiledMap::load("assets/level1.tmx")?;
+//   let spawned = world.spawn_tiled_map(&map, 0.0, 0.0, 1.0);
+//   let door = world.find_entity("door").unwrap();
+//   if let Some(Property::Bool(true)) = world.entity_get_property(door, "locked") { ... }
+//
+// Each tile layer becomes a tilemap entity per tileset it uses and each object
+// an entity, named after the layer/object, with its custom properties attached.
+
+// gid flags, the rest of the bits are the tile
+pub const TILED_FLIP_HORIZONTAL: u32 = 0x8000_0000;
+pub const TILED_FLIP_VERTICAL: u32 = 0x4000_0000;
+pub const TILED_FLIP_DIAGONAL: u32 = 0x2000_0000;
+const TILED_GID_MASK: u32 = 0x0FFF_FFFF;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Property {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    // also colors ("#aarrggbb"), files and object references
+    String(String),
+}
+
+pub type Properties = BTreeMap<String, Property>;
+
+#[derive(Clone, Debug, Default)]
+#[allow(dead_code)]
+pub struct TiledTileset {
+    pub first_gid: u32,
+    pub name: String,
+    // relative to the working directory, like every other asset path
+    pub image: String,
+    pub image_width: usize,
+    pub image_height: usize,
+    pub tile_width: usize,
+    pub tile_height: usize,
+    pub tile_count: u32,
+    pub columns: u32,
+    // tile id -> properties
+    pub tile_properties: BTreeMap<u32, Properties>,
+    // tile id -> (tile id, duration in ms) frames
+    pub animations: BTreeMap<u32, Vec<(u32, u32)>>,
+}
+
+#[derive(Clone, Debug, Default)]
+#[allow(dead_code)]
+pub struct TiledLayer {
+    pub name: String,
+    pub width: usize,
+    pub height: usize,
+    // gids including flip flags, 0 is empty
+    pub data: Vec<u32>,
+    pub visible: bool,
+    pub opacity: f32,
+    pub offset: (f32, f32),
+    pub properties: Properties,
+}
+
+#[derive(Clone, Debug, Default)]
+#[allow(dead_code)]
+pub struct TiledObject {
+    pub id: u32,
+    pub name: String,
+    // "type" in older files, "class" since Tiled 1.9
+    pub class: String,
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    // degrees, clockwise
+    pub rotation: f32,
+    // tile objects, including flip flags
+    pub gid: Option<u32>,
+    pub visible: bool,
+    // polygon or polyline points relative to x, y
+    pub points: Vec<(f32, f32)>,
+    pub properties: Properties,
+}
+
+#[derive(Clone, Debug, Default)]
+#[allow(dead_code)]
+pub struct TiledObjectGroup {
+    pub name: String,
+    pub visible: bool,
+    pub offset: (f32, f32),
+    pub objects: Vec<TiledObject>,
+    pub properties: Properties,
+}
+
+#[derive(Clone, Debug, Default)]
+#[allow(dead_code)]
+pub struct TiledMap {
+    pub width: usize,
+    pub height: usize,
+    pub tile_width: usize,
+    pub tile_height: usize,
+    pub tilesets: Vec<TiledTileset>,
+    // drawing order, bottom first
+    pub layers: Vec<TiledLayer>,
+    pub object_groups: Vec<TiledObjectGroup>,
+    pub properties: Properties,
+}
+
+// entities created by spawn_tiled_map
+#[derive(Clone, Debug, Default)]
+pub struct TiledSpawn {
+    pub tilesets: Vec<usize>,
+    pub layers: Vec<usize>,
+    pub objects: Vec<usize>,
+}
+
+// minimal element tree, the reader works on it instead of on events
+#[derive(Debug, Default)]
+struct Element {
+    name: String,
+    attributes: Vec<(String, String)>,
+    children: Vec<Element>,
+    text: String,
+}
+
+impl Element {
+    fn attr(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    fn parse_attr<T: std::str::FromStr>(&self, name: &str, default: T) -> Result<T, String> {
+        match self.attr(name) {
+            Some(v) => v
+                .trim()
+                .parse::<T>()
+                .map_err(|_| format!("<{}> bad {} '{}'", self.name, name, v)),
+            None => Ok(default),
+        }
+    }
+
+    fn children(&self, name: &str) -> impl Iterator<Item = &Element> {
+        let name = String::from(name);
+        self.children.iter().filter(move |c| c.name == name)
+    }
+
+    fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|c| c.name == name)
+    }
+}
+
+fn read_xml(buffer: &[u8]) -> Result<Element, String> {
+    let mut stack: Vec<Element> = vec![Element::default()];
+    for e in EventReader::new(buffer) {
+        match e.map_err(|e| e.to_string())? {
+            XmlEvent::StartElement { name, attributes, .. } => stack.push(Element {
+                name: name.local_name,
+                attributes: attributes
+                    .into_iter()
+                    .map(|a| (a.name.local_name, a.value))
+                    .collect(),
+                ..Default::default()
+            }),
+            XmlEvent::EndElement { .. } => {
+                let e = stack.pop().unwrap();
+                stack.last_mut().unwrap().children.push(e);
+            }
+            XmlEvent::Characters(s) | XmlEvent::CData(s) => {
+                stack.last_mut().unwrap().text.push_str(&s);
+            }
+            _ => (),
+        }
+    }
+    stack
+        .pop()
+        .and_then(|root| root.children.into_iter().next())
+        .ok_or_else(|| String::from("empty document"))
+}
+
+fn read_file(filename: &Path) -> Result<Element, String> {
+    let buffer = assets::read(&filename.to_string_lossy())?;
+    read_xml(&buffer).map_err(|e| format!("{}: {}", filename.display(), e))
+}
+
+// path relative to the directory of the file referencing it
+fn resolve(dir: &Path, file: &str) -> PathBuf {
+    match dir.as_os_str().is_empty() {
+        true => PathBuf::from(file),
+        false => dir.join(file),
+    }
+}
+
+fn parse_properties(e: &Element) -> Result<Properties, String> {
+    let mut ret = Properties::new();
+    let list = match e.child("properties") {
+        Some(p) => p,
+        None => return Ok(ret),
+    };
+    for p in list.children("property") {
+        let name = p.attr("name").unwrap_or_default();
+        // multi-line strings are stored as text instead of an attribute
+        let value = p.attr("value").unwrap_or(p.text.as_str());
+        let bad = || format!("property '{}' bad value '{}'", name, value);
+        let value = match p.attr("type").unwrap_or("string") {
+            "bool" => Property::Bool("true" == value),
+            "int" | "object" => Property::Int(value.parse::<i64>().map_err(|_| bad())?),
+            "float" => Property::Float(value.parse::<f64>().map_err(|_| bad())?),
+            _ => Property::String(String::from(value)),
+        };
+        ret.insert(String::from(name), value);
+    }
+    Ok(ret)
+}
+
+fn parse_tileset(e: &Element, dir: &Path, first_gid: u32) -> Result<TiledTileset, String> {
+    let mut ret = TiledTileset {
+        first_gid,
+        name: String::from(e.attr("name").unwrap_or_default()),
+        tile_width: e.parse_attr("tilewidth", 0)?,
+        tile_height: e.parse_attr("tileheight", 0)?,
+        tile_count: e.parse_attr("tilecount", 0)?,
+        columns: e.parse_attr("columns", 0)?,
+        ..Default::default()
+    };
+    if 0 != e.parse_attr("spacing", 0)? || 0 != e.parse_attr("margin", 0)? {
+        log(format!(
+            "WARNING: Tiled: tileset '{}' spacing and margin are not supported",
+            ret.name
+        ));
+    }
+    let image = e
+        .child("image")
+        .ok_or_else(|| format!("tileset '{}' has no image, image collections are not supported", ret.name))?;
+    ret.image = resolve(dir, image.attr("source").unwrap_or_default())
+        .to_string_lossy()
+        .to_string();
+    ret.image_width = image.parse_attr("width", 0)?;
+    ret.image_height = image.parse_attr("height", 0)?;
+
+    for tile in e.children("tile") {
+        let id: u32 = tile.parse_attr("id", 0)?;
+        let props = parse_properties(tile)?;
+        if !props.is_empty() {
+            ret.tile_properties.insert(id, props);
+        }
+        if let Some(anim) = tile.child("animation") {
+            let mut frames: Vec<(u32, u32)> = Vec::new();
+            for f in anim.children("frame") {
+                frames.push((f.parse_attr("tileid", 0)?, f.parse_attr("duration", 100)?));
+            }
+            ret.animations.insert(id, frames);
+        }
+    }
+    Ok(ret)
+}
+
+fn parse_data(e: &Element, count: usize) -> Result<Vec<u32>, String> {
+    if e.child("chunk").is_some() {
+        return Err(String::from("infinite maps are not supported"));
+    }
+    let ret: Vec<u32> = match e.attr("encoding") {
+        Some("csv") => e
+            .text
+            .split(',')
+            .map(|v| v.trim())
+            .filter(|v| !v.is_empty())
+            .map(|v| v.parse::<u32>().map_err(|_| format!("bad tile '{}'", v)))
+            .collect::<Result<Vec<u32>, String>>()?,
+        Some("base64") => {
+            let text: String = e.text.split_whitespace().collect();
+            let raw = base64::engine::general_purpose::STANDARD
+                .decode(text)
+                .map_err(|e| format!("base64: {}", e))?;
+            let mut bytes: Vec<u8> = Vec::new();
+            let inflated = match e.attr("compression") {
+                None => {
+                    bytes = raw;
+                    Ok(0)
+                }
+                Some("zlib") => flate2::read::ZlibDecoder::new(&raw[..]).read_to_end(&mut bytes),
+                Some("gzip") => flate2::read::GzDecoder::new(&raw[..]).read_to_end(&mut bytes),
+                Some(c) => return Err(format!("compression '{}' is not supported", c)),
+            };
+            inflated.map_err(|e| format!("decompress: {}", e))?;
+            bytes
+                .chunks_exact(4)
+                .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                .collect()
+        }
+        Some(enc) => return Err(format!("encoding '{}' is not supported", enc)),
+        // plain <tile gid=""/> elements, deprecated but still written
+        None => e
+            .children("tile")
+            .map(|t| t.parse_attr("gid", 0))
+            .collect::<Result<Vec<u32>, String>>()?,
+    };
+    if ret.len() != count {
+        return Err(format!("expected {} tiles, found {}", count, ret.len()));
+    }
+    Ok(ret)
+}
+
+fn parse_object(e: &Element) -> Result<TiledObject, String> {
+    let class = e.attr("class").or_else(|| e.attr("type")).unwrap_or_default();
+    let mut ret = TiledObject {
+        id: e.parse_attr("id", 0)?,
+        name: String::from(e.attr("name").unwrap_or_default()),
+        class: String::from(class),
+        x: e.parse_attr("x", 0.0)?,
+        y: e.parse_attr("y", 0.0)?,
+        width: e.parse_attr("width", 0.0)?,
+        height: e.parse_attr("height", 0.0)?,
+        rotation: e.parse_attr("rotation", 0.0)?,
+        gid: None,
+        visible: 0 != e.parse_attr("visible", 1)?,
+        points: Vec::new(),
+        properties: parse_properties(e)?,
+    };
+    if e.attr("gid").is_some() {
+        ret.gid = Some(e.parse_attr("gid", 0)?);
+    }
+    if let Some(poly) = e.child("polygon").or_else(|| e.child("polyline")) {
+        for p in poly.attr("points").unwrap_or_default().split_whitespace() {
+            let xy: Vec<f32> = p.split(',').filter_map(|v| v.parse::<f32>().ok()).collect();
+            if 2 != xy.len() {
+                return Err(format!("object {} bad point '{}'", ret.id, p));
+            }
+            ret.points.push((xy[0], xy[1]));
+        }
+    }
+    Ok(ret)
+}
+
+#[allow(dead_code)]
+impl TiledMap {
+    pub fn load(filename: &str) -> Result<TiledMap, String> {
+        log(format!("Tiled: Loading '{}'", filename));
+        let path = Path::new(filename);
+        let root = read_file(path)?;
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+        TiledMap::from_element(&root, dir).map_err(|e| format!("{}: {}", filename, e))
+    }
+
+    // dir is where external tilesets and images are looked up
+    pub fn parse(buffer: &str, dir: &Path) -> Result<TiledMap, String> {
+        TiledMap::from_element(&read_xml(buffer.as_bytes())?, dir)
+    }
+
+    fn from_element(root: &Element, dir: &Path) -> Result<TiledMap, String> {
+        if "map" != root.name {
+            return Err(format!("expected <map>, found <{}>", root.name));
+        }
+        let orientation = root.attr("orientation").unwrap_or("orthogonal");
+        if "orthogonal" != orientation {
+            return Err(format!("{} maps are not supported", orientation));
+        }
+        if 0 != root.parse_attr("infinite", 0)? {
+            return Err(String::from("infinite maps are not supported"));
+        }
+
+        let mut ret = TiledMap {
+            width: root.parse_attr("width", 0)?,
+            height: root.parse_attr("height", 0)?,
+            tile_width: root.parse_attr("tilewidth", 0)?,
+            tile_height: root.parse_attr("tileheight", 0)?,
+            properties: parse_properties(root)?,
+            ..Default::default()
+        };
+
+        for ts in root.children("tileset") {
+            let first_gid: u32 = ts.parse_attr("firstgid", 1)?;
+            let tileset = match ts.attr("source") {
+                Some(source) => {
+                    let path = resolve(dir, source);
+                    let e = read_file(&path)?;
+                    let tsx_dir = path.parent().unwrap_or_else(|| Path::new(""));
+                    parse_tileset(&e, tsx_dir, first_gid).map_err(|e| format!("{}: {}", path.display(), e))?
+                }
+                None => parse_tileset(ts, dir, first_gid)?,
+            };
+            ret.tilesets.push(tileset);
+        }
+        ret.tilesets.sort_by_key(|t| t.first_gid);
+
+        ret.read_layers(root, (0.0, 0.0), true)?;
+        Ok(ret)
+    }
+
+    fn read_layers(&mut self, parent: &Element, offset: (f32, f32), visible: bool) -> Result<(), String> {
+        for e in parent.children.iter() {
+            let name = String::from(e.attr("name").unwrap_or_default());
+            let offset = (
+                offset.0 + e.parse_attr("offsetx", 0.0)?,
+                offset.1 + e.parse_attr("offsety", 0.0)?,
+            );
+            let visible = visible && 0 != e.parse_attr("visible", 1)?;
+            match e.name.as_str() {
+                "layer" => {
+                    let width: usize = e.parse_attr("width", self.width)?;
+                    let height: usize = e.parse_attr("height", self.height)?;
+                    let data = match e.child("data") {
+                        Some(d) => parse_data(d, width * height).map_err(|err| format!("layer '{}': {}", name, err))?,
+                        None => vec![0; width * height],
+                    };
+                    self.layers.push(TiledLayer {
+                        name,
+                        width,
+                        height,
+                        data,
+                        visible,
+                        opacity: e.parse_attr("opacity", 1.0)?,
+                        offset,
+                        properties: parse_properties(e)?,
+                    });
+                }
+                "objectgroup" => {
+                    let mut objects: Vec<TiledObject> = Vec::new();
+                    for o in e.children("object") {
+                        objects.push(parse_object(o)?);
+                    }
+                    self.object_groups.push(TiledObjectGroup {
+                        name,
+                        visible,
+                        offset,
+                        objects,
+                        properties: parse_properties(e)?,
+                    });
+                }
+                "group" => self.read_layers(e, offset, visible)?,
+                "imagelayer" => log(format!("WARNING: Tiled: image layer '{}' is not supported", name)),
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+
+    // tileset holding the gid, flip flags are ignored
+    pub fn get_tileset(&self, gid: u32) -> Option<&TiledTileset> {
+        let gid = gid & TILED_GID_MASK;
+        if 0 == gid {
+            return None;
+        }
+        self.tilesets.iter().rev().find(|t| t.first_gid <= gid)
+    }
+
+    // custom properties of the tile, if it has any
+    pub fn get_tile_properties(&self, gid: u32) -> Option<&Properties> {
+        let tileset = self.get_tileset(gid)?;
+        tileset
+            .tile_properties
+            .get(&((gid & TILED_GID_MASK) - tileset.first_gid))
+    }
+
+    // TILE_FLIP_* flags of the gid
+    pub fn get_tile_flags(gid: u32) -> u8 {
+        let mut flags = 0;
+        if 0 != gid & TILED_FLIP_HORIZONTAL {
+            flags |= TILE_FLIP_HORIZONTAL;
+        }
+        if 0 != gid & TILED_FLIP_VERTICAL {
+            flags |= TILE_FLIP_VERTICAL;
+        }
+        if 0 != gid & TILED_FLIP_DIAGONAL {
+            flags |= TILE_FLIP_DIAGONAL;
+        }
+        flags
+    }
+
+    pub fn get_layer(&self, name: &str) -> Option<&TiledLayer> {
+        self.layers.iter().find(|l| l.name == name)
+    }
+
+    pub fn get_object_group(&self, name: &str) -> Option<&TiledObjectGroup> {
+        self.object_groups.iter().find(|g| g.name == name)
+    }
+}
+
+#[allow(dead_code)]
+impl World {
+    // creates a hidden tileset entity per tileset, a tilemap entity per tile
+    // layer and an entity per object, all placed at (x, y) and scaled.
+    // a tile layer using several tilesets becomes one tilemap entity per
+    // tileset, in tileset order, each named after the layer.
+    pub fn spawn_tiled_map(&mut self, map: &TiledMap, x: f32, y: f32, scale: f32) -> TiledSpawn {
+        let mut ret = TiledSpawn::default();
+
+        for ts in map.tilesets.iter() {
+            let idx = self.new_entity();
+            self.entity_set_tileset(
+                idx,
+                ts.image.clone(),
+                ts.image_width,
+                ts.image_height,
+                ts.tile_width,
+                ts.tile_height,
+            );
+            if !ts.name.is_empty() {
+                self.entity_set_name(idx, &ts.name);
+            }
+            for (id, frames) in ts.animations.iter() {
+                let frames: Vec<(u16, f32)> = frames
+                    .iter()
+                    .map(|(tile, ms)| (*tile as u16 + 1, *ms as f32 / 1000.0))
+                    .collect();
+                self.tileset_set_animation(idx, *id as u16 + 1, &frames);
+            }
+            ret.tilesets.push(idx);
+        }
+
+        let tileset_entity = |gid: u32| -> Option<(usize, u32)> {
+            let gid = gid & TILED_GID_MASK;
+            let t = map.tilesets.iter().rposition(|t| 0 < gid && t.first_gid <= gid)?;
+            Some((ret.tilesets[t], map.tilesets[t].first_gid))
+        };
+
+        for layer in map.layers.iter() {
+            let mut used: Vec<(usize, u32)> = Vec::new();
+            for g in layer.data.iter() {
+                if let Some(t) = tileset_entity(*g) {
+                    if !used.contains(&t) {
+                        used.push(t);
+                    }
+                }
+            }
+            // tileset entities were created in tileset order
+            used.sort();
+
+            for (tileset, first_gid) in used {
+                let data: Vec<u16> = layer
+                    .data
+                    .iter()
+                    .map(|g| match tileset_entity(*g) {
+                        Some((t, _)) if t == tileset => ((*g & TILED_GID_MASK) - first_gid + 1) as u16,
+                        _ => EMPTY_TILE,
+                    })
+                    .collect();
+
+                let idx = self.new_entity();
+                self.entity_set_tilemap(idx, tileset, layer.width, &data);
+                for (i, g) in layer.data.iter().enumerate() {
+                    let flags = TiledMap::get_tile_flags(*g);
+                    if 0 != flags && EMPTY_TILE != data[i] {
+                        self.tilemap_set_tile_flags(idx, 0, i % layer.width, i / layer.width, flags);
+                    }
+                }
+                self.entity_set_position_xy(idx, x + layer.offset.0 * scale, y + layer.offset.1 * scale);
+                self.entity_set_scale_xy(idx, map.tile_width as f32 * scale, map.tile_height as f32 * scale);
+                self.entity_set_alpha(idx, layer.opacity);
+                self.entity_set_visibility(idx, layer.visible);
+                self.entity_set_name(idx, &layer.name);
+                self.entity_set_properties(idx, &layer.properties);
+                ret.layers.push(idx);
+            }
+        }
+
+        for group in map.object_groups.iter() {
+            for o in group.objects.iter() {
+                let idx = self.new_entity();
+                let ox = x + (group.offset.0 + o.x) * scale;
+                let oy = y + (group.offset.1 + o.y) * scale;
+                match o.gid.and_then(tileset_entity) {
+                    // tile objects sit on their bottom left corner
+                    Some((tileset, first_gid)) => {
+                        let tile = ((o.gid.unwrap() & TILED_GID_MASK) - first_gid + 1) as u16;
+                        self.entity_set_tilemap(idx, tileset, 1, &vec![tile]);
+                        self.tilemap_set_tile_flags(idx, 0, 0, 0, TiledMap::get_tile_flags(o.gid.unwrap()));
+                        self.entity_set_position_xy(idx, ox, oy - o.height * scale);
+                        self.entity_set_scale_xy(idx, o.width * scale, o.height * scale);
+                    }
+                    None => {
+                        self.entity_set_position_xy(idx, ox, oy);
+                        self.entity_set_scale_xy(idx, o.width * scale, o.height * scale);
+                    }
+                }
+                self.entity_set_visibility(idx, group.visible && o.visible);
+                if !o.name.is_empty() {
+                    self.entity_set_name(idx, &o.name);
+                }
+                self.entity_set_properties(idx, &o.properties);
+                if !o.class.is_empty() {
+                    self.entity_set_property(idx, "class", Property::String(o.class.clone()));
+                }
+                ret.objects.push(idx);
+            }
+        }
+
+        ret
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAP: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" width="2" height="2" tilewidth="16" tileheight="16" infinite="0">
+ <properties>
+  <property name="music" value="level1.ogg"/>
+  <property name="gravity" type="float" value="9.5"/>
+ </properties>
+ <tileset firstgid="1" name="tiles" tilewidth="16" tileheight="16" tilecount="4" columns="2">
+  <image source="tiles.png" width="32" height="32"/>
+  <tile id="1">
+   <properties><property name="solid" type="bool" value="true"/></properties>
+   <animation><frame tileid="1" duration="100"/><frame tileid="2" duration="150"/></animation>
+  </tile>
+ </tileset>
+ <layer id="1" name="ground" width="2" height="2">
+  <data encoding="csv">
+1,2,
+3,2147483652
+</data>
+ </layer>
+ <group name="upper" offsetx="4">
+  <layer id="2" name="top" width="2" height="2" opacity="0.5">
+   <data encoding="base64">AAAAAAIAAAAAAAAAAQAAAA==</data>
+  </layer>
+ </group>
+ <objectgroup id="3" name="things">
+  <object id="1" name="door" type="trigger" x="16" y="8" width="16" height="16">
+   <properties><property name="locked" type="bool" value="true"/></properties>
+  </object>
+  <object id="2" name="path" x="0" y="0"><polyline points="0,0 8,4 16,0"/></object>
+ </objectgroup>
+</map>"#;
+
+    #[test]
+    fn parse_layers_objects_and_properties() {
+        let map = TiledMap::parse(MAP, Path::new("assets")).unwrap();
+        assert_eq!((2, 2, 16, 16), (map.width, map.height, map.tile_width, map.tile_height));
+        assert_eq!(Some(&Property::Float(9.5)), map.properties.get("gravity"));
+
+        let ts = &map.tilesets[0];
+        assert_eq!(Path::new("assets").join("tiles.png").to_string_lossy(), ts.image);
+        assert_eq!(vec![(1, 100), (2, 150)], ts.animations[&1]);
+        assert_eq!(
+            Some(&Property::Bool(true)),
+            map.get_tile_properties(2).and_then(|p| p.get("solid"))
+        );
+
+        assert_eq!(vec![1, 2, 3, 4 | TILED_FLIP_HORIZONTAL], map.layers[0].data);
+        let top = map.get_layer("top").unwrap();
+        assert_eq!(vec![0, 2, 0, 1], top.data);
+        assert_eq!((4.0, 0.0), top.offset);
+        assert_eq!(0.5, top.opacity);
+
+        let things = map.get_object_group("things").unwrap();
+        assert_eq!("trigger", things.objects[0].class);
+        assert_eq!(Some(&Property::Bool(true)), things.objects[0].properties.get("locked"));
+        assert_eq!(vec![(0.0, 0.0), (8.0, 4.0), (16.0, 0.0)], things.objects[1].points);
+    }
+
+    #[test]
+    fn reject_bad_maps() {
+        let short = MAP.replace("3,2147483652", "3");
+        let e = TiledMap::parse(&short, Path::new("")).unwrap_err();
+        assert!(e.contains("layer 'ground'"));
+        let iso = MAP.replace("orthogonal", "isometric");
+        assert!(TiledMap::parse(&iso, Path::new("")).is_err());
+    }
+
+    #[test]
+    fn spawn_splits_mixed_layers_and_hides_objects() {
+        let map = r#"<map orientation="orthogonal" width="2" height="1" tilewidth="16" tileheight="16">
+ <tileset firstgid="1" name="tiles" tilewidth="16" tileheight="16" tilecount="4" columns="2">
+  <image source="tiles.png" width="32" height="32"/>
+ </tileset>
+ <tileset firstgid="5" name="props" tilewidth="16" tileheight="16" tilecount="4" columns="2">
+  <image source="props.png" width="32" height="32"/>
+ </tileset>
+ <layer name="mixed" width="2" height="1"><data encoding="csv">6,2147483650</data></layer>
+ <objectgroup name="things">
+  <object id="1" name="hidden" x="0" y="0" width="8" height="8" visible="0"/>
+  <object id="2" name="shown" x="0" y="0"><point/></object>
+ </objectgroup>
+ <objectgroup name="off" visible="0">
+  <object id="3" name="in_hidden_group" x="0" y="0" width="8" height="8"/>
+ </objectgroup>
+</map>"#;
+        let map = TiledMap::parse(map, Path::new("")).unwrap();
+        let mut world = World::new(&mut CacheManager::new());
+        let spawn = world.spawn_tiled_map(&map, 0.0, 0.0, 1.0);
+
+        // one tilemap per tileset, each keeping only its own tiles
+        assert_eq!(2, spawn.layers.len());
+        let (tiles, props) = (spawn.layers[0], spawn.layers[1]);
+        assert_eq!(spawn.tilesets[0], world.tilemap_get_tileset_idx(tiles));
+        assert_eq!(spawn.tilesets[1], world.tilemap_get_tileset_idx(props));
+        assert_eq!((EMPTY_TILE, 2), (world.tilemap_get_tile(tiles, 0, 0, 0), world.tilemap_get_tile(tiles, 0, 1, 0)));
+        assert_eq!((2, EMPTY_TILE), (world.tilemap_get_tile(props, 0, 0, 0), world.tilemap_get_tile(props, 0, 1, 0)));
+        assert_eq!(TILE_FLIP_HORIZONTAL, world.tilemap_get_tile_flags(tiles, 0, 1, 0));
+        assert_eq!(0, world.tilemap_get_tile_flags(props, 0, 0, 0));
+        assert_eq!(Some(&String::from("mixed")), world.entity_get_name(props));
+
+        let visible: Vec<bool> = spawn.objects.iter().map(|o| world.entity_is_visible(*o)).collect();
+        assert_eq!(vec![false, true, false], visible);
+    }
+}
@@ -1,8 +1,8 @@
+use crate::mgfw::locale::Strings;
 use crate::mgfw::log;
 use rand;
 use rand::prelude::*;
-use std::fs::File;
-use std::io::{self, BufRead};
+use std::collections::HashMap;
 use std::ops::Range;
 
 use super::*;
@@ -23,9 +23,28 @@ pub struct World {
     ccm: std::boxed::Box<ColorComponentManager>,
     ecm: std::boxed::Box<EasingComponentManager>,
     fcm: std::boxed::Box<FrameComponentManager>,
+    mcm: std::boxed::Box<MaterialComponentManager>,
     pub mouse_x: i32,
     pub mouse_y: i32,
     rng: ThreadRng,
+    // lines of each parsed world file, used to find changes on reload
+    world_files: std::boxed::Box<HashMap<String, Vec<String>>>,
+    names: std::boxed::Box<HashMap<usize, String>>,
+    // custom properties, e.g. from Tiled maps
+    properties: std::boxed::Box<HashMap<usize, Properties>>,
+    // prefab definitions by name, kept across clear()
+    prefabs: std::boxed::Box<HashMap<String, SceneEntity>>,
+    // runtime post-processing overrides, kept across clear()
+    postfx_enabled: std::boxed::Box<HashMap<String, bool>>,
+    postfx_uniforms: std::boxed::Box<HashMap<String, Vec<f32>>>,
+    // developer console commands registered by the game, kept across clear()
+    commands: std::boxed::Box<Vec<(String, String)>>,
+    strings: std::boxed::Box<Strings>,
+    // (key, args) of entity text from the string tables, redone on a language switch
+    text_keys: std::boxed::Box<HashMap<usize, (String, Vec<String>)>>,
+    accessibility: std::boxed::Box<Accessibility>,
+    // bumped by clear(), entity ids from before it refer to something else
+    generation: usize,
 }
 
 #[allow(dead_code)]
@@ -47,9 +66,21 @@ impl World {
             ccm: Box::new(ColorComponentManager::new(cache)),
             ecm: Box::new(EasingComponentManager::new(cache)),
             fcm: Box::new(FrameComponentManager::new(cache)),
+            mcm: Box::new(MaterialComponentManager::new(cache)),
             mouse_x: 0,
             mouse_y: 0,
             rng: rand::thread_rng(),
+            world_files: Box::new(HashMap::new()),
+            names: Box::new(HashMap::new()),
+            properties: Box::new(HashMap::new()),
+            prefabs: Box::new(HashMap::new()),
+            postfx_enabled: Box::new(HashMap::new()),
+            postfx_uniforms: Box::new(HashMap::new()),
+            commands: Box::new(Vec::new()),
+            strings: Box::new(Strings::default()),
+            text_keys: Box::new(HashMap::new()),
+            accessibility: Box::new(Accessibility::default()),
+            generation: 0,
         }
     }
 
@@ -68,6 +99,16 @@ impl World {
         self.ccm.clear();
         self.ecm.clear();
         self.fcm.clear();
+        self.mcm.clear();
+        self.names.clear();
+        self.properties.clear();
+        self.text_keys.clear();
+        self.accessibility.entity_symbols.clear();
+        self.generation += 1;
+    }
+
+    pub fn get_generation(&self) -> usize {
+        self.generation
     }
 
     pub fn rnd(&mut self) -> f32 {
@@ -85,6 +126,167 @@ impl World {
         self.ent.add()
     }
 
+    pub fn entity_set_name(&mut self, idx: usize, name: &str) {
+        self.names.insert(idx, String::from(name));
+    }
+
+    pub fn entity_get_name(&self, idx: usize) -> Option<&String> {
+        self.names.get(&idx)
+    }
+
+    // first entity with this name, if any
+    pub fn find_entity(&self, name: &str) -> Option<usize> {
+        self.names
+            .iter()
+            .filter(|(_, n)| n.as_str() == name)
+            .map(|(idx, _)| *idx)
+            .min()
+    }
+
+    pub fn entity_set_property(&mut self, idx: usize, name: &str, value: Property) {
+        self.properties
+            .entry(idx)
+            .or_default()
+            .insert(String::from(name), value);
+    }
+
+    // adds to, and overwrites, the properties already set
+    pub fn entity_set_properties(&mut self, idx: usize, properties: &Properties) {
+        if properties.is_empty() {
+            return;
+        }
+        let p = self.properties.entry(idx).or_default();
+        for (k, v) in properties.iter() {
+            p.insert(k.clone(), v.clone());
+        }
+    }
+
+    pub fn entity_get_property(&self, idx: usize, name: &str) -> Option<&Property> {
+        self.properties.get(&idx).and_then(|p| p.get(name))
+    }
+
+    pub fn entity_get_properties(&self, idx: usize) -> Option<&Properties> {
+        self.properties.get(&idx)
+    }
+
+    pub fn register_prefab(&mut self, name: &str, prefab: SceneEntity) {
+        if self.prefabs.insert(String::from(name), prefab).is_some() {
+            log(format!("World: Replacing prefab '{}'", name));
+        }
+    }
+
+    pub fn get_prefab(&self, name: &str) -> Option<&SceneEntity> {
+        self.prefabs.get(name)
+    }
+
+    pub fn has_prefab(&self, name: &str) -> bool {
+        self.prefabs.contains_key(name)
+    }
+
+    // turn a pass from assets/mgfw/postfx.dat on or off
+    pub fn postfx_set_enabled(&mut self, pass: &str, enabled: bool) {
+        self.postfx_enabled.insert(String::from(pass), enabled);
+    }
+
+    // None when the pass uses the default from the chain file
+    pub fn postfx_get_enabled(&self, pass: &str) -> Option<bool> {
+        self.postfx_enabled.get(pass).copied()
+    }
+
+    // only uniforms declared in the chain file are applied
+    pub fn postfx_set_uniform(&mut self, pass: &str, uniform: &str, values: &[f32]) {
+        self.postfx_uniforms
+            .insert(format!("{}.{}", pass, uniform), values.to_vec());
+    }
+
+    pub fn postfx_get_uniform(&self, pass: &str, uniform: &str) -> Option<&Vec<f32>> {
+        self.postfx_uniforms.get(&format!("{}.{}", pass, uniform))
+    }
+
+    // COLORBLIND_NONE turns the filter off
+    pub fn accessibility_set_colorblind(&mut self, mode: u8) {
+        let mode = match mode < COLORBLIND_MODES {
+            true => mode,
+            false => {
+                log(format!("WARNING: World: unknown colorblind mode {}", mode));
+                COLORBLIND_NONE
+            }
+        };
+        self.accessibility.colorblind = mode;
+        self.postfx_set_enabled(COLORBLIND_PASS, mode != COLORBLIND_NONE);
+        if mode != COLORBLIND_NONE {
+            self.postfx_set_uniform(COLORBLIND_PASS, "mode", &[mode as f32]);
+        }
+        log(format!("World: Colorblind filter {}", colorblind_name(mode)));
+    }
+
+    pub fn accessibility_get_colorblind(&self) -> u8 {
+        self.accessibility.colorblind
+    }
+
+    pub fn accessibility_set_high_contrast(&mut self, enabled: bool) {
+        self.accessibility.high_contrast = enabled;
+        self.postfx_set_enabled(HIGH_CONTRAST_PASS, enabled);
+    }
+
+    pub fn accessibility_get_high_contrast(&self) -> bool {
+        self.accessibility.high_contrast
+    }
+
+    // draw the symbols given with entity_set_symbol over their entities
+    pub fn accessibility_set_symbols(&mut self, enabled: bool) {
+        self.accessibility.symbols = enabled;
+    }
+
+    pub fn accessibility_get_symbols(&self) -> bool {
+        self.accessibility.symbols
+    }
+
+    // image for a kind of piece, kept across clear()
+    pub fn symbol_define(&mut self, kind: &str, image: &str) {
+        self.accessibility
+            .symbol_images
+            .insert(String::from(kind), (String::from(image), 0));
+    }
+
+    // None removes the symbol
+    pub fn entity_set_symbol(&mut self, idx: usize, kind: Option<&str>) {
+        match kind {
+            Some(k) => {
+                if !self.accessibility.symbol_images.contains_key(k) {
+                    log(format!("WARNING: World: undefined symbol {}", k));
+                    return;
+                }
+                self.accessibility
+                    .entity_symbols
+                    .insert(idx, String::from(k));
+            }
+            None => {
+                self.accessibility.entity_symbols.remove(&idx);
+            }
+        }
+    }
+
+    pub fn entity_get_symbol(&self, idx: usize) -> Option<&String> {
+        self.accessibility.entity_symbols.get(&idx)
+    }
+
+    // texture to draw over the entity, None until the symbol image is loaded
+    pub fn entity_get_symbol_texture(&self, idx: usize) -> Option<u32> {
+        self.accessibility.get_symbol_texture(idx)
+    }
+
+    pub fn symbols_load(&mut self, gl: &Gl) {
+        if !self.accessibility.symbols_pending() {
+            return;
+        }
+        for (_, (image, tex)) in self.accessibility.symbol_images.iter_mut() {
+            if *tex == 0 {
+                *tex = self.bbcm.get_texture(image, gl);
+            }
+        }
+    }
+
     pub fn entity_add_component(&mut self, idx: usize, component: u32) {
         self.ent.add_component(idx, component);
     }
@@ -135,6 +337,69 @@ impl World {
         self.ent.add_component(idx, COMPONENT_FRAME);
     }
 
+    // material definitions outlive clear(), assignments to entities don't
+    pub fn material_create(&mut self, vertex: &str, fragment: &str) -> usize {
+        self.mcm.add(Material::new(vertex, fragment))
+    }
+
+    // loading the same file again returns the same material
+    pub fn load_material(&mut self, filename: &str) -> Result<usize, String> {
+        self.mcm.load(filename)
+    }
+
+    // independent copy, e.g. to give one entity its own uniform values
+    pub fn material_clone(&mut self, material: usize) -> Option<usize> {
+        let m = self.mcm.get(material)?.as_ref().clone();
+        Some(self.mcm.add(m))
+    }
+
+    pub fn material_get(&self, material: usize) -> Option<&Material> {
+        self.mcm.get(material).map(|m| m.as_ref())
+    }
+
+    pub fn material_set_float(&mut self, material: usize, name: &str, v: f32) {
+        self.mcm.set_uniform(material, name, MaterialValue::Float(v));
+    }
+
+    pub fn material_set_vec2(&mut self, material: usize, name: &str, x: f32, y: f32) {
+        self.mcm.set_uniform(material, name, MaterialValue::Vec2([x, y]));
+    }
+
+    pub fn material_set_vec3(&mut self, material: usize, name: &str, x: f32, y: f32, z: f32) {
+        self.mcm.set_uniform(material, name, MaterialValue::Vec3([x, y, z]));
+    }
+
+    pub fn material_set_vec4(&mut self, material: usize, name: &str, x: f32, y: f32, z: f32, w: f32) {
+        self.mcm
+            .set_uniform(material, name, MaterialValue::Vec4([x, y, z, w]));
+    }
+
+    pub fn material_set_texture(&mut self, material: usize, name: &str, image: &str) {
+        self.mcm
+            .set_uniform(material, name, MaterialValue::Texture(String::from(image)));
+    }
+
+    pub fn entity_set_material(&mut self, idx: usize, material: usize) {
+        if self.mcm.get(material).is_none() {
+            log(format!("WARNING: World: unknown material {}", material));
+            return;
+        }
+        self.mcm.set_material(idx, Some(material));
+        self.ent.add_component(idx, COMPONENT_MATERIAL);
+    }
+
+    pub fn entity_clear_material(&mut self, idx: usize) {
+        self.mcm.set_material(idx, None);
+        self.ent.clear_component(idx, COMPONENT_MATERIAL);
+    }
+
+    pub fn entity_get_material(&self, idx: usize) -> Option<usize> {
+        match self.ent.has_component(idx, COMPONENT_MATERIAL) {
+            true => self.mcm.get_material(idx),
+            false => None,
+        }
+    }
+
     pub fn entity_get_position(&mut self, idx: usize) -> Position {
         self.pcm.get_position(idx)
     }
@@ -205,11 +470,128 @@ impl World {
     }
 
     pub fn entity_set_text(&mut self, idx: usize, text: String) {
+        self.text_keys.remove(&idx);
         self.tcm.set_text(idx, text);
         self.ent.add_component(idx, COMPONENT_RENDER);
         self.rcm.set_type(idx, RENDER_TYPE_TEXT);
     }
 
+    // text from the string tables, kept up to date when the language changes
+    pub fn entity_set_text_key(&mut self, idx: usize, key: &str, args: &[String]) {
+        let text = self.strings.get(key, args);
+        self.entity_set_text(idx, text);
+        self.text_keys.insert(idx, (String::from(key), args.to_vec()));
+    }
+
+    // world and scene file text, "@key" is a string table id
+    pub(super) fn entity_set_text_source(&mut self, idx: usize, text: &str) {
+        match text.strip_prefix('@') {
+            Some(key) => self.entity_set_text_key(idx, key, &[]),
+            None => self.entity_set_text(idx, String::from(text)),
+        }
+    }
+
+    // string tables <dir>/<language>.dat, see locale.rs
+    pub fn load_strings(&mut self, dir: &str, language: &str) -> Result<(), String> {
+        self.strings.load(dir, language)?;
+        self.check_string_glyphs();
+        Ok(())
+    }
+
+    pub fn set_language(&mut self, language: &str) -> Result<(), String> {
+        self.strings.set_language(language)?;
+        self.check_string_glyphs();
+        let keys: Vec<(usize, (String, Vec<String>))> = self.text_keys.drain().collect();
+        for (idx, (key, args)) in keys {
+            self.entity_set_text_key(idx, &key, &args);
+        }
+        Ok(())
+    }
+
+    pub fn get_language(&self) -> &str {
+        self.strings.get_language()
+    }
+
+    pub fn get_languages(&self) -> Vec<String> {
+        self.strings.get_languages()
+    }
+
+    // current language text for `key`, {n} replaced by args[n]
+    pub fn tr(&mut self, key: &str, args: &[String]) -> String {
+        self.strings.get(key, args)
+    }
+
+    fn check_string_glyphs(&self) {
+        let mut missing: Vec<char> = Vec::new();
+        for text in self.strings.values() {
+            for c in self.tcm.get_missing_glyphs(0, text) {
+                if !missing.contains(&c) {
+                    missing.push(c);
+                }
+            }
+        }
+        if !missing.is_empty() {
+            log(format!(
+                "WARNING: Strings: {} has characters missing from the default font: {:?}",
+                self.strings.get_language(),
+                missing
+            ));
+        }
+    }
+
+    pub fn entity_set_font(&mut self, idx: usize, font: usize) {
+        self.tcm.set_font(idx, font);
+    }
+
+    // TEXT_ALIGN_LEFT, TEXT_ALIGN_CENTER or TEXT_ALIGN_RIGHT about the entity position
+    pub fn entity_set_text_align(&mut self, idx: usize, align: u8) {
+        let mut layout = self.tcm.get_layout(idx);
+        layout.align = align;
+        self.tcm.set_layout(idx, layout);
+    }
+
+    pub fn entity_get_text_align(&self, idx: usize) -> u8 {
+        self.tcm.get_layout(idx).align
+    }
+
+    // word wrap text to width pixels (before scaling), 0.0 disables wrapping
+    pub fn entity_set_text_wrap(&mut self, idx: usize, width: f32) {
+        let mut layout = self.tcm.get_layout(idx);
+        layout.wrap_width = width;
+        self.tcm.set_layout(idx, layout);
+    }
+
+    // distance between lines in pixels (before scaling), 0.0 uses the font line height
+    pub fn entity_set_text_line_height(&mut self, idx: usize, height: f32) {
+        let mut layout = self.tcm.get_layout(idx);
+        layout.line_height = height;
+        self.tcm.set_layout(idx, layout);
+    }
+
+    pub fn entity_get_font(&self, idx: usize) -> usize {
+        self.tcm.get_font(idx)
+    }
+
+    // load an AngelCode BMFont (.fnt), returns the font id for entity_set_font
+    pub fn load_font(&mut self, filename: &str) -> usize {
+        self.tcm.load_font(filename)
+    }
+
+    // character drawn in place of glyphs missing from the font
+    pub fn font_set_fallback_glyph(&mut self, font: usize, c: char) -> bool {
+        self.tcm.set_fallback_glyph(font, c)
+    }
+
+    // missing glyphs are skipped instead of drawn with a fallback
+    pub fn font_clear_fallback_glyph(&mut self, font: usize) {
+        self.tcm.clear_fallback_glyph(font);
+    }
+
+    // characters in text that aren't covered by the font
+    pub fn font_get_missing_glyphs(&self, font: usize, text: &str) -> Vec<char> {
+        self.tcm.get_missing_glyphs(font, text)
+    }
+
     pub fn entity_set_billboard(&mut self, idx: usize, image: String) {
         self.bbcm.set_image(idx, image);
         self.ent.add_component(idx, COMPONENT_RENDER);
@@ -248,6 +630,33 @@ impl World {
         self.rcm.set_type(idx, RENDER_TYPE_TILEMAP);
     }
 
+    // layers are drawn in order over the first one set by entity_set_tilemap
+    pub fn entity_set_tilemap_layer(&mut self, idx: usize, layer: usize, data: &Vec<u16>) {
+        self.tmcm.set_layer(idx, layer, data);
+    }
+
+    pub fn tilemap_set_tile(&mut self, idx: usize, layer: usize, column: usize, row: usize, tile: u16) {
+        self.tmcm.set_tile(idx, layer, column, row, tile);
+    }
+
+    pub fn tilemap_get_tile(&self, idx: usize, layer: usize, column: usize, row: usize) -> u16 {
+        self.tmcm.get_tile(idx, layer, column, row)
+    }
+
+    // TILE_FLIP_* flags
+    pub fn tilemap_set_tile_flags(&mut self, idx: usize, layer: usize, column: usize, row: usize, flags: u8) {
+        self.tmcm.set_tile_flags(idx, layer, column, row, flags);
+    }
+
+    pub fn tilemap_get_tile_flags(&self, idx: usize, layer: usize, column: usize, row: usize) -> u8 {
+        self.tmcm.get_tile_flags(idx, layer, column, row)
+    }
+
+    // (tile, seconds) frames, shown by every tilemap using the tileset
+    pub fn tileset_set_animation(&mut self, tileset_idx: usize, tile: u16, frames: &[(u16, f32)]) {
+        self.tmcm.set_animation(tileset_idx, tile, frames);
+    }
+
     pub fn entity_set_line_buffer(&mut self, idx: usize, pnts: &Vec<Position>, clrs: &Vec<Color>) {
         self.lcm.set_line_buffer(idx, pnts, clrs);
         self.ent.add_component(idx, COMPONENT_RENDER);
@@ -265,6 +674,10 @@ impl World {
         self.rcm.set_type(idx, RENDER_TYPE_TRIANGLE_BUFFER);
     }
 
+    pub fn entity_set_shape(&mut self, idx: usize, shape: &crate::mgfw::shapes::Shape) {
+        self.entity_set_triangle_buffer(idx, shape.get_points(), shape.get_colors());
+    }
+
     pub fn entity_set_active(&mut self, idx: usize, val: bool) {
         self.ent.set_active(idx, val);
     }
@@ -281,6 +694,83 @@ impl World {
         self.ent.is_visible(idx)
     }
 
+    // axis-aligned screen rectangle (x0, y0, x1, y1) around a rendered entity,
+    // rotated and scaled the way it is drawn
+    pub fn entity_get_bounds(&mut self, idx: usize) -> Option<(f32, f32, f32, f32)> {
+        if !self.ent.has_component(idx, COMPONENT_ACTIVE | COMPONENT_POSITION | COMPONENT_RENDER) {
+            return None;
+        }
+        let pos = self.pcm.get_position(idx);
+        let scale = match self.ent.has_component(idx, COMPONENT_SCALE) {
+            true => self.scm.get_scale(idx),
+            false => Scale { x: 1.0, y: 1.0 },
+        };
+
+        // in entity space, before scaling
+        let (x0, y0, x1, y1) = match self.rcm.get_type(idx) {
+            RENDER_TYPE_BILLBOARD => (-0.5, -0.5, 0.5, 0.5),
+            RENDER_TYPE_TEXT => {
+                let w = self.tcm.get_width(idx) as f32;
+                let h = self.tcm.get_height(idx) as f32;
+                let x = match self.tcm.get_layout(idx).align {
+                    TEXT_ALIGN_CENTER => -w * 0.5,
+                    TEXT_ALIGN_RIGHT => -w,
+                    _ => 0.0,
+                };
+                (x, 0.0, x + w, h)
+            }
+            RENDER_TYPE_TILEMAP => (0.0, 0.0, self.tmcm.get_columns(idx) as f32, self.tmcm.get_rows(idx) as f32),
+            RENDER_TYPE_LINE_BUFFER => get_points_bounds(self.lcm.get_points(idx))?,
+            RENDER_TYPE_TRIANGLE_BUFFER => get_points_bounds(self.trm.get_points(idx))?,
+            _ => return None,
+        };
+
+        let angle = match self.ent.has_component(idx, COMPONENT_ANGLE) {
+            true => self.acm.get_angle(idx),
+            false => 0.0,
+        };
+        let (sin, cos) = angle.sin_cos();
+
+        let mut ret = (f32::MAX, f32::MAX, f32::MIN, f32::MIN);
+        for (x, y) in [(x0, y0), (x1, y0), (x0, y1), (x1, y1)] {
+            let (x, y) = (x * scale.x, y * scale.y);
+            let (x, y) = (pos.x + x * cos - y * sin, pos.y + x * sin + y * cos);
+            ret = (ret.0.min(x), ret.1.min(y), ret.2.max(x), ret.3.max(y));
+        }
+        Some(ret)
+    }
+
+    // topmost visible entity under (x, y), later entities are drawn on top
+    pub fn entity_pick(&mut self, x: f32, y: f32) -> Option<usize> {
+        let span = self.ent.get_id_span();
+        if span.first > span.last {
+            return None;
+        }
+        for idx in (span.first..=span.last).rev() {
+            if !self.ent.is_visible(idx) {
+                continue;
+            }
+            if let Some((x0, y0, x1, y1)) = self.entity_get_bounds(idx) {
+                if x0 <= x && x <= x1 && y0 <= y && y <= y1 {
+                    return Some(idx);
+                }
+            }
+        }
+        None
+    }
+
+    // shows `name` in the developer console, which hands it to the game's command()
+    pub fn console_register(&mut self, name: &str, help: &str) {
+        match self.commands.iter_mut().find(|(n, _)| n == name) {
+            Some(c) => c.1 = String::from(help),
+            None => self.commands.push((String::from(name), String::from(help))),
+        }
+    }
+
+    pub fn console_get_commands(&self) -> &Vec<(String, String)> {
+        &self.commands
+    }
+
     pub fn get_entities(&self) -> &EntityRegistry {
         &self.ent
     }
@@ -303,6 +793,10 @@ impl World {
         &self.fcm
     }
 
+    pub fn get_manager_material(&self) -> &MaterialComponentManager {
+        &self.mcm
+    }
+
     pub fn get_manager_text(&self) -> &TextRenderComponentManager {
         &self.tcm
     }
@@ -339,15 +833,35 @@ impl World {
         &self.tmcm
     }
 
+    // integration results from the physics system, components are unchanged
+    pub fn physics_set_motion(&mut self, idx: usize, pos: Position, vel: Velocity, angle: f32) {
+        self.pcm.set_position(idx, pos.x, pos.y);
+        self.phcm.set_velocity(idx, vel.x, vel.y);
+        self.acm.set_angle(idx, angle);
+    }
+
     pub fn easing_disable(&mut self, idx: usize) {
         self.ecm.deactivate(idx);
     }
 
-    pub fn text_get_width(&self, idx: usize) -> usize {
+    pub fn text_get_width(&mut self, idx: usize) -> usize {
         self.tcm.get_width(idx)
     }
 
-    pub fn text_construct(&self, idx: usize, gl: &Gl, vao: u32, vbo: u32) {
+    pub fn text_get_height(&mut self, idx: usize) -> usize {
+        self.tcm.get_height(idx)
+    }
+
+    // text drawn outside the entity system in the default font, like overlays
+    pub fn text_build(&mut self, text: &str, line_height: f32, gl: &Gl, vao: u32, vbo: u32) -> Vec<TextSpan> {
+        let layout = fonts::layout::TextLayout {
+            line_height,
+            ..Default::default()
+        };
+        self.tcm.build(FONT_DEFAULT, text, &layout, gl, vao, vbo).0
+    }
+
+    pub fn text_construct(&mut self, idx: usize, gl: &Gl, vao: u32, vbo: u32) {
         self.tcm.construct(idx, gl, vao, vbo);
     }
 
@@ -367,7 +881,7 @@ impl World {
         self.bbcm.load_image(idx, gl);
     }
 
-    pub fn line_buffer_construct(&self, idx: usize, gl: &Gl, vao: u32, vbo: u32) {
+    pub fn line_buffer_construct(&mut self, idx: usize, gl: &Gl, vao: u32, vbo: u32) {
         self.lcm.construct(idx, gl, vao, vbo);
     }
 
@@ -375,194 +889,319 @@ impl World {
         self.tmcm.construct(idx, gl, vao, vbo);
     }
 
+    pub fn tilemap_update(&mut self, idx: usize, gl: &Gl, vbo: u32) {
+        self.tmcm.update(idx, gl, vbo);
+    }
+
     pub fn tilemap_get_tileset_idx(&mut self, idx: usize) -> usize {
         self.tmcm.get_tileset_idx(idx)
     }
 
-    pub fn triangle_buffer_construct(&self, idx: usize, gl: &Gl, vao: u32, vbo: u32) {
+    pub fn triangle_buffer_construct(&mut self, idx: usize, gl: &Gl, vao: u32, vbo: u32) {
         self.trm.construct(idx, gl, vao, vbo);
     }
 
-    pub fn parse_world(&mut self, filename: &str) {
+    // all-or-nothing: nothing is applied if the file has errors
+    pub fn parse_world(&mut self, filename: &str) -> Result<(), ParseError> {
         log(format!("World: Parsing '{}'", filename));
 
-        let file = File::open(filename).unwrap();
-        let reader = io::BufReader::new(file);
+        let world = parse_world_file(filename)?;
+        self.load_world(filename, world);
+        Ok(())
+    }
 
-        for line in reader.lines() {
-            let line = line.unwrap();
+    fn load_world(&mut self, filename: &str, world: WorldFile) {
+        for w in world.warnings.iter() {
+            log(format!("WARNING: {}", w));
+        }
+        for e in world.entries.iter() {
+            self.apply_world_entry(e);
+        }
 
-            let len = line.len();
-            if 2 > len {
-                continue;
-            }
+        let lines = world.entries.into_iter().map(|e| e.source).collect();
+        self.world_files.insert(String::from(filename), lines);
+    }
 
-            let bytes = line.as_bytes();
-            if b'/' == bytes[0] && b'/' == bytes[1] {
-                continue;
-            }
+    pub fn get_world_files(&self) -> Vec<String> {
+        self.world_files.keys().cloned().collect()
+    }
 
-            //log(format!("{:?}", line));
+    // re-apply every line of the entities whose lines changed since the file
+    // was last parsed, entities left untouched keep their runtime state
+    pub fn reload_world(&mut self, filename: &str) -> bool {
+        log(format!("World: Reloading '{}'", filename));
+        self.reload_world_from(filename, parse_world_file(filename))
+    }
 
-            let split: Vec<&str> = line.split(',').collect();
-            if 1 > split.len() {
-                continue;
+    // a parse error leaves the world and the remembered lines untouched
+    fn reload_world_from(&mut self, filename: &str, parsed: Result<WorldFile, ParseError>) -> bool {
+        let world = match parsed {
+            Ok(w) => w,
+            Err(e) => {
+                log(format!("WARNING: World: Reload failed: {}", e));
+                return false;
             }
+        };
+        for w in world.warnings.iter() {
+            log(format!("WARNING: {}", w));
+        }
 
-            let id = split[0].parse::<usize>().unwrap();
-            if id >= entity::ENTITY_SZ {
-                continue;
+        let old = match self.world_files.get(filename) {
+            Some(l) => l.clone(),
+            None => Vec::new(),
+        };
+        let lines: Vec<String> = world.entries.iter().map(|e| e.source.clone()).collect();
+
+        let mut changed: Vec<usize> = Vec::new();
+        for e in world.entries.iter() {
+            if !old.contains(&e.source) {
+                log(format!("World: {}:{} changed", filename, e.line));
+                changed.push(e.id);
             }
+        }
+        for line in old.iter().filter(|l| !lines.contains(l)) {
+            log(format!("World: '{}' removed, restart to undo", line));
+        }
 
-            let component = split[1];
+        for e in world.entries.iter() {
+            if changed.contains(&e.id) {
+                self.apply_world_entry(e);
+            }
+        }
 
-            match component {
-                "text" => {
-                    if 3 <= split.len() {
-                        let val = split[2].replace("\"", "");
-                        self.ent.add_component(id, COMPONENT_ACTIVE);
-                        self.entity_set_text(id, val);
-                    }
-                }
-                "color" => {
-                    if 5 == split.len() {
-                        let r = split[2].parse::<f32>().unwrap();
-                        let g = split[3].parse::<f32>().unwrap();
-                        let b = split[4].parse::<f32>().unwrap();
-                        self.entity_set_color_rgba(id, r, g, b, 1.0);
-                    } else if 6 == split.len() {
-                        let r = split[2].parse::<f32>().unwrap();
-                        let g = split[3].parse::<f32>().unwrap();
-                        let b = split[4].parse::<f32>().unwrap();
-                        let a = split[5].parse::<f32>().unwrap();
-                        self.entity_set_color_rgba(id, r, g, b, a);
-                    }
-                }
-                "billboard" => {
-                    if 3 == split.len() {
-                        let image = split[2];
-                        self.ent.add_component(id, COMPONENT_ACTIVE);
-                        self.entity_set_billboard(id, String::from(image));
-                    }
-                }
-                "linebuffer" => {
-                    let n = split.len() - 2;
-                    let n_lines: usize = n / 12;
-                    let n_points: usize = n_lines * 2;
-                    if n_lines * 12 == n {
-                        let mut pnts: Vec<Position> = Vec::new();
-                        let mut clrs: Vec<Color> = Vec::new();
-
-                        for p in 0..n_points {
-                            let pidx: usize = 2 + p * 6;
-                            pnts.push(Position {
-                                x: split[pidx + 0].parse::<f32>().unwrap(),
-                                y: split[pidx + 1].parse::<f32>().unwrap(),
-                            });
-                            clrs.push(Color {
-                                r: split[pidx + 2].parse::<f32>().unwrap(),
-                                g: split[pidx + 3].parse::<f32>().unwrap(),
-                                b: split[pidx + 4].parse::<f32>().unwrap(),
-                                a: split[pidx + 5].parse::<f32>().unwrap(),
-                            });
-                        }
-                        self.ent.add_component(id, COMPONENT_ACTIVE);
-                        self.entity_set_line_buffer(id, &pnts, &clrs);
-                    }
-                }
-                "tribuffer" => {
-                    let n = split.len() - 2;
-                    let n_triangles: usize = n / 18;
-                    let n_points: usize = n_triangles * 3;
-                    if n_triangles * 18 == n {
-                        let mut pnts: Vec<Position> = Vec::new();
-                        let mut clrs: Vec<Color> = Vec::new();
-
-                        for p in 0..n_points {
-                            let pidx: usize = 2 + p * 6;
-                            pnts.push(Position {
-                                x: split[pidx + 0].parse::<f32>().unwrap(),
-                                y: split[pidx + 1].parse::<f32>().unwrap(),
-                            });
-                            clrs.push(Color {
-                                r: split[pidx + 2].parse::<f32>().unwrap(),
-                                g: split[pidx + 3].parse::<f32>().unwrap(),
-                                b: split[pidx + 4].parse::<f32>().unwrap(),
-                                a: split[pidx + 5].parse::<f32>().unwrap(),
-                            });
-                        }
-                        self.ent.add_component(id, COMPONENT_ACTIVE);
-                        self.entity_set_triangle_buffer(id, &pnts, &clrs);
-                    }
-                }
-                "visible" => {
-                    if 3 == split.len() {
-                        let val = split[2];
-                        match val {
-                            "true" => self.ent.set_visibility(id, true),
-                            "false" => self.ent.set_visibility(id, false),
-                            _ => (),
-                        }
-                    }
-                }
-                "position" => {
-                    if 4 == split.len() {
-                        let x = split[2].parse::<f32>().unwrap();
-                        let y = split[3].parse::<f32>().unwrap();
-                        self.ent.add_component(id, COMPONENT_ACTIVE);
-                        self.entity_set_position_xy(id, x, y);
-                    }
-                }
-                "velocity" => {
-                    if 4 == split.len() {
-                        let x = split[2].parse::<f32>().unwrap();
-                        let y = split[3].parse::<f32>().unwrap();
-                        self.ent.add_component(id, COMPONENT_ACTIVE);
-                        self.entity_set_velocity_xy(id, x, y);
-                    }
-                }
-                "scale" => {
-                    if 3 <= split.len() {
-                        let x = split[2].parse::<f32>().unwrap();
-                        let mut y = 1.0 as f32;
-                        if 4 == split.len() {
-                            y = split[3].parse::<f32>().unwrap();
-                        }
-                        self.ent.add_component(id, COMPONENT_ACTIVE);
-                        self.entity_set_scale_xy(id, x, y);
-                    }
-                }
-                "angle" => {
-                    if 3 == split.len() {
-                        let angle = split[2].parse::<f32>().unwrap();
-                        self.ent.add_component(id, COMPONENT_ACTIVE);
-                        self.entity_set_angle(id, crate::mgfw::deg2rad(angle));
-                    }
-                }
-                "alpha" => {
-                    if 3 == split.len() {
-                        let alpha = split[2].parse::<f32>().unwrap();
-                        self.ent.add_component(id, COMPONENT_COLOR);
-                        self.entity_set_alpha(id, alpha);
-                    }
-                }
-                "angular_velocity" => {
-                    if 3 == split.len() {
-                        let avel = split[2].parse::<f32>().unwrap();
-                        self.ent.add_component(id, COMPONENT_ACTIVE);
-                        self.entity_set_angular_velocity(id, crate::mgfw::deg2rad(avel));
-                    }
-                }
-                "acceleration" => {
-                    if 4 == split.len() {
-                        let x = split[2].parse::<f32>().unwrap();
-                        let y = split[3].parse::<f32>().unwrap();
-                        self.ent.add_component(id, COMPONENT_ACTIVE);
-                        self.entity_set_acceleration_xy(id, x, y);
-                    }
-                }
-                _ => (),
+        self.world_files.insert(String::from(filename), lines);
+        true
+    }
+
+    pub fn apply_world_entry(&mut self, entry: &WorldEntry) {
+        let id = entry.id;
+
+        match &entry.component {
+            WorldComponent::Text(val) => {
+                self.ent.add_component(id, COMPONENT_ACTIVE);
+                self.entity_set_text_source(id, val);
+            }
+            WorldComponent::Font(file) => {
+                let font = self.load_font(file);
+                self.entity_set_font(id, font);
+            }
+            WorldComponent::Align(align) => self.entity_set_text_align(id, *align),
+            WorldComponent::Wrap(width) => self.entity_set_text_wrap(id, *width),
+            WorldComponent::LineHeight(height) => self.entity_set_text_line_height(id, *height),
+            WorldComponent::Color(color) => self.entity_set_color(id, *color),
+            WorldComponent::Billboard(image) => {
+                self.ent.add_component(id, COMPONENT_ACTIVE);
+                self.entity_set_billboard(id, image.clone());
+            }
+            WorldComponent::LineBuffer(pnts, clrs) => {
+                self.ent.add_component(id, COMPONENT_ACTIVE);
+                self.entity_set_line_buffer(id, pnts, clrs);
+            }
+            WorldComponent::TriBuffer(pnts, clrs) => {
+                self.ent.add_component(id, COMPONENT_ACTIVE);
+                self.entity_set_triangle_buffer(id, pnts, clrs);
+            }
+            WorldComponent::Visible(val) => self.ent.set_visibility(id, *val),
+            WorldComponent::Position(x, y) => {
+                self.ent.add_component(id, COMPONENT_ACTIVE);
+                self.entity_set_position_xy(id, *x, *y);
+            }
+            WorldComponent::Velocity(x, y) => {
+                self.ent.add_component(id, COMPONENT_ACTIVE);
+                self.entity_set_velocity_xy(id, *x, *y);
+            }
+            WorldComponent::Scale(x, y) => {
+                self.ent.add_component(id, COMPONENT_ACTIVE);
+                self.entity_set_scale_xy(id, *x, *y);
+            }
+            WorldComponent::Angle(angle) => {
+                self.ent.add_component(id, COMPONENT_ACTIVE);
+                self.entity_set_angle(id, crate::mgfw::deg2rad(*angle));
+            }
+            WorldComponent::Alpha(alpha) => {
+                self.ent.add_component(id, COMPONENT_COLOR);
+                self.entity_set_alpha(id, *alpha);
             }
+            WorldComponent::AngularVelocity(avel) => {
+                self.ent.add_component(id, COMPONENT_ACTIVE);
+                self.entity_set_angular_velocity(id, crate::mgfw::deg2rad(*avel));
+            }
+            WorldComponent::Acceleration(x, y) => {
+                self.ent.add_component(id, COMPONENT_ACTIVE);
+                self.entity_set_acceleration_xy(id, *x, *y);
+            }
+        }
+    }
+}
+
+fn get_points_bounds(pnts: &[Position]) -> Option<(f32, f32, f32, f32)> {
+    let first = pnts.first()?;
+    let mut ret = (first.x, first.y, first.x, first.y);
+    for p in pnts.iter() {
+        ret = (ret.0.min(p.x), ret.1.min(p.y), ret.2.max(p.x), ret.3.max(p.y));
+    }
+    Some(ret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WORLD: &str = "0,text,Hello\n0,position,10,20\n1,text,World\n1,position,30,40\n";
+
+    fn world() -> World {
+        let mut world = World::new(&mut CacheManager::new());
+        world.load_world("test.dat", parse_world_str("test.dat", WORLD).unwrap());
+
+        // as if rendered once and moved around at runtime
+        for idx in 0..2 {
+            world.tcm.set_constructed(idx);
+            world.entity_set_position_xy(idx, 100.0, 100.0);
         }
+        world
+    }
+
+    fn position(world: &mut World, idx: usize) -> (f32, f32) {
+        let p = world.entity_get_position(idx);
+        (p.x, p.y)
+    }
+
+    #[test]
+    fn reload_only_touches_changed_entities() {
+        let mut world = world();
+        let changed = WORLD.replace("World", "Earth");
+        assert!(world.reload_world_from("test.dat", parse_world_str("test.dat", &changed)));
+
+        // untouched entity keeps its runtime state
+        assert_eq!("Hello", world.tcm.get_text(0));
+        assert!(!world.text_reconstruct(0));
+        assert_eq!((100.0, 100.0), position(&mut world, 0));
+
+        // every line of the changed entity is applied again
+        assert_eq!("Earth", world.tcm.get_text(1));
+        assert!(world.text_reconstruct(1));
+        assert_eq!((30.0, 40.0), position(&mut world, 1));
+
+        // reloading the same file again changes nothing
+        world.tcm.set_constructed(1);
+        assert!(world.reload_world_from("test.dat", parse_world_str("test.dat", &changed)));
+        assert!(!world.text_reconstruct(1));
+    }
+
+    #[test]
+    fn malformed_reload_leaves_world_unchanged() {
+        let mut world = world();
+        let broken = WORLD.replace("World", "Earth").replace("30,40", "30,x");
+        assert!(!world.reload_world_from("test.dat", parse_world_str("test.dat", &broken)));
+
+        for idx in 0..2 {
+            assert!(!world.text_reconstruct(idx));
+            assert_eq!((100.0, 100.0), position(&mut world, idx));
+        }
+        assert_eq!("World", world.tcm.get_text(1));
+
+        // the last good file is still what the next reload diffs against
+        let lines: Vec<&str> = WORLD.lines().collect();
+        assert_eq!(lines, world.world_files["test.dat"]);
+    }
+
+    fn billboard(world: &mut World, x: f32, y: f32, sx: f32, sy: f32) -> usize {
+        let idx = world.new_entity();
+        world.entity_set_billboard(idx, String::from("test.png"));
+        world.entity_set_position_xy(idx, x, y);
+        world.entity_set_scale_xy(idx, sx, sy);
+        world.entity_set_visibility(idx, true);
+        idx
+    }
+
+    fn assert_bounds(expected: (f32, f32, f32, f32), bounds: Option<(f32, f32, f32, f32)>) {
+        let b = bounds.unwrap();
+        for (e, b) in [(expected.0, b.0), (expected.1, b.1), (expected.2, b.2), (expected.3, b.3)] {
+            assert!((e - b).abs() < 1.0e-3, "expected {:?}, found {:?}", expected, bounds);
+        }
+    }
+
+    #[test]
+    fn bounds_follow_scale_and_angle() {
+        let mut world = World::new(&mut CacheManager::new());
+        let idx = billboard(&mut world, 100.0, 100.0, 40.0, 20.0);
+        assert_bounds((80.0, 90.0, 120.0, 110.0), world.entity_get_bounds(idx));
+
+        // mirrored scale still gives an ordered rectangle
+        world.entity_set_scale_xy(idx, -40.0, 20.0);
+        assert_bounds((80.0, 90.0, 120.0, 110.0), world.entity_get_bounds(idx));
+
+        world.entity_set_angle(idx, std::f32::consts::FRAC_PI_2);
+        assert_bounds((90.0, 80.0, 110.0, 120.0), world.entity_get_bounds(idx));
+
+        // a square turned 45 degrees grows to its diagonal
+        world.entity_set_scale_xy(idx, 20.0, 20.0);
+        world.entity_set_angle(idx, std::f32::consts::FRAC_PI_4);
+        let h = 10.0 * std::f32::consts::SQRT_2;
+        assert_bounds((100.0 - h, 100.0 - h, 100.0 + h, 100.0 + h), world.entity_get_bounds(idx));
+
+        // nothing rendered, nothing to bound
+        let empty = world.new_entity();
+        world.entity_set_position_xy(empty, 100.0, 100.0);
+        assert_eq!(None, world.entity_get_bounds(empty));
+    }
+
+    #[test]
+    fn pick_topmost_visible_entity() {
+        let mut world = World::new(&mut CacheManager::new());
+        assert_eq!(None, world.entity_pick(100.0, 100.0));
+
+        let below = billboard(&mut world, 100.0, 100.0, 40.0, 40.0);
+        let above = billboard(&mut world, 110.0, 100.0, 40.0, 40.0);
+
+        // later entities draw on top
+        assert_eq!(Some(above), world.entity_pick(110.0, 100.0));
+        assert_eq!(Some(below), world.entity_pick(85.0, 100.0));
+        assert_eq!(None, world.entity_pick(200.0, 100.0));
+
+        world.entity_set_visibility(above, false);
+        assert_eq!(Some(below), world.entity_pick(110.0, 100.0));
+        assert_eq!(None, world.entity_pick(125.0, 100.0));
+
+        // picking uses the rotated bounds
+        world.entity_set_scale_xy(below, 40.0, 4.0);
+        assert_eq!(None, world.entity_pick(100.0, 115.0));
+        world.entity_set_angle(below, std::f32::consts::FRAC_PI_2);
+        assert_eq!(Some(below), world.entity_pick(100.0, 115.0));
+    }
+
+    #[test]
+    fn entity_symbols() {
+        let mut world = World::new(&mut CacheManager::new());
+        world.symbol_define("placed", "symbol_placed.png");
+
+        // undefined kinds are ignored, the previous symbol stays
+        world.entity_set_symbol(3, Some("placed"));
+        world.entity_set_symbol(3, Some("sparkle"));
+        assert_eq!(Some(&String::from("placed")), world.entity_get_symbol(3));
+        world.entity_set_symbol(4, Some("sparkle"));
+        assert_eq!(None, world.entity_get_symbol(4));
+
+        // no texture until the image is loaded
+        assert_eq!(None, world.entity_get_symbol_texture(3));
+
+        world.entity_set_symbol(3, None);
+        assert_eq!(None, world.entity_get_symbol(3));
+
+        // clear() drops assignments but keeps definitions
+        world.entity_set_symbol(5, Some("placed"));
+        world.clear();
+        assert_eq!(None, world.entity_get_symbol(5));
+        world.entity_set_symbol(5, Some("placed"));
+        assert_eq!(Some(&String::from("placed")), world.entity_get_symbol(5));
+    }
+
+    #[test]
+    fn unknown_font_ids_are_ignored() {
+        let mut world = World::new(&mut CacheManager::new());
+        assert!(!world.font_set_fallback_glyph(99, '#'));
+        world.font_clear_fallback_glyph(99);
+        assert!(world.font_get_missing_glyphs(99, "Ж").is_empty());
+        assert_eq!(vec!['Ж'], world.font_get_missing_glyphs(0, "aЖ"));
     }
 }
@@ -0,0 +1,395 @@
+use super::*;
+use std::fmt;
+
+// world.dat format, one component per line:
+//   entity id, component, values...
+// Values are separated by commas. Strings may be quoted to hold commas, with
+// \" \\ \n and \t escapes. Lines starting with // are comments.
+
+#[derive(Debug, Clone)]
+pub struct ParseError {
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}:{}: {}", self.file, self.line, self.column, self.message)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum WorldComponent {
+    Text(String),
+    Font(String),
+    Align(u8),
+    Wrap(f32),
+    LineHeight(f32),
+    Color(Color),
+    Billboard(String),
+    LineBuffer(Vec<Position>, Vec<Color>),
+    TriBuffer(Vec<Position>, Vec<Color>),
+    Visible(bool),
+    Position(f32, f32),
+    Velocity(f32, f32),
+    Scale(f32, f32),
+    // degrees, as written in the file
+    Angle(f32),
+    Alpha(f32),
+    AngularVelocity(f32),
+    Acceleration(f32, f32),
+}
+
+#[derive(Debug, Clone)]
+pub struct WorldEntry {
+    pub line: usize,
+    pub id: usize,
+    pub component: WorldComponent,
+    // trimmed source line, used to diff files on reload
+    pub source: String,
+}
+
+#[derive(Debug)]
+pub struct WorldFile {
+    pub entries: Vec<WorldEntry>,
+    pub warnings: Vec<ParseError>,
+}
+
+struct Field {
+    text: String,
+    column: usize,
+}
+
+pub fn parse_world_file(filename: &str) -> Result<WorldFile, ParseError> {
+    match assets::read_to_string(filename) {
+        Ok(buffer) => parse_world_str(filename, &buffer),
+        Err(e) => Err(ParseError {
+            file: String::from(filename),
+            line: 0,
+            column: 0,
+            message: e,
+        }),
+    }
+}
+
+// filename is only used in diagnostics
+pub fn parse_world_str(filename: &str, buffer: &str) -> Result<WorldFile, ParseError> {
+    let mut ret = WorldFile {
+        entries: Vec::new(),
+        warnings: Vec::new(),
+    };
+
+    for (n, line) in buffer.lines().enumerate() {
+        let err = |column: usize, message: String| ParseError {
+            file: String::from(filename),
+            line: n + 1,
+            column,
+            message,
+        };
+
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with("//") {
+            continue;
+        }
+
+        let fields = split_fields(line).map_err(|(col, msg)| err(col, msg))?;
+        if 2 > fields.len() {
+            return Err(err(line.len() + 1, String::from("expected 'id,component'")));
+        }
+
+        let id = match fields[0].text.parse::<usize>() {
+            Ok(id) => id,
+            Err(_) => {
+                return Err(err(
+                    fields[0].column,
+                    format!("invalid entity id '{}'", fields[0].text),
+                ))
+            }
+        };
+
+        let name = fields[1].text.as_str();
+        let values = &fields[2..];
+
+        let expected = match arity(name) {
+            Some(a) => a,
+            None => {
+                ret.warnings.push(err(
+                    fields[1].column,
+                    format!("unknown component '{}', skipped", name),
+                ));
+                continue;
+            }
+        };
+        if !expected.0(values.len()) {
+            ret.warnings.push(err(
+                fields[1].column,
+                format!(
+                    "'{}' expects {} values, found {}, skipped",
+                    name,
+                    expected.1,
+                    values.len()
+                ),
+            ));
+            continue;
+        }
+
+        if id >= entity::ENTITY_SZ {
+            ret.warnings.push(err(
+                fields[0].column,
+                format!("entity id {} out of range (max {}), skipped", id, entity::ENTITY_SZ - 1),
+            ));
+            continue;
+        }
+
+        let component = to_component(name, values).map_err(|(col, msg)| err(col, msg))?;
+        ret.entries.push(WorldEntry {
+            line: n + 1,
+            id,
+            component,
+            source: String::from(trimmed),
+        });
+    }
+
+    Ok(ret)
+}
+
+// checks each file and prints every diagnostic, returns false if any had errors
+pub fn validate_world_files(files: &[String]) -> bool {
+    let mut ok = true;
+    for f in files {
+        match parse_world_file(f) {
+            Ok(world) => {
+                for w in world.warnings.iter() {
+                    println!("warning: {}", w);
+                }
+                println!(
+                    "{}: {} components, {} warnings",
+                    f,
+                    world.entries.len(),
+                    world.warnings.len()
+                );
+            }
+            Err(e) => {
+                println!("error: {}", e);
+                ok = false;
+            }
+        }
+    }
+    ok
+}
+
+// (accepts value count, human readable description)
+type Arity = (fn(usize) -> bool, &'static str);
+
+fn arity(component: &str) -> Option<Arity> {
+    let a: Arity = match component {
+        "text" | "font" | "align" | "wrap" | "line_height" | "billboard" | "visible" | "angle"
+        | "alpha" | "angular_velocity" => (|n| 1 == n, "1"),
+        "position" | "velocity" | "acceleration" => (|n| 2 == n, "2"),
+        "scale" => (|n| 1 == n || 2 == n, "1 or 2"),
+        "color" => (|n| 3 == n || 4 == n, "3 or 4"),
+        "linebuffer" => (|n| 0 < n && 0 == n % 12, "a multiple of 12"),
+        "tribuffer" => (|n| 0 < n && 0 == n % 18, "a multiple of 18"),
+        _ => return None,
+    };
+    Some(a)
+}
+
+fn to_component(name: &str, v: &[Field]) -> Result<WorldComponent, (usize, String)> {
+    let c = match name {
+        "text" => WorldComponent::Text(v[0].text.clone()),
+        "font" => WorldComponent::Font(v[0].text.clone()),
+        "billboard" => WorldComponent::Billboard(v[0].text.clone()),
+        "align" => match v[0].text.as_str() {
+            "left" => WorldComponent::Align(TEXT_ALIGN_LEFT),
+            "center" => WorldComponent::Align(TEXT_ALIGN_CENTER),
+            "right" => WorldComponent::Align(TEXT_ALIGN_RIGHT),
+            _ => {
+                return Err((
+                    v[0].column,
+                    format!("expected left, center or right, found '{}'", v[0].text),
+                ))
+            }
+        },
+        "visible" => match v[0].text.as_str() {
+            "true" => WorldComponent::Visible(true),
+            "false" => WorldComponent::Visible(false),
+            _ => {
+                return Err((
+                    v[0].column,
+                    format!("expected true or false, found '{}'", v[0].text),
+                ))
+            }
+        },
+        "wrap" => WorldComponent::Wrap(float(&v[0])?),
+        "line_height" => WorldComponent::LineHeight(float(&v[0])?),
+        "angle" => WorldComponent::Angle(float(&v[0])?),
+        "alpha" => WorldComponent::Alpha(float(&v[0])?),
+        "angular_velocity" => WorldComponent::AngularVelocity(float(&v[0])?),
+        "position" => WorldComponent::Position(float(&v[0])?, float(&v[1])?),
+        "velocity" => WorldComponent::Velocity(float(&v[0])?, float(&v[1])?),
+        "acceleration" => WorldComponent::Acceleration(float(&v[0])?, float(&v[1])?),
+        "scale" => {
+            let mut y = 1.0;
+            if 2 == v.len() {
+                y = float(&v[1])?;
+            }
+            WorldComponent::Scale(float(&v[0])?, y)
+        }
+        "color" => {
+            let mut a = 1.0;
+            if 4 == v.len() {
+                a = float(&v[3])?;
+            }
+            WorldComponent::Color(Color {
+                r: float(&v[0])?,
+                g: float(&v[1])?,
+                b: float(&v[2])?,
+                a,
+            })
+        }
+        "linebuffer" => {
+            let (pnts, clrs) = vertices(v)?;
+            WorldComponent::LineBuffer(pnts, clrs)
+        }
+        _ => {
+            let (pnts, clrs) = vertices(v)?;
+            WorldComponent::TriBuffer(pnts, clrs)
+        }
+    };
+    Ok(c)
+}
+
+// x, y, r, g, b, a per vertex
+fn vertices(v: &[Field]) -> Result<(Vec<Position>, Vec<Color>), (usize, String)> {
+    let mut pnts: Vec<Position> = Vec::new();
+    let mut clrs: Vec<Color> = Vec::new();
+    for p in v.chunks(6) {
+        pnts.push(Position {
+            x: float(&p[0])?,
+            y: float(&p[1])?,
+        });
+        clrs.push(Color {
+            r: float(&p[2])?,
+            g: float(&p[3])?,
+            b: float(&p[4])?,
+            a: float(&p[5])?,
+        });
+    }
+    Ok((pnts, clrs))
+}
+
+fn float(f: &Field) -> Result<f32, (usize, String)> {
+    f.text
+        .parse::<f32>()
+        .map_err(|_| (f.column, format!("expected a number, found '{}'", f.text)))
+}
+
+// splits on commas outside of quotes, columns are 1-based char positions
+fn split_fields(line: &str) -> Result<Vec<Field>, (usize, String)> {
+    let chars: Vec<char> = line.chars().collect();
+    let mut fields: Vec<Field> = Vec::new();
+    let mut i = 0;
+
+    loop {
+        while i < chars.len() && chars[i].is_whitespace() {
+            i += 1;
+        }
+        let column = i + 1;
+        let mut text = String::new();
+
+        if i < chars.len() && '"' == chars[i] {
+            i += 1;
+            loop {
+                if i >= chars.len() {
+                    return Err((column, String::from("unterminated string")));
+                }
+                match chars[i] {
+                    '"' => {
+                        i += 1;
+                        break;
+                    }
+                    '\\' if i + 1 < chars.len() => {
+                        i += 1;
+                        match chars[i] {
+                            'n' => text.push('\n'),
+                            't' => text.push('\t'),
+                            '"' | '\\' => text.push(chars[i]),
+                            c => return Err((i, format!("unknown escape '\\{}'", c))),
+                        }
+                    }
+                    c => text.push(c),
+                }
+                i += 1;
+            }
+            while i < chars.len() && chars[i].is_whitespace() {
+                i += 1;
+            }
+            if i < chars.len() && ',' != chars[i] {
+                return Err((i + 1, String::from("expected ',' after closing quote")));
+            }
+        } else {
+            while i < chars.len() && ',' != chars[i] {
+                text.push(chars[i]);
+                i += 1;
+            }
+            text = String::from(text.trim_end());
+        }
+
+        fields.push(Field { text, column });
+
+        if i >= chars.len() {
+            break;
+        }
+        i += 1; // skip ','
+    }
+
+    Ok(fields)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(src: &str) -> Result<WorldFile, ParseError> {
+        parse_world_str("test.dat", src)
+    }
+
+    #[test]
+    fn quoted_text_keeps_commas_and_escapes() {
+        let world = parse("// comment\n\n0,text,\"Hello, \\\"World\\\"\"\n").unwrap();
+        assert_eq!(1, world.entries.len());
+        assert_eq!(3, world.entries[0].line);
+        match &world.entries[0].component {
+            WorldComponent::Text(t) => assert_eq!("Hello, \"World\"", t),
+            c => panic!("unexpected {:?}", c),
+        }
+    }
+
+    #[test]
+    fn errors_report_line_and_column() {
+        let e = parse("0,position,1,2\n1,position, 4,x\n").unwrap_err();
+        assert_eq!((2, 15), (e.line, e.column));
+        assert_eq!("test.dat:2:15: expected a number, found 'x'", e.to_string());
+
+        let e = parse("0,text,\"open\n").unwrap_err();
+        assert_eq!((1, 8), (e.line, e.column));
+
+        let e = parse("x").unwrap_err();
+        assert_eq!(1, e.line);
+
+        let e = parse("abc,visible,true").unwrap_err();
+        assert_eq!((1, 1), (e.line, e.column));
+    }
+
+    #[test]
+    fn unknown_components_and_arity_warn() {
+        let world = parse("0,sparkle,1\n0,color,1,1\n0,scale,2\n").unwrap();
+        assert_eq!(1, world.entries.len());
+        assert_eq!(2, world.warnings.len());
+        assert_eq!(1, world.warnings[0].line);
+        assert_eq!(2, world.warnings[1].line);
+    }
+}
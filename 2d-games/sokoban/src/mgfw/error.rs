@@ -0,0 +1,74 @@
+use super::{log_at, logging};
+use std::fmt;
+
+// Errors that stop a game from starting. Anything with a sensible fallback
+// (window icon, textures, post-processing, materials) logs a warning instead.
+
+#[derive(Debug)]
+pub enum Error {
+    // window or OpenGL context creation
+    Window(String),
+    // making the context current, presenting frames
+    Context(String),
+    // built-in shaders that failed to load, compile or link
+    Shader(String),
+    // asset files the game can't run without
+    Asset(String),
+}
+
+impl Error {
+    // process exit status, distinct per kind for launch scripts
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Window(_) => 2,
+            Error::Context(_) => 3,
+            Error::Shader(_) => 4,
+            Error::Asset(_) => 5,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Window(e) => write!(f, "could not create the window: {}", e),
+            Error::Context(e) => write!(f, "OpenGL context error: {}", e),
+            Error::Shader(e) => write!(f, "could not load shader {}", e),
+            Error::Asset(e) => write!(f, "could not load {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+// log the error, tell the player where the log is and quit without a panic
+pub fn exit_with(error: &Error) -> ! {
+    log_at(logging::LOG_ERROR, &error.to_string());
+    logging::flush();
+    eprintln!("error: {}", error);
+    if let Some(file) = logging::get_file() {
+        eprintln!("see {} for details", file.display());
+    }
+    std::process::exit(error.exit_code())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exit_codes_are_nonzero_and_distinct() {
+        let errors = [
+            Error::Window(String::new()),
+            Error::Context(String::new()),
+            Error::Shader(String::new()),
+            Error::Asset(String::new()),
+        ];
+        let mut codes: Vec<i32> = errors.iter().map(|e| e.exit_code()).collect();
+        assert!(codes.iter().all(|c| 0 != *c));
+        codes.sort();
+        codes.dedup();
+        assert_eq!(errors.len(), codes.len());
+        assert_eq!("could not load a.slc: gone", Error::Asset(String::from("a.slc: gone")).to_string());
+    }
+}
@@ -0,0 +1,453 @@
+extern crate xml;
+
+use crate::mgfw::assets;
+use crate::mgfw::log;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use xml::reader::{EventReader, XmlEvent};
+
+pub const DEFAULT_FALLBACK: char = '?';
+
+// more texture pages than this in one font is a broken file
+const MAX_PAGES: i64 = 64;
+
+// AngelCode BMFont descriptor loaded at runtime
+// glyph data layout matches the compiled-in tables:
+// [x, y, width, height, xoffset, yoffset, xadvance, page]
+pub struct Font {
+    pub scale_w: i32,
+    pub scale_h: i32,
+    pub line_height: i32,
+    pub base: i32,
+    pub page_files: Vec<String>,
+    pub data: HashMap<u32, [i16; 8]>,
+    pub kerning: HashMap<(u32, u32), i16>,
+    pub fallback: Option<u32>,
+}
+
+#[allow(dead_code)]
+impl Font {
+    fn empty() -> Font {
+        Font {
+            scale_w: 256,
+            scale_h: 256,
+            line_height: 0,
+            base: 0,
+            page_files: Vec::new(),
+            data: HashMap::new(),
+            kerning: HashMap::new(),
+            fallback: None,
+        }
+    }
+
+    // load a text or xml .fnt file, page files are resolved relative to the .fnt
+    pub fn load(filename: &str) -> Result<Font, String> {
+        log(format!("Font: Loading '{}'", filename));
+
+        let buffer = assets::read_to_string(filename)?;
+        let mut font = Font::parse(&buffer).map_err(|e| format!("{}: {}", filename, e))?;
+
+        let dir = Path::new(filename).parent().unwrap_or(Path::new(""));
+        for p in font.page_files.iter_mut() {
+            *p = dir.join(p.as_str()).to_string_lossy().to_string();
+        }
+
+        Ok(font)
+    }
+
+    // a text or xml .fnt descriptor, page files are left as written
+    fn parse(buffer: &str) -> Result<Font, String> {
+        let mut font = Font::empty();
+        if buffer.trim_start().starts_with('<') {
+            font.parse_xml(buffer)?;
+        } else {
+            font.parse_text(buffer)?;
+        }
+
+        if font.page_files.is_empty() {
+            return Err(String::from("no pages defined"));
+        }
+        if let Some(id) = font.page_files.iter().position(|p| p.is_empty()) {
+            return Err(format!("page {} not defined", id));
+        }
+
+        font.set_fallback_glyph(DEFAULT_FALLBACK);
+        Ok(font)
+    }
+
+    fn parse_text(&mut self, buffer: &str) -> Result<(), String> {
+        let reader = BufReader::new(buffer.as_bytes());
+        for (n, line) in reader.lines().enumerate() {
+            let line = line.map_err(|e| e.to_string())?;
+            let (tag, attrs) = split_text_line(&line);
+            if tag.is_empty() {
+                continue;
+            }
+            self.apply_tag(tag, &attrs)
+                .map_err(|e| format!("line {}: {}", n + 1, e))?;
+        }
+        Ok(())
+    }
+
+    fn parse_xml(&mut self, buffer: &str) -> Result<(), String> {
+        let parser = EventReader::new(buffer.as_bytes());
+        for e in parser {
+            match e {
+                Ok(XmlEvent::StartElement {
+                    name, attributes, ..
+                }) => {
+                    let mut attrs: HashMap<String, String> = HashMap::new();
+                    for a in attributes {
+                        attrs.insert(a.name.local_name, a.value);
+                    }
+                    self.apply_tag(name.local_name.as_str(), &attrs)?;
+                }
+                Err(e) => return Err(e.to_string()),
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+
+    fn apply_tag(&mut self, tag: &str, attrs: &HashMap<String, String>) -> Result<(), String> {
+        if ("page" == tag || "char" == tag) && self.page_files.is_empty() {
+            return Err(format!("'{}' before 'common'", tag));
+        }
+        match tag {
+            "common" => {
+                self.line_height = get_attr(attrs, "lineHeight")? as i32;
+                self.base = get_attr(attrs, "base")? as i32;
+                self.scale_w = get_attr(attrs, "scaleW")? as i32;
+                self.scale_h = get_attr(attrs, "scaleH")? as i32;
+                let pages = get_attr_in(attrs, "pages", 1, MAX_PAGES)?;
+                self.page_files = vec![String::new(); pages as usize];
+            }
+            "page" => {
+                let id = get_attr_in(attrs, "id", 0, self.page_files.len() as i64 - 1)?;
+                let file = match attrs.get("file") {
+                    Some(f) => f.clone(),
+                    None => return Err(String::from("page missing 'file'")),
+                };
+                self.page_files[id as usize] = file;
+            }
+            "char" => {
+                let id = get_attr(attrs, "id")?;
+                if 0 > id || std::char::from_u32(id as u32).is_none() {
+                    log(format!("WARNING: Font: Skipping invalid glyph id {}", id));
+                    return Ok(());
+                }
+                let mut glyph: [i16; 8] = [0; 8];
+                let keys = [
+                    "x", "y", "width", "height", "xoffset", "yoffset", "xadvance",
+                ];
+                for i in 0..keys.len() {
+                    glyph[i] = get_attr_in(attrs, keys[i], i16::MIN as i64, i16::MAX as i64)? as i16;
+                }
+                glyph[7] = get_attr_in(attrs, "page", 0, self.page_files.len() as i64 - 1)? as i16;
+                self.data.insert(id as u32, glyph);
+            }
+            "kerning" => {
+                let first = get_attr(attrs, "first")?;
+                let second = get_attr(attrs, "second")?;
+                let amount = get_attr_in(attrs, "amount", i16::MIN as i64, i16::MAX as i64)?;
+                if 0 <= first && 0 <= second {
+                    self.kerning
+                        .insert((first as u32, second as u32), amount as i16);
+                }
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+
+    pub fn get_kerning(&self, first: u32, second: u32) -> i16 {
+        match self.kerning.get(&(first, second)) {
+            Some(k) => *k,
+            None => 0,
+        }
+    }
+
+    // glyph drawn in place of characters missing from the font, returns false
+    // if the font doesn't contain the requested fallback either
+    pub fn set_fallback_glyph(&mut self, c: char) -> bool {
+        if self.data.contains_key(&(c as u32)) {
+            self.fallback = Some(c as u32);
+            return true;
+        }
+        log(format!(
+            "WARNING: Font: Fallback glyph {:?} not in font",
+            c
+        ));
+        false
+    }
+
+    pub fn clear_fallback_glyph(&mut self) {
+        self.fallback = None;
+    }
+
+    pub fn has_glyph(&self, c: char) -> bool {
+        self.data.contains_key(&(c as u32))
+    }
+
+    // resolves a character to (glyph id, glyph data), using the fallback glyph
+    // for missing characters. None means the character is skipped entirely.
+    pub fn get_glyph(&self, c: char) -> Option<(u32, &[i16; 8])> {
+        let id = c as u32;
+        if let Some(data) = self.data.get(&id) {
+            return Some((id, data));
+        }
+        match self.fallback {
+            Some(fb) => self.data.get(&fb).map(|data| (fb, data)),
+            None => None,
+        }
+    }
+
+    // unique characters in text that the font can't draw, in order of appearance
+    pub fn missing_glyphs(&self, text: &str) -> Vec<char> {
+        let mut ret: Vec<char> = Vec::new();
+        for c in text.chars() {
+            if !self.has_glyph(c) && !ret.contains(&c) {
+                ret.push(c);
+            }
+        }
+        ret
+    }
+
+    // advance width of text in pixels, including kerning
+    pub fn measure(&self, text: &str) -> f32 {
+        let mut basex: f32 = 0.0;
+        let mut prev: Option<u32> = None;
+
+        for c in text.chars() {
+            if let Some((id, data)) = self.get_glyph(c) {
+                if let Some(p) = prev {
+                    basex += self.get_kerning(p, id) as f32;
+                }
+                basex += data[6] as f32;
+                prev = Some(id);
+            }
+        }
+        basex
+    }
+}
+
+impl From<super::retro_gaming::Font> for Font {
+    fn from(f: super::retro_gaming::Font) -> Font {
+        let mut font = Font {
+            scale_w: f.scale_w,
+            scale_h: f.scale_h,
+            line_height: 16,
+            base: 13,
+            page_files: f.page_files,
+            data: f.data.into_iter().map(|(k, v)| (k as u32, v)).collect(),
+            kerning: HashMap::new(),
+            fallback: None,
+        };
+        font.set_fallback_glyph(DEFAULT_FALLBACK);
+        font
+    }
+}
+
+fn get_attr(attrs: &HashMap<String, String>, key: &str) -> Result<i64, String> {
+    match attrs.get(key) {
+        Some(v) => v
+            .parse::<i64>()
+            .map_err(|_| format!("invalid value '{}' for '{}'", v, key)),
+        None => Err(format!("missing '{}'", key)),
+    }
+}
+
+// like get_attr, but out of range values are an error instead of wrapping
+fn get_attr_in(attrs: &HashMap<String, String>, key: &str, min: i64, max: i64) -> Result<i64, String> {
+    let v = get_attr(attrs, key)?;
+    if v < min || max < v {
+        return Err(format!("'{}' {} out of range {}..={}", key, v, min, max));
+    }
+    Ok(v)
+}
+
+// split a line like: char id=32 x=11 face="Retro Gaming"
+fn split_text_line(line: &str) -> (&str, HashMap<String, String>) {
+    let mut attrs: HashMap<String, String> = HashMap::new();
+    let line = line.trim();
+    let (tag, rest) = match line.find(char::is_whitespace) {
+        Some(n) => (&line[..n], &line[n..]),
+        None => (line, ""),
+    };
+
+    let mut chars = rest.chars().peekable();
+    loop {
+        while let Some(c) = chars.peek() {
+            if !c.is_whitespace() {
+                break;
+            }
+            chars.next();
+        }
+
+        let mut key = String::new();
+        for c in chars.by_ref() {
+            if '=' == c {
+                break;
+            }
+            key.push(c);
+        }
+        if key.is_empty() {
+            break;
+        }
+
+        let mut val = String::new();
+        if Some(&'"') == chars.peek() {
+            chars.next();
+            for c in chars.by_ref() {
+                if '"' == c {
+                    break;
+                }
+                val.push(c);
+            }
+        } else {
+            for c in chars.by_ref() {
+                if c.is_whitespace() {
+                    break;
+                }
+                val.push(c);
+            }
+        }
+        attrs.insert(key, val);
+    }
+
+    (tag, attrs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn retro_gaming() -> Font {
+        super::super::retro_gaming::Font::new().into()
+    }
+
+    #[test]
+    fn accented_chars_use_their_own_glyph() {
+        let font = retro_gaming();
+        // 'é' is two bytes in utf-8 but a single glyph (233) in the font
+        let (id, _) = font.get_glyph('é').unwrap();
+        assert_eq!(233, id);
+        assert!(font.missing_glyphs("Café Ünïcødé").is_empty());
+        assert_eq!(font.measure("e"), font.measure("é"));
+    }
+
+    #[test]
+    fn missing_chars_use_fallback() {
+        let font = retro_gaming();
+        let (id, _) = font.get_glyph('漢').unwrap();
+        assert_eq!('?' as u32, id);
+        assert_eq!(font.measure("a?b"), font.measure("a漢b"));
+        assert_eq!(font.measure("??"), font.measure("🎮😀"));
+    }
+
+    #[test]
+    fn configurable_fallback() {
+        let mut font = retro_gaming();
+        assert!(font.set_fallback_glyph('#'));
+        assert_eq!('#' as u32, font.get_glyph('Ж').unwrap().0);
+
+        // can't fall back to a glyph the font doesn't have
+        assert!(!font.set_fallback_glyph('Ж'));
+        assert_eq!('#' as u32, font.fallback.unwrap());
+
+        font.clear_fallback_glyph();
+        assert!(font.get_glyph('Ж').is_none());
+        assert_eq!(font.measure("ab"), font.measure("aЖb"));
+    }
+
+    #[test]
+    fn coverage_report() {
+        let font = retro_gaming();
+        assert_eq!(vec!['Ж', '→', '😀'], font.missing_glyphs("Ж→ab😀Ж→"));
+        assert!(font.missing_glyphs("").is_empty());
+    }
+
+    #[test]
+    fn kerning_applies_between_resolved_glyphs() {
+        let mut font = retro_gaming();
+        font.kerning.insert(('A' as u32, '?' as u32), -2);
+        assert_eq!(font.measure("A?"), font.measure("A漢"));
+        assert_eq!(font.measure("A") + font.measure("?") - 2.0, font.measure("A?"));
+    }
+
+    const TEXT_FNT: &str = r#"info face="Tiny" size=8 bold=0 italic=0
+common lineHeight=10 base=8 scaleW=64 scaleH=32 pages=2 packed=0
+page id=0 file="tiny_0.png"
+page id=1 file="tiny_1.png"
+chars count=3
+char id=63   x=0    y=0    width=5    height=8    xoffset=0    yoffset=1    xadvance=6    page=0  chnl=15
+char id=65   x=6    y=0    width=5    height=8    xoffset=0    yoffset=1    xadvance=6    page=0  chnl=15
+char id=86   x=0    y=0    width=5    height=8    xoffset=-1   yoffset=1    xadvance=5    page=1  chnl=15
+kernings count=1
+kerning first=65  second=86  amount=-1
+"#;
+
+    const XML_FNT: &str = r#"<?xml version="1.0"?>
+<font>
+  <info face="Tiny" size="8"/>
+  <common lineHeight="10" base="8" scaleW="64" scaleH="32" pages="1" packed="0"/>
+  <pages>
+    <page id="0" file="tiny_0.png"/>
+  </pages>
+  <chars count="2">
+    <char id="65" x="6" y="0" width="5" height="8" xoffset="0" yoffset="1" xadvance="6" page="0" chnl="15"/>
+    <char id="86" x="12" y="0" width="5" height="8" xoffset="-1" yoffset="1" xadvance="5" page="0" chnl="15"/>
+  </chars>
+  <kernings count="1">
+    <kerning first="65" second="86" amount="-2"/>
+  </kernings>
+</font>
+"#;
+
+    #[test]
+    fn parse_text_descriptor() {
+        let font = Font::parse(TEXT_FNT).unwrap();
+        assert_eq!((10, 8, 64, 32), (font.line_height, font.base, font.scale_w, font.scale_h));
+        assert_eq!(vec!["tiny_0.png", "tiny_1.png"], font.page_files);
+        assert_eq!([0, 0, 5, 8, -1, 1, 5, 1], font.data[&86]);
+        assert_eq!(-1, font.get_kerning(65, 86));
+        assert_eq!(0, font.get_kerning(86, 65));
+        assert_eq!(Some('?' as u32), font.fallback);
+        assert_eq!(6.0 + 5.0 - 1.0, font.measure("AV"));
+    }
+
+    #[test]
+    fn parse_xml_descriptor() {
+        let font = Font::parse(XML_FNT).unwrap();
+        assert_eq!((10, 8, 64, 32), (font.line_height, font.base, font.scale_w, font.scale_h));
+        assert_eq!(vec!["tiny_0.png"], font.page_files);
+        assert_eq!([12, 0, 5, 8, -1, 1, 5, 0], font.data[&86]);
+        assert_eq!(-2, font.get_kerning(65, 86));
+        // no '?' in this font, missing glyphs are skipped
+        assert_eq!(None, font.fallback);
+        assert_eq!(6.0 + 5.0 - 2.0, font.measure("AVW"));
+    }
+
+    #[test]
+    fn malformed_descriptors_are_rejected() {
+        let broken = [
+            TEXT_FNT.replace("page id=1", "page id=-1"),
+            TEXT_FNT.replace("page id=1", "page id=99999999999"),
+            TEXT_FNT.replace("page id=1", "page id=2"),
+            TEXT_FNT.replace("pages=2", "pages=100000"),
+            TEXT_FNT.replace(r#"page id=1 file="tiny_1.png""#, "page id=1"),
+            TEXT_FNT.replace(r#"page id=1 file="tiny_1.png""#, ""),
+            TEXT_FNT.replace("page=1", "page=2"),
+            TEXT_FNT.replace("xadvance=5", "xadvance=40000"),
+            TEXT_FNT.replace("amount=-1", "amount=-40000"),
+            TEXT_FNT.replace("common lineHeight", "nommoc lineHeight"),
+            XML_FNT.replace(r#" file="tiny_0.png""#, ""),
+            XML_FNT.replace(r#"page id="0""#, r#"page id="-3""#),
+            XML_FNT.replace("</font>", ""),
+        ];
+        for b in broken.iter() {
+            assert!(Font::parse(b).is_err(), "accepted:\n{}", b);
+        }
+    }
+}
@@ -0,0 +1,329 @@
+use super::bmfont::Font;
+
+pub const TEXT_ALIGN_LEFT: u8 = 0;
+pub const TEXT_ALIGN_CENTER: u8 = 1;
+pub const TEXT_ALIGN_RIGHT: u8 = 2;
+
+const WHITE: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+
+// alignment anchors each line on the entity position, wrap_width of 0.0
+// disables word wrap and line_height of 0.0 uses the font line height
+#[derive(Debug, Copy, Clone)]
+pub struct TextLayout {
+    pub align: u8,
+    pub wrap_width: f32,
+    pub line_height: f32,
+}
+
+impl Default for TextLayout {
+    fn default() -> TextLayout {
+        TextLayout {
+            align: TEXT_ALIGN_LEFT,
+            wrap_width: 0.0,
+            line_height: 0.0,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct LayoutGlyph {
+    pub x: f32,
+    pub y: f32,
+    pub glyph: [i16; 8],
+    pub color: [f32; 4],
+}
+
+pub struct LayoutResult {
+    pub glyphs: Vec<LayoutGlyph>,
+    pub width: f32,
+    pub height: f32,
+}
+
+type Run = Vec<(char, [f32; 4])>;
+
+// Inline color markup:
+//   {#rrggbb} or {#rrggbbaa} - switch vertex color
+//   {/}                      - back to white (entity color still applies)
+//   {{                       - literal '{'
+// Anything else inside braces is drawn as-is.
+fn parse_markup(text: &str) -> Vec<Run> {
+    let mut paragraphs: Vec<Run> = vec![Vec::new()];
+    let mut color = WHITE;
+    let chars: Vec<char> = text.chars().collect();
+
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if '{' == c {
+            if i + 1 < chars.len() && '{' == chars[i + 1] {
+                paragraphs.last_mut().unwrap().push(('{', color));
+                i += 2;
+                continue;
+            }
+            if let Some(len) = chars[i..].iter().position(|&c| '}' == c) {
+                let tag: String = chars[i + 1..i + len].iter().collect();
+                if "/" == tag {
+                    color = WHITE;
+                    i += len + 1;
+                    continue;
+                }
+                if let Some(clr) = parse_hex_color(&tag) {
+                    color = clr;
+                    i += len + 1;
+                    continue;
+                }
+            }
+        }
+
+        if '\n' == c {
+            paragraphs.push(Vec::new());
+        } else if '\r' != c {
+            paragraphs.last_mut().unwrap().push((c, color));
+        }
+        i += 1;
+    }
+    paragraphs
+}
+
+fn parse_hex_color(tag: &str) -> Option<[f32; 4]> {
+    let hex = tag.strip_prefix('#')?;
+    if !(6 == hex.len() || 8 == hex.len()) || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let mut clr = WHITE;
+    for i in 0..hex.len() / 2 {
+        let v = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok()?;
+        clr[i] = v as f32 / 255.0;
+    }
+    Some(clr)
+}
+
+// text with color markup removed, newlines preserved
+pub fn strip_markup(text: &str) -> String {
+    let paragraphs = parse_markup(text);
+    let lines: Vec<String> = paragraphs
+        .iter()
+        .map(|p| p.iter().map(|g| g.0).collect())
+        .collect();
+    lines.join("\n")
+}
+
+fn measure_run(font: &Font, run: &[(char, [f32; 4])]) -> f32 {
+    let text: String = run.iter().map(|g| g.0).collect();
+    font.measure(&text)
+}
+
+// greedy word wrap, words longer than the wrap width are broken per character
+fn wrap_paragraph(font: &Font, para: Run, wrap_width: f32) -> Vec<Run> {
+    if 0.0 >= wrap_width {
+        return vec![para];
+    }
+
+    let mut lines: Vec<Run> = Vec::new();
+    let mut line: Run = Vec::new();
+
+    for word in para.split(|g| ' ' == g.0) {
+        if word.is_empty() {
+            continue;
+        }
+
+        let mut candidate = line.clone();
+        if !candidate.is_empty() {
+            candidate.push((' ', word[0].1));
+        }
+        candidate.extend_from_slice(word);
+
+        if measure_run(font, &candidate) <= wrap_width {
+            line = candidate;
+            continue;
+        }
+
+        if !line.is_empty() {
+            lines.push(line);
+            line = Vec::new();
+        }
+
+        for g in word {
+            line.push(*g);
+            if 1 < line.len() && measure_run(font, &line) > wrap_width {
+                let last = line.pop().unwrap();
+                lines.push(line);
+                line = vec![last];
+            }
+        }
+    }
+    lines.push(line);
+    lines
+}
+
+pub fn layout(font: &Font, text: &str, opts: &TextLayout) -> LayoutResult {
+    let line_height = match 0.0 < opts.line_height {
+        true => opts.line_height,
+        false => font.line_height as f32,
+    };
+
+    let mut lines: Vec<Run> = Vec::new();
+    for para in parse_markup(text) {
+        lines.extend(wrap_paragraph(font, para, opts.wrap_width));
+    }
+
+    let mut glyphs: Vec<LayoutGlyph> = Vec::new();
+    let mut width: f32 = 0.0;
+
+    for (n, line) in lines.iter().enumerate() {
+        let line_width = measure_run(font, line);
+        if line_width > width {
+            width = line_width;
+        }
+
+        let mut basex = match opts.align {
+            TEXT_ALIGN_CENTER => (-line_width * 0.5).round(),
+            TEXT_ALIGN_RIGHT => -line_width,
+            _ => 0.0,
+        };
+        let basey = n as f32 * line_height;
+        let mut prev: Option<u32> = None;
+
+        for (c, color) in line {
+            let (id, data) = match font.get_glyph(*c) {
+                Some(g) => g,
+                None => continue,
+            };
+            if let Some(p) = prev {
+                basex += font.get_kerning(p, id) as f32;
+            }
+            prev = Some(id);
+
+            glyphs.push(LayoutGlyph {
+                x: basex,
+                y: basey,
+                glyph: *data,
+                color: *color,
+            });
+            basex += data[6] as f32;
+        }
+    }
+
+    LayoutResult {
+        glyphs,
+        width,
+        height: lines.len() as f32 * line_height,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: [f32; 4] = [1.0, 0.0, 0.0, 1.0];
+
+    fn retro_gaming() -> Font {
+        super::super::retro_gaming::Font::new().into()
+    }
+
+    fn run_text(run: &Run) -> String {
+        run.iter().map(|g| g.0).collect()
+    }
+
+    fn wrap(font: &Font, text: &str, wrap_width: f32) -> Vec<String> {
+        let para = parse_markup(text).remove(0);
+        wrap_paragraph(font, para, wrap_width)
+            .iter()
+            .map(run_text)
+            .collect()
+    }
+
+    #[test]
+    fn greedy_word_wrap() {
+        let font = retro_gaming();
+        let width = font.measure("aaa bbb");
+        assert_eq!(vec!["aaa bbb", "ccc"], wrap(&font, "aaa bbb ccc", width));
+        assert_eq!(vec!["aaa", "bbb", "ccc"], wrap(&font, "aaa  bbb ccc", width - 1.0));
+        assert_eq!(vec!["aaa bbb ccc"], wrap(&font, "aaa bbb ccc", 0.0));
+    }
+
+    #[test]
+    fn long_words_break_per_character() {
+        let font = retro_gaming();
+        let width = font.measure("aaa");
+        assert_eq!(vec!["x", "aaa", "aaa", "a"], wrap(&font, "x aaaaaaa", width));
+
+        // a single glyph wider than the wrap width still gets its own line
+        assert_eq!(vec!["a", "a"], wrap(&font, "aa", 1.0));
+    }
+
+    #[test]
+    fn newlines_split_paragraphs_and_cr_is_dropped() {
+        let paragraphs = parse_markup("one\r\ntwo\n\nthree");
+        let lines: Vec<String> = paragraphs.iter().map(run_text).collect();
+        assert_eq!(vec!["one", "two", "", "three"], lines);
+        assert_eq!("one\ntwo\n\nthree", strip_markup("one\r\ntwo\n\nthree"));
+    }
+
+    #[test]
+    fn color_markup() {
+        let p = parse_markup("a{#ff0000}b{#00ff0080}c{/}d").remove(0);
+        assert_eq!("abcd", run_text(&p));
+        assert_eq!(WHITE, p[0].1);
+        assert_eq!(RED, p[1].1);
+        assert_eq!([0.0, 1.0, 0.0, 128.0 / 255.0], p[2].1);
+        assert_eq!(WHITE, p[3].1);
+    }
+
+    #[test]
+    fn brace_escape_keeps_color() {
+        let p = parse_markup("{#ff0000}{{x}").remove(0);
+        assert_eq!("{x}", run_text(&p));
+        assert!(p.iter().all(|g| RED == g.1));
+    }
+
+    #[test]
+    fn unknown_tags_are_drawn_as_is() {
+        assert_eq!("{b}old{#12}{#gg0000}{", strip_markup("{b}old{#12}{#gg0000}{"));
+        assert!(parse_markup("{b}").remove(0).iter().all(|g| WHITE == g.1));
+    }
+
+    #[test]
+    fn hex_colors() {
+        assert_eq!(Some(RED), parse_hex_color("#ff0000"));
+        assert_eq!(Some([0.0, 0.0, 0.0, 0.0]), parse_hex_color("#00000000"));
+        assert_eq!(None, parse_hex_color("ff0000"));
+        assert_eq!(None, parse_hex_color("#fff"));
+        assert_eq!(None, parse_hex_color("#ff00001"));
+        assert_eq!(None, parse_hex_color("#zz0000"));
+    }
+
+    #[test]
+    fn alignment_offsets() {
+        let font = retro_gaming();
+        let width = font.measure("abc");
+        let first_x = |align: u8| {
+            let opts = TextLayout {
+                align,
+                ..Default::default()
+            };
+            layout(&font, "abc", &opts).glyphs[0].x
+        };
+        assert_eq!(0.0, first_x(TEXT_ALIGN_LEFT));
+        assert_eq!((-width * 0.5).round(), first_x(TEXT_ALIGN_CENTER));
+        assert_eq!(-width, first_x(TEXT_ALIGN_RIGHT));
+    }
+
+    #[test]
+    fn line_height_defaults_to_font() {
+        let font = retro_gaming();
+        let res = layout(&font, "a\nb", &TextLayout::default());
+        assert_eq!(2, res.glyphs.len());
+        assert_eq!(font.line_height as f32, res.glyphs[1].y);
+        assert_eq!(2.0 * font.line_height as f32, res.height);
+        assert_eq!(font.measure("a"), res.width);
+
+        let opts = TextLayout {
+            line_height: 20.0,
+            ..Default::default()
+        };
+        let res = layout(&font, "a\nb", &opts);
+        assert_eq!(20.0, res.glyphs[1].y);
+        assert_eq!(40.0, res.height);
+    }
+}
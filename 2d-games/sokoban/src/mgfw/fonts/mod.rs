@@ -1,2 +1,4 @@
+pub mod bmfont;
+pub mod layout;
 pub mod retro_gaming;
 //pub mod alagard;
@@ -0,0 +1,212 @@
+use super::*;
+use std::collections::VecDeque;
+
+pub const PAD_UP: u8 = 0;
+pub const PAD_DOWN: u8 = 1;
+pub const PAD_LEFT: u8 = 2;
+pub const PAD_RIGHT: u8 = 3;
+pub const PAD_ACCEPT: u8 = 4;
+pub const PAD_BACK: u8 = 5;
+pub const PAD_START: u8 = 6;
+pub const PAD_PREV: u8 = 7;
+pub const PAD_NEXT: u8 = 8;
+
+// stick deflection that counts as a press, and the one it has to drop back
+// under before it counts as released again
+const STICK_PRESS: f32 = 0.5;
+const STICK_RELEASE: f32 = 0.3;
+
+// Controller input is turned into the keyboard events it stands in for, so the
+// game and Ui::event() handle both the same way:
+//   d-pad / left stick -> arrow keys
+//   south (A)          -> space
+//   east (B)           -> backspace
+//   start              -> escape
+//   bumpers            -> page up / page down
+// Controllers are only read with the gamepad feature, without it poll() does nothing.
+pub struct Gamepad {
+    // left stick direction currently held, per axis: -1, 0 or 1
+    stick: [i8; 2],
+    #[cfg(feature = "gamepad")]
+    gilrs: Option<gilrs::Gilrs>,
+}
+
+#[allow(dead_code)]
+impl Gamepad {
+    pub fn new() -> Gamepad {
+        log(format!("Constructing Gamepad"));
+        Gamepad {
+            stick: [0; 2],
+            #[cfg(feature = "gamepad")]
+            gilrs: match gilrs::Gilrs::new() {
+                Ok(g) => Some(g),
+                Err(e) => {
+                    log(format!("WARNING: Gamepad: {}", e));
+                    None
+                }
+            },
+        }
+    }
+
+    // queue the events of everything that happened since the last poll
+    #[cfg(feature = "gamepad")]
+    pub fn poll(&mut self, events: &mut VecDeque<u8>) {
+        use gilrs::{Axis, Button, EventType};
+
+        let mut pending = Vec::new();
+        if let Some(gilrs) = &mut self.gilrs {
+            while let Some(ev) = gilrs.next_event() {
+                pending.push(ev.event);
+            }
+        }
+
+        for ev in pending {
+            let (button, pressed) = match ev {
+                EventType::ButtonPressed(b, _) => (b, true),
+                EventType::ButtonReleased(b, _) => (b, false),
+                EventType::AxisChanged(Axis::LeftStickX, v, _) => {
+                    self.axis(0, v, events);
+                    continue;
+                }
+                // stick y points up, screen y down
+                EventType::AxisChanged(Axis::LeftStickY, v, _) => {
+                    self.axis(1, -v, events);
+                    continue;
+                }
+                EventType::Connected => {
+                    log(format!("Gamepad: Connected"));
+                    continue;
+                }
+                _ => continue,
+            };
+            let input = match button {
+                Button::DPadUp => PAD_UP,
+                Button::DPadDown => PAD_DOWN,
+                Button::DPadLeft => PAD_LEFT,
+                Button::DPadRight => PAD_RIGHT,
+                Button::South => PAD_ACCEPT,
+                Button::East => PAD_BACK,
+                Button::Start => PAD_START,
+                Button::LeftTrigger => PAD_PREV,
+                Button::RightTrigger => PAD_NEXT,
+                _ => continue,
+            };
+            self.button(input, pressed, events);
+        }
+    }
+
+    #[cfg(not(feature = "gamepad"))]
+    pub fn poll(&mut self, _events: &mut VecDeque<u8>) {}
+
+    // PAD_* input pressed or released
+    pub fn button(&mut self, input: u8, pressed: bool, events: &mut VecDeque<u8>) {
+        let (down, up) = match input {
+            PAD_UP => (EVENT_INPUT_KEYBOARD_PRESSED_UP, EVENT_INPUT_KEYBOARD_RELEASED_UP),
+            PAD_DOWN => (EVENT_INPUT_KEYBOARD_PRESSED_DOWN, EVENT_INPUT_KEYBOARD_RELEASED_DOWN),
+            PAD_LEFT => (EVENT_INPUT_KEYBOARD_PRESSED_LEFT, EVENT_INPUT_KEYBOARD_RELEASED_LEFT),
+            PAD_RIGHT => (EVENT_INPUT_KEYBOARD_PRESSED_RIGHT, EVENT_INPUT_KEYBOARD_RELEASED_RIGHT),
+            PAD_ACCEPT => (EVENT_INPUT_KEYBOARD_PRESSED_SPACE, EVENT_INPUT_KEYBOARD_RELEASED_SPACE),
+            PAD_BACK => (EVENT_INVALID, EVENT_INPUT_KEYBOARD_RELEASED_BKSPC),
+            PAD_START => (EVENT_INPUT_KEYBOARD_PRESSED_ESCAPE, EVENT_INPUT_KEYBOARD_RELEASED_ESCAPE),
+            PAD_PREV => (EVENT_INVALID, EVENT_INPUT_KEYBOARD_RELEASED_PGUP),
+            PAD_NEXT => (EVENT_INVALID, EVENT_INPUT_KEYBOARD_RELEASED_PGDN),
+            _ => return,
+        };
+        let event = if pressed { down } else { up };
+        if EVENT_INVALID != event {
+            events.push_back(event);
+        }
+    }
+
+    // left stick axis 0 (x) or 1 (y, down positive) moved to value
+    pub fn axis(&mut self, axis: usize, value: f32, events: &mut VecDeque<u8>) {
+        let held = self.stick[axis];
+        let dir = if STICK_PRESS <= value {
+            1
+        } else if -STICK_PRESS >= value {
+            -1
+        } else if STICK_RELEASE > value.abs() {
+            0
+        } else {
+            held
+        };
+        if dir == held {
+            return;
+        }
+
+        let input = |d: i8| match (axis, d) {
+            (0, -1) => PAD_LEFT,
+            (0, _) => PAD_RIGHT,
+            (_, -1) => PAD_UP,
+            (_, _) => PAD_DOWN,
+        };
+        if 0 != held {
+            self.button(input(held), false, events);
+        }
+        if 0 != dir {
+            self.button(input(dir), true, events);
+        }
+        self.stick[axis] = dir;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gamepad() -> Gamepad {
+        Gamepad {
+            stick: [0; 2],
+            #[cfg(feature = "gamepad")]
+            gilrs: None,
+        }
+    }
+
+    #[test]
+    fn buttons_map_to_keyboard_events() {
+        let mut pad = gamepad();
+        let mut events = VecDeque::new();
+        pad.button(PAD_ACCEPT, true, &mut events);
+        pad.button(PAD_ACCEPT, false, &mut events);
+        pad.button(PAD_BACK, true, &mut events);
+        pad.button(PAD_BACK, false, &mut events);
+        pad.button(PAD_START, false, &mut events);
+        pad.button(99, false, &mut events);
+        assert_eq!(
+            vec![
+                EVENT_INPUT_KEYBOARD_PRESSED_SPACE,
+                EVENT_INPUT_KEYBOARD_RELEASED_SPACE,
+                EVENT_INPUT_KEYBOARD_RELEASED_BKSPC,
+                EVENT_INPUT_KEYBOARD_RELEASED_ESCAPE,
+            ],
+            Vec::from(events)
+        );
+    }
+
+    #[test]
+    fn stick_presses_once_per_deflection() {
+        let mut pad = gamepad();
+        let mut events = VecDeque::new();
+        for v in [0.2, 0.6, 0.9, 0.4, 0.6, 0.1] {
+            pad.axis(1, v, &mut events);
+        }
+        assert_eq!(
+            vec![EVENT_INPUT_KEYBOARD_PRESSED_DOWN, EVENT_INPUT_KEYBOARD_RELEASED_DOWN],
+            Vec::from(events.split_off(0))
+        );
+
+        // flicking straight across releases one side before pressing the other
+        pad.axis(0, -0.8, &mut events);
+        pad.axis(0, 0.8, &mut events);
+        pad.axis(0, 0.0, &mut events);
+        assert_eq!(
+            vec![
+                EVENT_INPUT_KEYBOARD_PRESSED_LEFT,
+                EVENT_INPUT_KEYBOARD_RELEASED_LEFT,
+                EVENT_INPUT_KEYBOARD_PRESSED_RIGHT,
+                EVENT_INPUT_KEYBOARD_RELEASED_RIGHT,
+            ],
+            Vec::from(events)
+        );
+    }
+}
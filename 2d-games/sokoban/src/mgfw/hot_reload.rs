@@ -0,0 +1,81 @@
+use super::assets;
+use super::ecs::World;
+use super::log;
+use super::support::Gl;
+use std::collections::HashMap;
+use std::time::SystemTime;
+
+const POLL_INTERVAL_MS: u128 = 500;
+
+pub const RELOAD_TEXTURE: u8 = 0;
+pub const RELOAD_SHADER: u8 = 1;
+pub const RELOAD_WORLD: u8 = 2;
+
+// Polls modification times of every texture, shader and world file loaded so
+// far and reloads the ones that changed. Only runs in debug builds.
+pub struct HotReload {
+    files: HashMap<String, (u8, Option<SystemTime>)>,
+    last_poll: std::time::Instant,
+}
+
+#[allow(dead_code)]
+impl HotReload {
+    pub fn new() -> HotReload {
+        log(format!("Constructing HotReload"));
+        HotReload {
+            files: HashMap::new(),
+            last_poll: std::time::Instant::now(),
+        }
+    }
+
+    pub fn update(&mut self, gl: &mut Gl, world: &mut World) {
+        if !cfg!(debug_assertions) {
+            return;
+        }
+
+        if POLL_INTERVAL_MS > self.last_poll.elapsed().as_millis() {
+            return;
+        }
+        self.last_poll = std::time::Instant::now();
+
+        // pick up anything loaded since the last poll
+        for f in gl.get_texture_files() {
+            self.watch(f, RELOAD_TEXTURE);
+        }
+        for f in gl.get_shader_files() {
+            self.watch(f, RELOAD_SHADER);
+        }
+        for f in world.get_world_files() {
+            self.watch(f, RELOAD_WORLD);
+        }
+
+        let mut changed: Vec<(String, u8)> = Vec::new();
+        for (file, (kind, mtime)) in self.files.iter_mut() {
+            let now = modified(file);
+            if now.is_some() && now != *mtime {
+                *mtime = now;
+                changed.push((file.clone(), *kind));
+            }
+        }
+
+        for (file, kind) in changed {
+            log(format!("HotReload: '{}' changed", file));
+            let ok = match kind {
+                RELOAD_TEXTURE => gl.reload_texture(&file),
+                RELOAD_SHADER => gl.reload_shader(&file),
+                _ => world.reload_world(&file),
+            };
+            if !ok {
+                log(format!("WARNING: HotReload: '{}' not fully reloaded", file));
+            }
+        }
+    }
+
+    fn watch(&mut self, file: String, kind: u8) {
+        self.files.entry(file).or_insert_with_key(|f| (kind, modified(f)));
+    }
+}
+
+fn modified(file: &str) -> Option<SystemTime> {
+    assets::modified(file)
+}
@@ -0,0 +1,209 @@
+use super::ecs::World;
+use super::persist::{Persist, PersistError, Storage};
+use super::ui::Ui;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// Local high score / best time tables, one per game mode, persisted as
+// "leaderboard" in the game's Storage.
+//
+//   board.define("level1", LEADERBOARD_LOWER_IS_BETTER, LEADERBOARD_FORMAT_INTEGER, 5);
+//   if board.qualifies("level1", moves).is_some() { ...ask for a name... }
+//   let rank = board.submit("level1", "ABC", moves);
+//   board.save(&storage);
+
+#[allow(dead_code)]
+pub const LEADERBOARD_HIGHER_IS_BETTER: u8 = 0;
+pub const LEADERBOARD_LOWER_IS_BETTER: u8 = 1;
+
+pub const LEADERBOARD_FORMAT_INTEGER: u8 = 0;
+// score is in seconds, shown as m:ss.cc
+pub const LEADERBOARD_FORMAT_TIME: u8 = 1;
+
+const LEADERBOARD_FILE: &str = "leaderboard";
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct LeaderboardEntry {
+    pub name: String,
+    pub score: f64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct LeaderboardTable {
+    pub sort: u8,
+    pub format: u8,
+    pub capacity: usize,
+    // best first
+    pub entries: Vec<LeaderboardEntry>,
+}
+
+impl LeaderboardTable {
+    fn is_better(&self, a: f64, b: f64) -> bool {
+        match self.sort {
+            LEADERBOARD_LOWER_IS_BETTER => a < b,
+            _ => a > b,
+        }
+    }
+
+    // ties rank below existing entries
+    fn rank(&self, score: f64) -> Option<usize> {
+        let rank = self
+            .entries
+            .iter()
+            .position(|e| self.is_better(score, e.score))
+            .unwrap_or(self.entries.len());
+        match rank < self.capacity {
+            true => Some(rank),
+            false => None,
+        }
+    }
+
+    fn sort(&mut self) {
+        let lower = LEADERBOARD_LOWER_IS_BETTER == self.sort;
+        self.entries.sort_by(|a, b| {
+            let ord = a.score.partial_cmp(&b.score).unwrap_or(std::cmp::Ordering::Equal);
+            match lower {
+                true => ord,
+                false => ord.reverse(),
+            }
+        });
+        self.entries.truncate(self.capacity);
+    }
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct Leaderboard {
+    tables: BTreeMap<String, LeaderboardTable>,
+}
+
+impl Persist for Leaderboard {
+    const VERSION: u32 = 1;
+}
+
+#[allow(dead_code)]
+impl Leaderboard {
+    pub fn load(storage: &Storage) -> Leaderboard {
+        storage.load_or_default(LEADERBOARD_FILE)
+    }
+
+    pub fn save(&self, storage: &Storage) -> Result<(), PersistError> {
+        storage.save(LEADERBOARD_FILE, self)
+    }
+
+    // creates the table, or updates the rules of an existing one and keeps
+    // whatever entries still fit
+    pub fn define(&mut self, mode: &str, sort: u8, format: u8, capacity: usize) {
+        let table = self
+            .tables
+            .entry(String::from(mode))
+            .or_insert_with(|| LeaderboardTable {
+                sort,
+                format,
+                capacity,
+                entries: Vec::new(),
+            });
+        table.sort = sort;
+        table.format = format;
+        table.capacity = capacity;
+        table.sort();
+    }
+
+    pub fn get_table(&self, mode: &str) -> Option<&LeaderboardTable> {
+        self.tables.get(mode)
+    }
+
+    pub fn clear(&mut self, mode: &str) {
+        if let Some(table) = self.tables.get_mut(mode) {
+            table.entries.clear();
+        }
+    }
+
+    // rank the score would get, None if it doesn't make the table
+    pub fn qualifies(&self, mode: &str, score: f64) -> Option<usize> {
+        self.tables.get(mode).and_then(|t| t.rank(score))
+    }
+
+    // returns the rank of the new entry, None if it didn't make the table
+    pub fn submit(&mut self, mode: &str, name: &str, score: f64) -> Option<usize> {
+        let table = self.tables.get_mut(mode)?;
+        let rank = table.rank(score)?;
+        table.entries.insert(
+            rank,
+            LeaderboardEntry {
+                name: String::from(name),
+                score,
+            },
+        );
+        table.entries.truncate(table.capacity);
+        Some(rank)
+    }
+
+    pub fn format_score(&self, mode: &str, score: f64) -> String {
+        let format = self.tables.get(mode).map(|t| t.format).unwrap_or(LEADERBOARD_FORMAT_INTEGER);
+        match format {
+            LEADERBOARD_FORMAT_TIME => {
+                let cs = (score.max(0.0) * 100.0).round() as u64;
+                format!("{}:{:02}.{:02}", cs / 6000, (cs / 100) % 60, cs % 100)
+            }
+            _ => format!("{}", score.round() as i64),
+        }
+    }
+
+    // one ui row per entry plus empty rows up to capacity, inside a ui list.
+    // highlight marks a freshly submitted rank.
+    pub fn draw(&self, ui: &mut Ui, world: &mut World, mode: &str, highlight: Option<usize>) {
+        let table = match self.tables.get(mode) {
+            Some(t) => t,
+            None => return,
+        };
+        for i in 0..table.capacity {
+            let rank = format!("{}.", i + 1);
+            let (name, score) = match table.entries.get(i) {
+                Some(e) => (e.name.clone(), self.format_score(mode, e.score)),
+                None => (String::from("---"), String::new()),
+            };
+            ui.table_row(
+                world,
+                &[(&rank, 0.2), (&name, 0.4), (&score, 0.4)],
+                Some(i) == highlight,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sorting_rules_and_capacity() {
+        let mut board = Leaderboard::default();
+        board.define("score", LEADERBOARD_HIGHER_IS_BETTER, LEADERBOARD_FORMAT_INTEGER, 3);
+        board.define("time", LEADERBOARD_LOWER_IS_BETTER, LEADERBOARD_FORMAT_TIME, 3);
+
+        assert_eq!(Some(0), board.submit("score", "AAA", 10.0));
+        assert_eq!(Some(0), board.submit("score", "BBB", 30.0));
+        assert_eq!(Some(2), board.submit("score", "CCC", 10.0));
+        assert_eq!(None, board.qualifies("score", 5.0));
+        assert_eq!(Some(1), board.submit("score", "DDD", 20.0));
+        let names: Vec<&str> = board.get_table("score").unwrap().entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(vec!["BBB", "DDD", "AAA"], names);
+
+        assert_eq!(Some(0), board.submit("time", "AAA", 75.5));
+        assert_eq!(Some(0), board.submit("time", "BBB", 61.25));
+        assert_eq!("1:01.25", board.format_score("time", 61.25));
+        assert_eq!(None, board.submit("unknown", "AAA", 1.0));
+    }
+
+    #[test]
+    fn redefine_keeps_entries_that_fit() {
+        let mut board = Leaderboard::default();
+        board.define("m", LEADERBOARD_HIGHER_IS_BETTER, LEADERBOARD_FORMAT_INTEGER, 3);
+        board.submit("m", "A", 1.0);
+        board.submit("m", "B", 2.0);
+        board.submit("m", "C", 3.0);
+        board.define("m", LEADERBOARD_LOWER_IS_BETTER, LEADERBOARD_FORMAT_INTEGER, 2);
+        let scores: Vec<f64> = board.get_table("m").unwrap().entries.iter().map(|e| e.score).collect();
+        assert_eq!(vec![1.0, 2.0], scores);
+    }
+}
@@ -0,0 +1,378 @@
+use super::assets;
+use super::log;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+// In-game text lives in string tables, one per language, keyed by id:
+//
+//   // assets/strings/en.dat
+//   language = English
+//   moves = Moves: {0}
+//   win.one = Solved in {0} move!
+//   win.other = Solved in {0} moves!
+//
+// {0}, {1}, ... are replaced by the arguments, {{ and }} are literal braces.
+// Keys with .zero/.one/.other forms pick one by the language's plural rule for
+// the first argument. Anything missing from the current language comes from the default
+// one, and failing that shows the key.
+//
+// Code refers to strings with world.tr("key", ...) or
+// world.entity_set_text_key(idx, "key", ...), world files with "@key" text.
+// --check-strings reports keys that are used but not defined, and defined but
+// not used.
+
+pub const DEFAULT_LANGUAGE: &str = "en";
+// every table names its own language, for menus
+pub const LANGUAGE_KEY: &str = "language";
+const PLURAL_FORMS: [&str; 3] = ["zero", "one", "other"];
+
+#[derive(Default)]
+pub struct StringTable {
+    pub language: String,
+    strings: HashMap<String, String>,
+}
+
+#[allow(dead_code)]
+impl StringTable {
+    pub fn load(filename: &str) -> Result<StringTable, String> {
+        let text = assets::read_to_string(filename)?;
+        let language = Path::new(filename)
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        StringTable::parse(&language, filename, &text)
+    }
+
+    pub fn parse(language: &str, filename: &str, text: &str) -> Result<StringTable, String> {
+        let mut strings = HashMap::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with("//") {
+                continue;
+            }
+            let (key, value) = match line.split_once('=') {
+                Some((k, v)) if !k.trim().is_empty() => (k.trim(), v.trim()),
+                _ => return Err(format!("{}:{}: expected key = text", filename, i + 1)),
+            };
+            let value = value.replace("\\n", "\n");
+            if strings.insert(String::from(key), value).is_some() {
+                log(format!("WARNING: {}:{}: '{}' defined twice", filename, i + 1, key));
+            }
+        }
+        Ok(StringTable {
+            language: String::from(language),
+            strings,
+        })
+    }
+
+    pub fn get(&self, key: &str) -> Option<&String> {
+        self.strings.get(key)
+    }
+
+    pub fn keys(&self) -> Vec<&String> {
+        let mut keys: Vec<&String> = self.strings.keys().collect();
+        keys.sort();
+        keys
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &String> {
+        self.strings.values()
+    }
+
+    // the key itself or any of its plural forms
+    pub fn has(&self, key: &str) -> bool {
+        self.strings.contains_key(key) || PLURAL_FORMS.iter().any(|f| self.strings.contains_key(&format!("{}.{}", key, f)))
+    }
+
+    // the text for `key`, choosing a plural form by `count` if it has them
+    fn lookup(&self, key: &str, count: Option<f64>) -> Option<&String> {
+        if let Some(n) = count {
+            if 0.0 == n {
+                if let Some(s) = self.strings.get(&format!("{}.zero", key)) {
+                    return Some(s);
+                }
+            }
+            let form = plural_form(&self.language, n);
+            if let Some(s) = self.strings.get(&format!("{}.{}", key, form)) {
+                return Some(s);
+            }
+            if let Some(s) = self.strings.get(&format!("{}.other", key)) {
+                return Some(s);
+            }
+        }
+        self.strings.get(key)
+    }
+}
+
+#[derive(Default)]
+pub struct Strings {
+    dir: String,
+    current: StringTable,
+    fallback: StringTable,
+    // missing keys already warned about
+    missing: HashSet<String>,
+}
+
+#[allow(dead_code)]
+impl Strings {
+    // the string tables in `dir`, in `language`
+    pub fn load(&mut self, dir: &str, language: &str) -> Result<(), String> {
+        self.fallback = StringTable::load(&table_file(dir, DEFAULT_LANGUAGE))?;
+        self.dir = String::from(dir);
+        self.set_language(language)
+    }
+
+    pub fn set_language(&mut self, language: &str) -> Result<(), String> {
+        self.current = StringTable::load(&table_file(&self.dir, language))?;
+        self.missing.clear();
+        log(format!("Strings: language {}", language));
+        Ok(())
+    }
+
+    pub fn get_language(&self) -> &str {
+        &self.current.language
+    }
+
+    // languages with a table, sorted
+    pub fn get_languages(&self) -> Vec<String> {
+        let mut ret = Vec::new();
+        for file in assets::list(&self.dir) {
+            if let Some(stem) = file.strip_suffix(".dat") {
+                ret.push(String::from(stem));
+            }
+        }
+        ret
+    }
+
+    // current language text for `key` with {n} replaced by args[n]
+    pub fn get(&mut self, key: &str, args: &[String]) -> String {
+        let count = args.first().and_then(|a| a.parse::<f64>().ok());
+        let template = match self.current.lookup(key, count) {
+            Some(s) => s,
+            None => {
+                if self.missing.insert(String::from(key)) {
+                    log(format!(
+                        "WARNING: Strings: '{}' missing for language {}",
+                        key, self.current.language
+                    ));
+                }
+                match self.fallback.lookup(key, count) {
+                    Some(s) => s,
+                    None => return String::from(key),
+                }
+            }
+        };
+        format_string(template, args)
+    }
+
+    // every text in the current language, falling back for missing ones
+    pub fn values(&self) -> Vec<&String> {
+        let mut ret: Vec<&String> = self.current.values().collect();
+        for k in self.fallback.keys() {
+            if self.current.get(k).is_none() {
+                ret.push(self.fallback.get(k).unwrap());
+            }
+        }
+        ret
+    }
+}
+
+fn table_file(dir: &str, language: &str) -> String {
+    format!("{}/{}.dat", dir, language)
+}
+
+// CLDR "one" for the languages we have a rule for, "other" otherwise
+pub fn plural_form(language: &str, n: f64) -> &'static str {
+    let one = match language {
+        "fr" | "pt" => n.abs() < 2.0,
+        "ja" | "ko" | "zh" => false,
+        _ => 1.0 == n.abs(),
+    };
+    if one {
+        "one"
+    } else {
+        "other"
+    }
+}
+
+pub fn format_string(template: &str, args: &[String]) -> String {
+    let mut ret = String::new();
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' | '}' if Some(&c) == chars.peek() => {
+                chars.next();
+                ret.push(c);
+            }
+            '{' => {
+                let mut idx = String::new();
+                while let Some(d) = chars.next_if(|d| d.is_ascii_digit()) {
+                    idx.push(d);
+                }
+                match (idx.parse::<usize>().ok(), chars.next_if_eq(&'}')) {
+                    (Some(i), Some(_)) => ret += args.get(i).map_or("", |a| a.as_str()),
+                    (_, close) => {
+                        ret.push('{');
+                        ret += &idx;
+                        ret.extend(close);
+                    }
+                }
+            }
+            c => ret.push(c),
+        }
+    }
+    ret
+}
+
+// string ids in source: .tr("key" and _text_key(x, "key" in Rust, "@key" in world files
+pub fn find_keys(source: &str, rust: bool) -> Vec<String> {
+    let mut ret = Vec::new();
+    for line in source.lines() {
+        let mut rest = line;
+        let markers: &[&str] = if rust { &[".tr(", "_text_key("] } else { &["\"@"] };
+        while let Some((i, m)) = markers.iter().filter_map(|m| rest.find(m).map(|i| (i, m))).min() {
+            rest = &rest[i + m.len()..];
+            let start = match rust {
+                true => match rest.find('"') {
+                    Some(q) if !rest[..q].contains(')') => q + 1,
+                    _ => continue,
+                },
+                false => 0,
+            };
+            if let Some(end) = rest[start..].find('"') {
+                ret.push(String::from(&rest[start..start + end]));
+                rest = &rest[start + end + 1..];
+            }
+        }
+    }
+    ret
+}
+
+// the keys a game's code and world files use, from "<dir>/src" and "<dir>/assets"
+fn find_game_keys(dir: &Path) -> HashSet<String> {
+    let mut ret = HashSet::new();
+    let mut pending = vec![dir.join("src"), dir.join("assets")];
+    while let Some(d) = pending.pop() {
+        // the framework itself has no text
+        if d == dir.join("src/mgfw") {
+            continue;
+        }
+        let entries = match std::fs::read_dir(&d) {
+            Ok(e) => e,
+            Err(_) => continue,
+        };
+        for e in entries.flatten() {
+            let path = e.path();
+            if path.is_dir() {
+                pending.push(path);
+                continue;
+            }
+            let rust = match path.extension().and_then(|x| x.to_str()) {
+                Some("rs") => true,
+                Some("dat") if !path.starts_with(dir.join("assets/strings")) => false,
+                _ => continue,
+            };
+            if let Ok(text) = std::fs::read_to_string(&path) {
+                ret.extend(find_keys(&text, rust));
+            }
+        }
+    }
+    ret
+}
+
+// for each game directory, string table keys that are used but missing
+// (errors) or defined but unused (warnings), returns false on errors
+pub fn check_strings(game_dirs: &[String]) -> bool {
+    let mut ok = true;
+    for dir in game_dirs {
+        let strings_dir = Path::new(dir).join("assets/strings");
+        let mut files: Vec<_> = match std::fs::read_dir(&strings_dir) {
+            Ok(e) => e.flatten().map(|e| e.path()).filter(|p| p.extension().is_some_and(|x| "dat" == x)).collect(),
+            Err(_) => {
+                println!("{}: no string tables", dir);
+                continue;
+            }
+        };
+        files.sort();
+
+        let used = find_game_keys(Path::new(dir));
+        // absolute, game directories aren't under the asset root
+        let cwd = std::env::current_dir().unwrap_or_default();
+        for f in files {
+            let filename = cwd.join(f).to_string_lossy().into_owned();
+            let table = match StringTable::load(&filename) {
+                Ok(t) => t,
+                Err(e) => {
+                    println!("error: {}", e);
+                    ok = false;
+                    continue;
+                }
+            };
+
+            let mut used_sorted: Vec<&String> = used.iter().collect();
+            used_sorted.sort();
+            let mut missing = 0;
+            for k in used_sorted {
+                if !table.has(k) {
+                    println!("error: {}: missing '{}'", filename, k);
+                    missing += 1;
+                }
+            }
+            let mut unused = 0;
+            for k in table.keys() {
+                let base = match k.rsplit_once('.') {
+                    Some((b, form)) if PLURAL_FORMS.contains(&form) => b,
+                    _ => k.as_str(),
+                };
+                if LANGUAGE_KEY != base && !used.contains(base) {
+                    println!("warning: {}: unused '{}'", filename, k);
+                    unused += 1;
+                }
+            }
+            println!("{}: {} missing, {} unused", filename, missing, unused);
+            ok &= 0 == missing;
+        }
+    }
+    ok
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(a: &[&str]) -> Vec<String> {
+        a.iter().map(|s| String::from(*s)).collect()
+    }
+
+    #[test]
+    fn plural_forms_and_placeholders() {
+        let table = StringTable::parse(
+            "en",
+            "en.dat",
+            "// comment\nlanguage = English\nhi = Hi {1}, {0}! {{0}} {2}\nwin.one = {0} move\nwin.other = {0} moves\nwin.zero = no moves",
+        )
+        .unwrap();
+        let mut strings = Strings {
+            current: table,
+            ..Default::default()
+        };
+        assert_eq!("Hi b, a! {0} ", strings.get("hi", &args(&["a", "b"])));
+        assert_eq!("1 move", strings.get("win", &args(&["1"])));
+        assert_eq!("12 moves", strings.get("win", &args(&["12"])));
+        assert_eq!("no moves", strings.get("win", &args(&["0"])));
+        assert_eq!("nope", strings.get("nope", &[]));
+        assert!(strings.current.has("win") && !strings.current.has("win.two"));
+
+        assert_eq!("one", plural_form("fr", 0.0));
+        assert_eq!("other", plural_form("en", 0.0));
+        assert!(StringTable::parse("en", "en.dat", "no equals").is_err());
+        assert_eq!("{x} {3", format_string("{x} {3", &[]));
+    }
+
+    #[test]
+    fn keys_found_in_code_and_world_files() {
+        let rust = "ui.label(world, &world.tr(\"paused\", &[]));\nworld.entity_set_text_key(e, \"win\", &[n]); // tr(x)";
+        assert_eq!(vec!["paused", "win"], find_keys(rust, true));
+        assert_eq!(vec!["menu", "move"], find_keys("4,text,\"@menu\"\n5,text,\"@move\"\n6,text,\"x\"", false));
+    }
+}
@@ -0,0 +1,388 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Instant;
+
+// Log entries have a level and a category, the module that logged them
+// ("ecs::component_render_text", "game::game"). Entries at or below the
+// verbosity go to a log file in the data directory, and to the console in
+// debug builds. The verbosity can be set per category at runtime or with the
+// MGFW_LOG environment variable (F5 cycles the overall level):
+//
+//   MGFW_LOG=debug,ecs=trace,persist=warn
+//
+// A panic writes a crash report next to the log with the panic, the last
+// entries logged and the state set with set_state.
+
+pub const LOG_OFF: u8 = 0;
+pub const LOG_ERROR: u8 = 1;
+pub const LOG_WARN: u8 = 2;
+pub const LOG_INFO: u8 = 3;
+pub const LOG_DEBUG: u8 = 4;
+pub const LOG_TRACE: u8 = 5;
+
+const LOG_FILE: &str = "mgfw.log";
+// the current file and this many older ones
+const LOG_ROTATIONS: usize = 3;
+const LOG_MAX_BYTES: u64 = 1024 * 1024;
+const RECENT_SZ: usize = 200;
+
+struct Logger {
+    start: Option<Instant>,
+    level: u8,
+    // (category, level), longest match wins
+    categories: Vec<(String, u8)>,
+    recent: VecDeque<String>,
+    state: Vec<(String, String)>,
+    dir: Option<PathBuf>,
+    file: Option<File>,
+    written: u64,
+}
+
+static LOGGER: Mutex<Logger> = Mutex::new(Logger {
+    start: None,
+    level: if cfg!(debug_assertions) { LOG_DEBUG } else { LOG_INFO },
+    categories: Vec::new(),
+    recent: VecDeque::new(),
+    state: Vec::new(),
+    dir: None,
+    file: None,
+    written: 0,
+});
+
+fn lock() -> std::sync::MutexGuard<'static, Logger> {
+    // a panic while logging shouldn't take logging down with it
+    LOGGER.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+// opens the log file in `dir`, rotating older ones, and installs the panic hook
+pub fn init(dir: &Path) {
+    let mut logger = lock();
+    if let Ok(spec) = std::env::var("MGFW_LOG") {
+        logger.configure(&spec);
+    }
+
+    if let Err(e) = std::fs::create_dir_all(dir) {
+        drop(logger);
+        write(LOG_WARN, "logging", &format!("can't create {}: {}", dir.display(), e));
+        return;
+    }
+    logger.dir = Some(dir.to_path_buf());
+    logger.open();
+
+    // entries from before init
+    let recent: Vec<String> = logger.recent.iter().cloned().collect();
+    for line in recent {
+        logger.write_file(&line);
+    }
+    drop(logger);
+
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        write_crash_report(&info.to_string());
+        default_hook(info);
+    }));
+
+    write(LOG_INFO, "logging", &format!("Logging to {}", dir.join(LOG_FILE).display()));
+}
+
+// the data directory log folder for a game title, "My Game" -> .../my-game/logs
+pub fn default_dir(title: &str) -> PathBuf {
+    let slug: String = title
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '-' })
+        .collect();
+    match dirs::data_dir() {
+        Some(d) => d.join(slug).join("logs"),
+        None => PathBuf::from("logs"),
+    }
+}
+
+pub fn write(level: u8, category: &str, message: &str) {
+    let mut logger = lock();
+    if !logger.enabled(level, category) {
+        return;
+    }
+    let secs = match logger.start {
+        Some(s) => s.elapsed().as_secs_f32(),
+        None => {
+            logger.start = Some(Instant::now());
+            0.0
+        }
+    };
+    let line = format!("[{:>9.3}] {:<5} {}: {}", secs, level_name(level), category, message);
+
+    if cfg!(debug_assertions) {
+        println!("{}", line);
+    }
+
+    if RECENT_SZ == logger.recent.len() {
+        logger.recent.pop_front();
+    }
+    logger.recent.push_back(line.clone());
+    logger.write_file(&line);
+}
+
+pub fn set_level(level: u8) {
+    lock().level = level;
+}
+
+pub fn get_level() -> u8 {
+    lock().level
+}
+
+// LOG_OFF..LOG_TRACE for the category and everything below it
+#[allow(dead_code)]
+pub fn set_category_level(category: &str, level: u8) {
+    lock().set_category_level(category, level);
+}
+
+// same format as MGFW_LOG, returns false if any part was not understood
+#[allow(dead_code)]
+pub fn configure(spec: &str) -> bool {
+    lock().configure(spec)
+}
+
+// shown in crash reports, e.g. set_state("level", format!("{}", level))
+pub fn set_state(key: &str, value: String) {
+    let mut logger = lock();
+    match logger.state.iter_mut().find(|(k, _)| k == key) {
+        Some(s) => s.1 = value,
+        None => logger.state.push((String::from(key), value)),
+    }
+}
+
+// last entries logged, oldest first
+#[allow(dead_code)]
+pub fn get_recent() -> Vec<String> {
+    lock().recent.iter().cloned().collect()
+}
+
+// the current log file, None before init or if it couldn't be opened
+pub fn get_file() -> Option<PathBuf> {
+    let logger = lock();
+    match logger.file {
+        Some(_) => logger.dir.as_ref().map(|d| d.join(LOG_FILE)),
+        None => None,
+    }
+}
+
+pub fn flush() {
+    if let Some(f) = lock().file.as_mut() {
+        let _ = f.flush();
+    }
+}
+
+pub fn level_name(level: u8) -> &'static str {
+    match level {
+        LOG_ERROR => "ERROR",
+        LOG_WARN => "WARN",
+        LOG_INFO => "INFO",
+        LOG_DEBUG => "DEBUG",
+        LOG_TRACE => "TRACE",
+        _ => "OFF",
+    }
+}
+
+pub fn parse_level(name: &str) -> Option<u8> {
+    match name.trim().to_lowercase().as_str() {
+        "off" => Some(LOG_OFF),
+        "error" => Some(LOG_ERROR),
+        "warn" | "warning" => Some(LOG_WARN),
+        "info" => Some(LOG_INFO),
+        "debug" => Some(LOG_DEBUG),
+        "trace" => Some(LOG_TRACE),
+        _ => None,
+    }
+}
+
+// "src/mgfw/ecs/component_position.rs" -> "ecs::component_position"
+pub fn category_from_file(file: &str) -> String {
+    let file = file.replace('\\', "/");
+    let file = match file.rfind("src/") {
+        Some(i) => &file[i + 4..],
+        None => &file,
+    };
+    let file = file.trim_end_matches(".rs").trim_end_matches("/mod");
+    let file = file.strip_prefix("mgfw/").unwrap_or(file);
+    file.replace('/', "::")
+}
+
+// the level and message of a `log` call, "WARNING: x" is a warning
+pub fn split_level(output: &str) -> (u8, &str) {
+    for (prefix, level) in [("ERROR:", LOG_ERROR), ("WARNING:", LOG_WARN), ("DEBUG:", LOG_DEBUG)] {
+        if let Some(m) = output.strip_prefix(prefix) {
+            return (level, m.trim_start());
+        }
+    }
+    (LOG_INFO, output)
+}
+
+impl Logger {
+    fn enabled(&self, level: u8, category: &str) -> bool {
+        let mut max = self.level;
+        let mut best = 0;
+        for (c, l) in self.categories.iter() {
+            let matches = category == c || (category.starts_with(c.as_str()) && category[c.len()..].starts_with("::"));
+            if matches && c.len() >= best {
+                best = c.len();
+                max = *l;
+            }
+        }
+        LOG_OFF != level && level <= max
+    }
+
+    fn set_category_level(&mut self, category: &str, level: u8) {
+        match self.categories.iter_mut().find(|(c, _)| c == category) {
+            Some(c) => c.1 = level,
+            None => self.categories.push((String::from(category), level)),
+        }
+    }
+
+    fn configure(&mut self, spec: &str) -> bool {
+        let mut ok = true;
+        for part in spec.split(',').map(|p| p.trim()).filter(|p| !p.is_empty()) {
+            match part.split_once('=') {
+                Some((category, level)) => match parse_level(level) {
+                    Some(l) => self.set_category_level(category.trim(), l),
+                    None => ok = false,
+                },
+                None => match parse_level(part) {
+                    Some(l) => self.level = l,
+                    None => ok = false,
+                },
+            }
+        }
+        ok
+    }
+
+    fn open(&mut self) {
+        let dir = match &self.dir {
+            Some(d) => d.clone(),
+            None => return,
+        };
+        self.file = None;
+        rotate(&dir, LOG_FILE, LOG_ROTATIONS);
+        self.file = File::create(dir.join(LOG_FILE)).ok();
+        self.written = 0;
+    }
+
+    fn write_file(&mut self, line: &str) {
+        if LOG_MAX_BYTES < self.written {
+            self.open();
+        }
+        if let Some(f) = self.file.as_mut() {
+            if writeln!(f, "{}", line).is_ok() {
+                self.written += line.len() as u64 + 1;
+            }
+        }
+    }
+}
+
+// name -> name.1 -> name.2 ..., dropping the oldest
+fn rotate(dir: &Path, name: &str, count: usize) {
+    let path = |i: usize| match i {
+        0 => dir.join(name),
+        _ => dir.join(format!("{}.{}", name, i)),
+    };
+    let _ = std::fs::remove_file(path(count));
+    for i in (0..count).rev() {
+        let _ = std::fs::rename(path(i), path(i + 1));
+    }
+}
+
+fn write_crash_report(panic: &str) {
+    // the panic may have happened while logging
+    let mut logger = match LOGGER.try_lock() {
+        Ok(l) => l,
+        Err(std::sync::TryLockError::Poisoned(e)) => e.into_inner(),
+        Err(std::sync::TryLockError::WouldBlock) => {
+            eprintln!("panic while logging: {}", panic);
+            return;
+        }
+    };
+
+    let line = format!("[{:>9}] ERROR panic: {}", "", panic);
+    logger.write_file(&line);
+    if let Some(f) = logger.file.as_mut() {
+        let _ = f.flush();
+    }
+
+    let dir = match &logger.dir {
+        Some(d) => d.clone(),
+        None => return,
+    };
+    let secs = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let filename = dir.join(format!("crash_{}.log", secs));
+
+    let mut report = format!("{}\n\n", panic);
+    report += &format!("{}\n\n", std::backtrace::Backtrace::force_capture());
+    report += "State:\n";
+    if let Some(s) = logger.start {
+        report += &format!("  uptime: {:.3}s\n", s.elapsed().as_secs_f32());
+    }
+    for (k, v) in logger.state.iter() {
+        report += &format!("  {}: {}\n", k, v);
+    }
+    report += &format!("\nLast {} entries:\n", logger.recent.len());
+    for l in logger.recent.iter() {
+        report += &format!("{}\n", l);
+    }
+
+    match std::fs::write(&filename, report) {
+        Ok(_) => eprintln!("crash report written to {}", filename.display()),
+        Err(e) => eprintln!("failed to write crash report {}: {}", filename.display(), e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn categories_and_levels() {
+        assert_eq!("ecs::component_position", category_from_file("src/mgfw/ecs/component_position.rs"));
+        assert_eq!("cache", category_from_file("src\\mgfw\\cache\\mod.rs"));
+        assert_eq!("game::game", category_from_file("src/game/game.rs"));
+        assert_eq!((LOG_WARN, "Persist: no data"), split_level("WARNING: Persist: no data"));
+        assert_eq!((LOG_INFO, "Constructing X"), split_level("Constructing X"));
+
+        let mut logger = Logger {
+            start: None,
+            level: LOG_INFO,
+            categories: Vec::new(),
+            recent: VecDeque::new(),
+            state: Vec::new(),
+            dir: None,
+            file: None,
+            written: 0,
+        };
+        assert!(logger.configure("warn, ecs=debug ,ecs::component_text=off"));
+        assert!(!logger.configure("ecs=loud"));
+        assert!(logger.enabled(LOG_WARN, "persist"));
+        assert!(!logger.enabled(LOG_INFO, "persist"));
+        assert!(logger.enabled(LOG_DEBUG, "ecs::world"));
+        assert!(!logger.enabled(LOG_ERROR, "ecs::component_text"));
+        assert!(!logger.enabled(LOG_DEBUG, "ecstatic"));
+    }
+
+    #[test]
+    fn rotation_keeps_newest_files() {
+        let dir = std::env::temp_dir().join(format!("mgfw_log_test_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for i in 0..4 {
+            std::fs::write(dir.join("a.log"), format!("{}", i)).unwrap();
+            rotate(&dir, "a.log", 2);
+        }
+        assert!(!dir.join("a.log").exists());
+        assert_eq!("3", std::fs::read_to_string(dir.join("a.log.1")).unwrap());
+        assert_eq!("2", std::fs::read_to_string(dir.join("a.log.2")).unwrap());
+        assert!(!dir.join("a.log.3").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,10 +1,24 @@
+pub mod assets;
 pub mod cache;
+mod capture;
+mod console;
 pub mod ecs;
+pub mod error;
 mod fonts;
+mod gamepad;
+mod hot_reload;
+pub mod leaderboard;
+pub mod locale;
+pub mod logging;
+pub mod persist;
+mod profiler;
+pub mod shapes;
 mod support;
+pub mod timing;
+pub mod ui;
 
 use crate::game::GameWrapper;
-use cache::CacheManager;
+use cache::{CacheBlock, CacheManager};
 use std::collections::VecDeque;
 use support::Gl;
 
@@ -24,6 +38,33 @@ pub fn deg2rad(val: f32) -> f32 {
     val * PI as f32 / 180.0
 }
 
+pub fn rad2deg(val: f32) -> f32 {
+    val * 180.0 / PI as f32
+}
+
+// winit panics when there is no display to connect to, report that as an
+// error instead of a backtrace
+pub fn new_event_loop() -> Result<EventLoop<()>, error::Error> {
+    let hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(|_| {}));
+    let ret = std::panic::catch_unwind(EventLoop::new);
+    std::panic::set_hook(hook);
+    ret.map_err(|e| {
+        let msg = match (e.downcast_ref::<&str>(), e.downcast_ref::<String>()) {
+            (Some(s), _) => String::from(*s),
+            (_, Some(s)) => s.clone(),
+            _ => String::from("no display available"),
+        };
+        error::Error::Window(msg)
+    })
+}
+
+fn load_icon(file: &str) -> Result<Icon, String> {
+    let icon: image::RgbaImage = assets::read_image(file)?.to_rgba8();
+    let (w, h) = icon.dimensions();
+    Icon::from_rgba(icon.into_vec(), w, h).map_err(|e| format!("{}: {}", file, e))
+}
+
 struct CoreData {
     initialized: bool,
     running: bool,
@@ -42,6 +83,9 @@ struct CoreData {
     update_frame_load: f64,
     render_frame_load: f64,
     scale_factor: f64,
+    timing: timing::Timing,
+    // simulation time given up when updates couldn't keep up
+    dropped_steps: u128,
 }
 
 #[allow(dead_code)]
@@ -65,11 +109,12 @@ pub const EVENT_INPUT_KEYBOARD_RELEASED_SPACE: u8 = 125;
 pub const EVENT_INPUT_KEYBOARD_RELEASED_PGUP: u8 = 126;
 pub const EVENT_INPUT_KEYBOARD_RELEASED_PGDN: u8 = 127;
 pub const EVENT_INPUT_KEYBOARD_RELEASED_BKSPC: u8 = 128;
+pub const EVENT_INPUT_KEYBOARD_RELEASED_ENTER: u8 = 129;
 
 
 #[allow(dead_code)]
 pub struct Core {
-    data: *mut CoreData,
+    data: CacheBlock<CoreData>,
     // WARNING: Anything below this line is not in cache!
     pub windowed_context: glutin::ContextWrapper<glutin::PossiblyCurrent, glutin::window::Window>,
     gl: std::boxed::Box<Gl>,
@@ -80,21 +125,34 @@ pub struct Core {
     physics_system: std::boxed::Box<ecs::PhysicsSystem>,
     easing_system: std::boxed::Box<ecs::EasingSystem>,
     events: std::boxed::Box<VecDeque<u8>>,
+    gamepad: std::boxed::Box<gamepad::Gamepad>,
+    hot_reload: std::boxed::Box<hot_reload::HotReload>,
+    profiler: std::boxed::Box<profiler::Profiler>,
+    console: std::boxed::Box<console::Console>,
+    capture: std::boxed::Box<capture::Capture>,
 }
 
 impl Core {
-    pub fn new(title: &str, xres: i32, yres: i32, el: &EventLoop<()>) -> Core {
+    pub fn new(title: &str, xres: i32, yres: i32, el: &EventLoop<()>) -> Result<Core, error::Error> {
+        logging::init(&logging::default_dir(title));
         log(format!("Constructing MGFW Core"));
+        assets::init(None);
+        let timing = timing::Timing::load("assets/mgfw/timing.dat");
+        log(format!(
+            "Timing: update {} Hz, render {}, vsync {}",
+            timing.update_hz,
+            timing::render_rate_name(timing.render_hz),
+            timing.vsync
+        ));
 
-        // Construct a new RGB ImageBuffer with the specified width and height.
-        let icon: image::RgbaImage = image::open("assets/mgfw/mgfw_64_trim.ico")
-            .unwrap()
-            .to_rgba8();
-        let w = icon.dimensions().0 as u32;
-        let h = icon.dimensions().1 as u32;
-        let b = Some(Icon::from_rgba(icon.into_vec(), w, h).unwrap());
-
-        // img.into_raw().as_ptr() as *const _,
+        // the window works without an icon
+        let b = match load_icon("assets/mgfw/mgfw_64_trim.ico") {
+            Ok(icon) => Some(icon),
+            Err(e) => {
+                log(format!("WARNING: Window icon: {}", e));
+                None
+            }
+        };
 
         let window = WindowBuilder::new()
             .with_title(title)
@@ -106,12 +164,13 @@ impl Core {
             ));
 
         let windowed_context = ContextBuilder::new()
-            .with_vsync(true)
+            .with_vsync(timing.vsync)
             .with_multisampling(2)
             .build_windowed(window, &el)
-            .unwrap();
+            .map_err(|e| error::Error::Window(e.to_string()))?;
 
-        let windowed_context = unsafe { windowed_context.make_current().unwrap() };
+        let windowed_context = unsafe { windowed_context.make_current() }
+            .map_err(|(_, e)| error::Error::Context(e.to_string()))?;
 
         let scale_factor = windowed_context.window().scale_factor();
         //windowed_context.window().set_cursor_visible(false);
@@ -123,49 +182,54 @@ impl Core {
             xres,
             yres,
             (scale_factor * WINDOW_SCALE) as f32,
-        ));
+        )?);
         let mut cache = Box::new(CacheManager::new());
 
         // force clear the display buffers
-        gl.clear_frame();
-        windowed_context.swap_buffers().unwrap();
-        gl.clear_frame();
-        windowed_context.swap_buffers().unwrap();
-
-        let sz_bytes = std::mem::size_of::<CoreData>();
-        let data = cache.allocate(sz_bytes) as *mut CoreData;
-        unsafe {
-            *data = CoreData {
-                running: false,
-                last_update: start_time,
-                last_render: start_time,
-                last_physics: start_time,
-                initialized: false,
-                shutdown: false,
-                blown_update_frames: 0,
-                blown_update_frames_expected: 0,
-                blown_update_frames_significant: 0,
-                count_update_frames: 0,
-                blown_render_frames: 0,
-                count_render_frames: 0,
-                completed_first_frame: false,
-                start_time,
-                update_frame_load: 0.0,
-                render_frame_load: 0.0,
-                scale_factor,
-            };
+        for _ in 0..2 {
+            gl.clear_frame();
+            windowed_context
+                .swap_buffers()
+                .map_err(|e| error::Error::Context(e.to_string()))?;
         }
 
+        let data = cache.allocate_with("Core", 1, |_| CoreData {
+            running: false,
+            last_update: start_time,
+            last_render: start_time,
+            last_physics: start_time,
+            initialized: false,
+            shutdown: false,
+            blown_update_frames: 0,
+            blown_update_frames_expected: 0,
+            blown_update_frames_significant: 0,
+            count_update_frames: 0,
+            blown_render_frames: 0,
+            count_render_frames: 0,
+            completed_first_frame: false,
+            start_time,
+            update_frame_load: 0.0,
+            render_frame_load: 0.0,
+            scale_factor,
+            timing,
+            dropped_steps: 0,
+        });
+
         let world = Box::new(ecs::World::new(&mut cache));
         let render_system = Box::new(ecs::RenderSystem::new(&mut cache, &gl));
         let physics_system = Box::new(ecs::PhysicsSystem::new(&mut cache));
         let easing_system = Box::new(ecs::EasingSystem::new(&mut cache));
         let game = Box::new(GameWrapper::new(&mut cache));
         let events = Box::new(VecDeque::new());
+        let gamepad = Box::new(gamepad::Gamepad::new());
+        let hot_reload = Box::new(hot_reload::HotReload::new());
+        let profiler = Box::new(profiler::Profiler::new(title, &gl));
+        let console = Box::new(console::Console::new(&gl));
+        let capture = Box::new(capture::Capture::new(title, &gl));
 
         cache.print_loading();
 
-        Core {
+        Ok(Core {
             windowed_context,
             gl,
             data,
@@ -176,13 +240,16 @@ impl Core {
             physics_system,
             easing_system,
             events,
-        }
+            gamepad,
+            hot_reload,
+            profiler,
+            console,
+            capture,
+        })
     }
 
     pub fn check_events(&mut self, event: &glutin::event::Event<()>) -> bool {
-        let cache = unsafe { &mut *(self.data.offset(0)) };
-
-        if !cache.initialized {
+        if !self.data[0].initialized {
             self.initialize();
         }
 
@@ -192,15 +259,13 @@ impl Core {
             Event::LoopDestroyed => ret = false,
             Event::WindowEvent { event, .. } => match event {
                 WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
-                    cache.scale_factor = *scale_factor;
+                    self.data[0].scale_factor = *scale_factor;
                 }
                 WindowEvent::Resized(physical_size) => self.windowed_context.resize(*physical_size),
                 WindowEvent::CloseRequested => ret = false,
                 WindowEvent::CursorMoved { position, .. } => {
-                    self.update_mouse_xy(
-                        (position.x / (cache.scale_factor * WINDOW_SCALE)) as i32,
-                        (position.y / (cache.scale_factor * WINDOW_SCALE)) as i32,
-                    );
+                    let scale = self.data[0].scale_factor * WINDOW_SCALE;
+                    self.update_mouse_xy((position.x / scale) as i32, (position.y / scale) as i32);
                 }
                 WindowEvent::MouseInput { state, button, .. } => {
                     self.update_mouse_button(button, state);
@@ -208,16 +273,19 @@ impl Core {
                 WindowEvent::KeyboardInput { input, .. } => {
                     self.update_keyboard_input(&input);
                 }
+                WindowEvent::ReceivedCharacter(c) if self.console.is_visible() => {
+                    self.console.type_char(*c);
+                }
                 _ => (),
             },
-            Event::RedrawRequested(_) => self.render(std::time::Instant::now()),
+            Event::RedrawRequested(_) => self.render(),
             _ => (),
         }
 
         if ret {
             self.update();
         } else {
-            if !cache.shutdown {
+            if !self.data[0].shutdown {
                 self.shutdown();
             }
         }
@@ -225,8 +293,35 @@ impl Core {
     }
 
     fn update_keyboard_input(&mut self, input: &KeyboardInput) {
+        if ElementState::Pressed == input.state && Some(VirtualKeyCode::Grave) == input.virtual_keycode {
+            self.console.toggle();
+            return;
+        }
+        if self.console.is_visible() {
+            self.update_console_input(input);
+            return;
+        }
+
         if ElementState::Pressed == input.state {
             match input.virtual_keycode {
+                Some(VirtualKeyCode::F3) => self.profiler.toggle(),
+                Some(VirtualKeyCode::F4) => self.profiler.export_trace(),
+                Some(VirtualKeyCode::F5) => {
+                    let level = logging::get_level() % logging::LOG_TRACE + 1;
+                    logging::set_level(level);
+                    log_at(level, &format!("Log level {}", logging::level_name(level)));
+                }
+                Some(VirtualKeyCode::F6) => {
+                    let timing = &mut self.data[0].timing;
+                    timing.next_render_rate();
+                    log(format!("Render rate {}", timing::render_rate_name(timing.render_hz)));
+                    if timing.vsync {
+                        log(format!("WARNING: vsync is on, frames are capped at the display refresh rate"));
+                    }
+                }
+                Some(VirtualKeyCode::F9) => self.capture.toggle_recording(capture::CAPTURE_GIF),
+                Some(VirtualKeyCode::F10) => self.capture.toggle_recording(capture::CAPTURE_APNG),
+                Some(VirtualKeyCode::F12) => self.capture.screenshot(),
                 Some(VirtualKeyCode::Escape) => {
                     self.events.push_back(EVENT_INPUT_KEYBOARD_PRESSED_ESCAPE)
                 }
@@ -272,11 +367,38 @@ impl Core {
                 Some(VirtualKeyCode::Back) => {
                     self.events.push_back(EVENT_INPUT_KEYBOARD_RELEASED_BKSPC)
                 }
+                Some(VirtualKeyCode::Return) => {
+                    self.events.push_back(EVENT_INPUT_KEYBOARD_RELEASED_ENTER)
+                }
                 _ => (),
             }
         }
     }
 
+    // the console takes all keys while open
+    fn update_console_input(&mut self, input: &KeyboardInput) {
+        if ElementState::Pressed != input.state {
+            return;
+        }
+        match input.virtual_keycode {
+            Some(VirtualKeyCode::Escape) => self.console.toggle(),
+            Some(VirtualKeyCode::Back) => self.console.backspace(),
+            Some(VirtualKeyCode::Up) => self.console.history_step(-1),
+            Some(VirtualKeyCode::Down) => self.console.history_step(1),
+            Some(VirtualKeyCode::PageUp) => self.console.scroll(4),
+            Some(VirtualKeyCode::PageDown) => self.console.scroll(-4),
+            Some(VirtualKeyCode::Return) => {
+                if let Some(args) = self.console.submit(&mut self.world) {
+                    match self.game.command(&mut self.world, &args) {
+                        Ok(reply) => self.console.print(&reply),
+                        Err(e) => self.console.print(&e),
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
     fn update_mouse_xy(&mut self, x: i32, y: i32) {
         self.world.mouse_x = x;
         self.world.mouse_y = y;
@@ -287,17 +409,23 @@ impl Core {
         button: &glutin::event::MouseButton,
         state: &glutin::event::ElementState,
     ) {
-        //let cache = unsafe { &mut *(self.data.offset(0)) };
+        //let cache = &mut self.data[0];
         if MouseButton::Left == *button && ElementState::Released == *state {
             //log(format!("mouse clicked at {}, {}", cache.mouse_x, cache.mouse_y);
 
+            if self.console.is_visible() {
+                let (x, y) = (self.world.mouse_x as f32, self.world.mouse_y as f32);
+                self.console.pick(&mut self.world, x, y);
+                return;
+            }
+
             // insert message into the input FIFO
             self.events.push_back(EVENT_INPUT_MOUSE_BUTTON_UP);
         }
     }
 
     fn initialize(&mut self) {
-        let cache = unsafe { &mut *(self.data.offset(0)) };
+        let cache = &mut self.data[0];
 
         self.game.initialize(&mut self.world);
         cache.initialized = true;
@@ -317,15 +445,48 @@ impl Core {
         }
     }
 
-    fn render(&mut self, start_time: std::time::Instant) {
+    fn render(&mut self) {
+        let timer_start = std::time::Instant::now();
+        let cache = &self.data[0];
+        let since_physics = timer_start.duration_since(cache.last_physics).as_micros();
+        let alpha = timing::interpolation_alpha(since_physics, cache.timing.physics_dt());
+
         self.gl.clear_frame();
-        self.render_system.render(&self.gl, &self.world, start_time);
+        self.render_system.render(&self.gl, &self.world, alpha);
+        self.capture.update(&self.gl);
+        self.console.draw(&self.gl);
+        self.profiler.draw(&self.gl);
+        self.profiler.record("render", profiler::TRACE_RENDER, timer_start);
+    }
+
+    // once per displayed frame, the counts are only gathered while shown
+    fn end_profiler_frame(&mut self) {
+        let mut counters = profiler::ProfilerCounters {
+            draw_calls: self.gl.take_draw_calls(),
+            vbo_rebuilds: self.render_system.take_vbo_rebuilds(),
+            ..Default::default()
+        };
+        if self.profiler.is_visible() {
+            let cache = &self.data[0];
+            let ent = self.world.get_entities();
+            let span = ent.get_id_span();
+            counters.entities = (span.first..=span.last).filter(|i| ent.is_active(*i)).count();
+            counters.vbos = self.render_system.get_num_vbos(&self.world);
+            counters.cache_used = self.cache.get_used();
+            counters.cache_size = self.cache.get_size();
+            counters.blown_update_frames = cache.blown_update_frames;
+            counters.blown_update_frames_expected = cache.blown_update_frames_expected;
+            counters.blown_render_frames = cache.blown_render_frames;
+        }
+        self.profiler.end_frame(counters);
+        self.profiler.update(&self.gl, &mut self.world);
     }
 
     fn shutdown(&mut self) {
-        let cache = unsafe { &mut *(self.data.offset(0)) };
+        let cache = &mut self.data[0];
 
         self.game.shutdown();
+        self.capture.shutdown(&self.gl);
 
         if 0 < cache.blown_update_frames_significant {
             log(format!(
@@ -348,6 +509,13 @@ impl Core {
             ));
         }
 
+        if 0 < cache.dropped_steps {
+            log(format!(
+                "WARNING: {} ms of simulation dropped to keep up",
+                cache.dropped_steps * cache.timing.update_dt() / 1000
+            ));
+        }
+
         log(format!(
             "Avg Update frame loading: {}%",
             (cache.update_frame_load * 100.0 / cache.count_render_frames as f64) as i32
@@ -357,10 +525,11 @@ impl Core {
             (cache.render_frame_load * 100.0 / cache.count_render_frames as f64) as i32
         ));
         cache.shutdown = true;
+        logging::flush();
     }
 
     fn update(&mut self) {
-        let cache = unsafe { &mut *(self.data.offset(0)) };
+        let cache = &mut self.data[0];
 
         if !cache.running {
             cache.last_update = std::time::Instant::now();
@@ -374,25 +543,42 @@ impl Core {
             self.easing_system.update(&mut self.world, 0);
         }
 
-        // inner update loop
-        let mut loop_counter = 0;
-        const UPDATE_DT: u128 = 833; // microseconds
-        loop {
-            let delta = std::time::Instant::now().duration_since(cache.last_update);
+        // controller input queues keyboard events, dropped while the console has the keyboard
+        let mut pad_events = VecDeque::new();
+        self.gamepad.poll(&mut pad_events);
+        if !self.console.is_visible() {
+            self.events.append(&mut pad_events);
+        }
 
-            // break out of loop if stuck or finished
-            loop_counter += 1;
-            if UPDATE_DT > delta.as_micros() || loop_counter > 100 {
+        // fixed step accumulator, up to max_steps per call. Past that the
+        // backlog is dropped so a slow machine runs the game slower instead
+        // of falling further behind every frame.
+        let update_dt = cache.timing.update_dt();
+        let mut steps = 0;
+        loop {
+            let behind = std::time::Instant::now()
+                .duration_since(cache.last_update)
+                .as_micros();
+            if update_dt > behind {
+                break;
+            }
+            if steps >= cache.timing.max_steps {
+                let dropped = behind / update_dt;
+                cache.dropped_steps += dropped;
+                cache.last_update += std::time::Duration::from_micros((dropped * update_dt) as u64);
                 break;
             }
+            steps += 1;
 
-            cache.last_update += std::time::Duration::from_micros(UPDATE_DT as u64);
+            cache.last_update += std::time::Duration::from_micros(update_dt as u64);
             let timer_start = std::time::Instant::now();
 
             let mut expect_blown = false;
+            let t = std::time::Instant::now();
 
             // update game
-            expect_blown |= self.game.update(&mut self.world, UPDATE_DT);
+            expect_blown |= self.game.update(&mut self.world, update_dt);
+            self.profiler.record("game", profiler::TRACE_UPDATE, t);
 
             // update systems
             if 0 == cache.count_update_frames % 1 {
@@ -401,19 +587,33 @@ impl Core {
 
             if 0 == cache.count_update_frames % 2 {
                 // priority 2 systems
+                let t = std::time::Instant::now();
                 expect_blown |= self.render_system.update(&self.gl, &mut self.world);
+                self.profiler.record("render_vbo", profiler::TRACE_UPDATE, t);
             }
 
-            if 1 == cache.count_update_frames % 4 {
+            if 1 == cache.count_update_frames % timing::PHYSICS_DIV as usize {
                 // priority 3 systems
-                expect_blown |= self.physics_system.update(&mut self.world, UPDATE_DT * 4);
-                cache.last_physics = std::time::Instant::now();
-
                 if let Some(val) = self.events.pop_front() {
+                    let t = std::time::Instant::now();
                     expect_blown |= self.game.event(&mut self.world, val);
+                    self.profiler.record("event", profiler::TRACE_UPDATE, t);
                 }
 
-                expect_blown |= self.easing_system.update(&mut self.world, UPDATE_DT * 4);
+                // rendering interpolates between the states before and after
+                // physics and easing, changes made by the game snap
+                self.render_system.snapshot_prev(&self.world);
+
+                let t = std::time::Instant::now();
+                expect_blown |= self.physics_system.update(&mut self.world, cache.timing.physics_dt());
+                cache.last_physics = std::time::Instant::now();
+                self.profiler.record("physics", profiler::TRACE_UPDATE, t);
+
+                let t = std::time::Instant::now();
+                expect_blown |= self.easing_system.update(&mut self.world, cache.timing.physics_dt());
+                self.profiler.record("easing", profiler::TRACE_UPDATE, t);
+
+                self.render_system.snapshot_current(&self.world);
 
                 /*if cfg!(debug_assertions) {
                     // artificial jitter
@@ -430,43 +630,54 @@ impl Core {
                 }*/
             }
 
-            let delta = std::time::Instant::now()
-                .duration_since(timer_start)
-                .as_micros();
-            if UPDATE_DT < delta {
+            let delta = self.profiler.record("update", profiler::TRACE_UPDATE, timer_start) as u128;
+            if update_dt < delta {
+                self.profiler.mark_blown(expect_blown);
                 if expect_blown {
                     cache.blown_update_frames_expected += 1;
                 }
-                if UPDATE_DT * 3 < delta {
+                if update_dt * 3 < delta {
                     cache.blown_update_frames_significant += 1;
                 }
                 cache.blown_update_frames += 1;
             }
             cache.count_update_frames += 1;
-            cache.update_frame_load += delta as f64 / UPDATE_DT as f64;
+            cache.update_frame_load += delta as f64 / update_dt as f64;
         }
 
-        // outter render loop
-        let delta = std::time::Instant::now().duration_since(cache.last_render);
-
-        const RENDER_DT: u128 = 16666; // microseconds
+        if cfg!(debug_assertions) {
+            let t = std::time::Instant::now();
+            self.hot_reload.update(&mut self.gl, &mut self.world);
+            self.profiler.record("hot_reload", profiler::TRACE_UPDATE, t);
+        }
 
-        if RENDER_DT < delta.as_micros() {
-            cache.last_render = std::time::Instant::now();
+        // outter render loop, frames are paced from when they were due so
+        // the rate holds even when one is late
+        let render_dt = cache.timing.render_dt();
+        let budget = cache.timing.render_budget();
+        let now = std::time::Instant::now();
+        let since_render = now.duration_since(cache.last_render).as_micros();
+
+        if render_dt <= since_render {
+            cache.last_render = match since_render < render_dt * 2 {
+                true => cache.last_render + std::time::Duration::from_micros(render_dt as u64),
+                false => now,
+            };
 
             // render frame
-            self.render(cache.last_physics);
+            self.render();
+            let cache = &mut self.data[0];
 
-            let delta = std::time::Instant::now()
-                .duration_since(cache.last_render)
-                .as_micros();
-            if RENDER_DT < delta {
+            let delta = std::time::Instant::now().duration_since(now).as_micros();
+            if budget < delta {
                 cache.blown_render_frames += 1;
             }
             cache.count_render_frames += 1;
-            cache.render_frame_load += delta as f64 / RENDER_DT as f64;
+            cache.render_frame_load += delta as f64 / budget as f64;
 
-            self.windowed_context.swap_buffers().unwrap();
+            if let Err(e) = self.windowed_context.swap_buffers() {
+                error::exit_with(&error::Error::Context(e.to_string()));
+            }
 
             if !cache.completed_first_frame {
                 cache.completed_first_frame = true;
@@ -485,12 +696,23 @@ impl Core {
                     ));
                 }
             }
+
+            self.console.update(&self.gl, &mut self.world);
+            self.end_profiler_frame();
         }
     }
 }
 
+// "WARNING: x" and "ERROR: x" are logged at those levels, anything else as
+// info, under the category of the calling module
+#[track_caller]
 pub fn log(output: String) {
-    if cfg!(debug_assertions) {
-        println!("{}", output);
-    }
+    let (level, message) = logging::split_level(&output);
+    log_at(level, message);
+}
+
+#[track_caller]
+pub fn log_at(level: u8, message: &str) {
+    let category = logging::category_from_file(std::panic::Location::caller().file());
+    logging::write(level, &category, message);
 }
@@ -0,0 +1,307 @@
+use crate::mgfw::log;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+// Save slots and settings, stored as RON in the platform data directory
+// ($XDG_DATA_HOME/<game> on Linux). Each file starts with a header line:
+//   mgfw-save,<version>,<crc32 of the body>
+// Writes go to a temporary file that is renamed into place, the previous
+// file is kept as .bak and used if the current one is missing or corrupt.
+
+const HEADER_TAG: &str = "mgfw-save";
+const EXT: &str = "sav";
+const SETTINGS: &str = "settings";
+
+pub trait Persist: Serialize + DeserializeOwned {
+    // bump when the format changes in a way #[serde(default)] can't absorb
+    const VERSION: u32;
+
+    // called for files written with an older VERSION, body is the RON text.
+    // the default tries the current type, which covers added fields.
+    fn migrate(version: u32, body: &str) -> Result<Self, String> {
+        log(format!("Persist: Migrating from version {}", version));
+        from_ron(body)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PersistError {
+    NotFound,
+    Io(String),
+    Corrupt(String),
+    // file was written by a newer build
+    Version { found: u32, supported: u32 },
+    Migrate(String),
+}
+
+impl fmt::Display for PersistError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PersistError::NotFound => write!(f, "not found"),
+            PersistError::Io(e) => write!(f, "{}", e),
+            PersistError::Corrupt(e) => write!(f, "corrupt: {}", e),
+            PersistError::Version { found, supported } => {
+                write!(f, "version {} is newer than supported {}", found, supported)
+            }
+            PersistError::Migrate(e) => write!(f, "migration failed: {}", e),
+        }
+    }
+}
+
+pub struct Storage {
+    dir: PathBuf,
+}
+
+#[allow(dead_code)]
+impl Storage {
+    pub fn new(game: &str) -> Storage {
+        let dir = match dirs::data_dir() {
+            Some(d) => d.join(game),
+            None => {
+                log(String::from("WARNING: Persist: no data directory, using ./save"));
+                PathBuf::from("save")
+            }
+        };
+        Storage::at(dir)
+    }
+
+    pub fn at(dir: PathBuf) -> Storage {
+        log(format!("Constructing Storage at '{}'", dir.display()));
+        Storage { dir }
+    }
+
+    pub fn get_dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn save_settings<T: Persist>(&self, data: &T) -> Result<(), PersistError> {
+        self.save(SETTINGS, data)
+    }
+
+    pub fn load_settings<T: Persist + Default>(&self) -> T {
+        self.load_or_default(SETTINGS)
+    }
+
+    pub fn save_slot<T: Persist>(&self, slot: usize, data: &T) -> Result<(), PersistError> {
+        self.save(&slot_name(slot), data)
+    }
+
+    pub fn load_slot<T: Persist>(&self, slot: usize) -> Result<T, PersistError> {
+        self.load(&slot_name(slot))
+    }
+
+    pub fn delete_slot(&self, slot: usize) -> Result<(), PersistError> {
+        self.delete(&slot_name(slot))
+    }
+
+    // slots that have a file on disk, sorted
+    pub fn list_slots(&self) -> Vec<usize> {
+        let mut ret: Vec<usize> = Vec::new();
+        if let Ok(entries) = fs::read_dir(&self.dir) {
+            for e in entries.flatten() {
+                let name = e.file_name().to_string_lossy().to_string();
+                let slot = name
+                    .strip_prefix("slot")
+                    .and_then(|s| s.strip_suffix(&format!(".{}", EXT)))
+                    .and_then(|s| s.parse::<usize>().ok());
+                if let Some(slot) = slot {
+                    ret.push(slot);
+                }
+            }
+        }
+        ret.sort_unstable();
+        ret
+    }
+
+    pub fn save<T: Persist>(&self, name: &str, data: &T) -> Result<(), PersistError> {
+        let body = ron::ser::to_string_pretty(data, ron::ser::PrettyConfig::new())
+            .map_err(|e| PersistError::Io(e.to_string()))?;
+        let buffer = format!(
+            "{},{},{:08x}\n{}",
+            HEADER_TAG,
+            T::VERSION,
+            crc32fast::hash(body.as_bytes()),
+            body
+        );
+
+        let io = |e: std::io::Error| PersistError::Io(format!("{}: {}", name, e));
+        fs::create_dir_all(&self.dir).map_err(io)?;
+
+        let path = self.path(name, EXT);
+        let tmp = self.path(name, "tmp");
+        {
+            let mut file = File::create(&tmp).map_err(io)?;
+            file.write_all(buffer.as_bytes()).map_err(io)?;
+            file.sync_all().map_err(io)?;
+        }
+        if path.exists() {
+            fs::rename(&path, self.path(name, "bak")).map_err(io)?;
+        }
+        fs::rename(&tmp, &path).map_err(io)?;
+        log(format!("Persist: Saved '{}'", path.display()));
+        Ok(())
+    }
+
+    pub fn load<T: Persist>(&self, name: &str) -> Result<T, PersistError> {
+        let ret = self.load_file(&self.path(name, EXT));
+        match ret {
+            Ok(data) => Ok(data),
+            // version and migration errors are not fixed by the backup
+            Err(PersistError::NotFound) | Err(PersistError::Corrupt(_)) => {
+                match self.load_file(&self.path(name, "bak")) {
+                    Ok(data) => {
+                        log(format!(
+                            "WARNING: Persist: '{}' {}, restored from backup",
+                            name,
+                            ret.err().unwrap()
+                        ));
+                        Ok(data)
+                    }
+                    Err(_) => ret,
+                }
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    // logs and falls back to the default on any error
+    pub fn load_or_default<T: Persist + Default>(&self, name: &str) -> T {
+        match self.load(name) {
+            Ok(data) => data,
+            Err(PersistError::NotFound) => T::default(),
+            Err(e) => {
+                log(format!("WARNING: Persist: '{}' {}, using defaults", name, e));
+                T::default()
+            }
+        }
+    }
+
+    pub fn delete(&self, name: &str) -> Result<(), PersistError> {
+        for ext in [EXT, "bak"] {
+            let path = self.path(name, ext);
+            if path.exists() {
+                fs::remove_file(&path).map_err(|e| PersistError::Io(e.to_string()))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn path(&self, name: &str, ext: &str) -> PathBuf {
+        self.dir.join(format!("{}.{}", name, ext))
+    }
+
+    fn load_file<T: Persist>(&self, path: &Path) -> Result<T, PersistError> {
+        let buffer = match fs::read(path) {
+            Ok(b) => b,
+            Err(e) if std::io::ErrorKind::NotFound == e.kind() => {
+                return Err(PersistError::NotFound)
+            }
+            Err(e) => return Err(PersistError::Io(e.to_string())),
+        };
+        let buffer = String::from_utf8(buffer)
+            .map_err(|_| PersistError::Corrupt(String::from("not valid utf-8")))?;
+        parse::<T>(&buffer)
+    }
+}
+
+fn slot_name(slot: usize) -> String {
+    format!("slot{}", slot)
+}
+
+fn from_ron<T: DeserializeOwned>(body: &str) -> Result<T, String> {
+    ron::from_str(body).map_err(|e| e.to_string())
+}
+
+fn parse<T: Persist>(buffer: &str) -> Result<T, PersistError> {
+    let corrupt = |msg: &str| PersistError::Corrupt(String::from(msg));
+
+    let (header, body) = buffer.split_once('\n').ok_or_else(|| corrupt("missing header"))?;
+    let fields: Vec<&str> = header.trim().split(',').collect();
+    if 3 != fields.len() || HEADER_TAG != fields[0] {
+        return Err(corrupt("bad header"));
+    }
+    let version = fields[1].parse::<u32>().map_err(|_| corrupt("bad version"))?;
+    let crc = u32::from_str_radix(fields[2], 16).map_err(|_| corrupt("bad checksum"))?;
+    if crc != crc32fast::hash(body.as_bytes()) {
+        return Err(corrupt("checksum mismatch"));
+    }
+
+    if version > T::VERSION {
+        return Err(PersistError::Version {
+            found: version,
+            supported: T::VERSION,
+        });
+    }
+    if version < T::VERSION {
+        return T::migrate(version, body).map_err(PersistError::Migrate);
+    }
+    from_ron(body).map_err(PersistError::Corrupt)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Serialize, Deserialize, Default, Debug, PartialEq)]
+    struct Progress {
+        level: usize,
+        #[serde(default)]
+        stars: Vec<u8>,
+    }
+
+    impl Persist for Progress {
+        const VERSION: u32 = 2;
+    }
+
+    fn storage(test: &str) -> Storage {
+        let dir = std::env::temp_dir().join(format!("mgfw-persist-{}-{}", test, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        Storage::at(dir)
+    }
+
+    #[test]
+    fn round_trip_and_slots() {
+        let s = storage("slots");
+        let p = Progress { level: 4, stars: vec![1, 3] };
+        s.save_slot(2, &p).unwrap();
+        s.save_slot(0, &p).unwrap();
+        assert_eq!(p, s.load_slot::<Progress>(2).unwrap());
+        assert_eq!(vec![0, 2], s.list_slots());
+        assert_eq!(Err(PersistError::NotFound), s.load_slot::<Progress>(1));
+        s.delete_slot(2).unwrap();
+        assert_eq!(vec![0], s.list_slots());
+        let _ = fs::remove_dir_all(s.get_dir());
+    }
+
+    #[test]
+    fn corruption_falls_back_to_backup() {
+        let s = storage("corrupt");
+        s.save("progress", &Progress { level: 1, stars: vec![] }).unwrap();
+        s.save("progress", &Progress { level: 2, stars: vec![] }).unwrap();
+
+        let path = s.get_dir().join("progress.sav");
+        let text = fs::read_to_string(&path).unwrap().replace("level: 2", "level: 9");
+        fs::write(&path, text).unwrap();
+
+        assert_eq!(1, s.load::<Progress>("progress").unwrap().level);
+        let _ = fs::remove_dir_all(s.get_dir());
+    }
+
+    #[test]
+    fn older_versions_migrate_and_newer_are_rejected() {
+        let body = "(level: 3)";
+        let old = format!("mgfw-save,1,{:08x}\n{}", crc32fast::hash(body.as_bytes()), body);
+        assert_eq!(Progress { level: 3, stars: vec![] }, parse::<Progress>(&old).unwrap());
+
+        let new = format!("mgfw-save,7,{:08x}\n{}", crc32fast::hash(body.as_bytes()), body);
+        assert_eq!(
+            Err(PersistError::Version { found: 7, supported: 2 }),
+            parse::<Progress>(&new)
+        );
+    }
+}
@@ -0,0 +1,362 @@
+use super::ecs::component_render_text::TextSpan;
+use super::ecs::{Color, World};
+use super::log;
+use super::support::Gl;
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::time::{Instant, SystemTime};
+
+// Times the systems Core runs every frame. F3 shows the numbers on screen,
+// F4 writes the last few seconds as a Chrome trace (chrome://tracing or
+// https://ui.perfetto.dev) with blown update frames marked, so spikes can be
+// matched to what caused them. Traces go to a traces directory beside the logs.
+
+pub const TRACE_UPDATE: u8 = 1;
+pub const TRACE_RENDER: u8 = 2;
+
+const TRACE_CAPACITY: usize = 65536;
+const GRAPH_FRAMES: usize = 120;
+const REFRESH_MS: u128 = 250;
+const FRAME_BUDGET_US: f32 = 16666.0;
+
+const PANEL_X: f32 = 4.0;
+const PANEL_Y: f32 = 4.0;
+const PANEL_W: f32 = 300.0;
+const LINE_HEIGHT: f32 = 13.0;
+const GRAPH_HEIGHT: f32 = 40.0;
+
+struct TraceEvent {
+    name: &'static str,
+    tid: u8,
+    // microseconds since the profiler started
+    start: u64,
+    dur: u64,
+    blown: bool,
+    expected: bool,
+}
+
+struct Timing {
+    name: &'static str,
+    tid: u8,
+    avg: f64,
+    // since the last overlay refresh
+    max: u64,
+}
+
+#[derive(Clone, Copy, Default)]
+pub struct ProfilerCounters {
+    pub draw_calls: usize,
+    pub entities: usize,
+    pub vbos: usize,
+    pub vbo_rebuilds: usize,
+    pub cache_used: usize,
+    pub cache_size: usize,
+    pub blown_update_frames: usize,
+    pub blown_update_frames_expected: usize,
+    pub blown_render_frames: usize,
+}
+
+pub struct Profiler {
+    dir: PathBuf,
+    start: Instant,
+    visible: bool,
+    timings: Vec<Timing>,
+    trace: VecDeque<TraceEvent>,
+    // update and render work per displayed frame, microseconds
+    graph: VecDeque<f32>,
+    work: u64,
+    counters: ProfilerCounters,
+    vbo_rebuilds: usize,
+    last_refresh: Instant,
+    text_vao: u32,
+    text_vbo: u32,
+    spans: Vec<TextSpan>,
+    panel_vao: u32,
+    panel_vbo: u32,
+    num_triangles: usize,
+}
+
+#[allow(dead_code)]
+impl Profiler {
+    pub fn new(title: &str, gl: &Gl) -> Profiler {
+        log(format!("Constructing Profiler"));
+        Profiler {
+            dir: super::logging::default_dir(title).with_file_name("traces"),
+            start: Instant::now(),
+            visible: false,
+            timings: Vec::new(),
+            trace: VecDeque::with_capacity(TRACE_CAPACITY),
+            graph: VecDeque::with_capacity(GRAPH_FRAMES),
+            work: 0,
+            counters: ProfilerCounters::default(),
+            vbo_rebuilds: 0,
+            last_refresh: Instant::now(),
+            text_vao: gl.gen_vao(),
+            text_vbo: gl.gen_vbo(),
+            spans: Vec::new(),
+            panel_vao: gl.gen_vao(),
+            panel_vbo: gl.gen_vbo(),
+            num_triangles: 0,
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+        // rebuild on the next update
+        self.last_refresh = self.start;
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    // records `name` as running from `start` until now, returns microseconds
+    pub fn record(&mut self, name: &'static str, tid: u8, start: Instant) -> u64 {
+        let dur = start.elapsed().as_micros() as u64;
+        self.record_span(name, tid, start.duration_since(self.start).as_micros() as u64, dur);
+        dur
+    }
+
+    fn record_span(&mut self, name: &'static str, tid: u8, start: u64, dur: u64) {
+        if TRACE_CAPACITY == self.trace.len() {
+            self.trace.pop_front();
+        }
+        self.trace.push_back(TraceEvent {
+            name,
+            tid,
+            start,
+            dur,
+            blown: false,
+            expected: false,
+        });
+
+        match self.timings.iter_mut().find(|t| t.name == name && t.tid == tid) {
+            Some(t) => {
+                t.avg = t.avg * 0.95 + dur as f64 * 0.05;
+                t.max = t.max.max(dur);
+            }
+            None => self.timings.push(Timing {
+                name,
+                tid,
+                avg: dur as f64,
+                max: dur,
+            }),
+        }
+
+        // nested scopes are part of their update/render frame
+        if "update" == name || "render" == name {
+            self.work += dur;
+        }
+    }
+
+    // flags the last recorded event as a blown frame
+    pub fn mark_blown(&mut self, expected: bool) {
+        if let Some(e) = self.trace.back_mut() {
+            e.blown = true;
+            e.expected = expected;
+        }
+    }
+
+    // once per displayed frame
+    pub fn end_frame(&mut self, counters: ProfilerCounters) {
+        if GRAPH_FRAMES == self.graph.len() {
+            self.graph.pop_front();
+        }
+        self.graph.push_back(self.work as f32);
+        self.work = 0;
+        self.vbo_rebuilds += counters.vbo_rebuilds;
+        self.counters = counters;
+    }
+
+    // rebuilds the overlay a few times a second while it is shown
+    pub fn update(&mut self, gl: &Gl, world: &mut World) {
+        if !self.visible || REFRESH_MS > self.last_refresh.elapsed().as_millis() {
+            return;
+        }
+        self.last_refresh = Instant::now();
+
+        let text = self.get_text();
+        self.spans = world.text_build(&text, LINE_HEIGHT, gl, self.text_vao, self.text_vbo);
+        let lines = text.lines().count() as f32;
+        self.build_panel(gl, lines * LINE_HEIGHT + 8.0);
+
+        for t in self.timings.iter_mut() {
+            t.max = 0;
+        }
+        self.vbo_rebuilds = 0;
+    }
+
+    fn get_text(&self) -> String {
+        let c = &self.counters;
+        let mut text = String::new();
+        for tid in [TRACE_UPDATE, TRACE_RENDER] {
+            for t in self.timings.iter().filter(|t| t.tid == tid) {
+                let indent = match t.name {
+                    "update" | "render" => "",
+                    _ => "  ",
+                };
+                text += &format!("{}{:<12} {:>7.1}us {:>7}us max\n", indent, t.name, t.avg, t.max);
+            }
+        }
+        text += &format!("draw calls {}  entities {}\n", c.draw_calls, c.entities);
+        text += &format!("vbos {}  rebuilt {}/{}ms\n", c.vbos, self.vbo_rebuilds, REFRESH_MS);
+        text += &format!(
+            "cache {:.1}/{} KB ({}%)\n",
+            c.cache_used as f32 / 1024.0,
+            c.cache_size / 1024,
+            c.cache_used * 100 / c.cache_size.max(1)
+        );
+        text += &format!(
+            "blown update {} ({} expected) render {}\n",
+            c.blown_update_frames, c.blown_update_frames_expected, c.blown_render_frames
+        );
+        text += "F4: export trace";
+        text
+    }
+
+    // background, and a bar per frame under the text scaled to the 60hz budget
+    fn build_panel(&mut self, gl: &Gl, text_height: f32) {
+        let mut data: Vec<f32> = Vec::new();
+        let mut quad = |x0: f32, y0: f32, x1: f32, y1: f32, c: [f32; 4]| {
+            for (x, y) in [(x0, y0), (x0, y1), (x1, y1), (x0, y0), (x1, y1), (x1, y0)] {
+                data.extend_from_slice(&[x, y, c[0], c[1], c[2], c[3]]);
+            }
+        };
+
+        let h = text_height + GRAPH_HEIGHT + 4.0;
+        quad(PANEL_X, PANEL_Y, PANEL_X + PANEL_W, PANEL_Y + h, [0.0, 0.0, 0.0, 0.7]);
+
+        let base = PANEL_Y + h - 4.0;
+        let w = (PANEL_W - 8.0) / GRAPH_FRAMES as f32;
+        for (i, us) in self.graph.iter().enumerate() {
+            let frac = us / FRAME_BUDGET_US;
+            let c = match frac {
+                f if 1.0 < f => [1.0, 0.2, 0.2, 1.0],
+                f if 0.5 < f => [1.0, 0.8, 0.2, 1.0],
+                _ => [0.3, 0.9, 0.3, 1.0],
+            };
+            let x = PANEL_X + 4.0 + i as f32 * w;
+            quad(x, base - frac.min(1.0) * GRAPH_HEIGHT, x + w * 0.8, base, c);
+        }
+        // budget line
+        quad(PANEL_X + 4.0, base - GRAPH_HEIGHT, PANEL_X + PANEL_W - 4.0, base - GRAPH_HEIGHT + 1.0, [1.0, 1.0, 1.0, 0.3]);
+
+        self.num_triangles = data.len() / 18;
+        gl.buffer_triangle_data(self.panel_vao, self.panel_vbo, self.num_triangles, data.as_ptr() as *const _);
+    }
+
+    pub fn draw(&self, gl: &Gl) {
+        if !self.visible {
+            return;
+        }
+        let white = Color {
+            r: 1.0,
+            g: 1.0,
+            b: 1.0,
+            a: 1.0,
+        };
+        gl.draw_triangles(0.0, 0.0, 0.0, 1.0, 1.0, self.panel_vao, self.num_triangles, white);
+        for span in self.spans.iter() {
+            gl.draw_text(
+                PANEL_X + 4.0,
+                PANEL_Y + 4.0,
+                0.0,
+                1.0,
+                1.0,
+                self.text_vao,
+                span.first,
+                span.count,
+                span.tex,
+                white,
+            );
+        }
+    }
+
+    pub fn export_trace(&self) {
+        let secs = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        let file = self.dir.join(format!("trace_{}.json", secs));
+        let res = std::fs::create_dir_all(&self.dir).and_then(|_| std::fs::write(&file, self.get_trace_json()));
+        match res {
+            Ok(_) => log(format!("Profiler: wrote {} events to {}", self.trace.len(), file.display())),
+            Err(e) => log(format!("WARNING: Profiler: failed to write {}: {}", file.display(), e)),
+        }
+    }
+
+    // Chrome trace event format, one complete ("X") event per record
+    pub fn get_trace_json(&self) -> String {
+        let mut events: Vec<String> = Vec::new();
+        for (tid, name) in [(TRACE_UPDATE, "update"), (TRACE_RENDER, "render")] {
+            events.push(format!(
+                "{{\"name\":\"thread_name\",\"ph\":\"M\",\"pid\":1,\"tid\":{},\"args\":{{\"name\":\"{}\"}}}}",
+                tid, name
+            ));
+        }
+        for e in self.trace.iter() {
+            let args = match e.blown {
+                true => format!(",\"args\":{{\"blown\":true,\"expected\":{}}}", e.expected),
+                false => String::new(),
+            };
+            events.push(format!(
+                "{{\"name\":\"{}\",\"cat\":\"{}\",\"ph\":\"X\",\"pid\":1,\"tid\":{},\"ts\":{},\"dur\":{}{}}}",
+                e.name,
+                if e.blown { "blown" } else { "frame" },
+                e.tid,
+                e.start,
+                e.dur,
+                args
+            ));
+        }
+        format!(
+            "{{\"displayTimeUnit\":\"ms\",\"traceEvents\":[\n{}\n]}}\n",
+            events.join(",\n")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profiler() -> Profiler {
+        Profiler {
+            dir: PathBuf::new(),
+            start: Instant::now(),
+            visible: false,
+            timings: Vec::new(),
+            trace: VecDeque::new(),
+            graph: VecDeque::new(),
+            work: 0,
+            counters: ProfilerCounters::default(),
+            vbo_rebuilds: 0,
+            last_refresh: Instant::now(),
+            text_vao: 0,
+            text_vbo: 0,
+            spans: Vec::new(),
+            panel_vao: 0,
+            panel_vbo: 0,
+            num_triangles: 0,
+        }
+    }
+
+    #[test]
+    fn trace_marks_blown_frames() {
+        let mut p = profiler();
+        p.record_span("game", TRACE_UPDATE, 10, 5);
+        p.record_span("update", TRACE_UPDATE, 10, 900);
+        p.mark_blown(true);
+        p.record_span("render", TRACE_RENDER, 1000, 300);
+
+        let json = p.get_trace_json();
+        assert!(json.starts_with("{\"displayTimeUnit\":\"ms\",\"traceEvents\":["));
+        assert!(json.contains("{\"name\":\"game\",\"cat\":\"frame\",\"ph\":\"X\",\"pid\":1,\"tid\":1,\"ts\":10,\"dur\":5}"));
+        assert!(json.contains("\"name\":\"update\",\"cat\":\"blown\""));
+        assert!(json.contains("\"args\":{\"blown\":true,\"expected\":true}"));
+        assert_eq!(5, json.matches("\"ph\":").count());
+
+        p.end_frame(ProfilerCounters::default());
+        assert_eq!(Some(&1200.0), p.graph.back());
+        assert_eq!(3, p.timings.len());
+    }
+}
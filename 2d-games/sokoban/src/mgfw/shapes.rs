@@ -0,0 +1,532 @@
+use super::ecs::{Color, Position};
+
+// Tessellates vector shapes into a triangle list for entity_set_shape() or
+// entity_set_triangle_buffer(). Shapes are added in the current color and
+// accumulate, so one entity can hold a whole drawing:
+//
+//   let mut shape = Shape::new();
+//   shape.set_antialias(1.0);
+//   shape.set_color(Color { r: 1.0, g: 0.8, b: 0.0, a: 1.0 });
+//   shape.circle(0.0, 0.0, 20.0);
+//   shape.polyline(&track, 6.0, SHAPE_JOIN_ROUND, SHAPE_CAP_BUTT, true);
+//   world.entity_set_shape(idx, &shape);
+//
+// Anti-aliasing adds a fringe of the given width around every edge that fades
+// to transparent, so shapes grow by that much on each side.
+
+pub const SHAPE_JOIN_MITER: u8 = 0;
+pub const SHAPE_JOIN_BEVEL: u8 = 1;
+pub const SHAPE_JOIN_ROUND: u8 = 2;
+
+pub const SHAPE_CAP_BUTT: u8 = 0;
+pub const SHAPE_CAP_SQUARE: u8 = 1;
+pub const SHAPE_CAP_ROUND: u8 = 2;
+
+// miter joins longer than this many half widths become bevels
+const MITER_LIMIT: f32 = 4.0;
+const EPSILON: f32 = 1.0e-6;
+
+pub struct Shape {
+    pnts: Vec<Position>,
+    clrs: Vec<Color>,
+    color: Color,
+    // segments in a full circle, arcs use a share of it
+    segments: usize,
+    antialias: f32,
+}
+
+impl Default for Shape {
+    fn default() -> Shape {
+        Shape::new()
+    }
+}
+
+#[allow(dead_code)]
+impl Shape {
+    pub fn new() -> Shape {
+        Shape {
+            pnts: Vec::new(),
+            clrs: Vec::new(),
+            color: Color {
+                r: 1.0,
+                g: 1.0,
+                b: 1.0,
+                a: 1.0,
+            },
+            segments: 32,
+            antialias: 0.0,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.pnts.clear();
+        self.clrs.clear();
+    }
+
+    pub fn set_color(&mut self, color: Color) {
+        self.color = color;
+    }
+
+    pub fn set_segments(&mut self, segments: usize) {
+        self.segments = segments.max(3);
+    }
+
+    // fringe width in pixels, 0 to turn anti-aliasing off
+    pub fn set_antialias(&mut self, width: f32) {
+        self.antialias = width.max(0.0);
+    }
+
+    pub fn get_points(&self) -> &Vec<Position> {
+        &self.pnts
+    }
+
+    pub fn get_colors(&self) -> &Vec<Color> {
+        &self.clrs
+    }
+
+    pub fn get_num_triangles(&self) -> usize {
+        self.pnts.len() / 3
+    }
+
+    pub fn triangle(&mut self, a: Position, b: Position, c: Position) {
+        self.polygon(&[a, b, c]);
+    }
+
+    pub fn rect(&mut self, x: f32, y: f32, w: f32, h: f32) {
+        self.polygon(&[pos(x, y), pos(x + w, y), pos(x + w, y + h), pos(x, y + h)]);
+    }
+
+    // corner radius is clamped to half the shorter side
+    pub fn rounded_rect(&mut self, x: f32, y: f32, w: f32, h: f32, radius: f32) {
+        let r = radius.min(0.5 * w.abs()).min(0.5 * h.abs()).max(0.0);
+        if r < EPSILON {
+            self.rect(x, y, w, h);
+            return;
+        }
+        let q = std::f32::consts::FRAC_PI_2;
+        let mut outline: Vec<Position> = Vec::new();
+        for (cx, cy, start) in [
+            (x + w - r, y + r, -q),
+            (x + w - r, y + h - r, 0.0),
+            (x + r, y + h - r, q),
+            (x + r, y + r, 2.0 * q),
+        ] {
+            outline.extend(self.arc_points(cx, cy, r, start, start + q));
+        }
+        self.polygon(&outline);
+    }
+
+    pub fn circle(&mut self, cx: f32, cy: f32, radius: f32) {
+        let tau = std::f32::consts::TAU;
+        let mut outline = self.arc_points(cx, cy, radius, 0.0, tau);
+        outline.pop();
+        self.polygon(&outline);
+    }
+
+    // filled slice between two angles in radians
+    pub fn pie(&mut self, cx: f32, cy: f32, radius: f32, start: f32, end: f32) {
+        let mut outline = vec![pos(cx, cy)];
+        outline.extend(self.arc_points(cx, cy, radius, start, end));
+        self.polygon(&outline);
+    }
+
+    // stroked arc between two angles in radians
+    pub fn arc(&mut self, cx: f32, cy: f32, radius: f32, start: f32, end: f32, width: f32, cap: u8) {
+        let pnts = self.arc_points(cx, cy, radius, start, end);
+        self.polyline(&pnts, width, SHAPE_JOIN_MITER, cap, false);
+    }
+
+    // filled polygon of any winding, concave is fine, self-intersecting is not
+    pub fn polygon(&mut self, pnts: &[Position]) {
+        let mut outline = dedup(pnts, true);
+        if outline.len() < 3 {
+            return;
+        }
+        // counter clockwise from here on
+        if signed_area(&outline) < 0.0 {
+            outline.reverse();
+        }
+        let color = self.color;
+        for [a, b, c] in triangulate(&outline) {
+            self.push(outline[a], color);
+            self.push(outline[b], color);
+            self.push(outline[c], color);
+        }
+        if 0.0 < self.antialias {
+            // right of a counter clockwise edge is outside
+            let clear = transparent(color);
+            let rails = [(0.0, color), (-self.antialias, clear)];
+            self.rails(&outline, true, SHAPE_JOIN_MITER, &rails);
+        }
+    }
+
+    // unknown joins are drawn bevelled and unknown caps butt
+    pub fn polyline(&mut self, pnts: &[Position], width: f32, join: u8, cap: u8, closed: bool) {
+        let join = match join {
+            SHAPE_JOIN_MITER | SHAPE_JOIN_ROUND => join,
+            _ => SHAPE_JOIN_BEVEL,
+        };
+        let cap = match cap {
+            SHAPE_CAP_SQUARE | SHAPE_CAP_ROUND => cap,
+            _ => SHAPE_CAP_BUTT,
+        };
+        let mut path = dedup(pnts, closed);
+        if path.len() < 2 || width <= 0.0 {
+            return;
+        }
+        let closed = closed && 2 < path.len();
+        let hw = 0.5 * width;
+
+        if !closed && SHAPE_CAP_SQUARE == cap {
+            let n = path.len();
+            let d = dir(path[1], path[0]);
+            path[0] = add(path[0], d, hw);
+            let d = dir(path[n - 2], path[n - 1]);
+            path[n - 1] = add(path[n - 1], d, hw);
+        }
+
+        let color = self.color;
+        let clear = transparent(color);
+        let aa = self.antialias;
+        self.rails(&path, closed, join, &[(-hw, color), (hw, color)]);
+        if 0.0 < aa {
+            self.rails(&path, closed, join, &[(-hw - aa, clear), (-hw, color)]);
+            self.rails(&path, closed, join, &[(hw, color), (hw + aa, clear)]);
+        }
+        if closed {
+            return;
+        }
+
+        let n = path.len();
+        for (p, d) in [(path[0], dir(path[1], path[0])), (path[n - 1], dir(path[n - 2], path[n - 1]))] {
+            let nrm = perp(d);
+            match cap {
+                SHAPE_CAP_ROUND => {
+                    // half circle from the left side to the right, going
+                    // clockwise passes through the outward direction d
+                    let start = nrm.y.atan2(nrm.x);
+                    let turn = -std::f32::consts::PI;
+                    let inner = self.arc_points(p.x, p.y, hw, start, start + turn);
+                    for k in 0..inner.len() - 1 {
+                        self.tri(p, inner[k], inner[k + 1], color, color, color);
+                    }
+                    if 0.0 < aa {
+                        let outer = self.arc_points(p.x, p.y, hw + aa, start, start + turn);
+                        self.strip(&inner, &outer, color, clear);
+                    }
+                }
+                _ if 0.0 < aa => {
+                    // fringe across the flat end, corners included
+                    let inner = [add(p, nrm, -hw - aa), add(p, nrm, -hw), add(p, nrm, hw), add(p, nrm, hw + aa)];
+                    let outer: Vec<Position> = inner.iter().map(|q| add(*q, d, aa)).collect();
+                    let c = [clear, color, color, clear];
+                    for k in 0..3 {
+                        self.tri(inner[k], inner[k + 1], outer[k], c[k], c[k + 1], clear);
+                        self.tri(inner[k + 1], outer[k + 1], outer[k], c[k + 1], clear, clear);
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+
+    // points from start to end angle inclusive, spaced by the segment count
+    fn arc_points(&self, cx: f32, cy: f32, radius: f32, start: f32, end: f32) -> Vec<Position> {
+        let steps = arc_steps(end - start, self.segments);
+        (0..=steps)
+            .map(|k| {
+                let a = start + (end - start) * k as f32 / steps as f32;
+                pos(cx + radius * a.cos(), cy + radius * a.sin())
+            })
+            .collect()
+    }
+
+    // band between two offsets from the path, each with its own color.
+    // positive offsets are left of the direction of travel.
+    fn rails(&mut self, path: &[Position], closed: bool, join: u8, rails: &[(f32, Color); 2]) {
+        let n = path.len();
+        let (o0, c0) = rails[0];
+        let (o1, c1) = rails[1];
+
+        let mut joins: Vec<(Vec<Position>, Vec<Position>)> = Vec::new();
+        for i in 0..n {
+            let prev = match (i, closed) {
+                (0, true) => path[n - 1],
+                (0, false) => path[0],
+                _ => path[i - 1],
+            };
+            let next = match (i + 1 == n, closed) {
+                (true, true) => path[0],
+                (true, false) => path[i],
+                _ => path[i + 1],
+            };
+            let d1 = match i + 1 == n && !closed {
+                true => dir(prev, path[i]),
+                false => dir(path[i], next),
+            };
+            let d0 = match 0 == i && !closed {
+                true => d1,
+                false => dir(prev, path[i]),
+            };
+            let a = self.join_points(path[i], d0, d1, o0, join);
+            let b = self.join_points(path[i], d0, d1, o1, join);
+            self.strip(&a, &b, c0, c1);
+            joins.push((a, b));
+        }
+
+        let segments = match closed {
+            true => n,
+            false => n - 1,
+        };
+        for i in 0..segments {
+            let j = (i + 1) % n;
+            let a0 = *joins[i].0.last().unwrap();
+            let b0 = *joins[i].1.last().unwrap();
+            let a1 = joins[j].0[0];
+            let b1 = joins[j].1[0];
+            self.tri(a0, b0, a1, c0, c1, c0);
+            self.tri(b0, b1, a1, c1, c1, c0);
+        }
+    }
+
+    // where the rail at offset o passes the joint p, in order of travel.
+    // one point on the inside of a turn, one or more around the outside.
+    fn join_points(&self, p: Position, d0: Position, d1: Position, o: f32, join: u8) -> Vec<Position> {
+        let n0 = perp(d0);
+        let n1 = perp(d1);
+        let turn = cross(d0, d1);
+        if turn.abs() < EPSILON && 0.0 < dot(d0, d1) {
+            return vec![add(p, n1, o)];
+        }
+
+        let sum = pos(n0.x + n1.x, n0.y + n1.y);
+        let len = length(sum);
+        // turning left puts the positive side on the inside
+        let outside = (0.0 < turn && o < 0.0) || (turn < 0.0 && 0.0 < o);
+        let cos_half = match EPSILON < len {
+            true => dot(scale(sum, 1.0 / len), n1),
+            false => 0.0,
+        };
+
+        if !outside {
+            if cos_half < EPSILON {
+                return vec![p];
+            }
+            let m = scale(sum, 1.0 / len);
+            return vec![add(p, m, o / cos_half.max(1.0 / MITER_LIMIT))];
+        }
+
+        if SHAPE_JOIN_MITER == join && 1.0 / MITER_LIMIT < cos_half {
+            let m = scale(sum, 1.0 / len);
+            return vec![add(p, m, o / cos_half)];
+        }
+        if SHAPE_JOIN_ROUND == join {
+            let a0 = (n0.y * o).atan2(n0.x * o);
+            let mut a1 = (n1.y * o).atan2(n1.x * o);
+            let pi = std::f32::consts::PI;
+            while a1 - a0 > pi {
+                a1 -= 2.0 * pi;
+            }
+            while a0 - a1 > pi {
+                a1 += 2.0 * pi;
+            }
+            return self.arc_points(p.x, p.y, o.abs(), a0, a1);
+        }
+        vec![add(p, n0, o), add(p, n1, o)]
+    }
+
+    // quads between two equally long point lists, or a fan when one of them
+    // is a single point
+    fn strip(&mut self, a: &[Position], b: &[Position], ca: Color, cb: Color) {
+        if a.len() == b.len() {
+            for k in 0..a.len() - 1 {
+                self.tri(a[k], b[k], a[k + 1], ca, cb, ca);
+                self.tri(b[k], b[k + 1], a[k + 1], cb, cb, ca);
+            }
+        } else if 1 == a.len() {
+            for k in 0..b.len() - 1 {
+                self.tri(a[0], b[k], b[k + 1], ca, cb, cb);
+            }
+        } else if 1 == b.len() {
+            for k in 0..a.len() - 1 {
+                self.tri(b[0], a[k], a[k + 1], cb, ca, ca);
+            }
+        }
+    }
+
+    fn tri(&mut self, a: Position, b: Position, c: Position, ca: Color, cb: Color, cc: Color) {
+        self.push(a, ca);
+        self.push(b, cb);
+        self.push(c, cc);
+    }
+
+    fn push(&mut self, p: Position, c: Color) {
+        self.pnts.push(p);
+        self.clrs.push(c);
+    }
+}
+
+// ear clipping, outline must be counter clockwise. returns index triples.
+fn triangulate(outline: &[Position]) -> Vec<[usize; 3]> {
+    let mut ret: Vec<[usize; 3]> = Vec::new();
+    let mut remaining: Vec<usize> = (0..outline.len()).collect();
+
+    while 3 < remaining.len() {
+        let n = remaining.len();
+        let ear = (0..n).find(|&i| {
+            let a = outline[remaining[(i + n - 1) % n]];
+            let b = outline[remaining[i]];
+            let c = outline[remaining[(i + 1) % n]];
+            if cross(sub(b, a), sub(c, b)) <= EPSILON {
+                return false;
+            }
+            remaining
+                .iter()
+                .map(|&k| outline[k])
+                .filter(|p| !same(*p, a) && !same(*p, b) && !same(*p, c))
+                .all(|p| !in_triangle(p, a, b, c))
+        });
+        // degenerate or self-intersecting outline, clip anyway
+        let i = ear.unwrap_or(0);
+        ret.push([remaining[(i + n - 1) % n], remaining[i], remaining[(i + 1) % n]]);
+        remaining.remove(i);
+    }
+    ret.push([remaining[0], remaining[1], remaining[2]]);
+    ret
+}
+
+fn arc_steps(sweep: f32, segments: usize) -> usize {
+    ((sweep.abs() / std::f32::consts::TAU * segments as f32).ceil() as usize).max(1)
+}
+
+// drops repeated points, including a closing point equal to the first
+fn dedup(pnts: &[Position], closed: bool) -> Vec<Position> {
+    let mut ret: Vec<Position> = Vec::new();
+    for p in pnts {
+        match ret.last() {
+            Some(q) if same(*p, *q) => (),
+            _ => ret.push(*p),
+        }
+    }
+    if closed && 1 < ret.len() && same(ret[0], ret[ret.len() - 1]) {
+        ret.pop();
+    }
+    ret
+}
+
+fn signed_area(pnts: &[Position]) -> f32 {
+    let n = pnts.len();
+    (0..n)
+        .map(|i| cross(pnts[i], pnts[(i + 1) % n]))
+        .sum::<f32>()
+        * 0.5
+}
+
+fn in_triangle(p: Position, a: Position, b: Position, c: Position) -> bool {
+    0.0 <= cross(sub(b, a), sub(p, a)) && 0.0 <= cross(sub(c, b), sub(p, b)) && 0.0 <= cross(sub(a, c), sub(p, c))
+}
+
+fn transparent(c: Color) -> Color {
+    Color { a: 0.0, ..c }
+}
+
+fn pos(x: f32, y: f32) -> Position {
+    Position { x, y }
+}
+
+fn same(a: Position, b: Position) -> bool {
+    (a.x - b.x).abs() < EPSILON && (a.y - b.y).abs() < EPSILON
+}
+
+fn sub(a: Position, b: Position) -> Position {
+    pos(a.x - b.x, a.y - b.y)
+}
+
+fn add(p: Position, d: Position, s: f32) -> Position {
+    pos(p.x + d.x * s, p.y + d.y * s)
+}
+
+fn scale(p: Position, s: f32) -> Position {
+    pos(p.x * s, p.y * s)
+}
+
+fn dot(a: Position, b: Position) -> f32 {
+    a.x * b.x + a.y * b.y
+}
+
+fn cross(a: Position, b: Position) -> f32 {
+    a.x * b.y - a.y * b.x
+}
+
+fn length(p: Position) -> f32 {
+    dot(p, p).sqrt()
+}
+
+// unit direction from a to b
+fn dir(a: Position, b: Position) -> Position {
+    let d = sub(b, a);
+    let len = length(d);
+    match EPSILON < len {
+        true => scale(d, 1.0 / len),
+        false => pos(1.0, 0.0),
+    }
+}
+
+// left normal
+fn perp(d: Position) -> Position {
+    pos(-d.y, d.x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn area(shape: &Shape) -> f32 {
+        shape
+            .get_points()
+            .chunks(3)
+            .map(|t| signed_area(t).abs())
+            .sum()
+    }
+
+    #[test]
+    fn concave_polygon_covers_its_area() {
+        // L shape, clockwise, area 3
+        let l = [pos(0.0, 0.0), pos(0.0, 2.0), pos(1.0, 2.0), pos(1.0, 1.0), pos(2.0, 1.0), pos(2.0, 0.0)];
+        let mut shape = Shape::new();
+        shape.polygon(&l);
+        assert_eq!(4, shape.get_num_triangles());
+        assert!((area(&shape) - 3.0).abs() < 1.0e-4);
+        assert_eq!(shape.get_points().len(), shape.get_colors().len());
+    }
+
+    #[test]
+    fn polyline_joins_and_caps() {
+        let path = [pos(0.0, 0.0), pos(10.0, 0.0), pos(10.0, 10.0)];
+        let mut shape = Shape::new();
+        shape.polyline(&path, 2.0, SHAPE_JOIN_MITER, SHAPE_CAP_BUTT, false);
+        // 11x2 along x with the mitered corner, then 2x9 up
+        assert!((area(&shape) - 40.0).abs() < 1.0e-3);
+
+        shape.clear();
+        shape.polyline(&path, 2.0, SHAPE_JOIN_BEVEL, SHAPE_CAP_SQUARE, false);
+        // the bevel cuts half a square off the corner, each cap adds 1x2
+        assert!((area(&shape) - (40.0 - 0.5 + 4.0)).abs() < 1.0e-3);
+
+        // unknown styles fall back to bevel and butt
+        shape.clear();
+        shape.polyline(&path, 2.0, 9, 9, false);
+        assert!((area(&shape) - (40.0 - 0.5)).abs() < 1.0e-3);
+
+        shape.clear();
+        shape.set_segments(64);
+        shape.circle(0.0, 0.0, 10.0);
+        assert!((area(&shape) - 314.159).abs() < 2.0);
+        let plain = shape.get_num_triangles();
+        shape.clear();
+        shape.set_antialias(1.0);
+        shape.circle(0.0, 0.0, 10.0);
+        assert!(plain < shape.get_num_triangles());
+        assert!(shape.get_colors().iter().any(|c| 0.0 == c.a));
+    }
+}
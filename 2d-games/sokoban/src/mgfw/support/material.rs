@@ -0,0 +1,194 @@
+use super::super::ecs::{Material, MaterialValue};
+use super::super::log;
+use super::{gl, Gl, Shader};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::ops::Deref;
+use std::rc::Rc;
+
+// Programs for entity materials, compiled the first time a material is drawn
+// and shared by every material with the same shader pair. A program that
+// fails to build is logged once and the default shader is used instead.
+
+pub(super) struct MaterialProgram {
+    shader: Shader,
+    uniform_time: gl::types::GLint,
+    locations: RefCell<HashMap<String, gl::types::GLint>>,
+}
+
+// the shader a draw call ends up using
+pub(super) enum ShaderRef<'a> {
+    Default(&'a Shader),
+    Material(Rc<MaterialProgram>),
+}
+
+impl<'a> Deref for ShaderRef<'a> {
+    type Target = Shader;
+
+    fn deref(&self) -> &Shader {
+        match self {
+            ShaderRef::Default(s) => s,
+            ShaderRef::Material(p) => &p.shader,
+        }
+    }
+}
+
+// (vertex, fragment) -> program, None if it failed to build
+type ProgramCache = HashMap<(String, String), Option<Rc<MaterialProgram>>>;
+
+pub struct Materials {
+    programs: RefCell<ProgramCache>,
+    textures: RefCell<HashMap<String, u32>>,
+    // material for the next draw calls, set by the render system
+    active: RefCell<Option<Rc<Material>>>,
+    start: std::time::Instant,
+}
+
+impl Materials {
+    pub fn new() -> Materials {
+        log(format!("Constructing Materials"));
+        Materials {
+            programs: RefCell::new(HashMap::new()),
+            textures: RefCell::new(HashMap::new()),
+            active: RefCell::new(None),
+            start: std::time::Instant::now(),
+        }
+    }
+
+    pub fn bind(&self, material: Option<Rc<Material>>) {
+        *self.active.borrow_mut() = material;
+    }
+
+    // uses the active material's program, falling back to `default`, and
+    // sets the material uniforms. The caller sets the default uniforms.
+    pub(super) fn use_shader<'a>(&self, owner: &Gl, default: &'a Shader) -> ShaderRef<'a> {
+        let gl = &owner.gl;
+        let active = self.active.borrow();
+        let material = match active.as_ref() {
+            Some(m) => m,
+            None => {
+                default.use_program(gl);
+                return ShaderRef::Default(default);
+            }
+        };
+
+        let program = match self.get_program(gl, material, default) {
+            Some(p) => p,
+            None => {
+                default.use_program(gl);
+                return ShaderRef::Default(default);
+            }
+        };
+
+        program.shader.use_program(gl);
+        unsafe {
+            gl.Uniform1f(program.uniform_time, self.start.elapsed().as_secs_f32());
+
+            // unit 0 belongs to the draw call's own texture
+            let mut unit: u32 = 1;
+            for (name, value) in material.uniforms.iter() {
+                let location = program.location(gl, name);
+                match value {
+                    MaterialValue::Float(v) => gl.Uniform1f(location, *v),
+                    MaterialValue::Vec2(v) => gl.Uniform2f(location, v[0], v[1]),
+                    MaterialValue::Vec3(v) => gl.Uniform3f(location, v[0], v[1], v[2]),
+                    MaterialValue::Vec4(v) => gl.Uniform4f(location, v[0], v[1], v[2], v[3]),
+                    MaterialValue::Texture(image) => {
+                        gl.ActiveTexture(gl::TEXTURE0 + unit);
+                        gl.BindTexture(gl::TEXTURE_2D, self.get_texture(owner, image));
+                        gl.Uniform1i(location, unit as i32);
+                        unit += 1;
+                    }
+                }
+            }
+            gl.ActiveTexture(gl::TEXTURE0);
+        }
+        ShaderRef::Material(program)
+    }
+
+    fn get_program(&self, gl: &gl::Gl, material: &Material, default: &Shader) -> Option<Rc<MaterialProgram>> {
+        let vs = match material.vertex.is_empty() {
+            true => default.vs_src.clone(),
+            false => material.vertex.clone(),
+        };
+        let fs = match material.fragment.is_empty() {
+            true => default.fs_src.clone(),
+            false => material.fragment.clone(),
+        };
+
+        let mut programs = self.programs.borrow_mut();
+        programs
+            .entry((vs, fs))
+            .or_insert_with_key(|(vs, fs)| match Shader::load(gl, vs, fs) {
+                Ok(shader) => Some(Rc::new(MaterialProgram::new(gl, shader))),
+                Err(e) => {
+                    log(format!("WARNING: Material: {}, using default shader", e));
+                    None
+                }
+            })
+            .clone()
+    }
+
+    // loaded through Gl so they are hot reloaded like any other texture
+    fn get_texture(&self, owner: &Gl, image: &String) -> u32 {
+        let mut textures = self.textures.borrow_mut();
+        match textures.get(image) {
+            Some(t) => *t,
+            None => {
+                let t = owner.load_texture(image);
+                textures.insert(image.clone(), t);
+                t
+            }
+        }
+    }
+
+    pub fn get_shader_files(&self) -> Vec<String> {
+        let mut files: Vec<String> = Vec::new();
+        for (vs, fs) in self.programs.borrow().keys() {
+            files.push(vs.clone());
+            files.push(fs.clone());
+        }
+        files
+    }
+
+    // drops programs built from the file, they are rebuilt on the next draw
+    pub fn reload_shader(&self, gl: &gl::Gl, filename: &String) -> bool {
+        self.programs.borrow_mut().retain(|(vs, fs), p| {
+            if vs != filename && fs != filename {
+                return true;
+            }
+            if let Some(p) = p {
+                unsafe {
+                    gl.DeleteProgram(p.shader.program);
+                }
+            }
+            false
+        });
+        true
+    }
+}
+
+impl MaterialProgram {
+    fn new(gl: &gl::Gl, shader: Shader) -> MaterialProgram {
+        let uniform_time = unsafe { gl.GetUniformLocation(shader.program, b"time\0".as_ptr() as *const _) };
+        MaterialProgram {
+            shader,
+            uniform_time,
+            locations: RefCell::new(HashMap::new()),
+        }
+    }
+
+    fn location(&self, gl: &gl::Gl, name: &str) -> gl::types::GLint {
+        let mut locations = self.locations.borrow_mut();
+        match locations.get(name) {
+            Some(l) => *l,
+            None => {
+                let cname = CString::new(name).unwrap_or_default();
+                let l = unsafe { gl.GetUniformLocation(self.shader.program, cname.as_ptr()) };
+                locations.insert(String::from(name), l);
+                l
+            }
+        }
+    }
+}
@@ -1,9 +1,15 @@
+use super::assets;
+use super::error::Error;
 use super::log;
 use cgmath::*;
 use glutin::{self, PossiblyCurrent};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
 use std::ffi::CString;
-use std::fs::File;
-use std::io::prelude::*;
+use std::rc::Rc;
+
+mod material;
+mod postfx;
 
 pub mod gl {
     //pub use self::Gl as Gl;
@@ -16,12 +22,17 @@ pub struct Gl {
     line_shader: Shader,
     poly_shader: Shader,
     tex_shader: Shader,
-    texture: Texture,
     xres: f32,
     yres: f32,
     fbo: u32,
     colorbuf: u32,
     window_scale: f32,
+    // texture handle -> source file, for hot reloading
+    texture_files: RefCell<HashMap<u32, String>>,
+    postfx: postfx::PostFx,
+    materials: material::Materials,
+    // since the last take_draw_calls, for the profiler
+    draw_calls: Cell<usize>,
 }
 
 impl Gl {
@@ -54,7 +65,82 @@ impl Gl {
     }*/
 
     pub fn load_texture(&self, image: &String) -> u32 {
-        Texture::new(&self.gl, image).handle
+        let handle = Texture::new(&self.gl, image).handle;
+        self.texture_files.borrow_mut().insert(handle, image.clone());
+        handle
+    }
+
+    pub fn get_texture_files(&self) -> Vec<String> {
+        let mut files: Vec<String> = self.texture_files.borrow().values().cloned().collect();
+        files.sort();
+        files.dedup();
+        files
+    }
+
+    pub fn get_shader_files(&self) -> Vec<String> {
+        let mut files: Vec<String> = Vec::new();
+        for s in [&self.font_shader, &self.line_shader, &self.poly_shader, &self.tex_shader] {
+            files.push(s.vs_src.clone());
+            files.push(s.fs_src.clone());
+        }
+        files.extend(self.postfx.get_shader_files());
+        files.extend(self.materials.get_shader_files());
+        files
+    }
+
+    // re-upload every texture loaded from this file into its existing handle,
+    // so entities keep drawing with the same texture id
+    pub fn reload_texture(&self, image: &String) -> bool {
+        let mut ret = true;
+        for (handle, file) in self.texture_files.borrow().iter() {
+            if file != image {
+                continue;
+            }
+            if let Err(e) = Texture::upload(&self.gl, *handle, image) {
+                log(format!("WARNING: Texture: Reload failed: {}", e));
+                ret = false;
+            }
+        }
+        ret
+    }
+
+    // recompile any shader using this source file, the old program stays in
+    // use if the new one fails to compile or link
+    pub fn reload_shader(&mut self, filename: &String) -> bool {
+        let mut ret = true;
+        for s in [
+            &mut self.font_shader,
+            &mut self.line_shader,
+            &mut self.poly_shader,
+            &mut self.tex_shader,
+        ] {
+            if *filename != s.vs_src && *filename != s.fs_src {
+                continue;
+            }
+            match Shader::load(&self.gl, &s.vs_src, &s.fs_src) {
+                Ok(shader) => {
+                    if shader.attrib_pos != s.attrib_pos
+                        || shader.attrib_color != s.attrib_color
+                        || shader.attrib_uv != s.attrib_uv
+                    {
+                        log(format!(
+                            "WARNING: Shader: '{}' attribute layout changed, restart to rebuild vertex arrays",
+                            filename
+                        ));
+                    }
+                    unsafe {
+                        self.gl.DeleteProgram(s.program);
+                    }
+                    *s = shader;
+                }
+                Err(e) => {
+                    log(format!("WARNING: Shader: Reload failed: {}", e));
+                    ret = false;
+                }
+            }
+        }
+        let ret = self.materials.reload_shader(&self.gl, filename) && ret;
+        self.postfx.reload_shader(&self.gl, filename) && ret
     }
 
     pub fn buffer_font_data(
@@ -69,7 +155,7 @@ impl Gl {
             self.gl.BindBuffer(gl::ARRAY_BUFFER, vbo);
             self.gl.BufferData(
                 gl::ARRAY_BUFFER,
-                (num_chars * 2 * 3 * 4 * std::mem::size_of::<f32>()) as gl::types::GLsizeiptr,
+                (num_chars * 2 * 3 * 8 * std::mem::size_of::<f32>()) as gl::types::GLsizeiptr,
                 data_ptr,
                 gl::STATIC_DRAW,
             );
@@ -80,7 +166,7 @@ impl Gl {
                 2,
                 gl::FLOAT,
                 0,
-                4 * std::mem::size_of::<f32>() as gl::types::GLsizei,
+                8 * std::mem::size_of::<f32>() as gl::types::GLsizei,
                 std::ptr::null(),
             );
 
@@ -90,9 +176,19 @@ impl Gl {
                 2,
                 gl::FLOAT,
                 0,
-                4 * std::mem::size_of::<f32>() as gl::types::GLsizei,
+                8 * std::mem::size_of::<f32>() as gl::types::GLsizei,
                 (2 * std::mem::size_of::<f32>()) as *const () as *const _,
             );
+
+            self.gl.EnableVertexAttribArray(self.font_shader.attrib_color);
+            self.gl.VertexAttribPointer(
+                self.font_shader.attrib_color,
+                4,
+                gl::FLOAT,
+                0,
+                8 * std::mem::size_of::<f32>() as gl::types::GLsizei,
+                (4 * std::mem::size_of::<f32>()) as *const () as *const _,
+            );
         }
     }
 
@@ -208,6 +304,26 @@ impl Gl {
         }
     }
 
+    // rewrites tiles in a buffer filled by buffer_tilemap_data
+    pub fn update_tilemap_data(
+        &self,
+        vbo: u32,
+        first_tile: usize,
+        num_tiles: usize,
+        data_ptr: *const std::ffi::c_void,
+    ) {
+        let tile_sz = 2 * 3 * 4 * std::mem::size_of::<f32>();
+        unsafe {
+            self.gl.BindBuffer(gl::ARRAY_BUFFER, vbo);
+            self.gl.BufferSubData(
+                gl::ARRAY_BUFFER,
+                (first_tile * tile_sz) as gl::types::GLintptr,
+                (num_tiles * tile_sz) as gl::types::GLsizeiptr,
+                data_ptr,
+            );
+        }
+    }
+
     pub fn buffer_triangle_data(
         &self,
         vao: u32,
@@ -254,14 +370,30 @@ struct Texture {
 }
 
 impl Texture {
+    // a missing or broken image gets a checkerboard, so it stands out
+    // without stopping the game
     pub fn new(gl: &gl::Gl, image: &String) -> Texture {
+        log(format!("Texture: Loading '{}'", image));
+        let mut tex: u32 = 0;
         unsafe {
-            // Construct a new RGB ImageBuffer with the specified width and height.
-            log(format!("Texture: Loading '{}'", image));
-            let img: image::RgbaImage = image::open(image).unwrap().to_rgba8();
-
-            let mut tex: u32 = 0;
             gl.GenTextures(1, &mut tex);
+        }
+        if let Err(e) = Texture::upload(gl, tex, image) {
+            log(format!("WARNING: Texture: {}, using a placeholder", e));
+            Texture::upload_image(gl, tex, placeholder_image());
+        }
+        Texture { handle: tex }
+    }
+
+    // (re)load image data into an existing texture handle
+    pub fn upload(gl: &gl::Gl, tex: u32, image: &str) -> Result<(), String> {
+        let img: image::RgbaImage = assets::read_image(image)?.to_rgba8();
+        Texture::upload_image(gl, tex, img);
+        Ok(())
+    }
+
+    fn upload_image(gl: &gl::Gl, tex: u32, img: image::RgbaImage) {
+        unsafe {
             gl.BindTexture(gl::TEXTURE_2D, tex);
             let tw = img.dimensions().0 as gl::types::GLsizei;
             let th = img.dimensions().1 as gl::types::GLsizei;
@@ -297,14 +429,24 @@ impl Texture {
                 gl::TEXTURE_WRAP_T,
                 gl::REPEAT as gl::types::GLint,
             );
-
-            Texture { handle: tex }
         }
     }
 }
 
+// magenta and black squares
+fn placeholder_image() -> image::RgbaImage {
+    const SZ: u32 = 32;
+    const CHECK: u32 = 8;
+    image::RgbaImage::from_fn(SZ, SZ, |x, y| match (x / CHECK + y / CHECK) % 2 {
+        0 => image::Rgba([255, 0, 255, 255]),
+        _ => image::Rgba([0, 0, 0, 255]),
+    })
+}
+
 struct Shader {
     program: u32,
+    vs_src: String,
+    fs_src: String,
     pub attrib_pos: gl::types::GLuint,
     pub attrib_color: gl::types::GLuint,
     pub attrib_uv: gl::types::GLuint,
@@ -318,76 +460,104 @@ struct Shader {
 
 impl Shader {
     pub fn new(gl: &gl::Gl, vs_src: &String, fs_src: &String) -> Shader {
-        unsafe {
-            let vs = gl.CreateShader(gl::VERTEX_SHADER);
+        match Shader::load(gl, vs_src, fs_src) {
+            Ok(shader) => shader,
+            Err(e) => {
+                log(format!("ERROR: Shader: {}", e));
+                Shader {
+                    program: 0,
+                    vs_src: vs_src.clone(),
+                    fs_src: fs_src.clone(),
+                    attrib_pos: 0,
+                    attrib_color: 0,
+                    attrib_uv: 0,
+                    uniform_tex_sampler: -1,
+                    uniform_mvp: -1,
+                    uniform_color: -1,
+                    uniform_uv: -1,
+                    uniform_duv: -1,
+                    uniform_override_uv: -1,
+                }
+            }
+        }
+    }
 
-            println!("Loading Vertex Shader: {}", vs_src);
-            let mut vsfile = File::open(vs_src.as_str()).unwrap();
-            let mut vsbuffer: Vec<u8> = Vec::new();
-            vsfile.read_to_end(&mut vsbuffer).unwrap();
-            let raw = CString::new(vsbuffer).unwrap();
-            gl.ShaderSource(vs, 1, &raw.as_ptr(), std::ptr::null());
+    fn compile(gl: &gl::Gl, kind: gl::types::GLenum, src: &String) -> Result<u32, String> {
+        let buffer = assets::read(src)?;
+        let raw = CString::new(buffer).map_err(|e| format!("{}: {}", src, e))?;
 
-            println!("Compile Vertex Shader: {}", vs_src);
-            gl.CompileShader(vs);
+        unsafe {
+            let shader = gl.CreateShader(kind);
+            gl.ShaderSource(shader, 1, &raw.as_ptr(), std::ptr::null());
 
-            let mut pass = i32::from(gl::FALSE);
-            let mut loglen: i32 = 0;
-            gl.GetShaderiv(vs, gl::INFO_LOG_LENGTH, &mut loglen);
-            let mut output = Vec::<u8>::with_capacity(loglen as usize + 1);
-            output.set_len(loglen as usize);
+            log(format!("Shader: Compiling '{}'", src));
+            gl.CompileShader(shader);
 
-            gl.GetShaderiv(vs, gl::COMPILE_STATUS, &mut pass);
+            let mut pass = i32::from(gl::FALSE);
+            gl.GetShaderiv(shader, gl::COMPILE_STATUS, &mut pass);
             if i32::from(gl::TRUE) != pass {
+                let mut loglen: i32 = 0;
+                gl.GetShaderiv(shader, gl::INFO_LOG_LENGTH, &mut loglen);
+                let mut output = vec![0u8; loglen.max(1) as usize];
                 gl.GetShaderInfoLog(
-                    vs,
-                    5120,
+                    shader,
+                    output.len() as i32,
                     std::ptr::null_mut(),
                     output.as_mut_ptr() as *mut gl::types::GLchar,
                 );
-                println!(
-                    "Compiler Error:\n {}",
-                    std::str::from_utf8(&output).unwrap()
-                );
+                gl.DeleteShader(shader);
+                return Err(format!("{}: {}", src, String::from_utf8_lossy(&output)));
             }
+            Ok(shader)
+        }
+    }
 
-            let fs = gl.CreateShader(gl::FRAGMENT_SHADER);
-
-            println!("Loading Fragment Shader: {}", fs_src);
-            let mut fsfile = File::open(fs_src.as_str()).unwrap();
-            let mut fsbuffer: Vec<u8> = Vec::new();
-            fsfile.read_to_end(&mut fsbuffer).unwrap();
-            let raw = CString::new(fsbuffer).unwrap();
-            gl.ShaderSource(fs, 1, &raw.as_ptr(), std::ptr::null());
+    pub fn load(gl: &gl::Gl, vs_src: &String, fs_src: &String) -> Result<Shader, String> {
+        let vs = Shader::compile(gl, gl::VERTEX_SHADER, vs_src)?;
+        let fs = match Shader::compile(gl, gl::FRAGMENT_SHADER, fs_src) {
+            Ok(fs) => fs,
+            Err(e) => {
+                unsafe {
+                    gl.DeleteShader(vs);
+                }
+                return Err(e);
+            }
+        };
 
-            println!("Compile Fragment Shader: {}", fs_src);
-            gl.CompileShader(fs);
+        unsafe {
+            let program = gl.CreateProgram();
+            gl.AttachShader(program, vs);
+            gl.AttachShader(program, fs);
+            // fixed locations so material programs can draw the vertex
+            // arrays built for the default shaders
+            gl.BindAttribLocation(program, 0, b"position\0".as_ptr() as *const _);
+            gl.BindAttribLocation(program, 1, b"color\0".as_ptr() as *const _);
+            gl.BindAttribLocation(program, 2, b"uv\0".as_ptr() as *const _);
+            gl.LinkProgram(program);
+            gl.DeleteShader(vs);
+            gl.DeleteShader(fs);
 
             let mut pass = i32::from(gl::FALSE);
-            let mut loglen: i32 = 0;
-            gl.GetShaderiv(vs, gl::INFO_LOG_LENGTH, &mut loglen);
-            let mut output = Vec::<u8>::with_capacity(loglen as usize + 1);
-            output.set_len(loglen as usize);
-
-            gl.GetShaderiv(fs, gl::COMPILE_STATUS, &mut pass);
+            gl.GetProgramiv(program, gl::LINK_STATUS, &mut pass);
             if i32::from(gl::TRUE) != pass {
-                gl.GetShaderInfoLog(
-                    vs,
-                    512,
+                let mut loglen: i32 = 0;
+                gl.GetProgramiv(program, gl::INFO_LOG_LENGTH, &mut loglen);
+                let mut output = vec![0u8; loglen.max(1) as usize];
+                gl.GetProgramInfoLog(
+                    program,
+                    output.len() as i32,
                     std::ptr::null_mut(),
                     output.as_mut_ptr() as *mut gl::types::GLchar,
                 );
-                println!(
-                    "Compiler Error:\n {}",
-                    std::str::from_utf8(&output).unwrap()
-                );
+                gl.DeleteProgram(program);
+                return Err(format!(
+                    "{} + {}: {}",
+                    vs_src,
+                    fs_src,
+                    String::from_utf8_lossy(&output)
+                ));
             }
 
-            let program = gl.CreateProgram();
-            gl.AttachShader(program, vs);
-            gl.AttachShader(program, fs);
-            gl.LinkProgram(program);
-
             let attrib_pos = gl.GetAttribLocation(program, b"position\0".as_ptr() as *const _)
                 as gl::types::GLuint;
             let attrib_color =
@@ -407,8 +577,10 @@ impl Shader {
             let uniform_override_uv =
                 gl.GetUniformLocation(program, b"uniform_override_uv\0".as_ptr() as *const _);
 
-            Shader {
+            Ok(Shader {
                 program,
+                vs_src: vs_src.clone(),
+                fs_src: fs_src.clone(),
                 attrib_pos,
                 attrib_color,
                 attrib_uv,
@@ -418,7 +590,7 @@ impl Shader {
                 uniform_uv,
                 uniform_duv,
                 uniform_override_uv,
-            }
+            })
         }
     }
 
@@ -434,32 +606,22 @@ pub fn load(
     xres: i32,
     yres: i32,
     window_scale: f32,
-) -> Gl {
+) -> Result<Gl, Error> {
     let gl = gl::Gl::load_with(|ptr| gl_context.get_proc_address(ptr) as *const _);
 
-    let line_shader = Shader::new(
-        &gl,
-        &String::from("assets/mgfw/line_shader.vs"),
-        &String::from("assets/mgfw/line_shader.fs"),
-    );
-    let poly_shader = Shader::new(
-        &gl,
-        &String::from("assets/mgfw/poly_shader.vs"),
-        &String::from("assets/mgfw/poly_shader.fs"),
-    );
-    let font_shader = Shader::new(
-        &gl,
-        &String::from("assets/mgfw/tex_shader.vs"),
-        &String::from("assets/mgfw/tex_shader.fs"),
-    );
-    let tex_shader = Shader::new(
-        &gl,
-        &String::from("assets/mgfw/tex_shader.vs"),
-        &String::from("assets/mgfw/tex_shader.fs"),
-    );
-
-    // todo - pull this from the retro_gaming.rs
-    let texture = Texture::new(&gl, &String::from("assets/mgfw/retro_gaming_0.png"));
+    // nothing draws without these
+    let builtin = |name: &str| {
+        Shader::load(
+            &gl,
+            &format!("assets/mgfw/{}.vs", name),
+            &format!("assets/mgfw/{}.fs", name),
+        )
+        .map_err(Error::Shader)
+    };
+    let line_shader = builtin("line_shader")?;
+    let poly_shader = builtin("poly_shader")?;
+    let font_shader = builtin("text_shader")?;
+    let tex_shader = builtin("tex_shader")?;
 
     unsafe {
         gl.Viewport(0, 0, xres, yres);
@@ -525,19 +687,24 @@ pub fn load(
         }
         gl.BindFramebuffer(gl::FRAMEBUFFER, 0);
 
-        Gl {
+        let postfx = postfx::PostFx::load(&gl, "assets/mgfw/postfx.dat", xres, yres);
+
+        Ok(Gl {
             gl,
             font_shader,
             line_shader,
             poly_shader,
             tex_shader,
-            texture,
             xres: xres as f32,
             yres: yres as f32,
             fbo,
             colorbuf,
             window_scale,
-        }
+            texture_files: RefCell::new(HashMap::new()),
+            postfx,
+            materials: material::Materials::new(),
+            draw_calls: Cell::new(0),
+        })
     }
 }
 
@@ -567,6 +734,95 @@ impl Gl {
         }
     }
 
+    // runs the post-processing chain from the offscreen frame into the
+    // window, returns false if every pass is disabled
+    pub fn draw_postfx(&self, world: &super::ecs::World) -> bool {
+        let window = (
+            (self.xres * self.window_scale) as i32,
+            (self.yres * self.window_scale) as i32,
+        );
+        self.postfx
+            .draw(&self.gl, world, self.colorbuf, self.xres, self.yres, window)
+    }
+
+    // game resolution, the size of read_frame_async frames
+    pub fn get_frame_size(&self) -> (u32, u32) {
+        (self.xres as u32, self.yres as u32)
+    }
+
+    // pixel pack buffer for read_frame_async
+    pub fn gen_frame_buffer(&self) -> u32 {
+        let (w, h) = self.get_frame_size();
+        let mut pbo: u32 = 0;
+        unsafe {
+            self.gl.GenBuffers(1, &mut pbo);
+            self.gl.BindBuffer(gl::PIXEL_PACK_BUFFER, pbo);
+            self.gl.BufferData(
+                gl::PIXEL_PACK_BUFFER,
+                (w * h * 4) as gl::types::GLsizeiptr,
+                std::ptr::null(),
+                gl::STREAM_READ,
+            );
+            self.gl.BindBuffer(gl::PIXEL_PACK_BUFFER, 0);
+        }
+        pbo
+    }
+
+    // start copying the offscreen framebuffer into pbo without waiting for
+    // the GPU, map_frame picks it up a frame or two later
+    pub fn read_frame_async(&self, pbo: u32) {
+        let (w, h) = self.get_frame_size();
+        unsafe {
+            self.gl.BindFramebuffer(gl::READ_FRAMEBUFFER, self.fbo);
+            self.gl.BindBuffer(gl::PIXEL_PACK_BUFFER, pbo);
+            self.gl.PixelStorei(gl::PACK_ALIGNMENT, 1);
+            self.gl.ReadPixels(
+                0,
+                0,
+                w as i32,
+                h as i32,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                std::ptr::null_mut(),
+            );
+            self.gl.BindBuffer(gl::PIXEL_PACK_BUFFER, 0);
+            self.gl.BindFramebuffer(gl::READ_FRAMEBUFFER, 0);
+        }
+    }
+
+    // RGBA rows bottom to top
+    pub fn map_frame(&self, pbo: u32) -> Option<Vec<u8>> {
+        let (w, h) = self.get_frame_size();
+        let size = (w * h * 4) as usize;
+        unsafe {
+            self.gl.BindBuffer(gl::PIXEL_PACK_BUFFER, pbo);
+            let ptr = self.gl.MapBufferRange(
+                gl::PIXEL_PACK_BUFFER,
+                0,
+                size as gl::types::GLsizeiptr,
+                gl::MAP_READ_BIT,
+            ) as *const u8;
+            let ret = match ptr.is_null() {
+                true => None,
+                false => Some(std::slice::from_raw_parts(ptr, size).to_vec()),
+            };
+            if !ptr.is_null() {
+                self.gl.UnmapBuffer(gl::PIXEL_PACK_BUFFER);
+            }
+            self.gl.BindBuffer(gl::PIXEL_PACK_BUFFER, 0);
+            ret
+        }
+    }
+
+    pub fn take_draw_calls(&self) -> usize {
+        self.draw_calls.replace(0)
+    }
+
+    // material for the following draw calls, None for the default shaders
+    pub fn bind_material(&self, material: Option<Rc<super::ecs::Material>>) {
+        self.materials.bind(material);
+    }
+
     pub fn draw_text(
         &self,
         x: f32,
@@ -575,27 +831,25 @@ impl Gl {
         sx: f32,
         sy: f32,
         vao: u32,
+        first: usize,
         count: usize,
+        tex: u32,
         color: super::ecs::Color,
     ) {
-        self.font_shader.use_program(&self.gl);
+        let shader = self.materials.use_shader(self, &self.font_shader);
 
         unsafe {
             self.gl.ActiveTexture(gl::TEXTURE0);
-            self.gl.BindTexture(gl::TEXTURE_2D, self.texture.handle);
-            self.gl.Uniform1i(self.font_shader.uniform_tex_sampler, 0);
+            self.gl.BindTexture(gl::TEXTURE_2D, tex);
+            self.gl.Uniform1i(shader.uniform_tex_sampler, 0);
             self.gl.Uniform4f(
-                self.font_shader.uniform_color,
+                shader.uniform_color,
                 color.r,
                 color.g,
                 color.b,
                 color.a,
             );
 
-            self.gl.Uniform1i(self.tex_shader.uniform_override_uv, 0);
-            self.gl.Uniform2f(self.tex_shader.uniform_uv, 0.0, 0.0);
-            self.gl.Uniform2f(self.tex_shader.uniform_duv, 1.0, 1.0);
-
             self.gl.BindVertexArray(vao);
             let mvp = self.get_mvp();
 
@@ -609,13 +863,15 @@ impl Gl {
             let mvp = mvp * mat;
 
             self.gl.UniformMatrix4fv(
-                self.font_shader.uniform_mvp,
+                shader.uniform_mvp,
                 1,
                 gl::FALSE,
                 mvp.as_ptr() as *const _,
             );
 
-            self.gl.DrawArrays(gl::TRIANGLES, 0, (count * 2 * 3) as i32);
+            self.draw_calls.set(self.draw_calls.get() + 1);
+            self.gl
+                .DrawArrays(gl::TRIANGLES, (first * 2 * 3) as i32, (count * 2 * 3) as i32);
 
             self.gl.BindVertexArray(0);
         }
@@ -629,7 +885,7 @@ impl Gl {
         sx: f32,
         sy: f32,
         vao: u32,
-        tex: u16,
+        tex: u32,
         color: super::ecs::Color,
         frame: bool,
         frame_u: f32,
@@ -637,14 +893,14 @@ impl Gl {
         frame_du: f32,
         frame_dv: f32,
     ) {
-        self.tex_shader.use_program(&self.gl);
+        let shader = self.materials.use_shader(self, &self.tex_shader);
 
         unsafe {
             self.gl.ActiveTexture(gl::TEXTURE0);
-            self.gl.BindTexture(gl::TEXTURE_2D, tex as u32);
-            self.gl.Uniform1i(self.tex_shader.uniform_tex_sampler, 0);
+            self.gl.BindTexture(gl::TEXTURE_2D, tex);
+            self.gl.Uniform1i(shader.uniform_tex_sampler, 0);
             self.gl.Uniform4f(
-                self.tex_shader.uniform_color,
+                shader.uniform_color,
                 color.r,
                 color.g,
                 color.b,
@@ -652,11 +908,11 @@ impl Gl {
             );
 
             self.gl
-                .Uniform1i(self.tex_shader.uniform_override_uv, frame as i32);
+                .Uniform1i(shader.uniform_override_uv, frame as i32);
             self.gl
-                .Uniform2f(self.tex_shader.uniform_uv, frame_u, frame_v);
+                .Uniform2f(shader.uniform_uv, frame_u, frame_v);
             self.gl
-                .Uniform2f(self.tex_shader.uniform_duv, frame_du, frame_dv);
+                .Uniform2f(shader.uniform_duv, frame_du, frame_dv);
 
             self.gl.BindVertexArray(vao);
             let mvp = self.get_mvp();
@@ -671,12 +927,13 @@ impl Gl {
             let mvp = mvp * mat;
 
             self.gl.UniformMatrix4fv(
-                self.tex_shader.uniform_mvp,
+                shader.uniform_mvp,
                 1,
                 gl::FALSE,
                 mvp.as_ptr() as *const _,
             );
 
+            self.draw_calls.set(self.draw_calls.get() + 1);
             self.gl.DrawArrays(gl::TRIANGLES, 0, 6 as i32);
 
             self.gl.BindVertexArray(0);
@@ -722,6 +979,7 @@ impl Gl {
                 mvp.as_ptr() as *const _,
             );
 
+            self.draw_calls.set(self.draw_calls.get() + 1);
             self.gl.DrawArrays(gl::TRIANGLES, 0, 6 as i32);
 
             self.gl.BindVertexArray(0);
@@ -737,26 +995,26 @@ impl Gl {
         sy: f32,
         vao: u32,
         count: usize,
-        tex: u16,
+        tex: u32,
         color: super::ecs::Color,
     ) {
-        self.tex_shader.use_program(&self.gl);
+        let shader = self.materials.use_shader(self, &self.tex_shader);
 
         unsafe {
             self.gl.ActiveTexture(gl::TEXTURE0);
-            self.gl.BindTexture(gl::TEXTURE_2D, tex as u32);
-            self.gl.Uniform1i(self.tex_shader.uniform_tex_sampler, 0);
+            self.gl.BindTexture(gl::TEXTURE_2D, tex);
+            self.gl.Uniform1i(shader.uniform_tex_sampler, 0);
             self.gl.Uniform4f(
-                self.tex_shader.uniform_color,
+                shader.uniform_color,
                 color.r,
                 color.g,
                 color.b,
                 color.a,
             );
 
-            self.gl.Uniform1i(self.tex_shader.uniform_override_uv, 0);
-            self.gl.Uniform2f(self.tex_shader.uniform_uv, 0.0, 0.0);
-            self.gl.Uniform2f(self.tex_shader.uniform_duv, 1.0, 1.0);
+            self.gl.Uniform1i(shader.uniform_override_uv, 0);
+            self.gl.Uniform2f(shader.uniform_uv, 0.0, 0.0);
+            self.gl.Uniform2f(shader.uniform_duv, 1.0, 1.0);
 
             self.gl.BindVertexArray(vao);
             let mvp = self.get_mvp();
@@ -771,12 +1029,13 @@ impl Gl {
             let mvp = mvp * mat;
 
             self.gl.UniformMatrix4fv(
-                self.tex_shader.uniform_mvp,
+                shader.uniform_mvp,
                 1,
                 gl::FALSE,
                 mvp.as_ptr() as *const _,
             );
 
+            self.draw_calls.set(self.draw_calls.get() + 1);
             self.gl.DrawArrays(gl::TRIANGLES, 0, (count * 6) as i32);
 
             self.gl.BindVertexArray(0);
@@ -794,11 +1053,11 @@ impl Gl {
         count: usize,
         color: super::ecs::Color,
     ) {
-        self.line_shader.use_program(&self.gl);
+        let shader = self.materials.use_shader(self, &self.line_shader);
 
         unsafe {
             self.gl.Uniform4f(
-                self.line_shader.uniform_color,
+                shader.uniform_color,
                 color.r,
                 color.g,
                 color.b,
@@ -818,12 +1077,13 @@ impl Gl {
             let mvp = mvp * mat;
 
             self.gl.UniformMatrix4fv(
-                self.line_shader.uniform_mvp,
+                shader.uniform_mvp,
                 1,
                 gl::FALSE,
                 mvp.as_ptr() as *const _,
             );
 
+            self.draw_calls.set(self.draw_calls.get() + 1);
             self.gl.DrawArrays(gl::LINES, 0, (count * 2) as i32);
 
             self.gl.BindVertexArray(0);
@@ -841,11 +1101,11 @@ impl Gl {
         count: usize,
         color: super::ecs::Color,
     ) {
-        self.poly_shader.use_program(&self.gl);
+        let shader = self.materials.use_shader(self, &self.poly_shader);
 
         unsafe {
             self.gl.Uniform4f(
-                self.poly_shader.uniform_color,
+                shader.uniform_color,
                 color.r,
                 color.g,
                 color.b,
@@ -865,12 +1125,13 @@ impl Gl {
             let mvp = mvp * mat;
 
             self.gl.UniformMatrix4fv(
-                self.poly_shader.uniform_mvp,
+                shader.uniform_mvp,
                 1,
                 gl::FALSE,
                 mvp.as_ptr() as *const _,
             );
 
+            self.draw_calls.set(self.draw_calls.get() + 1);
             self.gl.DrawArrays(gl::TRIANGLES, 0, (count * 3) as i32);
 
             self.gl.BindVertexArray(0);
@@ -0,0 +1,321 @@
+use super::super::ecs::World;
+use super::super::log;
+use super::{gl, Shader};
+use std::ffi::CString;
+use super::super::assets;
+
+// Full screen passes run on the offscreen frame before it reaches the window.
+// The chain is read from a file, one pass per line in drawing order:
+//
+//   pass,<name>,<fragment shader>,<enabled>
+//   uniform,<pass name>,<uniform name>,<1 to 4 floats>
+//
+// Every pass shares postfx.vs and gets tex_sampler (output of the previous
+// pass), scene_sampler (the unprocessed frame), resolution and time. Passes
+// and uniforms can be overridden at runtime through the World.
+
+const POSTFX_VS: &str = "assets/mgfw/postfx/postfx.vs";
+
+struct Uniform {
+    name: String,
+    location: gl::types::GLint,
+    values: Vec<f32>,
+}
+
+pub struct PostPass {
+    pub name: String,
+    shader: Shader,
+    enabled: bool,
+    vao: u32,
+    vbo: u32,
+    uniform_scene: gl::types::GLint,
+    uniform_resolution: gl::types::GLint,
+    uniform_time: gl::types::GLint,
+    uniforms: Vec<Uniform>,
+}
+
+pub struct PostFx {
+    passes: Vec<PostPass>,
+    // ping-pong targets for every pass but the last
+    fbo: [u32; 2],
+    tex: [u32; 2],
+    start: std::time::Instant,
+}
+
+impl PostFx {
+    pub fn load(gl: &gl::Gl, filename: &str, xres: i32, yres: i32) -> PostFx {
+        log(format!("Constructing PostFx"));
+        let mut ret = PostFx {
+            passes: Vec::new(),
+            fbo: [0; 2],
+            tex: [0; 2],
+            start: std::time::Instant::now(),
+        };
+
+        let text = match assets::read_to_string(filename) {
+            Ok(t) => t,
+            Err(_) => {
+                log(format!("PostFx: No '{}', post-processing disabled", filename));
+                return ret;
+            }
+        };
+
+        for (n, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with("//") {
+                continue;
+            }
+            let split: Vec<&str> = line.split(',').map(|s| s.trim()).collect();
+            if !ret.apply(gl, &split) {
+                log(format!(
+                    "WARNING: PostFx: {}:{}: ignoring '{}'",
+                    filename,
+                    n + 1,
+                    line
+                ));
+            }
+        }
+
+        if !ret.passes.is_empty() {
+            for i in 0..2 {
+                let (fbo, tex) = create_target(gl, xres, yres);
+                ret.fbo[i] = fbo;
+                ret.tex[i] = tex;
+            }
+        }
+        ret
+    }
+
+    fn apply(&mut self, gl: &gl::Gl, split: &[&str]) -> bool {
+        match split[0] {
+            "pass" if 4 == split.len() => {
+                let enabled = match split[3] {
+                    "true" => true,
+                    "false" => false,
+                    _ => return false,
+                };
+                let shader = Shader::new(gl, &String::from(POSTFX_VS), &String::from(split[2]));
+                let mut pass = PostPass {
+                    name: String::from(split[1]),
+                    shader,
+                    enabled,
+                    vao: 0,
+                    vbo: 0,
+                    uniform_scene: -1,
+                    uniform_resolution: -1,
+                    uniform_time: -1,
+                    uniforms: Vec::new(),
+                };
+                unsafe {
+                    gl.GenVertexArrays(1, &mut pass.vao);
+                    gl.GenBuffers(1, &mut pass.vbo);
+                }
+                pass.setup(gl);
+                self.passes.push(pass);
+                true
+            }
+            "uniform" if 4 <= split.len() && 7 >= split.len() => {
+                let mut values: Vec<f32> = Vec::new();
+                for v in split[3..].iter() {
+                    match v.parse::<f32>() {
+                        Ok(f) => values.push(f),
+                        Err(_) => return false,
+                    }
+                }
+                match self.passes.iter_mut().find(|p| p.name == split[1]) {
+                    Some(pass) => {
+                        let location = uniform_location(gl, pass.shader.program, split[2]);
+                        pass.uniforms.push(Uniform {
+                            name: String::from(split[2]),
+                            location,
+                            values,
+                        });
+                        true
+                    }
+                    None => false,
+                }
+            }
+            _ => false,
+        }
+    }
+
+    pub fn get_shader_files(&self) -> Vec<String> {
+        let mut files: Vec<String> = Vec::new();
+        for p in self.passes.iter() {
+            files.push(p.shader.vs_src.clone());
+            files.push(p.shader.fs_src.clone());
+        }
+        files
+    }
+
+    pub fn reload_shader(&mut self, gl: &gl::Gl, filename: &String) -> bool {
+        let mut ret = true;
+        for p in self.passes.iter_mut() {
+            if *filename != p.shader.vs_src && *filename != p.shader.fs_src {
+                continue;
+            }
+            match Shader::load(gl, &p.shader.vs_src, &p.shader.fs_src) {
+                Ok(shader) => {
+                    unsafe {
+                        gl.DeleteProgram(p.shader.program);
+                    }
+                    p.shader = shader;
+                    p.setup(gl);
+                }
+                Err(e) => {
+                    log(format!("WARNING: PostFx: Reload failed: {}", e));
+                    ret = false;
+                }
+            }
+        }
+        ret
+    }
+
+    // runs the enabled passes, the last one draws into the window.
+    // returns false if no pass ran.
+    pub fn draw(&self, gl: &gl::Gl, world: &World, scene: u32, xres: f32, yres: f32, window: (i32, i32)) -> bool {
+        let active: Vec<&PostPass> = self
+            .passes
+            .iter()
+            .filter(|p| 0 != p.shader.program)
+            .filter(|p| world.postfx_get_enabled(&p.name).unwrap_or(p.enabled))
+            .collect();
+        if active.is_empty() {
+            return false;
+        }
+
+        let time = self.start.elapsed().as_secs_f32();
+        let mut src = scene;
+
+        unsafe {
+            for (i, p) in active.iter().enumerate() {
+                if i + 1 == active.len() {
+                    gl.BindFramebuffer(gl::FRAMEBUFFER, 0);
+                    gl.Viewport(0, 0, window.0, window.1);
+                } else {
+                    gl.BindFramebuffer(gl::FRAMEBUFFER, self.fbo[i % 2]);
+                    gl.Viewport(0, 0, xres as i32, yres as i32);
+                }
+                gl.Clear(gl::COLOR_BUFFER_BIT);
+
+                p.shader.use_program(gl);
+
+                gl.ActiveTexture(gl::TEXTURE1);
+                gl.BindTexture(gl::TEXTURE_2D, scene);
+                gl.Uniform1i(p.uniform_scene, 1);
+                gl.ActiveTexture(gl::TEXTURE0);
+                gl.BindTexture(gl::TEXTURE_2D, src);
+                gl.Uniform1i(p.shader.uniform_tex_sampler, 0);
+
+                gl.Uniform2f(p.uniform_resolution, xres, yres);
+                gl.Uniform1f(p.uniform_time, time);
+
+                for u in p.uniforms.iter() {
+                    let v = world.postfx_get_uniform(&p.name, &u.name).unwrap_or(&u.values);
+                    match v.len() {
+                        1 => gl.Uniform1f(u.location, v[0]),
+                        2 => gl.Uniform2f(u.location, v[0], v[1]),
+                        3 => gl.Uniform3f(u.location, v[0], v[1], v[2]),
+                        4 => gl.Uniform4f(u.location, v[0], v[1], v[2], v[3]),
+                        _ => (),
+                    }
+                }
+
+                gl.BindVertexArray(p.vao);
+                gl.DrawArrays(gl::TRIANGLES, 0, 6);
+                gl.BindVertexArray(0);
+
+                src = self.tex[i % 2];
+            }
+        }
+        true
+    }
+}
+
+impl PostPass {
+    // (re)build the quad for this program's attribute locations and look up
+    // uniforms, needed again after a shader reload
+    fn setup(&mut self, gl: &gl::Gl) {
+        #[rustfmt::skip]
+        let vertex_data: [f32; 24] = [
+            -1.0, -1.0, 0.0, 0.0,
+            -1.0, 1.0, 0.0, 1.0,
+            1.0, 1.0, 1.0, 1.0,
+            -1.0, -1.0, 0.0, 0.0,
+            1.0, 1.0, 1.0, 1.0,
+            1.0, -1.0, 1.0, 0.0,
+        ];
+        let stride = 4 * std::mem::size_of::<f32>() as gl::types::GLsizei;
+
+        unsafe {
+            gl.BindVertexArray(self.vao);
+            gl.BindBuffer(gl::ARRAY_BUFFER, self.vbo);
+            gl.BufferData(
+                gl::ARRAY_BUFFER,
+                std::mem::size_of_val(&vertex_data) as gl::types::GLsizeiptr,
+                vertex_data.as_ptr() as *const _,
+                gl::STATIC_DRAW,
+            );
+            gl.EnableVertexAttribArray(self.shader.attrib_pos);
+            gl.VertexAttribPointer(self.shader.attrib_pos, 2, gl::FLOAT, 0, stride, std::ptr::null());
+            gl.EnableVertexAttribArray(self.shader.attrib_uv);
+            gl.VertexAttribPointer(
+                self.shader.attrib_uv,
+                2,
+                gl::FLOAT,
+                0,
+                stride,
+                (2 * std::mem::size_of::<f32>()) as *const () as *const _,
+            );
+            gl.BindVertexArray(0);
+        }
+
+        let program = self.shader.program;
+        self.uniform_scene = uniform_location(gl, program, "scene_sampler");
+        self.uniform_resolution = uniform_location(gl, program, "resolution");
+        self.uniform_time = uniform_location(gl, program, "time");
+        for u in self.uniforms.iter_mut() {
+            u.location = uniform_location(gl, program, &u.name);
+        }
+    }
+}
+
+fn uniform_location(gl: &gl::Gl, program: u32, name: &str) -> gl::types::GLint {
+    let name = CString::new(name).unwrap_or_default();
+    unsafe { gl.GetUniformLocation(program, name.as_ptr()) }
+}
+
+// color only render target, returns (fbo, texture)
+fn create_target(gl: &gl::Gl, xres: i32, yres: i32) -> (u32, u32) {
+    let mut fbo: u32 = 0;
+    let mut tex: u32 = 0;
+    unsafe {
+        gl.GenFramebuffers(1, &mut fbo);
+        gl.BindFramebuffer(gl::FRAMEBUFFER, fbo);
+
+        gl.GenTextures(1, &mut tex);
+        gl.BindTexture(gl::TEXTURE_2D, tex);
+        gl.TexImage2D(
+            gl::TEXTURE_2D,
+            0,
+            gl::RGB as gl::types::GLint,
+            xres,
+            yres,
+            0,
+            gl::RGB,
+            gl::UNSIGNED_BYTE,
+            std::ptr::null(),
+        );
+        gl.TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as gl::types::GLint);
+        gl.TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as gl::types::GLint);
+        gl.TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as gl::types::GLint);
+        gl.TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as gl::types::GLint);
+        gl.FramebufferTexture2D(gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0, gl::TEXTURE_2D, tex, 0);
+
+        if gl.CheckFramebufferStatus(gl::FRAMEBUFFER) != gl::FRAMEBUFFER_COMPLETE {
+            log(format!("WARNING: PostFx: Failed to setup Framebuffer"));
+        }
+        gl.BindFramebuffer(gl::FRAMEBUFFER, 0);
+    }
+    (fbo, tex)
+}
@@ -0,0 +1,194 @@
+use super::assets;
+use super::log;
+
+// Simulation and render rates, read from assets/mgfw/timing.dat:
+//
+//   update_hz,1200   fixed simulation steps per second
+//   render_hz,60     frames per second, 0 renders as fast as possible
+//   vsync,true       wait for the display, which caps render_hz at its refresh
+//   max_steps,100    simulation steps per update before the backlog is dropped
+//
+// Physics, easing and input events run every PHYSICS_DIV steps; rendering
+// interpolates between the last two physics states. F6 cycles render_hz
+// through RENDER_RATES.
+
+pub const PHYSICS_DIV: u128 = 4;
+pub const RENDER_RATES: [u32; 4] = [60, 120, 144, 0];
+// for frame load stats when uncapped
+const NOMINAL_RENDER_HZ: u32 = 60;
+const MAX_UPDATE_HZ: u32 = 10000;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Timing {
+    pub update_hz: u32,
+    pub render_hz: u32,
+    pub vsync: bool,
+    pub max_steps: u32,
+}
+
+impl Default for Timing {
+    fn default() -> Timing {
+        Timing {
+            update_hz: 1200,
+            render_hz: 60,
+            vsync: true,
+            max_steps: 100,
+        }
+    }
+}
+
+#[allow(dead_code)]
+impl Timing {
+    // same line format as ui_theme.dat, a missing file keeps the defaults
+    pub fn load(filename: &str) -> Timing {
+        match assets::read_to_string(filename) {
+            Ok(text) => Timing::parse(filename, &text),
+            Err(_) => {
+                log(format!("Timing: No '{}', using defaults", filename));
+                Timing::default()
+            }
+        }
+    }
+
+    pub fn parse(filename: &str, text: &str) -> Timing {
+        let mut timing = Timing::default();
+        for (n, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with("//") {
+                continue;
+            }
+            let split: Vec<&str> = line.split(',').map(|s| s.trim()).collect();
+            if !timing.apply(&split) {
+                log(format!(
+                    "WARNING: Timing: {}:{}: ignoring '{}'",
+                    filename,
+                    n + 1,
+                    line
+                ));
+            }
+        }
+        timing
+    }
+
+    fn apply(&mut self, split: &[&str]) -> bool {
+        if 2 != split.len() {
+            return false;
+        }
+        match split[0] {
+            "update_hz" => match split[1].parse::<u32>() {
+                Ok(v) if 0 < v && v <= MAX_UPDATE_HZ => self.update_hz = v,
+                _ => return false,
+            },
+            "render_hz" => match split[1].parse::<u32>() {
+                Ok(v) => self.render_hz = v,
+                _ => return false,
+            },
+            "vsync" => match split[1].parse::<bool>() {
+                Ok(v) => self.vsync = v,
+                _ => return false,
+            },
+            "max_steps" => match split[1].parse::<u32>() {
+                Ok(v) if 0 < v => self.max_steps = v,
+                _ => return false,
+            },
+            _ => return false,
+        }
+        true
+    }
+
+    // microseconds
+    pub fn update_dt(&self) -> u128 {
+        1_000_000 / self.update_hz.max(1) as u128
+    }
+
+    pub fn physics_dt(&self) -> u128 {
+        self.update_dt() * PHYSICS_DIV
+    }
+
+    // 0 when uncapped
+    pub fn render_dt(&self) -> u128 {
+        match self.render_hz {
+            0 => 0,
+            hz => 1_000_000 / hz as u128,
+        }
+    }
+
+    // frame time the render load is measured against
+    pub fn render_budget(&self) -> u128 {
+        match self.render_hz {
+            0 => 1_000_000 / NOMINAL_RENDER_HZ as u128,
+            _ => self.render_dt(),
+        }
+    }
+
+    pub fn next_render_rate(&mut self) {
+        let idx = RENDER_RATES
+            .iter()
+            .position(|r| *r == self.render_hz)
+            .map_or(0, |i| i + 1);
+        self.render_hz = RENDER_RATES[idx % RENDER_RATES.len()];
+    }
+}
+
+pub fn render_rate_name(hz: u32) -> String {
+    match hz {
+        0 => String::from("uncapped"),
+        _ => format!("{} Hz", hz),
+    }
+}
+
+// how far rendering is between the last two physics states
+pub fn interpolation_alpha(since_physics: u128, physics_dt: u128) -> f32 {
+    (since_physics as f32 / physics_dt.max(1) as f32).clamp(0.0, 1.0)
+}
+
+pub fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+// the short way round, for angles in radians
+pub fn lerp_angle(a: f32, b: f32, t: f32) -> f32 {
+    let tau = std::f32::consts::TAU;
+    let d = (b - a).rem_euclid(tau);
+    let d = if d > std::f32::consts::PI { d - tau } else { d };
+    a + d * t
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_rates() {
+        let t = Timing::parse(
+            "timing.dat",
+            "// comment\nupdate_hz,600\nrender_hz,0\nvsync,false\nmax_steps,0\nbogus,1\n",
+        );
+        assert_eq!(600, t.update_hz);
+        assert_eq!(1666, t.update_dt());
+        assert_eq!(1666 * PHYSICS_DIV, t.physics_dt());
+        assert_eq!(0, t.render_dt());
+        assert_eq!(16666, t.render_budget());
+        assert!(!t.vsync);
+        assert_eq!(Timing::default().max_steps, t.max_steps);
+
+        let mut t = Timing::default();
+        assert_eq!(833, t.update_dt());
+        for hz in [120, 144, 0, 60] {
+            t.next_render_rate();
+            assert_eq!(hz, t.render_hz);
+        }
+    }
+
+    #[test]
+    fn interpolation() {
+        assert_eq!(0.5, interpolation_alpha(1666, 3332));
+        assert_eq!(1.0, interpolation_alpha(9000, 3332));
+        assert_eq!(15.0, lerp(10.0, 20.0, 0.5));
+
+        let pi = std::f32::consts::PI;
+        let a = lerp_angle(pi - 0.1, -pi + 0.1, 0.5);
+        assert!((a.abs() - pi).abs() < 1e-4);
+        assert!((lerp_angle(0.0, 1.0, 0.25) - 0.25).abs() < 1e-6);
+    }
+}
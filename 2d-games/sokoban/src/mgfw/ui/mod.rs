@@ -0,0 +1,771 @@
+pub mod theme;
+
+pub use theme::Theme;
+
+use super::ecs::*;
+use super::*;
+
+// navigation input, keyboard events (and controllers, which mgfw turns into
+// keyboard events) are mapped by Ui::event(). Anything else, e.g. remapped keys,
+// can feed these through Ui::nav().
+pub const UI_NAV_NONE: u8 = 0;
+pub const UI_NAV_UP: u8 = 1;
+pub const UI_NAV_DOWN: u8 = 2;
+pub const UI_NAV_LEFT: u8 = 3;
+pub const UI_NAV_RIGHT: u8 = 4;
+pub const UI_NAV_ACCEPT: u8 = 5;
+pub const UI_NAV_BACK: u8 = 6;
+
+const ELEMENT_QUAD: u8 = 0;
+const ELEMENT_TEXT: u8 = 1;
+
+// one pooled entity, last submitted state is kept so the world is only
+// touched when something actually changes between frames
+struct Element {
+    ent: usize,
+    kind: u8,
+    text: String,
+    align: u8,
+    pos: (f32, f32),
+    size: (f32, f32),
+    color: Color,
+    visible: bool,
+}
+
+#[derive(Copy, Clone)]
+struct Rect {
+    x: f32,
+    y: f32,
+    w: f32,
+    h: f32,
+}
+
+// arcade style name entry: left/right pick the character under the cursor,
+// accept moves to the next one, back to the previous one
+pub struct NameEntry {
+    pub chars: Vec<char>,
+    pub cursor: usize,
+}
+
+const NAME_CHARS: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789 ";
+
+#[allow(dead_code)]
+impl NameEntry {
+    pub fn new(len: usize) -> NameEntry {
+        NameEntry {
+            chars: vec!['A'; len.max(1)],
+            cursor: 0,
+        }
+    }
+
+    // starts from a previous name, e.g. the last one entered
+    pub fn from_name(name: &str, len: usize) -> NameEntry {
+        let mut entry = NameEntry::new(len);
+        for (i, c) in name.to_uppercase().chars().take(entry.chars.len()).enumerate() {
+            if NAME_CHARS.contains(c) {
+                entry.chars[i] = c;
+            }
+        }
+        entry
+    }
+
+    pub fn get_name(&self) -> String {
+        let name: String = self.chars.iter().collect();
+        String::from(name.trim())
+    }
+
+    fn step(&mut self, dir: i32) {
+        let chars: Vec<char> = NAME_CHARS.chars().collect();
+        let n = chars.len() as i32;
+        let cur = chars.iter().position(|c| *c == self.chars[self.cursor]).unwrap_or(0) as i32;
+        self.chars[self.cursor] = chars[((cur + dir + n) % n) as usize];
+    }
+}
+
+impl Rect {
+    fn contains(&self, x: i32, y: i32) -> bool {
+        let x = x as f32;
+        let y = y as f32;
+        x >= self.x && x < self.x + self.w && y >= self.y && y < self.y + self.h
+    }
+}
+
+// Immediate mode widgets on top of the ECS. Call begin(), then the widget
+// functions in the same order every frame, then end(). Widgets draw through a
+// pool of entities allocated on first use, so a Ui created after the game's
+// own entities always draws on top of them.
+//
+//   ui.begin(world);
+//   ui.panel(world, 220.0, 100.0, 200.0, 120.0);
+//   ui.begin_list(230.0, 110.0, 180.0);
+//   if ui.button(world, "Resume") { ... }
+//   ui.toggle(world, "Sound", &mut sound);
+//   ui.slider(world, "Volume", &mut volume, 0.0, 1.0, 0.1);
+//   ui.end(world);
+pub struct Ui {
+    pub theme: Theme,
+    font: Option<usize>,
+    elements: Vec<Element>,
+    element_cursor: usize,
+    // focus is an index into the focusable widgets of the current frame
+    focus: usize,
+    focus_count: usize,
+    last_focus_count: usize,
+    widget_cursor: usize,
+    nav_pending: u8,
+    nav_active: u8,
+    click_pending: bool,
+    click_active: bool,
+    last_mouse: (i32, i32),
+    mouse_moved: bool,
+    list: Rect,
+}
+
+impl Default for Ui {
+    fn default() -> Ui {
+        Ui::new(Theme::default())
+    }
+}
+
+#[allow(dead_code)]
+impl Ui {
+    pub fn new(theme: Theme) -> Ui {
+        log(format!("Constructing Ui"));
+        Ui {
+            theme,
+            font: None,
+            elements: Vec::new(),
+            element_cursor: 0,
+            focus: 0,
+            focus_count: 0,
+            last_focus_count: 0,
+            widget_cursor: 0,
+            nav_pending: UI_NAV_NONE,
+            nav_active: UI_NAV_NONE,
+            click_pending: false,
+            click_active: false,
+            last_mouse: (0, 0),
+            mouse_moved: false,
+            list: Rect { x: 0.0, y: 0.0, w: 0.0, h: 0.0 },
+        }
+    }
+
+    // forget pooled entities, call after world.clear()
+    pub fn reset(&mut self) {
+        self.elements.clear();
+        self.font = None;
+    }
+
+    pub fn set_focus(&mut self, focus: usize) {
+        self.focus = focus;
+    }
+
+    pub fn get_focus(&self) -> usize {
+        self.focus
+    }
+
+    // feed mgfw input events, returns true if the event was used
+    pub fn event(&mut self, event_id: u8) -> bool {
+        match event_id {
+            EVENT_INPUT_KEYBOARD_RELEASED_UP => self.nav(UI_NAV_UP),
+            EVENT_INPUT_KEYBOARD_RELEASED_DOWN => self.nav(UI_NAV_DOWN),
+            EVENT_INPUT_KEYBOARD_RELEASED_LEFT => self.nav(UI_NAV_LEFT),
+            EVENT_INPUT_KEYBOARD_RELEASED_RIGHT => self.nav(UI_NAV_RIGHT),
+            EVENT_INPUT_KEYBOARD_RELEASED_SPACE => self.nav(UI_NAV_ACCEPT),
+            EVENT_INPUT_KEYBOARD_RELEASED_ENTER => self.nav(UI_NAV_ACCEPT),
+            EVENT_INPUT_KEYBOARD_RELEASED_BKSPC => self.nav(UI_NAV_BACK),
+            EVENT_INPUT_MOUSE_BUTTON_UP => self.click_pending = true,
+            _ => return false,
+        }
+        true
+    }
+
+    pub fn nav(&mut self, nav: u8) {
+        self.nav_pending = nav;
+    }
+
+    pub fn begin(&mut self, world: &mut World) {
+        if self.font.is_none() {
+            self.font = match self.theme.font.is_empty() {
+                true => Some(component_render_text::FONT_DEFAULT),
+                false => Some(world.load_font(&self.theme.font)),
+            };
+        }
+
+        self.element_cursor = 0;
+        self.widget_cursor = 0;
+        self.focus_count = 0;
+
+        self.nav_active = self.nav_pending;
+        self.nav_pending = UI_NAV_NONE;
+        self.click_active = self.click_pending;
+        self.click_pending = false;
+
+        let mouse = (world.mouse_x, world.mouse_y);
+        self.mouse_moved = mouse != self.last_mouse;
+        self.last_mouse = mouse;
+
+        if 0 < self.last_focus_count {
+            match self.nav_active {
+                UI_NAV_UP => {
+                    self.focus = (self.focus + self.last_focus_count - 1) % self.last_focus_count
+                }
+                UI_NAV_DOWN => self.focus = (self.focus + 1) % self.last_focus_count,
+                _ => (),
+            }
+        }
+    }
+
+    pub fn end(&mut self, world: &mut World) {
+        for i in self.element_cursor..self.elements.len() {
+            if self.elements[i].visible {
+                self.elements[i].visible = false;
+                world.entity_set_visibility(self.elements[i].ent, false);
+            }
+        }
+
+        self.last_focus_count = self.focus_count;
+        if self.focus >= self.focus_count && 0 < self.focus_count {
+            self.focus = self.focus_count - 1;
+        }
+        self.nav_active = UI_NAV_NONE;
+        self.click_active = false;
+    }
+
+    // hide every widget, for when a menu closes and begin/end stop being called
+    pub fn hide(&mut self, world: &mut World) {
+        self.element_cursor = 0;
+        self.end(world);
+    }
+
+    pub fn panel(&mut self, world: &mut World, x: f32, y: f32, w: f32, h: f32) {
+        let clr = self.theme.panel_color;
+        self.quad(world, Rect { x, y, w, h }, clr);
+    }
+
+    // following widgets stack downwards from (x, y), each one row tall
+    pub fn begin_list(&mut self, x: f32, y: f32, w: f32) {
+        self.list = Rect { x, y, w, h: 0.0 };
+    }
+
+    pub fn label(&mut self, world: &mut World, text: &str) {
+        let row = self.next_row();
+        let clr = self.theme.text_color;
+        self.text(world, text, row, TEXT_ALIGN_CENTER, clr);
+    }
+
+    pub fn button(&mut self, world: &mut World, text: &str) -> bool {
+        let row = self.next_row();
+        let (focused, clicked) = self.focusable(row);
+        self.widget_background(world, row, focused);
+        let clr = self.text_color(focused);
+        self.text(world, text, row, TEXT_ALIGN_CENTER, clr);
+
+        focused && (clicked || UI_NAV_ACCEPT == self.nav_active)
+    }
+
+    // returns true when the value changed
+    pub fn toggle(&mut self, world: &mut World, text: &str, value: &mut bool) -> bool {
+        let row = self.next_row();
+        let (focused, clicked) = self.focusable(row);
+        self.widget_background(world, row, focused);
+        let clr = self.text_color(focused);
+        self.text(world, text, self.inset(row), TEXT_ALIGN_LEFT, clr);
+
+        let sz = row.h - self.theme.padding;
+        let bx = Rect {
+            x: row.x + row.w - self.theme.padding * 0.5 - sz,
+            y: row.y + self.theme.padding * 0.5,
+            w: sz,
+            h: sz,
+        };
+        let bg = self.theme.panel_color;
+        self.quad(world, bx, bg);
+
+        let mut changed = false;
+        if focused {
+            match self.nav_active {
+                UI_NAV_ACCEPT | UI_NAV_LEFT | UI_NAV_RIGHT => changed = true,
+                _ => changed = clicked,
+            }
+        }
+        if changed {
+            *value = !*value;
+        }
+
+        let mut fill = self.theme.accent_color;
+        if !*value {
+            fill.a = 0.0;
+        }
+        let inner = Rect {
+            x: bx.x + 2.0,
+            y: bx.y + 2.0,
+            w: bx.w - 4.0,
+            h: bx.h - 4.0,
+        };
+        self.quad(world, inner, fill);
+
+        changed
+    }
+
+    // left/right step the value, clicking jumps to the mouse position
+    pub fn slider(
+        &mut self,
+        world: &mut World,
+        text: &str,
+        value: &mut f32,
+        min: f32,
+        max: f32,
+        step: f32,
+    ) -> bool {
+        let row = self.next_row();
+        let (focused, clicked) = self.focusable(row);
+        self.widget_background(world, row, focused);
+        let clr = self.text_color(focused);
+        self.text(world, text, self.inset(row), TEXT_ALIGN_LEFT, clr);
+
+        let track = Rect {
+            x: row.x + row.w * 0.5,
+            y: row.y + row.h * 0.5 - 1.0,
+            w: row.w * 0.5 - self.theme.padding,
+            h: 2.0,
+        };
+
+        let old = *value;
+        if focused {
+            match self.nav_active {
+                UI_NAV_LEFT => *value -= step,
+                UI_NAV_RIGHT => *value += step,
+                _ => (),
+            }
+            if clicked {
+                let t = (world.mouse_x as f32 - track.x) / track.w;
+                *value = min + t * (max - min);
+                if 0.0 < step {
+                    *value = min + ((*value - min) / step).round() * step;
+                }
+            }
+        }
+        *value = value.max(min).min(max);
+
+        let bg = self.theme.panel_color;
+        self.quad(world, track, bg);
+
+        let t = match max > min {
+            true => (*value - min) / (max - min),
+            false => 0.0,
+        };
+        let kw = 6.0;
+        let knob = Rect {
+            x: track.x + t * track.w - kw * 0.5,
+            y: row.y + self.theme.padding * 0.5,
+            w: kw,
+            h: row.h - self.theme.padding,
+        };
+        let accent = self.theme.accent_color;
+        self.quad(world, knob, accent);
+
+        old != *value
+    }
+
+    // cells are (text, fraction of the list width), the last cell is right
+    // aligned so numbers line up. not focusable.
+    pub fn table_row(&mut self, world: &mut World, cells: &[(&str, f32)], highlight: bool) {
+        let row = self.next_row();
+        if highlight {
+            let clr = self.theme.widget_focus_color;
+            self.quad(world, row, clr);
+        }
+        let clr = self.text_color(highlight);
+        let inner = self.inset(row);
+        let mut x = inner.x;
+        for (i, (text, frac)) in cells.iter().enumerate() {
+            let w = inner.w * frac;
+            let r = Rect { x, y: row.y, w, h: row.h };
+            let align = match i + 1 == cells.len() {
+                true => TEXT_ALIGN_RIGHT,
+                false => TEXT_ALIGN_LEFT,
+            };
+            self.text(world, text, r, align, clr);
+            x += w;
+        }
+    }
+
+    // returns true when accept is pressed on the last character
+    pub fn name_entry(&mut self, world: &mut World, text: &str, entry: &mut NameEntry) -> bool {
+        let row = self.next_row();
+        let (focused, _) = self.focusable(row);
+        self.widget_background(world, row, focused);
+        let clr = self.text_color(focused);
+        self.text(world, text, self.inset(row), TEXT_ALIGN_LEFT, clr);
+
+        let mut done = false;
+        if focused {
+            match self.nav_active {
+                UI_NAV_LEFT => entry.step(-1),
+                UI_NAV_RIGHT => entry.step(1),
+                UI_NAV_BACK => entry.cursor = entry.cursor.saturating_sub(1),
+                UI_NAV_ACCEPT => match entry.cursor + 1 < entry.chars.len() {
+                    true => entry.cursor += 1,
+                    false => done = true,
+                },
+                _ => (),
+            }
+        }
+
+        // one cell per character, right aligned in the row
+        let sz = row.h;
+        let x0 = row.x + row.w - self.theme.padding * 0.5 - sz * entry.chars.len() as f32;
+        for i in 0..entry.chars.len() {
+            let cell = Rect {
+                x: x0 + sz * i as f32,
+                y: row.y,
+                w: sz,
+                h: row.h,
+            };
+            let under = focused && i == entry.cursor;
+            if under {
+                let bg = self.theme.panel_color;
+                self.quad(world, cell, bg);
+            }
+            let c = match under {
+                true => self.theme.accent_color,
+                false => clr,
+            };
+            self.text(world, &entry.chars[i].to_string(), cell, TEXT_ALIGN_CENTER, c);
+        }
+
+        done
+    }
+
+    fn next_row(&mut self) -> Rect {
+        let row = Rect {
+            x: self.list.x,
+            y: self.list.y + self.list.h,
+            w: self.list.w,
+            h: self.theme.row_height,
+        };
+        self.list.h += self.theme.row_height + self.theme.row_spacing;
+        row
+    }
+
+    fn inset(&self, r: Rect) -> Rect {
+        Rect {
+            x: r.x + self.theme.padding,
+            y: r.y,
+            w: r.w - self.theme.padding * 2.0,
+            h: r.h,
+        }
+    }
+
+    // registers a focusable widget, returns (focused, clicked)
+    fn focusable(&mut self, r: Rect) -> (bool, bool) {
+        let id = self.focus_count;
+        self.focus_count += 1;
+
+        let hover = r.contains(self.last_mouse.0, self.last_mouse.1);
+        if hover && (self.mouse_moved || self.click_active) {
+            self.focus = id;
+        }
+        (id == self.focus, hover && self.click_active)
+    }
+
+    fn text_color(&self, focused: bool) -> Color {
+        match focused {
+            true => self.theme.text_focus_color,
+            false => self.theme.text_color,
+        }
+    }
+
+    fn widget_background(&mut self, world: &mut World, r: Rect, focused: bool) {
+        let clr = match focused {
+            true => self.theme.widget_focus_color,
+            false => self.theme.widget_color,
+        };
+        self.quad(world, r, clr);
+    }
+
+    fn next_element(&mut self, world: &mut World, kind: u8) -> usize {
+        let idx = self.element_cursor;
+        self.element_cursor += 1;
+
+        if idx == self.elements.len() {
+            let ent = world.new_entity();
+            self.elements.push(Element {
+                ent,
+                kind: kind + 1, // force setup below
+                text: String::new(),
+                align: u8::MAX,
+                pos: (0.0, 0.0),
+                size: (0.0, 0.0),
+                color: Color { r: 0.0, g: 0.0, b: 0.0, a: 0.0 },
+                visible: false,
+            });
+        }
+
+        // widget order changed since last frame, repurpose the entity
+        if kind != self.elements[idx].kind {
+            let e = &mut self.elements[idx];
+            e.kind = kind;
+            e.text.clear();
+            e.align = u8::MAX;
+            e.pos = (f32::NAN, f32::NAN);
+            e.size = (f32::NAN, f32::NAN);
+            e.color.a = f32::NAN;
+
+            match kind {
+                ELEMENT_QUAD => {
+                    let white = Color { r: 1.0, g: 1.0, b: 1.0, a: 1.0 };
+                    let pnts = vec![
+                        Position { x: 0.0, y: 0.0 },
+                        Position { x: 0.0, y: 1.0 },
+                        Position { x: 1.0, y: 1.0 },
+                        Position { x: 0.0, y: 0.0 },
+                        Position { x: 1.0, y: 1.0 },
+                        Position { x: 1.0, y: 0.0 },
+                    ];
+                    world.entity_set_triangle_buffer(e.ent, &pnts, &vec![white; 6]);
+                }
+                _ => {
+                    world.entity_set_text(e.ent, String::new());
+                    world.entity_set_font(e.ent, self.font.unwrap_or_default());
+                }
+            }
+        }
+
+        if !self.elements[idx].visible {
+            self.elements[idx].visible = true;
+            world.entity_set_visibility(self.elements[idx].ent, true);
+        }
+        idx
+    }
+
+    fn set_element(&mut self, world: &mut World, idx: usize, pos: (f32, f32), size: (f32, f32), clr: Color) {
+        let e = &mut self.elements[idx];
+        if pos != e.pos {
+            e.pos = pos;
+            world.entity_set_position_xy(e.ent, pos.0, pos.1);
+        }
+        if size != e.size {
+            e.size = size;
+            world.entity_set_scale_xy(e.ent, size.0, size.1);
+        }
+        let c = e.color;
+        if clr.r != c.r || clr.g != c.g || clr.b != c.b || clr.a != c.a {
+            e.color = clr;
+            world.entity_set_color(e.ent, clr);
+        }
+    }
+
+    fn quad(&mut self, world: &mut World, r: Rect, clr: Color) {
+        let idx = self.next_element(world, ELEMENT_QUAD);
+        self.set_element(world, idx, (r.x, r.y), (r.w, r.h), clr);
+    }
+
+    fn text(&mut self, world: &mut World, text: &str, r: Rect, align: u8, clr: Color) {
+        let idx = self.next_element(world, ELEMENT_TEXT);
+        let ent = self.elements[idx].ent;
+        if text != self.elements[idx].text {
+            self.elements[idx].text = String::from(text);
+            world.entity_set_text(ent, String::from(text));
+        }
+        if align != self.elements[idx].align {
+            self.elements[idx].align = align;
+            world.entity_set_text_align(ent, align);
+        }
+
+        let s = self.theme.text_scale;
+        let x = match align {
+            TEXT_ALIGN_CENTER => r.x + r.w * 0.5,
+            TEXT_ALIGN_RIGHT => r.x + r.w,
+            _ => r.x,
+        };
+        let h = world.text_get_height(ent) as f32 * s;
+        let y = (r.y + (r.h - h) * 0.5).round();
+        self.set_element(world, idx, (x, y), (s, s), clr);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mgfw::cache::CacheManager;
+
+    // rows are 20 tall with 4 spacing, the first one at y = 100
+    const LIST_X: f32 = 100.0;
+    const LIST_Y: f32 = 100.0;
+
+    fn world() -> World {
+        World::new(&mut CacheManager::new())
+    }
+
+    fn buttons(ui: &mut Ui, world: &mut World, n: usize) -> Vec<bool> {
+        ui.begin(world);
+        ui.begin_list(LIST_X, LIST_Y, 100.0);
+        let ret = (0..n).map(|_| ui.button(world, "b")).collect();
+        ui.end(world);
+        ret
+    }
+
+    fn row_center(row: usize) -> (i32, i32) {
+        (LIST_X as i32 + 50, LIST_Y as i32 + 24 * row as i32 + 10)
+    }
+
+    #[test]
+    fn focus_wraps_over_last_frame_widgets() {
+        let mut world = world();
+        let mut ui = Ui::default();
+        buttons(&mut ui, &mut world, 3);
+        assert_eq!(0, ui.get_focus());
+
+        ui.nav(UI_NAV_UP);
+        buttons(&mut ui, &mut world, 3);
+        assert_eq!(2, ui.get_focus());
+
+        ui.nav(UI_NAV_DOWN);
+        buttons(&mut ui, &mut world, 3);
+        assert_eq!(0, ui.get_focus());
+
+        ui.event(EVENT_INPUT_KEYBOARD_RELEASED_DOWN);
+        buttons(&mut ui, &mut world, 3);
+        assert_eq!(1, ui.get_focus());
+    }
+
+    #[test]
+    fn reused_text_takes_the_new_alignment() {
+        let mut world = world();
+        let mut ui = Ui::default();
+        let mut sound = true;
+        let mut frame = |world: &mut World, toggle: bool| {
+            ui.begin(world);
+            ui.begin_list(LIST_X, LIST_Y, 100.0);
+            match toggle {
+                true => _ = ui.toggle(world, "Sound", &mut sound),
+                false => _ = ui.button(world, "Sound"),
+            }
+            ui.end(world);
+            let text = ui.elements.iter().find(|e| ELEMENT_TEXT == e.kind).unwrap();
+            world.entity_get_text_align(text.ent)
+        };
+
+        assert_eq!(TEXT_ALIGN_CENTER, frame(&mut world, false));
+        assert_eq!(TEXT_ALIGN_LEFT, frame(&mut world, true));
+        assert_eq!(TEXT_ALIGN_CENTER, frame(&mut world, false));
+    }
+
+    #[test]
+    fn end_clamps_focus_to_widget_count() {
+        let mut world = world();
+        let mut ui = Ui::default();
+        ui.set_focus(5);
+        buttons(&mut ui, &mut world, 2);
+        assert_eq!(1, ui.get_focus());
+
+        // nothing focusable leaves the focus alone
+        ui.set_focus(5);
+        buttons(&mut ui, &mut world, 0);
+        assert_eq!(5, ui.get_focus());
+    }
+
+    #[test]
+    fn hover_moves_focus_and_click_presses() {
+        let mut world = world();
+        let mut ui = Ui::default();
+        buttons(&mut ui, &mut world, 3);
+
+        (world.mouse_x, world.mouse_y) = row_center(1);
+        assert_eq!(vec![false, false, false], buttons(&mut ui, &mut world, 3));
+        assert_eq!(1, ui.get_focus());
+
+        // keyboard focus sticks while the mouse stays still
+        ui.nav(UI_NAV_DOWN);
+        buttons(&mut ui, &mut world, 3);
+        assert_eq!(2, ui.get_focus());
+
+        // clicking refocuses the widget under the mouse
+        ui.event(EVENT_INPUT_MOUSE_BUTTON_UP);
+        assert_eq!(vec![false, true, false], buttons(&mut ui, &mut world, 3));
+        assert_eq!(1, ui.get_focus());
+
+        // clicking outside every widget presses nothing
+        (world.mouse_x, world.mouse_y) = (0, 0);
+        ui.event(EVENT_INPUT_MOUSE_BUTTON_UP);
+        assert_eq!(vec![false, false, false], buttons(&mut ui, &mut world, 3));
+    }
+
+    #[test]
+    fn accept_presses_focused_button() {
+        let mut world = world();
+        let mut ui = Ui::default();
+        ui.set_focus(2);
+        ui.nav(UI_NAV_ACCEPT);
+        assert_eq!(vec![false, false, true], buttons(&mut ui, &mut world, 3));
+        assert_eq!(vec![false, false, false], buttons(&mut ui, &mut world, 3));
+    }
+
+    #[test]
+    fn toggle_flips_when_focused() {
+        let mut world = world();
+        let mut ui = Ui::default();
+        let mut a = false;
+        let mut b = false;
+        let frame = |ui: &mut Ui, world: &mut World, a: &mut bool, b: &mut bool| {
+            ui.begin(world);
+            ui.begin_list(LIST_X, LIST_Y, 100.0);
+            let ret = (ui.toggle(world, "a", a), ui.toggle(world, "b", b));
+            ui.end(world);
+            ret
+        };
+
+        assert_eq!((false, false), frame(&mut ui, &mut world, &mut a, &mut b));
+
+        ui.nav(UI_NAV_ACCEPT);
+        assert_eq!((true, false), frame(&mut ui, &mut world, &mut a, &mut b));
+        assert!(a && !b);
+
+        ui.nav(UI_NAV_DOWN);
+        frame(&mut ui, &mut world, &mut a, &mut b);
+        ui.nav(UI_NAV_LEFT);
+        assert_eq!((false, true), frame(&mut ui, &mut world, &mut a, &mut b));
+        ui.nav(UI_NAV_RIGHT);
+        assert_eq!((false, true), frame(&mut ui, &mut world, &mut a, &mut b));
+        assert!(a && !b);
+    }
+
+    #[test]
+    fn slider_steps_clamps_and_jumps_to_click() {
+        let mut world = world();
+        let mut ui = Ui::default();
+        let mut value = 0.5;
+        let frame = |ui: &mut Ui, world: &mut World, value: &mut f32| {
+            ui.begin(world);
+            ui.begin_list(LIST_X, LIST_Y, 100.0);
+            let ret = ui.slider(world, "v", value, 0.0, 1.0, 0.25);
+            ui.end(world);
+            ret
+        };
+
+        assert!(!frame(&mut ui, &mut world, &mut value));
+        ui.nav(UI_NAV_RIGHT);
+        assert!(frame(&mut ui, &mut world, &mut value));
+        assert_eq!(0.75, value);
+        ui.nav(UI_NAV_RIGHT);
+        frame(&mut ui, &mut world, &mut value);
+        ui.nav(UI_NAV_RIGHT);
+        assert!(!frame(&mut ui, &mut world, &mut value));
+        assert_eq!(1.0, value);
+        ui.nav(UI_NAV_LEFT);
+        frame(&mut ui, &mut world, &mut value);
+        assert_eq!(0.75, value);
+
+        // track runs from the middle of the row to padding before its end,
+        // a click 30% along it snaps to the nearest step
+        let track_x = LIST_X + 50.0;
+        let track_w = 50.0 - ui.theme.padding;
+        world.mouse_x = (track_x + track_w * 0.3) as i32;
+        world.mouse_y = row_center(0).1;
+        ui.event(EVENT_INPUT_MOUSE_BUTTON_UP);
+        assert!(frame(&mut ui, &mut world, &mut value));
+        assert_eq!(0.25, value);
+    }
+}
@@ -0,0 +1,162 @@
+use crate::mgfw::ecs::Color;
+use crate::mgfw::log;
+use crate::mgfw::assets;
+
+pub struct Theme {
+    pub panel_color: Color,
+    pub widget_color: Color,
+    pub widget_focus_color: Color,
+    pub text_color: Color,
+    pub text_focus_color: Color,
+    pub accent_color: Color,
+    pub row_height: f32,
+    pub row_spacing: f32,
+    pub padding: f32,
+    pub text_scale: f32,
+    pub font: String,
+}
+
+impl Default for Theme {
+    fn default() -> Theme {
+        Theme {
+            panel_color: Color { r: 0.0, g: 0.0, b: 0.0, a: 0.85 },
+            widget_color: Color { r: 0.2, g: 0.2, b: 0.2, a: 1.0 },
+            widget_focus_color: Color { r: 0.4, g: 0.25, b: 0.1, a: 1.0 },
+            text_color: Color { r: 0.8, g: 0.8, b: 0.8, a: 1.0 },
+            text_focus_color: Color { r: 1.0, g: 0.5, b: 0.2, a: 1.0 },
+            accent_color: Color { r: 1.0, g: 0.5, b: 0.2, a: 1.0 },
+            row_height: 20.0,
+            row_spacing: 4.0,
+            padding: 8.0,
+            text_scale: 1.0,
+            font: String::new(),
+        }
+    }
+}
+
+#[allow(dead_code)]
+impl Theme {
+    // same line format as world.dat: key,values... with // comments.
+    // unknown keys and bad values are logged and left at their defaults.
+    pub fn load(filename: &str) -> Theme {
+        log(format!("Theme: Parsing '{}'", filename));
+        let mut theme = Theme::default();
+
+        let text = match assets::read_to_string(filename) {
+            Ok(t) => t,
+            Err(e) => {
+                log(format!("WARNING: Theme: {}: {}, using defaults", filename, e));
+                return theme;
+            }
+        };
+
+        for (n, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with("//") {
+                continue;
+            }
+
+            let split: Vec<&str> = line.split(',').map(|s| s.trim()).collect();
+            if !theme.apply(&split) {
+                log(format!(
+                    "WARNING: Theme: {}:{}: ignoring '{}'",
+                    filename,
+                    n + 1,
+                    line
+                ));
+            }
+        }
+        theme
+    }
+
+    fn apply(&mut self, split: &[&str]) -> bool {
+        match split[0] {
+            "panel_color" => parse_color(split, &mut self.panel_color),
+            "widget_color" => parse_color(split, &mut self.widget_color),
+            "widget_focus_color" => parse_color(split, &mut self.widget_focus_color),
+            "text_color" => parse_color(split, &mut self.text_color),
+            "text_focus_color" => parse_color(split, &mut self.text_focus_color),
+            "accent_color" => parse_color(split, &mut self.accent_color),
+            "row_height" => parse_f32(split, &mut self.row_height),
+            "row_spacing" => parse_f32(split, &mut self.row_spacing),
+            "padding" => parse_f32(split, &mut self.padding),
+            "text_scale" => parse_f32(split, &mut self.text_scale),
+            "font" => {
+                if 2 == split.len() {
+                    self.font = String::from(split[1]);
+                    return true;
+                }
+                false
+            }
+            _ => false,
+        }
+    }
+}
+
+fn parse_f32(split: &[&str], val: &mut f32) -> bool {
+    if 2 != split.len() {
+        return false;
+    }
+    match split[1].parse::<f32>() {
+        Ok(v) => {
+            *val = v;
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+fn parse_color(split: &[&str], clr: &mut Color) -> bool {
+    if !(4 == split.len() || 5 == split.len()) {
+        return false;
+    }
+    let mut v = [1.0; 4];
+    for i in 1..split.len() {
+        match split[i].parse::<f32>() {
+            Ok(f) => v[i - 1] = f,
+            Err(_) => return false,
+        }
+    }
+    *clr = Color { r: v[0], g: v[1], b: v[2], a: v[3] };
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn theme_keys_override_defaults() {
+        let mut theme = Theme::default();
+        assert!(theme.apply(&["text_color", "0.1", "0.2", "0.3"]));
+        assert!(theme.apply(&["panel_color", "0", "0", "0", "0.5"]));
+        assert!(theme.apply(&["row_height", "32"]));
+        assert!(theme.apply(&["font", "assets/font.fnt"]));
+
+        let c = theme.text_color;
+        assert_eq!((0.1, 0.2, 0.3, 1.0), (c.r, c.g, c.b, c.a));
+        assert_eq!(0.5, theme.panel_color.a);
+        assert_eq!(32.0, theme.row_height);
+        assert_eq!("assets/font.fnt", theme.font);
+    }
+
+    #[test]
+    fn bad_theme_values_keep_defaults() {
+        let defaults = Theme::default();
+        let mut theme = Theme::default();
+        assert!(!theme.apply(&["text_color", "1", "x", "1"]));
+        assert!(!theme.apply(&["text_color", "1", "1"]));
+        assert!(!theme.apply(&["accent_color", "1", "1", "1", "1", "1"]));
+        assert!(!theme.apply(&["padding", "wide"]));
+        assert!(!theme.apply(&["padding"]));
+        assert!(!theme.apply(&["font"]));
+        assert!(!theme.apply(&["border_color", "1", "1", "1"]));
+
+        let (a, b) = (theme.text_color, defaults.text_color);
+        assert_eq!((a.r, a.g, a.b, a.a), (b.r, b.g, b.b, b.a));
+        let (a, b) = (theme.accent_color, defaults.accent_color);
+        assert_eq!((a.r, a.g, a.b, a.a), (b.r, b.g, b.b, b.a));
+        assert_eq!(defaults.padding, theme.padding);
+        assert!(theme.font.is_empty());
+    }
+}
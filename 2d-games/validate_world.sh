@@ -0,0 +1,5 @@
+#!/bin/sh
+# Check every game's assets/world.dat with the parser in sokoban's mgfw.
+# Prints file:line:column diagnostics, exits non-zero on errors.
+cd "$(dirname "$0")" || exit 1
+cargo run --quiet --manifest-path sokoban/Cargo.toml -- --validate */assets/world.dat